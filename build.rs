@@ -0,0 +1,69 @@
+//! ビルド時に `src/` を走査し、ビューが参照しているテンプレート名の一覧を生成します。
+//! 生成した一覧はシステムチェック (`src/checks.rs` の `TemplatesCheck`) が
+//! `templates/` に実在するかの確認に使います (Djangoの `templates.E00x` チェックに相当)。
+
+use std::collections::BTreeSet;
+use std::fs;
+use std::path::Path;
+
+/// テンプレート名の文字列リテラルが直後に続く呼び出し
+const CALL_PATTERNS: [&str; 2] = ["AppTemplate::new(", "render("];
+
+fn main() {
+    println!("cargo:rerun-if-changed=src");
+
+    let mut found = BTreeSet::new();
+    scan_dir(Path::new("src"), &mut found);
+
+    let entries: String = found
+        .iter()
+        .map(|(name, file)| format!("    ({:?}, {:?}),\n", name, file))
+        .collect();
+    let out = Path::new(&std::env::var("OUT_DIR").unwrap()).join("referenced_templates.rs");
+    fs::write(out, format!("&[\n{}]\n", entries)).unwrap();
+}
+
+fn scan_dir(dir: &Path, found: &mut BTreeSet<(String, String)>) {
+    let Ok(entries) = fs::read_dir(dir) else { return };
+    for entry in entries.flatten() {
+        let path = entry.path();
+        if path.is_dir() {
+            scan_dir(&path, found);
+        } else if path.extension().is_some_and(|ext| ext == "rs") {
+            let source = fs::read_to_string(&path).unwrap_or_default();
+            let file = path.to_string_lossy().replace('\\', "/");
+            for name in template_names(&source) {
+                found.insert((name, file.clone()));
+            }
+        }
+    }
+}
+
+/// `AppTemplate::new("...")` / `render("...")` の第1引数と、`fn template_name()` が返すリテラルを取り出す
+fn template_names(source: &str) -> Vec<String> {
+    let mut names = Vec::new();
+    for pattern in CALL_PATTERNS {
+        for (index, _) in source.match_indices(pattern) {
+            if let Some(name) = leading_literal(&source[index + pattern.len()..]) {
+                names.push(name);
+            }
+        }
+    }
+    for (index, _) in source.match_indices("fn template_name(") {
+        let body = &source[index..];
+        if let Some(brace) = body.find('{') {
+            if let Some(name) = leading_literal(&body[brace + 1..]) {
+                names.push(name);
+            }
+        }
+    }
+    names
+}
+
+/// 空白の直後にある文字列リテラルの中身 (変数や式の場合は `None`)
+fn leading_literal(rest: &str) -> Option<String> {
+    let rest = rest.trim_start().strip_prefix('"')?;
+    let end = rest.find('"')?;
+    let name = &rest[..end];
+    (!name.is_empty() && !name.contains(char::is_whitespace)).then(|| name.to_string())
+}
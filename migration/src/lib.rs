@@ -8,6 +8,14 @@ mod m20250102_000001_add_group_to_todo;
 mod m20260203_000001_create_permission_tables;
 mod m20260204_000001_fix_table_names;
 mod m20260205_000001_seed_permissions;
+mod m20260206_000001_create_session_table;
+mod m20260207_000001_add_email_to_user;
+mod m20260208_000001_create_login_failure_table;
+mod m20260209_000001_add_two_factor;
+mod m20260210_000001_create_api_tokens;
+mod m20260211_000001_create_user_identities;
+mod m20260212_000001_create_log_entries;
+mod m20260213_000001_add_activated_at_to_user;
 
 pub struct Migrator;
 
@@ -23,6 +31,14 @@ impl MigratorTrait for Migrator {
             Box::new(m20260203_000001_create_permission_tables::Migration),
             Box::new(m20260204_000001_fix_table_names::Migration),
             Box::new(m20260205_000001_seed_permissions::Migration),
+            Box::new(m20260206_000001_create_session_table::Migration),
+            Box::new(m20260207_000001_add_email_to_user::Migration),
+            Box::new(m20260208_000001_create_login_failure_table::Migration),
+            Box::new(m20260209_000001_add_two_factor::Migration),
+            Box::new(m20260210_000001_create_api_tokens::Migration),
+            Box::new(m20260211_000001_create_user_identities::Migration),
+            Box::new(m20260212_000001_create_log_entries::Migration),
+            Box::new(m20260213_000001_add_activated_at_to_user::Migration),
         ]
     }
 }
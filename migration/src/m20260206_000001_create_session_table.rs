@@ -0,0 +1,83 @@
+use sea_orm_migration::prelude::*;
+
+#[derive(DeriveMigrationName)]
+pub struct Migration;
+
+#[async_trait::async_trait]
+impl MigrationTrait for Migration {
+    // Djangoの `django_session` テーブルに相当
+    async fn up(&self, manager: &SchemaManager) -> Result<(), DbErr> {
+        manager
+            .create_table(
+                Table::create()
+                    .table(Sessions::Table)
+                    .if_not_exists()
+                    .col(
+                        ColumnDef::new(Sessions::SessionKey)
+                            .string_len(64)
+                            .not_null()
+                            .primary_key(),
+                    )
+                    .col(ColumnDef::new(Sessions::UserId).integer().not_null())
+                    .col(
+                        ColumnDef::new(Sessions::CreatedAt)
+                            .timestamp_with_time_zone()
+                            .not_null()
+                            .default(Expr::current_timestamp()),
+                    )
+                    .col(
+                        ColumnDef::new(Sessions::LastActivity)
+                            .timestamp_with_time_zone()
+                            .not_null()
+                            .default(Expr::current_timestamp()),
+                    )
+                    .col(ColumnDef::new(Sessions::ExpireDate).timestamp_with_time_zone().not_null())
+                    .col(ColumnDef::new(Sessions::IpAddress).string_len(45).null())
+                    .col(ColumnDef::new(Sessions::UserAgent).text().null())
+                    .foreign_key(
+                        ForeignKey::create()
+                            .name("fk-sessions-user_id")
+                            .from(Sessions::Table, Sessions::UserId)
+                            .to(User::Table, User::Id)
+                            .on_delete(ForeignKeyAction::Cascade),
+                    )
+                    .to_owned(),
+            )
+            .await?;
+
+        // 期限切れセッションの一括削除 (clearsessions) 用
+        manager
+            .create_index(
+                Index::create()
+                    .name("idx-sessions-expire_date")
+                    .table(Sessions::Table)
+                    .col(Sessions::ExpireDate)
+                    .to_owned(),
+            )
+            .await
+    }
+
+    async fn down(&self, manager: &SchemaManager) -> Result<(), DbErr> {
+        manager
+            .drop_table(Table::drop().table(Sessions::Table).to_owned())
+            .await
+    }
+}
+
+#[derive(Iden)]
+enum Sessions {
+    Table,
+    SessionKey,
+    UserId,
+    CreatedAt,
+    LastActivity,
+    ExpireDate,
+    IpAddress,
+    UserAgent,
+}
+
+#[derive(Iden)]
+enum User {
+    Table,
+    Id,
+}
@@ -0,0 +1,42 @@
+use sea_orm_migration::prelude::*;
+
+/// ユーザーにメールアドレスを追加する。
+/// Djangoの `AbstractUser.email` (`EmailField(blank=True)`) に相当し、パスワードリセットの送信先に使用します。
+#[derive(DeriveMigrationName)]
+pub struct Migration;
+
+#[async_trait::async_trait]
+impl MigrationTrait for Migration {
+    async fn up(&self, manager: &SchemaManager) -> Result<(), DbErr> {
+        manager
+            .alter_table(
+                Table::alter()
+                    .table(User::Table)
+                    .add_column_if_not_exists(
+                        ColumnDef::new(User::Email)
+                            .string_len(254)
+                            .not_null()
+                            .default(""),
+                    )
+                    .to_owned(),
+            )
+            .await
+    }
+
+    async fn down(&self, manager: &SchemaManager) -> Result<(), DbErr> {
+        manager
+            .alter_table(
+                Table::alter()
+                    .table(User::Table)
+                    .drop_column(User::Email)
+                    .to_owned(),
+            )
+            .await
+    }
+}
+
+#[derive(Iden)]
+enum User {
+    Table,
+    Email,
+}
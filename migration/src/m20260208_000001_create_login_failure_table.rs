@@ -0,0 +1,61 @@
+use sea_orm_migration::prelude::*;
+
+#[derive(DeriveMigrationName)]
+pub struct Migration;
+
+#[async_trait::async_trait]
+impl MigrationTrait for Migration {
+    // ログイン失敗の記録 (django-axes の `AccessAttempt` に相当)
+    async fn up(&self, manager: &SchemaManager) -> Result<(), DbErr> {
+        manager
+            .create_table(
+                Table::create()
+                    .table(LoginFailures::Table)
+                    .if_not_exists()
+                    .col(
+                        ColumnDef::new(LoginFailures::Id)
+                            .integer()
+                            .not_null()
+                            .auto_increment()
+                            .primary_key(),
+                    )
+                    .col(ColumnDef::new(LoginFailures::Scope).string_len(16).not_null())
+                    .col(ColumnDef::new(LoginFailures::Identifier).string_len(255).not_null())
+                    .col(ColumnDef::new(LoginFailures::FailureCount).integer().not_null().default(0))
+                    .col(ColumnDef::new(LoginFailures::LastFailureAt).timestamp_with_time_zone().not_null())
+                    .col(ColumnDef::new(LoginFailures::LockedUntil).timestamp_with_time_zone().null())
+                    .to_owned(),
+            )
+            .await?;
+
+        // ユーザー名 / IPアドレスごとに1行だけ持つ
+        manager
+            .create_index(
+                Index::create()
+                    .name("idx-login_failures-scope-identifier")
+                    .table(LoginFailures::Table)
+                    .col(LoginFailures::Scope)
+                    .col(LoginFailures::Identifier)
+                    .unique()
+                    .to_owned(),
+            )
+            .await
+    }
+
+    async fn down(&self, manager: &SchemaManager) -> Result<(), DbErr> {
+        manager
+            .drop_table(Table::drop().table(LoginFailures::Table).to_owned())
+            .await
+    }
+}
+
+#[derive(Iden)]
+enum LoginFailures {
+    Table,
+    Id,
+    Scope,
+    Identifier,
+    FailureCount,
+    LastFailureAt,
+    LockedUntil,
+}
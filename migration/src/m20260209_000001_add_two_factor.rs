@@ -0,0 +1,86 @@
+use sea_orm_migration::prelude::*;
+
+#[derive(DeriveMigrationName)]
+pub struct Migration;
+
+#[async_trait::async_trait]
+impl MigrationTrait for Migration {
+    // 2要素認証 (django-otp の `TOTPDevice` / `StaticDevice` に相当)
+    async fn up(&self, manager: &SchemaManager) -> Result<(), DbErr> {
+        manager
+            .alter_table(
+                Table::alter()
+                    .table(User::Table)
+                    .add_column_if_not_exists(ColumnDef::new(User::TotpSecret).string_len(64).null())
+                    .add_column_if_not_exists(ColumnDef::new(User::TotpLastCounter).big_integer().null())
+                    .to_owned(),
+            )
+            .await?;
+
+        manager
+            .create_table(
+                Table::create()
+                    .table(OtpRecoveryCodes::Table)
+                    .if_not_exists()
+                    .col(
+                        ColumnDef::new(OtpRecoveryCodes::Id)
+                            .integer()
+                            .not_null()
+                            .auto_increment()
+                            .primary_key(),
+                    )
+                    .col(ColumnDef::new(OtpRecoveryCodes::UserId).integer().not_null())
+                    .col(ColumnDef::new(OtpRecoveryCodes::CodeHash).string_len(64).not_null())
+                    .col(
+                        ColumnDef::new(OtpRecoveryCodes::CreatedAt)
+                            .timestamp_with_time_zone()
+                            .not_null()
+                            .default(Expr::current_timestamp()),
+                    )
+                    .col(ColumnDef::new(OtpRecoveryCodes::UsedAt).timestamp_with_time_zone().null())
+                    .foreign_key(
+                        ForeignKey::create()
+                            .name("fk-otp_recovery_codes-user_id")
+                            .from(OtpRecoveryCodes::Table, OtpRecoveryCodes::UserId)
+                            .to(User::Table, User::Id)
+                            .on_delete(ForeignKeyAction::Cascade),
+                    )
+                    .to_owned(),
+            )
+            .await
+    }
+
+    async fn down(&self, manager: &SchemaManager) -> Result<(), DbErr> {
+        manager
+            .drop_table(Table::drop().table(OtpRecoveryCodes::Table).to_owned())
+            .await?;
+
+        manager
+            .alter_table(
+                Table::alter()
+                    .table(User::Table)
+                    .drop_column(User::TotpSecret)
+                    .drop_column(User::TotpLastCounter)
+                    .to_owned(),
+            )
+            .await
+    }
+}
+
+#[derive(Iden)]
+enum User {
+    Table,
+    Id,
+    TotpSecret,
+    TotpLastCounter,
+}
+
+#[derive(Iden)]
+enum OtpRecoveryCodes {
+    Table,
+    Id,
+    UserId,
+    CodeHash,
+    CreatedAt,
+    UsedAt,
+}
@@ -0,0 +1,122 @@
+use sea_orm_migration::prelude::*;
+
+#[derive(DeriveMigrationName)]
+pub struct Migration;
+
+#[async_trait::async_trait]
+impl MigrationTrait for Migration {
+    // APIトークン (DRF の `authtoken.Token` に、名前・有効期限・権限の範囲を加えたもの)
+    async fn up(&self, manager: &SchemaManager) -> Result<(), DbErr> {
+        manager
+            .create_table(
+                Table::create()
+                    .table(ApiTokens::Table)
+                    .if_not_exists()
+                    .col(
+                        ColumnDef::new(ApiTokens::Id)
+                            .integer()
+                            .not_null()
+                            .auto_increment()
+                            .primary_key(),
+                    )
+                    .col(ColumnDef::new(ApiTokens::UserId).integer().not_null())
+                    .col(ColumnDef::new(ApiTokens::Name).string_len(100).not_null())
+                    .col(ColumnDef::new(ApiTokens::TokenPrefix).string_len(16).not_null())
+                    .col(ColumnDef::new(ApiTokens::TokenHash).string_len(64).not_null().unique_key())
+                    .col(
+                        ColumnDef::new(ApiTokens::CreatedAt)
+                            .timestamp_with_time_zone()
+                            .not_null()
+                            .default(Expr::current_timestamp()),
+                    )
+                    .col(ColumnDef::new(ApiTokens::ExpiresAt).timestamp_with_time_zone().null())
+                    .col(ColumnDef::new(ApiTokens::LastUsedAt).timestamp_with_time_zone().null())
+                    .foreign_key(
+                        ForeignKey::create()
+                            .name("fk-api_tokens-user_id")
+                            .from(ApiTokens::Table, ApiTokens::UserId)
+                            .to(User::Table, User::Id)
+                            .on_delete(ForeignKeyAction::Cascade),
+                    )
+                    .to_owned(),
+            )
+            .await?;
+
+        // ApiTokenPermission Table (Many-to-Many)
+        manager
+            .create_table(
+                Table::create()
+                    .table(ApiTokenPermission::Table)
+                    .if_not_exists()
+                    .col(ColumnDef::new(ApiTokenPermission::ApiTokenId).integer().not_null())
+                    .col(ColumnDef::new(ApiTokenPermission::PermissionId).integer().not_null())
+                    // Composite Primary Key
+                    .primary_key(
+                        Index::create()
+                            .name("pk-api_token_permission")
+                            .col(ApiTokenPermission::ApiTokenId)
+                            .col(ApiTokenPermission::PermissionId),
+                    )
+                    .foreign_key(
+                        ForeignKey::create()
+                            .name("fk-api_token_permission-api_token_id")
+                            .from(ApiTokenPermission::Table, ApiTokenPermission::ApiTokenId)
+                            .to(ApiTokens::Table, ApiTokens::Id)
+                            .on_delete(ForeignKeyAction::Cascade),
+                    )
+                    .foreign_key(
+                        ForeignKey::create()
+                            .name("fk-api_token_permission-permission_id")
+                            .from(ApiTokenPermission::Table, ApiTokenPermission::PermissionId)
+                            .to(Permission::Table, Permission::Id)
+                            .on_delete(ForeignKeyAction::Cascade),
+                    )
+                    .to_owned(),
+            )
+            .await
+    }
+
+    async fn down(&self, manager: &SchemaManager) -> Result<(), DbErr> {
+        manager
+            .drop_table(Table::drop().table(ApiTokenPermission::Table).to_owned())
+            .await?;
+
+        manager
+            .drop_table(Table::drop().table(ApiTokens::Table).to_owned())
+            .await
+    }
+}
+
+#[derive(Iden)]
+enum ApiTokens {
+    Table,
+    Id,
+    UserId,
+    Name,
+    TokenPrefix,
+    TokenHash,
+    CreatedAt,
+    ExpiresAt,
+    LastUsedAt,
+}
+
+#[derive(Iden)]
+enum ApiTokenPermission {
+    #[iden = "api_token_permissions"]
+    Table,
+    ApiTokenId,
+    PermissionId,
+}
+
+#[derive(Iden)]
+enum Permission {
+    #[iden = "permissions"]
+    Table,
+    Id,
+}
+
+#[derive(Iden)]
+enum User {
+    Table,
+    Id,
+}
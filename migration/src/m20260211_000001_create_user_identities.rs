@@ -0,0 +1,80 @@
+use sea_orm_migration::prelude::*;
+
+#[derive(DeriveMigrationName)]
+pub struct Migration;
+
+#[async_trait::async_trait]
+impl MigrationTrait for Migration {
+    // 外部IdP (OpenID Connect) のアカウントとローカルユーザーの紐付け
+    // (django-allauth の `socialaccount.SocialAccount` に相当)
+    async fn up(&self, manager: &SchemaManager) -> Result<(), DbErr> {
+        manager
+            .create_table(
+                Table::create()
+                    .table(UserIdentities::Table)
+                    .if_not_exists()
+                    .col(
+                        ColumnDef::new(UserIdentities::Id)
+                            .integer()
+                            .not_null()
+                            .auto_increment()
+                            .primary_key(),
+                    )
+                    .col(ColumnDef::new(UserIdentities::UserId).integer().not_null())
+                    .col(ColumnDef::new(UserIdentities::Provider).string_len(50).not_null())
+                    .col(ColumnDef::new(UserIdentities::Subject).string_len(255).not_null())
+                    .col(
+                        ColumnDef::new(UserIdentities::CreatedAt)
+                            .timestamp_with_time_zone()
+                            .not_null()
+                            .default(Expr::current_timestamp()),
+                    )
+                    .col(ColumnDef::new(UserIdentities::LastLoginAt).timestamp_with_time_zone().null())
+                    .foreign_key(
+                        ForeignKey::create()
+                            .name("fk-user_identities-user_id")
+                            .from(UserIdentities::Table, UserIdentities::UserId)
+                            .to(User::Table, User::Id)
+                            .on_delete(ForeignKeyAction::Cascade),
+                    )
+                    .to_owned(),
+            )
+            .await?;
+
+        // 同じIdPの同じアカウント (sub) は1ユーザーにのみ紐付く
+        manager
+            .create_index(
+                Index::create()
+                    .name("idx-user_identities-provider-subject")
+                    .table(UserIdentities::Table)
+                    .col(UserIdentities::Provider)
+                    .col(UserIdentities::Subject)
+                    .unique()
+                    .to_owned(),
+            )
+            .await
+    }
+
+    async fn down(&self, manager: &SchemaManager) -> Result<(), DbErr> {
+        manager
+            .drop_table(Table::drop().table(UserIdentities::Table).to_owned())
+            .await
+    }
+}
+
+#[derive(Iden)]
+enum UserIdentities {
+    Table,
+    Id,
+    UserId,
+    Provider,
+    Subject,
+    CreatedAt,
+    LastLoginAt,
+}
+
+#[derive(Iden)]
+enum User {
+    Table,
+    Id,
+}
@@ -0,0 +1,83 @@
+use sea_orm_migration::prelude::*;
+
+#[derive(DeriveMigrationName)]
+pub struct Migration;
+
+#[async_trait::async_trait]
+impl MigrationTrait for Migration {
+    // 管理画面での追加・変更・削除の履歴 (Djangoの `django_admin_log` テーブルに相当)。
+    // 操作したユーザーが削除されても履歴は残す
+    async fn up(&self, manager: &SchemaManager) -> Result<(), DbErr> {
+        manager
+            .create_table(
+                Table::create()
+                    .table(LogEntries::Table)
+                    .if_not_exists()
+                    .col(
+                        ColumnDef::new(LogEntries::Id)
+                            .integer()
+                            .not_null()
+                            .auto_increment()
+                            .primary_key(),
+                    )
+                    .col(
+                        ColumnDef::new(LogEntries::ActionTime)
+                            .timestamp_with_time_zone()
+                            .not_null()
+                            .default(Expr::current_timestamp()),
+                    )
+                    .col(ColumnDef::new(LogEntries::UserId).integer().null())
+                    .col(ColumnDef::new(LogEntries::ContentType).string_len(100).not_null())
+                    .col(ColumnDef::new(LogEntries::ObjectId).string_len(255).not_null())
+                    .col(ColumnDef::new(LogEntries::ObjectRepr).string_len(200).not_null())
+                    .col(ColumnDef::new(LogEntries::ActionFlag).small_integer().not_null())
+                    .col(ColumnDef::new(LogEntries::ChangeMessage).text().not_null().default(""))
+                    .foreign_key(
+                        ForeignKey::create()
+                            .name("fk-log_entries-user_id")
+                            .from(LogEntries::Table, LogEntries::UserId)
+                            .to(User::Table, User::Id)
+                            .on_delete(ForeignKeyAction::SetNull),
+                    )
+                    .to_owned(),
+            )
+            .await?;
+
+        // オブジェクトごとの履歴ページ用
+        manager
+            .create_index(
+                Index::create()
+                    .name("idx-log_entries-content_type-object_id")
+                    .table(LogEntries::Table)
+                    .col(LogEntries::ContentType)
+                    .col(LogEntries::ObjectId)
+                    .to_owned(),
+            )
+            .await
+    }
+
+    async fn down(&self, manager: &SchemaManager) -> Result<(), DbErr> {
+        manager
+            .drop_table(Table::drop().table(LogEntries::Table).to_owned())
+            .await
+    }
+}
+
+#[derive(Iden)]
+enum LogEntries {
+    Table,
+    Id,
+    ActionTime,
+    UserId,
+    ContentType,
+    ObjectId,
+    ObjectRepr,
+    ActionFlag,
+    ChangeMessage,
+}
+
+#[derive(Iden)]
+enum User {
+    Table,
+    Id,
+}
@@ -0,0 +1,50 @@
+use sea_orm_migration::prelude::*;
+
+/// ユーザーに有効化した日時を追加する。
+/// 有効化リンクはこの値をHMACに含めるため、一度有効化した後に無効化されたユーザーでは再利用できません。
+/// 既存の有効なユーザーは有効化済みとして扱います。
+#[derive(DeriveMigrationName)]
+pub struct Migration;
+
+#[async_trait::async_trait]
+impl MigrationTrait for Migration {
+    async fn up(&self, manager: &SchemaManager) -> Result<(), DbErr> {
+        manager
+            .alter_table(
+                Table::alter()
+                    .table(User::Table)
+                    .add_column_if_not_exists(ColumnDef::new(User::ActivatedAt).timestamp_with_time_zone().null())
+                    .to_owned(),
+            )
+            .await?;
+
+        manager
+            .exec_stmt(
+                Query::update()
+                    .table(User::Table)
+                    .value(User::ActivatedAt, Expr::current_timestamp())
+                    .and_where(Expr::col(User::IsActive).eq(true))
+                    .and_where(Expr::col(User::ActivatedAt).is_null())
+                    .to_owned(),
+            )
+            .await
+    }
+
+    async fn down(&self, manager: &SchemaManager) -> Result<(), DbErr> {
+        manager
+            .alter_table(
+                Table::alter()
+                    .table(User::Table)
+                    .drop_column(User::ActivatedAt)
+                    .to_owned(),
+            )
+            .await
+    }
+}
+
+#[derive(Iden)]
+enum User {
+    Table,
+    IsActive,
+    ActivatedAt,
+}
@@ -0,0 +1,274 @@
+use std::collections::HashMap;
+use rocket::http::{ContentType, Header};
+use rocket::request::{FromRequest, Outcome, Request};
+use rocket::response::{Flash, Redirect};
+use rocket::serde::json::serde_json;
+use rocket::{Orbit, Rocket};
+use sea_orm::*;
+use serde::Serialize;
+use crate::admin::deletion;
+use crate::admin::options::AdminEntity;
+use crate::admin::sites::{AdminSite, ModelOptions};
+use crate::entities::user;
+use crate::services::admin_log::{self, AdminLogService};
+use crate::views::app_template::AppTemplate;
+
+/// 一括操作の結果。Djangoのアクションが返すレスポンスと `message_user` に相当します
+#[allow(clippy::large_enum_variant)]
+pub enum ActionResponse {
+    /// 一覧に戻り、メッセージを表示します
+    Success(String),
+    Warning(String),
+    Error(String),
+    /// ファイルをダウンロードさせます (エクスポートなど)
+    Download {
+        filename: String,
+        content_type: ContentType,
+        body: String,
+    },
+}
+
+/// 一括操作の対象と実行時の情報 (Djangoのアクションの `request` と `queryset`)
+pub struct ActionRequest<'a, E: EntityTrait> {
+    /// 設定やセッションなど、アプリの状態 (`rocket.state::<T>()`) を参照するために使います
+    pub rocket: &'a Rocket<Orbit>,
+    pub site: &'a AdminSite,
+    pub opts: &'a ModelOptions,
+    /// 操作したユーザー
+    pub actor: &'a user::Model,
+    /// 選択したオブジェクト
+    pub objects: Vec<E::Model>,
+    /// 確認画面で入力した値 (`action` / `selected_ids` / `post` 以外のフィールド)
+    pub data: &'a HashMap<String, String>,
+}
+
+/// 確認画面の選択欄 (例: 追加するグループ)
+#[derive(Serialize, Clone, Debug)]
+pub struct ActionField {
+    pub name: &'static str,
+    pub label: &'static str,
+    /// `(値, 表示名)`
+    pub choices: Vec<(String, String)>,
+}
+
+/// 一覧の一括操作。Djangoの `@admin.action` を付けた関数に相当し、
+/// `ModelAdmin::actions` で返すと一覧の「アクション」に表示されます。
+///
+/// ```ignore
+/// struct MarkCompleted;
+///
+/// #[rocket::async_trait]
+/// impl AdminAction<Todo> for MarkCompleted {
+///     fn name(&self) -> &'static str { "mark_completed" }
+///     fn description(&self) -> &'static str { "選択されたTODOを完了にする" }
+///     async fn run(&self, db: &DatabaseConnection, request: &ActionRequest<'_, Todo>) -> Result<ActionResponse, DbErr> {
+///         // ...
+///         Ok(ActionResponse::Success(format!("{} 件のTODOを完了にしました", request.objects.len())))
+///     }
+/// }
+/// ```
+#[rocket::async_trait]
+pub trait AdminAction<E: AdminEntity>: Send + Sync + 'static {
+    /// フォームで送信する名前
+    fn name(&self) -> &'static str;
+
+    /// 一覧の選択肢に表示する名前 (Djangoの `description`)
+    fn description(&self) -> &'static str;
+
+    /// 実行に必要な権限 (`view` / `add` / `change` / `delete`)。Djangoの `permissions`
+    fn permission(&self) -> &'static str {
+        "change"
+    }
+
+    /// 実行前に確認画面を表示するか。確認画面から送信されるまで (`post=yes`) は実行しません
+    fn confirmation(&self) -> bool {
+        false
+    }
+
+    /// 確認画面のテンプレート
+    fn confirmation_template(&self) -> &'static str {
+        "admin/action_confirmation"
+    }
+
+    /// 確認画面の追加のコンテキスト。選択欄は `fields` (`ActionField` の配列) で指定します
+    async fn confirmation_context(&self, _db: &DatabaseConnection, _request: &ActionRequest<'_, E>) -> Result<serde_json::Value, DbErr> {
+        Ok(serde_json::json!({}))
+    }
+
+    /// 選択したオブジェクトに対して実行します
+    async fn run(&self, db: &DatabaseConnection, request: &ActionRequest<'_, E>) -> Result<ActionResponse, DbErr>;
+}
+
+/// アプリ (`Rocket<Orbit>`) を取得するガード。一括操作から設定やセッションを参照するために使います
+pub struct App<'r>(pub &'r Rocket<Orbit>);
+
+#[rocket::async_trait]
+impl<'r> FromRequest<'r> for App<'r> {
+    type Error = ();
+
+    async fn from_request(request: &'r Request<'_>) -> Outcome<Self, Self::Error> {
+        Outcome::Success(App(request.rocket()))
+    }
+}
+
+/// 一覧に表示する一括操作
+#[derive(Serialize, Clone, Debug)]
+pub struct ActionInfo {
+    pub name: &'static str,
+    pub description: &'static str,
+    pub permission: &'static str,
+}
+
+/// ダウンロードのレスポンス
+#[derive(Responder)]
+pub struct Download {
+    body: (ContentType, String),
+    disposition: Header<'static>,
+}
+
+/// 一括操作のレスポンス (一覧へのリダイレクト・確認画面・ダウンロード)
+#[derive(Responder)]
+#[allow(clippy::large_enum_variant)]
+pub enum ActionOutcome {
+    Redirect(Flash<Redirect>),
+    Page(AppTemplate),
+    Download(Download),
+}
+
+impl ActionOutcome {
+    /// 結果を一覧へのリダイレクトかダウンロードにします
+    pub fn from_response(response: ActionResponse, redirect_to: String) -> Self {
+        match response {
+            ActionResponse::Success(message) => ActionOutcome::Redirect(Flash::success(Redirect::to(redirect_to), message)),
+            ActionResponse::Warning(message) => ActionOutcome::Redirect(Flash::warning(Redirect::to(redirect_to), message)),
+            ActionResponse::Error(message) => ActionOutcome::Redirect(Flash::error(Redirect::to(redirect_to), message)),
+            ActionResponse::Download { filename, content_type, body } => ActionOutcome::Download(Download {
+                body: (content_type, body),
+                disposition: Header::new("Content-Disposition", format!("attachment; filename=\"{}\"", filename)),
+            }),
+        }
+    }
+}
+
+fn to_json<M: Serialize>(objects: &[M]) -> Vec<serde_json::Value> {
+    objects.iter().map(|o| serde_json::to_value(o).unwrap_or_default()).collect()
+}
+
+/// 選択したオブジェクトの削除 (Djangoの `delete_selected`)。全てのモデルの一覧で使えます。
+/// 確認画面には一緒に削除されるオブジェクトを表示し、削除を妨げるオブジェクトがあれば削除しません
+pub struct DeleteSelected;
+
+#[rocket::async_trait]
+impl<E: AdminEntity> AdminAction<E> for DeleteSelected {
+    fn name(&self) -> &'static str {
+        "delete_selected"
+    }
+
+    fn description(&self) -> &'static str {
+        "選択されたオブジェクトを削除"
+    }
+
+    fn permission(&self) -> &'static str {
+        "delete"
+    }
+
+    fn confirmation(&self) -> bool {
+        true
+    }
+
+    fn confirmation_template(&self) -> &'static str {
+        "admin/delete_selected_confirmation"
+    }
+
+    async fn confirmation_context(&self, db: &DatabaseConnection, request: &ActionRequest<'_, E>) -> Result<serde_json::Value, DbErr> {
+        let mut deleted = deletion::collect(db, E::default().table_name(), &to_json(&request.objects)).await?;
+        request.site.describe(&mut deleted);
+        Ok(serde_json::json!({
+            "deleted_objects": deleted.objects,
+            "model_count": deleted.model_count(),
+            "protected": deleted.protected,
+        }))
+    }
+
+    async fn run(&self, db: &DatabaseConnection, request: &ActionRequest<'_, E>) -> Result<ActionResponse, DbErr> {
+        let Some(pk) = <E::PrimaryKey as Iterable>::iter().next().map(|pk| pk.into_column()) else {
+            return Ok(ActionResponse::Warning("主キーのないモデルは削除できません".to_string()));
+        };
+        let objects = to_json(&request.objects);
+        let ids: Vec<i32> = objects.iter().filter_map(|o| admin_log::object_id(o).parse().ok()).collect();
+
+        let txn = db.begin().await?;
+        if deletion::collect(&txn, E::default().table_name(), &objects).await?.is_protected() {
+            return Ok(ActionResponse::Error(deletion::PROTECTED_MESSAGE.to_string()));
+        }
+        let result = E::delete_many().filter(pk.is_in(ids)).exec(&txn).await?;
+        for object in &objects {
+            AdminLogService::log_deletion(&txn, request.actor.id, &request.opts.model_name, object).await?;
+        }
+        txn.commit().await?;
+        Ok(ActionResponse::Success(format!("{} 件の{}を削除しました", result.rows_affected, request.opts.verbose_name)))
+    }
+}
+
+/// 選択したオブジェクトをCSVでダウンロードします。`fields` はカラム名 (モデルのJSONのキー)
+pub struct ExportCsv {
+    pub fields: &'static [&'static str],
+}
+
+/// CSVの値 (カンマ・引用符・改行を含む場合は引用符で囲む)
+fn csv_field(value: &serde_json::Value) -> String {
+    let value = match value {
+        serde_json::Value::Null => String::new(),
+        serde_json::Value::String(s) => s.clone(),
+        value => value.to_string(),
+    };
+    if value.contains([',', '"', '\n', '\r']) {
+        format!("\"{}\"", value.replace('"', "\"\""))
+    } else {
+        value
+    }
+}
+
+#[rocket::async_trait]
+impl<E: AdminEntity> AdminAction<E> for ExportCsv {
+    fn name(&self) -> &'static str {
+        "export_csv"
+    }
+
+    fn description(&self) -> &'static str {
+        "選択されたオブジェクトをCSVでエクスポート"
+    }
+
+    fn permission(&self) -> &'static str {
+        "view"
+    }
+
+    async fn run(&self, _db: &DatabaseConnection, request: &ActionRequest<'_, E>) -> Result<ActionResponse, DbErr> {
+        let mut body = self.fields.join(",");
+        body.push_str("\r\n");
+        for object in to_json(&request.objects) {
+            let row: Vec<String> = self.fields.iter().map(|field| csv_field(&object[*field])).collect();
+            body.push_str(&row.join(","));
+            body.push_str("\r\n");
+        }
+        Ok(ActionResponse::Download {
+            filename: format!("{}.csv", request.opts.model_name),
+            content_type: ContentType::CSV,
+            body,
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_csv_field_quotes_special_characters() {
+        assert_eq!(csv_field(&serde_json::json!("alice")), "alice");
+        assert_eq!(csv_field(&serde_json::json!("a,b")), "\"a,b\"");
+        assert_eq!(csv_field(&serde_json::json!("say \"hi\"")), "\"say \"\"hi\"\"\"");
+        assert_eq!(csv_field(&serde_json::json!(true)), "true");
+        assert_eq!(csv_field(&serde_json::Value::Null), "");
+    }
+}
@@ -0,0 +1,275 @@
+use std::future::Future;
+use std::pin::Pin;
+use rocket::serde::json::serde_json;
+use sea_orm::sea_query::{Asterisk, Expr, ForeignKeyAction, Query, TableRef};
+use sea_orm::*;
+use serde::Serialize;
+use crate::entities::*;
+use crate::services::admin_log;
+
+/// 削除を妨げるオブジェクトがある場合のメッセージ
+pub const PROTECTED_MESSAGE: &str = "他のオブジェクトから参照されているため削除できません";
+
+/// 関連をたどる深さの上限 (循環する外部キーがあっても止まるように)
+const MAX_DEPTH: usize = 8;
+
+/// 削除で一緒に削除・変更されるオブジェクト。
+/// `related` にはこのオブジェクトを参照しているオブジェクトが入ります
+#[derive(Serialize, Clone, Debug)]
+pub struct DeletedObject {
+    /// テーブル名 (操作履歴の `content_type` と同じ)
+    pub model_name: String,
+    /// モデルの表示名。`AdminSite` に登録していないモデルはテーブル名
+    pub verbose_name: String,
+    pub object_id: String,
+    /// 表示名。`username` / `name` / `title` も主キーもない場合は空
+    pub repr: String,
+    /// 管理画面の編集画面のURL (登録しているモデルのみ)
+    pub url: Option<String>,
+    /// 削除されずに参照が NULL になる (`on_delete = "SetNull"`)
+    pub set_null: bool,
+    pub related: Vec<DeletedObject>,
+}
+
+impl DeletedObject {
+    fn new(model_name: &str, row: &serde_json::Value) -> Self {
+        let object_id = admin_log::object_id(row);
+        let repr = admin_log::object_repr(row);
+        DeletedObject {
+            model_name: model_name.to_string(),
+            verbose_name: model_name.to_string(),
+            repr: if object_id.is_empty() && repr == "#" { String::new() } else { repr },
+            object_id,
+            url: None,
+            set_null: false,
+            related: Vec::new(),
+        }
+    }
+
+    fn for_each_mut(&mut self, f: &mut impl FnMut(&mut DeletedObject)) {
+        f(self);
+        for object in &mut self.related {
+            object.for_each_mut(f);
+        }
+    }
+}
+
+/// 削除の確認画面に表示する内容。
+/// Djangoの `django.contrib.admin.utils.get_deleted_objects` の戻り値に相当します
+#[derive(Serialize, Clone, Debug, Default)]
+pub struct DeletedObjects {
+    /// 削除するオブジェクト (関連するオブジェクトを入れ子で含む)
+    pub objects: Vec<DeletedObject>,
+    /// 削除を妨げるオブジェクト (Djangoの `protected`)。1件でもあれば削除できません
+    pub protected: Vec<DeletedObject>,
+}
+
+/// 外部キーの `on_delete` による削除時の扱い (Djangoの `CASCADE` / `SET_NULL` / `PROTECT`)
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+enum OnDelete {
+    Cascade,
+    SetNull,
+    Protect,
+}
+
+impl OnDelete {
+    /// `on_delete` の指定がない外部キーは、DBのデフォルト (`NO ACTION`) と同じく削除を妨げます
+    fn from_action(action: Option<&ForeignKeyAction>) -> Self {
+        match action {
+            Some(ForeignKeyAction::Cascade) => OnDelete::Cascade,
+            Some(ForeignKeyAction::SetNull | ForeignKeyAction::SetDefault) => OnDelete::SetNull,
+            _ => OnDelete::Protect,
+        }
+    }
+}
+
+/// エンティティのモジュール名と `Relation` の定義
+macro_rules! entity_relations {
+    ($($entity:ident),* $(,)?) => {
+        vec![$((stringify!($entity), $entity::Relation::iter().map(|r| r.def()).collect::<Vec<RelationDef>>())),*]
+    };
+}
+
+/// 全てのエンティティのリレーション。
+/// **エンティティを追加したらここにも追加してください。** 漏れたエンティティの参照は削除の確認画面に表示されず、
+/// 削除を妨げる参照はDBのエラーになります (`entities/mod.rs` との照合はテストで行います)
+fn entities() -> Vec<(&'static str, Vec<RelationDef>)> {
+    entity_relations![
+        user,
+        todo,
+        group,
+        group_user,
+        permission,
+        group_permission,
+        user_permission,
+        session,
+        login_failure,
+        otp_recovery_code,
+        api_token,
+        api_token_permission,
+        user_identity,
+        log_entry,
+    ]
+}
+
+/// 全てのエンティティの `belongs_to` のリレーション。
+/// `has_many` の定義には `on_delete` が含まれないため、参照する側の定義からたどります
+fn foreign_keys() -> Vec<RelationDef> {
+    entities().into_iter().flat_map(|(_, relations)| relations).filter(|rel| !rel.is_owner).collect()
+}
+
+fn table(table: &TableRef) -> Option<&DynIden> {
+    match table {
+        TableRef::Table(name) => Some(name),
+        _ => None,
+    }
+}
+
+fn table_name(table_ref: &TableRef) -> Option<String> {
+    table(table_ref).map(|name| name.to_string())
+}
+
+fn unary(identity: &Identity) -> Option<DynIden> {
+    match identity {
+        Identity::Unary(column) => Some(column.clone()),
+        _ => None,
+    }
+}
+
+/// 主キーなどのJSONの値をクエリの値にする
+fn json_to_value(value: &serde_json::Value) -> Option<Value> {
+    match value {
+        serde_json::Value::Number(n) => n.as_i64().map(Value::from),
+        serde_json::Value::String(s) => Some(Value::from(s.clone())),
+        _ => None,
+    }
+}
+
+/// `objects` (テーブル `model_name` の行のJSON) を削除したときに、
+/// 一緒に削除・変更されるオブジェクトと削除を妨げるオブジェクトを集めます。
+/// Djangoの `Collector.collect` と同じく、`on_delete` が `Cascade` の参照は再帰的にたどります
+pub async fn collect<C: ConnectionTrait>(db: &C, model_name: &str, objects: &[serde_json::Value]) -> Result<DeletedObjects, DbErr> {
+    let foreign_keys = foreign_keys();
+    let mut deleted = DeletedObjects::default();
+    for row in objects {
+        let mut object = DeletedObject::new(model_name, row);
+        object.related = collect_related(db, &foreign_keys, model_name, row, &mut deleted.protected, 0).await?;
+        deleted.objects.push(object);
+    }
+    Ok(deleted)
+}
+
+type RelatedFuture<'a> = Pin<Box<dyn Future<Output = Result<Vec<DeletedObject>, DbErr>> + Send + 'a>>;
+
+/// `row` を参照しているオブジェクト。削除を妨げるものは `protected` に追加します
+fn collect_related<'a, C: ConnectionTrait>(
+    db: &'a C,
+    foreign_keys: &'a [RelationDef],
+    model_name: &'a str,
+    row: &'a serde_json::Value,
+    protected: &'a mut Vec<DeletedObject>,
+    depth: usize,
+) -> RelatedFuture<'a> {
+    Box::pin(async move {
+        let mut related = Vec::new();
+        if depth >= MAX_DEPTH {
+            return Ok(related);
+        }
+        for fk in foreign_keys.iter().filter(|fk| table_name(&fk.to_tbl).as_deref() == Some(model_name)) {
+            let (Some(from_table), Some(column), Some(to_column)) = (table(&fk.from_tbl), unary(&fk.from_col), unary(&fk.to_col)) else {
+                continue;
+            };
+            let table = from_table.to_string();
+            let Some(value) = json_to_value(&row[to_column.to_string().as_str()]) else { continue };
+            let query = Query::select()
+                .column(Asterisk)
+                .from(from_table.clone())
+                .and_where(Expr::col(column).eq(value))
+                .to_owned();
+            let rows = <serde_json::Value as FromQueryResult>::find_by_statement(db.get_database_backend().build(&query)).all(db).await?;
+
+            let on_delete = OnDelete::from_action(fk.on_delete.as_ref());
+            for child in &rows {
+                let mut object = DeletedObject::new(&table, child);
+                match on_delete {
+                    OnDelete::Cascade => {
+                        object.related = collect_related(db, foreign_keys, &table, child, protected, depth + 1).await?;
+                        related.push(object);
+                    }
+                    OnDelete::SetNull => {
+                        object.set_null = true;
+                        related.push(object);
+                    }
+                    OnDelete::Protect => protected.push(object),
+                }
+            }
+        }
+        Ok(related)
+    })
+}
+
+impl DeletedObjects {
+    /// 削除を妨げるオブジェクトがあるか
+    pub fn is_protected(&self) -> bool {
+        !self.protected.is_empty()
+    }
+
+    /// 全てのオブジェクト (入れ子と削除を妨げるものを含む) を変更します。表示名やURLの設定に使います
+    pub fn for_each_mut(&mut self, mut f: impl FnMut(&mut DeletedObject)) {
+        for object in self.objects.iter_mut().chain(self.protected.iter_mut()) {
+            object.for_each_mut(&mut f);
+        }
+    }
+
+    /// 削除されるオブジェクトのモデルごとの件数 (Djangoの `model_count`)。
+    /// 参照が NULL になるだけのオブジェクトは数えません
+    pub fn model_count(&self) -> Vec<(String, usize)> {
+        fn count(objects: &[DeletedObject], counts: &mut Vec<(String, usize)>) {
+            for object in objects.iter().filter(|o| !o.set_null) {
+                match counts.iter_mut().find(|(name, _)| *name == object.verbose_name) {
+                    Some((_, n)) => *n += 1,
+                    None => counts.push((object.verbose_name.clone(), 1)),
+                }
+                count(&object.related, counts);
+            }
+        }
+        let mut counts = Vec::new();
+        count(&self.objects, &mut counts);
+        counts
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_every_entity_is_listed() {
+        let modules: Vec<&str> = include_str!("../entities/mod.rs")
+            .lines()
+            .filter_map(|line| line.trim().strip_prefix("pub mod ")?.strip_suffix(';'))
+            .filter(|name| *name != "prelude")
+            .collect();
+        let listed: Vec<&str> = entities().iter().map(|(name, _)| *name).collect();
+        for module in modules {
+            assert!(listed.contains(&module), "entities/{}.rs が deletion::entities() にありません", module);
+        }
+    }
+
+    #[test]
+    fn test_foreign_keys_follow_on_delete() {
+        let on_delete = |from: &str, to: &str| {
+            let fk = foreign_keys()
+                .into_iter()
+                .find(|fk| table_name(&fk.from_tbl).as_deref() == Some(from) && table_name(&fk.to_tbl).as_deref() == Some(to))
+                .unwrap();
+            OnDelete::from_action(fk.on_delete.as_ref())
+        };
+        assert_eq!(on_delete("todo", "group"), OnDelete::Cascade);
+        assert_eq!(on_delete("todo", "user"), OnDelete::Cascade);
+        assert_eq!(on_delete("group_permissions", "group"), OnDelete::Cascade);
+        assert_eq!(on_delete("log_entries", "user"), OnDelete::SetNull);
+        assert_eq!(OnDelete::from_action(None), OnDelete::Protect);
+        assert_eq!(OnDelete::from_action(Some(&ForeignKeyAction::Restrict)), OnDelete::Protect);
+    }
+}
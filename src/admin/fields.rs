@@ -0,0 +1,367 @@
+use chrono::{NaiveDate, NaiveDateTime};
+use rocket::serde::json::serde_json;
+use sea_orm::sea_query::{Asterisk, ColumnType, Expr, Query, TableRef};
+use sea_orm::*;
+use serde::Serialize;
+use crate::services::admin_log;
+use crate::validation::FieldErrors;
+
+/// 必須のフィールドが空の場合のメッセージ (Djangoの `required` エラー)
+const REQUIRED_MESSAGE: &str = "このフィールドは必須です。";
+
+/// 選択肢にない値が送信された場合のメッセージ (Djangoの `invalid_choice` エラー)
+pub const INVALID_CHOICE_MESSAGE: &str = "正しく選択してください。選択したものは候補にありません。";
+
+/// `datetime-local` の入力欄の書式。タイムゾーンのない値はUTCとして扱います
+const DATETIME_INPUT_FORMATS: [&str; 4] = ["%Y-%m-%dT%H:%M", "%Y-%m-%dT%H:%M:%S", "%Y-%m-%d %H:%M", "%Y-%m-%d %H:%M:%S"];
+
+/// フォームの入力欄の種類 (Djangoの `Widget`)
+#[derive(Serialize, Clone, Copy, Debug, PartialEq, Eq)]
+#[serde(rename_all = "snake_case")]
+pub enum Widget {
+    Text,
+    Textarea,
+    Number,
+    Checkbox,
+    Datetime,
+    Date,
+    /// 外部キー (`belongs_to`)
+    Select,
+    /// 中間テーブルを介した多対多
+    SelectMultiple,
+}
+
+impl Widget {
+    /// カラムの型に対応する入力欄。フォームで編集できない型は `None`
+    pub fn for_column_type(column_type: &ColumnType) -> Option<Widget> {
+        match column_type {
+            ColumnType::Char(_) | ColumnType::String(_) => Some(Widget::Text),
+            ColumnType::Text => Some(Widget::Textarea),
+            ColumnType::SmallInteger
+            | ColumnType::Integer
+            | ColumnType::BigInteger
+            | ColumnType::Float
+            | ColumnType::Double => Some(Widget::Number),
+            ColumnType::Boolean => Some(Widget::Checkbox),
+            ColumnType::DateTime | ColumnType::Timestamp | ColumnType::TimestampWithTimeZone => Some(Widget::Datetime),
+            ColumnType::Date => Some(Widget::Date),
+            _ => None,
+        }
+    }
+}
+
+/// 編集画面に表示するフィールド
+#[derive(Serialize, Clone, Debug)]
+pub struct FormField {
+    pub name: String,
+    pub label: String,
+    pub widget: Widget,
+    pub required: bool,
+    /// 値を表示するだけで変更できない (`readonly_fields` とフォームで編集できない型)
+    pub readonly: bool,
+    /// `select` の選択肢 (`(値, 表示名)`)
+    pub choices: Vec<(String, String)>,
+}
+
+/// 外部キーの参照先。モデルの `belongs_to` のリレーションから作ります
+#[derive(Clone, Debug)]
+pub struct ForeignKey {
+    /// 参照元のカラム
+    pub column: DynIden,
+    /// 参照先のテーブルとカラム
+    pub table: DynIden,
+    pub to_column: DynIden,
+}
+
+impl ForeignKey {
+    /// `belongs_to` のリレーションの場合のみ `Some` (`has_many` や複合キーは対象外)
+    pub fn from_relation(rel: &RelationDef) -> Option<Self> {
+        if rel.is_owner {
+            return None;
+        }
+        match (&rel.from_col, &rel.to_tbl, &rel.to_col) {
+            (Identity::Unary(column), TableRef::Table(table), Identity::Unary(to_column)) => Some(ForeignKey {
+                column: column.clone(),
+                table: table.clone(),
+                to_column: to_column.clone(),
+            }),
+            _ => None,
+        }
+    }
+
+    /// 参照先の全ての行を選択肢にします。表示名は操作履歴と同じ (`username` / `name` / `title`)
+    pub async fn choices<C: ConnectionTrait>(&self, db: &C) -> Result<Vec<(String, String)>, DbErr> {
+        let query = Query::select()
+            .column(Asterisk)
+            .from(self.table.clone())
+            .order_by(self.to_column.clone(), Order::Asc)
+            .to_owned();
+        let rows = <serde_json::Value as FromQueryResult>::find_by_statement(db.get_database_backend().build(&query)).all(db).await?;
+        let key = self.to_column.to_string();
+        Ok(rows
+            .iter()
+            .map(|row| (json_to_string(&row[&key]), admin_log::object_repr(row)))
+            .collect())
+    }
+
+    /// 参照先に値が存在するか
+    pub async fn exists<C: ConnectionTrait>(&self, db: &C, value: Value) -> Result<bool, DbErr> {
+        let query = Query::select()
+            .expr(Expr::val(1))
+            .from(self.table.clone())
+            .and_where(Expr::col(self.to_column.clone()).eq(value))
+            .limit(1)
+            .to_owned();
+        Ok(db.query_one(db.get_database_backend().build(&query)).await?.is_some())
+    }
+}
+
+/// 中間テーブルを介した多対多のフィールド (Djangoの `ManyToManyField`)。
+/// `ModelAdmin::many_to_many` で指定すると、複数選択の入力欄で中間テーブルの行を置き換えます。
+///
+/// ```ignore
+/// ManyToMany::new("permissions", group_permission::Relation::Group.def(), group_permission::Relation::Permission.def())
+/// ```
+#[derive(Clone, Debug)]
+pub struct ManyToMany {
+    pub name: &'static str,
+    /// 中間テーブルと、このモデルを参照するカラム
+    join_table: DynIden,
+    owner_column: DynIden,
+    /// 中間テーブルから選択するモデルへの外部キー
+    target: ForeignKey,
+}
+
+impl ManyToMany {
+    /// `owner` は中間テーブルからこのモデルへの、`target` は選択するモデルへの `belongs_to` のリレーション
+    pub fn new(name: &'static str, owner: RelationDef, target: RelationDef) -> Self {
+        let TableRef::Table(join_table) = &owner.from_tbl else {
+            panic!("The join table of {} must not have a schema or alias", name);
+        };
+        let join_table = join_table.clone();
+        let owner = ForeignKey::from_relation(&owner).expect("owner must be a belongs_to relation of the join entity");
+        let target = ForeignKey::from_relation(&target).expect("target must be a belongs_to relation of the join entity");
+        ManyToMany { name, join_table, owner_column: owner.column, target }
+    }
+
+    pub async fn choices<C: ConnectionTrait>(&self, db: &C) -> Result<Vec<(String, String)>, DbErr> {
+        self.target.choices(db).await
+    }
+
+    /// 選択されているモデルのID
+    pub async fn selected<C: ConnectionTrait>(&self, db: &C, object_id: i32) -> Result<Vec<i32>, DbErr> {
+        let query = Query::select()
+            .column(self.target.column.clone())
+            .from(self.join_table.clone())
+            .and_where(Expr::col(self.owner_column.clone()).eq(object_id))
+            .order_by(self.target.column.clone(), Order::Asc)
+            .to_owned();
+        let rows = <serde_json::Value as FromQueryResult>::find_by_statement(db.get_database_backend().build(&query)).all(db).await?;
+        let key = self.target.column.to_string();
+        Ok(rows.iter().filter_map(|row| row[&key].as_i64()).map(|id| id as i32).collect())
+    }
+
+    /// 中間テーブルの行を `ids` で置き換えます (Djangoの `ModelAdmin.save_related`)
+    pub async fn save<C: ConnectionTrait>(&self, db: &C, object_id: i32, ids: &[i32]) -> Result<(), DbErr> {
+        let backend = db.get_database_backend();
+        let delete = Query::delete()
+            .from_table(self.join_table.clone())
+            .and_where(Expr::col(self.owner_column.clone()).eq(object_id))
+            .to_owned();
+        db.execute(backend.build(&delete)).await?;
+        if ids.is_empty() {
+            return Ok(());
+        }
+
+        let mut insert = Query::insert();
+        insert.into_table(self.join_table.clone()).columns([self.owner_column.clone(), self.target.column.clone()]);
+        for id in ids {
+            insert.values_panic([object_id.into(), (*id).into()]);
+        }
+        db.execute(backend.build(&insert)).await?;
+        Ok(())
+    }
+
+    /// 送信された値 (文字列か文字列の配列) をIDにし、選択肢にあるか確認します
+    pub async fn clean<C: ConnectionTrait>(&self, db: &C, raw: &serde_json::Value) -> Result<Vec<i32>, String> {
+        let ids: Vec<i32> = json_to_list(raw)
+            .iter()
+            .filter(|s| !s.is_empty())
+            .map(|s| s.parse::<i32>())
+            .collect::<Result<_, _>>()
+            .map_err(|_| INVALID_CHOICE_MESSAGE.to_string())?;
+        for id in &ids {
+            if !self.target.exists(db, (*id).into()).await.map_err(|e| e.to_string())? {
+                return Err(INVALID_CHOICE_MESSAGE.to_string());
+            }
+        }
+        Ok(ids)
+    }
+}
+
+/// カラムの定義から、フォームで受け取った値をDBの値に変換します。
+/// Djangoの `Field.clean()` (`to_python()` と `required` のチェック) に相当します。
+///
+/// `raw` はフォームの値をJSONにしたもの (文字列、チェックボックスは未送信なら `null`)。
+/// 空欄はNULL可のカラムでは `NULL`、それ以外ではエラーになります。
+pub fn parse_value(def: &ColumnDef, raw: &serde_json::Value) -> Result<Value, String> {
+    let column_type = def.get_column_type();
+    if *column_type == ColumnType::Boolean {
+        let checked = match raw {
+            serde_json::Value::Bool(b) => *b,
+            serde_json::Value::String(s) => matches!(s.as_str(), "true" | "on" | "1"),
+            _ => false,
+        };
+        return Ok(Value::Bool(Some(checked)));
+    }
+
+    let text = match raw {
+        serde_json::Value::String(s) => s.trim().to_string(),
+        serde_json::Value::Null => String::new(),
+        other => other.to_string(),
+    };
+    if text.is_empty() {
+        return if def.is_null() { null_value(column_type) } else { Err(REQUIRED_MESSAGE.to_string()) };
+    }
+
+    let invalid = |_| "数値を入力してください。".to_string();
+    match column_type {
+        ColumnType::Char(_) | ColumnType::String(_) | ColumnType::Text => Ok(Value::String(Some(Box::new(text)))),
+        ColumnType::SmallInteger => text.parse::<i16>().map(|v| Value::SmallInt(Some(v))).map_err(|_| "整数を入力してください。".to_string()),
+        ColumnType::Integer => text.parse::<i32>().map(|v| Value::Int(Some(v))).map_err(|_| "整数を入力してください。".to_string()),
+        ColumnType::BigInteger => text.parse::<i64>().map(|v| Value::BigInt(Some(v))).map_err(|_| "整数を入力してください。".to_string()),
+        ColumnType::Float => text.parse::<f32>().map(|v| Value::Float(Some(v))).map_err(invalid),
+        ColumnType::Double => text.parse::<f64>().map(|v| Value::Double(Some(v))).map_err(invalid),
+        ColumnType::DateTime | ColumnType::Timestamp => {
+            parse_datetime(&text).map(|v| Value::ChronoDateTime(Some(Box::new(v))))
+        }
+        ColumnType::TimestampWithTimeZone => {
+            parse_datetime(&text).map(|v| Value::ChronoDateTimeWithTimeZone(Some(Box::new(v.and_utc().fixed_offset()))))
+        }
+        ColumnType::Date => NaiveDate::parse_from_str(&text, "%Y-%m-%d")
+            .map(|v| Value::ChronoDate(Some(Box::new(v))))
+            .map_err(|_| "正しい日付を入力してください。".to_string()),
+        _ => Err("このフィールドはフォームで編集できません。".to_string()),
+    }
+}
+
+/// 日時の入力を解釈する。RFC 3339 の値はUTCに変換します
+fn parse_datetime(text: &str) -> Result<NaiveDateTime, String> {
+    if let Ok(v) = chrono::DateTime::parse_from_rfc3339(text) {
+        return Ok(v.naive_utc());
+    }
+    DATETIME_INPUT_FORMATS
+        .iter()
+        .find_map(|format| NaiveDateTime::parse_from_str(text, format).ok())
+        .ok_or_else(|| "正しい日時を入力してください。".to_string())
+}
+
+/// 型ごとのNULL (ActiveModelには型の合った値を設定する必要がある)
+fn null_value(column_type: &ColumnType) -> Result<Value, String> {
+    match column_type {
+        ColumnType::Char(_) | ColumnType::String(_) | ColumnType::Text => Ok(Value::String(None)),
+        ColumnType::SmallInteger => Ok(Value::SmallInt(None)),
+        ColumnType::Integer => Ok(Value::Int(None)),
+        ColumnType::BigInteger => Ok(Value::BigInt(None)),
+        ColumnType::Float => Ok(Value::Float(None)),
+        ColumnType::Double => Ok(Value::Double(None)),
+        ColumnType::DateTime | ColumnType::Timestamp => Ok(Value::ChronoDateTime(None)),
+        ColumnType::TimestampWithTimeZone => Ok(Value::ChronoDateTimeWithTimeZone(None)),
+        ColumnType::Date => Ok(Value::ChronoDate(None)),
+        _ => Err("このフィールドはフォームで編集できません。".to_string()),
+    }
+}
+
+/// モデルやフォームの値を入力欄に表示する形にします (Djangoの `Widget.format_value()`)。
+/// 日時は `datetime-local` の書式に、選択肢の値は文字列にそろえます
+pub fn format_value(widget: Widget, value: serde_json::Value) -> serde_json::Value {
+    match (widget, value) {
+        (Widget::Datetime, serde_json::Value::String(s)) => match chrono::DateTime::parse_from_rfc3339(&s) {
+            Ok(v) => serde_json::Value::String(v.naive_utc().format("%Y-%m-%dT%H:%M").to_string()),
+            Err(_) => serde_json::Value::String(s),
+        },
+        (Widget::Select, value @ serde_json::Value::Number(_)) => serde_json::Value::String(json_to_string(&value)),
+        (Widget::SelectMultiple, value) => json_to_list(&value).into_iter().map(serde_json::Value::String).collect(),
+        (_, value) => value,
+    }
+}
+
+fn json_to_string(value: &serde_json::Value) -> String {
+    match value {
+        serde_json::Value::String(s) => s.clone(),
+        serde_json::Value::Null => String::new(),
+        other => other.to_string(),
+    }
+}
+
+/// 複数選択の値 (1件だけ選択した場合は文字列で送信される)
+fn json_to_list(value: &serde_json::Value) -> Vec<String> {
+    match value {
+        serde_json::Value::Array(values) => values.iter().map(json_to_string).collect(),
+        serde_json::Value::Null => Vec::new(),
+        other => vec![json_to_string(other)],
+    }
+}
+
+/// フォームの値を `ActiveModel` に設定します (Djangoの `construct_instance()`)。
+/// `fields` に含まれるカラムだけを変更し、変換できない値はフィールドごとのエラーとして返します。
+pub fn construct_instance<A>(active_model: &mut A, fields: &[String], data: &serde_json::Value) -> Result<(), FieldErrors>
+where
+    A: ActiveModelTrait,
+{
+    let mut errors = FieldErrors::new();
+    for column in <A::Entity as EntityTrait>::Column::iter() {
+        let name = column.as_str();
+        if !fields.iter().any(|f| f == name) {
+            continue;
+        }
+        match parse_value(&column.def(), &data[name]) {
+            Ok(value) => active_model.set(column, value),
+            Err(message) => errors.add(name, message),
+        }
+    }
+    errors.into_result()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::entities::todo;
+
+    #[test]
+    fn test_parse_value_by_column_type() {
+        let title = todo::Column::Title.def();
+        let group_id = todo::Column::GroupId.def();
+        let completed = todo::Column::Completed.def();
+        let priority = todo::Column::Priority.def();
+
+        assert_eq!(parse_value(&title, &serde_json::json!(" 買い物 ")), Ok(Value::String(Some(Box::new("買い物".into())))));
+        assert_eq!(parse_value(&title, &serde_json::json!("")), Err(REQUIRED_MESSAGE.to_string()));
+        assert_eq!(parse_value(&group_id, &serde_json::json!("")), Ok(Value::Int(None)));
+        assert_eq!(parse_value(&priority, &serde_json::json!("3")), Ok(Value::Int(Some(3))));
+        assert!(parse_value(&priority, &serde_json::json!("high")).is_err());
+        // チェックボックスは未送信なら false
+        assert_eq!(parse_value(&completed, &serde_json::Value::Null), Ok(Value::Bool(Some(false))));
+        assert_eq!(parse_value(&completed, &serde_json::json!("true")), Ok(Value::Bool(Some(true))));
+    }
+
+    #[test]
+    fn test_datetime_values() {
+        let created_at = todo::Column::CreatedAt.def();
+        let expected = chrono::DateTime::parse_from_rfc3339("2026-01-02T03:04:00+00:00").unwrap();
+        assert_eq!(
+            parse_value(&created_at, &serde_json::json!("2026-01-02T03:04")),
+            Ok(Value::ChronoDateTimeWithTimeZone(Some(Box::new(expected))))
+        );
+        assert!(parse_value(&created_at, &serde_json::json!("yesterday")).is_err());
+
+        // モデルの値は `datetime-local` の書式で表示する
+        assert_eq!(
+            format_value(Widget::Datetime, serde_json::json!("2026-01-02T12:04:05.123+09:00")),
+            serde_json::json!("2026-01-02T03:04")
+        );
+        assert_eq!(format_value(Widget::Select, serde_json::json!(5)), serde_json::json!("5"));
+        assert_eq!(format_value(Widget::SelectMultiple, serde_json::json!("5")), serde_json::json!(["5"]));
+        assert_eq!(Widget::for_column_type(todo::Column::Description.def().get_column_type()), Some(Widget::Textarea));
+    }
+}
@@ -0,0 +1,10 @@
+pub mod actions;
+pub mod deletion;
+pub mod fields;
+pub mod options;
+pub mod sites;
+
+pub use actions::{ActionRequest, ActionResponse, AdminAction};
+pub use fields::ManyToMany;
+pub use options::{Fieldset, ModelAdmin};
+pub use sites::AdminSite;
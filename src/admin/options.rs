@@ -0,0 +1,164 @@
+use rocket::serde::json::serde_json;
+use sea_orm::*;
+use serde::Serialize;
+use crate::admin::actions::AdminAction;
+use crate::admin::fields::ManyToMany;
+
+/// 管理画面に登録できるエンティティ。
+/// 主キーが `i32` で、モデルをJSONにできるエンティティに自動で実装されます。
+pub trait AdminEntity:
+    EntityTrait<
+        Model: Serialize + Sync + IntoActiveModel<<Self as EntityTrait>::ActiveModel>,
+        ActiveModel: Send + Sync,
+        PrimaryKey: PrimaryKeyTrait<ValueType: From<i32>>,
+    >
+{
+}
+
+impl<E> AdminEntity for E
+where
+    E: EntityTrait,
+    E::Model: Serialize + Sync + IntoActiveModel<E::ActiveModel>,
+    E::ActiveModel: Send + Sync,
+    <E::PrimaryKey as PrimaryKeyTrait>::ValueType: From<i32>,
+{
+}
+
+/// 編集画面のフィールドのまとまり (Djangoの `fieldsets` の要素)
+#[derive(Serialize, Clone, Debug)]
+pub struct Fieldset {
+    /// 見出し (`None` の場合は表示しない)
+    pub name: Option<&'static str>,
+    pub fields: Vec<String>,
+}
+
+impl Fieldset {
+    pub fn new(name: Option<&'static str>, fields: &[&str]) -> Self {
+        Fieldset { name, fields: fields.iter().map(|f| (*f).to_owned()).collect() }
+    }
+}
+
+/// モデルごとの管理画面の設定。
+/// Djangoの `ModelAdmin` に相当し、`AdminSite::register` で登録すると
+/// 一覧・追加・編集・削除・履歴の画面と、ダッシュボード・メニューの項目が作られます。
+///
+/// ```ignore
+/// pub struct TodoAdmin;
+///
+/// impl ModelAdmin for TodoAdmin {
+///     type Entity = Todo;
+///     fn app_label(&self) -> &'static str { "todo" }
+///     fn verbose_name(&self) -> &'static str { "TODO" }
+///     fn list_display(&self) -> &'static [&'static str] { &["id", "title", "completed"] }
+///     fn search_fields(&self) -> &'static [&'static str] { &["title"] }
+/// }
+/// ```
+///
+/// フィールド名はカラム名 (`Column::as_str()`) で指定します。
+/// 権限は `<app_label>.view_<model_name>` のようなコードネームで確認します。
+#[rocket::async_trait]
+pub trait ModelAdmin: Send + Sync + 'static {
+    type Entity: AdminEntity;
+
+    /// アプリ名。権限のコードネームの接頭辞とダッシュボードのまとまりに使います (例: `auth`)
+    fn app_label(&self) -> &'static str;
+
+    /// アプリの表示名 (Djangoの `AppConfig.verbose_name`)
+    fn app_verbose_name(&self) -> &'static str {
+        self.app_label()
+    }
+
+    /// モデルの表示名 (Djangoの `verbose_name`)
+    fn verbose_name(&self) -> &'static str;
+
+    /// モデル名。権限のコードネームと操作履歴に使います (デフォルトはテーブル名)
+    fn model_name(&self) -> String {
+        Self::Entity::default().table_name().to_string()
+    }
+
+    /// URLのパス (`/admin/<slug>`)。デフォルトはモデル名に `s` を付けたもの
+    fn slug(&self) -> String {
+        format!("{}s", self.model_name())
+    }
+
+    /// フィールドの表示名 (`(フィールド名, 表示名)`)。指定がなければフィールド名を表示します
+    fn labels(&self) -> &'static [(&'static str, &'static str)] {
+        &[]
+    }
+
+    /// 一覧に表示するフィールド (Djangoの `list_display`)。空の場合は全てのカラム。
+    /// カラム以外の値を表示する場合は `list_rows` で値を追加します
+    fn list_display(&self) -> &'static [&'static str] {
+        &[]
+    }
+
+    /// 編集画面へのリンクにするフィールド (Djangoの `list_display_links`)。空の場合は先頭の列
+    fn list_display_links(&self) -> &'static [&'static str] {
+        &[]
+    }
+
+    /// 検索の対象にする文字列のフィールド (Djangoの `search_fields`)。部分一致で検索します
+    fn search_fields(&self) -> &'static [&'static str] {
+        &[]
+    }
+
+    /// 絞り込みに使うフィールド (Djangoの `list_filter`)。
+    /// 真偽値は「はい / いいえ」、それ以外はDBにある値を選択肢にします
+    fn list_filter(&self) -> &'static [&'static str] {
+        &[]
+    }
+
+    /// 一覧のデフォルトの並び順 (Djangoの `ordering`)。`-` を付けると降順
+    fn ordering(&self) -> &'static [&'static str] {
+        &["-id"]
+    }
+
+    /// 1ページあたりの件数 (Djangoの `list_per_page`)。`None` の場合は設定の `LIST_PER_PAGE`
+    fn list_per_page(&self) -> Option<u64> {
+        None
+    }
+
+    /// 一覧の一括操作 (Djangoの `actions`)。`delete_selected` は全てのモデルに追加されます
+    fn actions(&self) -> Vec<Box<dyn AdminAction<Self::Entity>>> {
+        Vec::new()
+    }
+
+    /// 編集画面で変更できないフィールド (Djangoの `readonly_fields`)
+    fn readonly_fields(&self) -> &'static [&'static str] {
+        &[]
+    }
+
+    /// 中間テーブルを介した多対多のフィールド。`fieldsets` ではここで付けた名前で指定します
+    fn many_to_many(&self) -> Vec<ManyToMany> {
+        Vec::new()
+    }
+
+    /// 編集画面のフィールドの並び (Djangoの `fieldsets`)。空の場合は主キー以外の全てのカラムと多対多のフィールド
+    fn fieldsets(&self) -> Vec<Fieldset> {
+        Vec::new()
+    }
+
+    /// 一覧の各行の値。`list_display` にカラム以外の値がある場合に上書きします
+    async fn list_rows(
+        &self,
+        _db: &DatabaseConnection,
+        objects: Vec<<Self::Entity as EntityTrait>::Model>,
+    ) -> Result<Vec<serde_json::Value>, DbErr> {
+        Ok(objects.iter().map(|o| serde_json::to_value(o).unwrap_or_default()).collect())
+    }
+
+    /// フォームの値を設定したモデルを保存します (Djangoの `save_model`)。
+    /// `change` は編集の場合に `true`。更新日時の設定などはここで行います
+    async fn save_model(
+        &self,
+        db: &DatabaseConnection,
+        active_model: <Self::Entity as EntityTrait>::ActiveModel,
+        change: bool,
+    ) -> Result<<Self::Entity as EntityTrait>::Model, DbErr> {
+        if change {
+            active_model.update(db).await
+        } else {
+            active_model.insert(db).await
+        }
+    }
+}
@@ -0,0 +1,919 @@
+use std::collections::HashMap;
+use rocket::response::{Flash, Redirect};
+use rocket::{Orbit, Rocket};
+use rocket::serde::json::serde_json;
+use sea_orm::sea_query::ColumnType;
+use sea_orm::*;
+use serde::Serialize;
+use crate::admin::actions::{ActionInfo, ActionOutcome, ActionRequest, AdminAction, DeleteSelected};
+use crate::admin::deletion::DeletedObjects;
+use crate::admin::fields::{self, ForeignKey, FormField, ManyToMany, Widget};
+use crate::admin::options::{Fieldset, ModelAdmin};
+use crate::entities::{permission, prelude::Permission, user};
+use crate::services::admin_log;
+use crate::validation::FieldErrors;
+use crate::views::app_template::AppTemplate;
+use crate::views::edit::{CreateView, DeleteView, UpdateView};
+use crate::views::list::{AdminFilter, ListView};
+
+/// モデルごとに作成する権限と表示名 (Djangoの `Meta.default_permissions`)
+const DEFAULT_PERMISSIONS: [(&str, &str); 4] = [("view", "閲覧"), ("add", "追加"), ("change", "変更"), ("delete", "削除")];
+
+type ModelOf<M> = <<M as ModelAdmin>::Entity as EntityTrait>::Model;
+type ActiveModelOf<M> = <<M as ModelAdmin>::Entity as EntityTrait>::ActiveModel;
+type ColumnOf<M> = <<M as ModelAdmin>::Entity as EntityTrait>::Column;
+
+/// 登録したモデルの情報。テンプレートでは `opts` として参照します (Djangoの `opts`)
+#[derive(Serialize, Clone, Debug)]
+pub struct ModelOptions {
+    pub app_label: &'static str,
+    pub app_verbose_name: &'static str,
+    pub model_name: String,
+    pub verbose_name: &'static str,
+    pub slug: String,
+}
+
+impl ModelOptions {
+    /// 一覧のURL (`/admin/<slug>`)
+    pub fn base_url(&self) -> String {
+        format!("/admin/{}", self.slug)
+    }
+
+    /// 権限のコードネーム。`action` は `view` / `add` / `change` / `delete`
+    pub fn perm(&self, action: &str) -> String {
+        format!("{}.{}_{}", self.app_label, action, self.model_name)
+    }
+}
+
+/// 一覧画面のクエリパラメータ
+pub struct ChangeListParams {
+    pub page: usize,
+    pub q: Option<String>,
+    pub sort: Option<String>,
+    pub dir: Option<String>,
+    /// `list_filter` の絞り込み (`フィールド名 => 値`)
+    pub filters: HashMap<String, String>,
+}
+
+/// ダッシュボードとメニューに表示するアプリ (Djangoの `available_apps`)
+#[derive(Serialize, Clone, Debug)]
+pub struct AppInfo {
+    pub app_label: &'static str,
+    pub name: &'static str,
+    pub models: Vec<ModelInfo>,
+}
+
+/// ダッシュボードとメニューに表示するモデル
+#[derive(Serialize, Clone, Debug)]
+pub struct ModelInfo {
+    pub slug: String,
+    pub verbose_name: &'static str,
+    /// 追加の権限があるか
+    pub add: bool,
+}
+
+/// 型を消した `ModelAdmin`。`AdminSite` が登録したモデルを同じように扱うために使います。
+/// 各画面の権限は呼び出し側 (`controllers::admin_site`) で確認します。
+#[rocket::async_trait]
+pub trait AdminModel: Send + Sync {
+    fn opts(&self) -> &ModelOptions;
+
+    /// 一覧画面 (Djangoの `changelist_view`)
+    async fn changelist_view(
+        &self,
+        db: &DatabaseConnection,
+        per_page: u64,
+        params: ChangeListParams,
+        extra_context: serde_json::Value,
+    ) -> AppTemplate;
+
+    /// 追加画面 (Djangoの `add_view`)
+    async fn add_view(&self, db: &DatabaseConnection, extra_context: serde_json::Value) -> AppTemplate;
+
+    /// 追加処理。`data` はフォームの値 (フィールド名 => 文字列)
+    async fn add(
+        &self,
+        db: &DatabaseConnection,
+        actor: &user::Model,
+        data: serde_json::Value,
+        extra_context: serde_json::Value,
+    ) -> Result<Flash<Redirect>, AppTemplate>;
+
+    /// 編集画面 (Djangoの `change_view`)
+    async fn change_view(&self, db: &DatabaseConnection, id: i32, extra_context: serde_json::Value) -> Result<AppTemplate, Flash<Redirect>>;
+
+    /// 編集処理
+    async fn change(
+        &self,
+        db: &DatabaseConnection,
+        actor: &user::Model,
+        id: i32,
+        data: serde_json::Value,
+        extra_context: serde_json::Value,
+    ) -> Result<Flash<Redirect>, AppTemplate>;
+
+    /// 削除の確認画面 (Djangoの `delete_view` のGET)
+    async fn delete_view(&self, db: &DatabaseConnection, site: &AdminSite, id: i32, extra_context: serde_json::Value) -> Result<AppTemplate, Flash<Redirect>>;
+
+    /// 削除処理
+    async fn delete(&self, db: &DatabaseConnection, site: &AdminSite, actor: &user::Model, id: i32) -> Result<Flash<Redirect>, Flash<Redirect>>;
+
+    /// 一覧の一括操作
+    fn actions(&self) -> Vec<ActionInfo>;
+
+    /// 選択したオブジェクトに一括操作を実行します (Djangoの `response_action`)。
+    /// 確認画面のある操作は、確認画面から送信される (`confirmed`) までは確認画面を返します。
+    /// 権限は呼び出し側で確認します
+    #[allow(clippy::too_many_arguments)]
+    async fn run_action(
+        &self,
+        db: &DatabaseConnection,
+        rocket: &Rocket<Orbit>,
+        site: &AdminSite,
+        actor: &user::Model,
+        name: &str,
+        ids: &[i32],
+        confirmed: bool,
+        data: &HashMap<String, String>,
+        extra_context: serde_json::Value,
+    ) -> ActionOutcome;
+
+    /// オブジェクトの表示名。存在しない場合は `None`
+    async fn object_repr(&self, db: &DatabaseConnection, id: i32) -> Result<Option<String>, DbErr>;
+}
+
+/// 登録した `ModelAdmin` と、そこから作ったモデルの情報
+struct Registered<M: ModelAdmin> {
+    admin: M,
+    opts: ModelOptions,
+    /// `belongs_to` のリレーションの外部キー
+    foreign_keys: Vec<ForeignKey>,
+    many_to_many: Vec<ManyToMany>,
+    /// 一括操作 (`delete_selected` と `ModelAdmin::actions`)
+    actions: Vec<Box<dyn AdminAction<M::Entity>>>,
+}
+
+/// カラム名からカラムを探す
+fn column<M: ModelAdmin>(name: &str) -> Option<ColumnOf<M>> {
+    ColumnOf::<M>::iter().find(|c| c.as_str() == name)
+}
+
+/// 主キーのカラム (複合キーの場合は先頭)
+fn primary_key<M: ModelAdmin>() -> Option<ColumnOf<M>> {
+    <M::Entity as EntityTrait>::PrimaryKey::iter().next().map(|pk| pk.into_column())
+}
+
+/// `from` のキーを `into` に追加する (同じキーは上書き)
+fn merge_context(into: &mut serde_json::Value, from: serde_json::Value) {
+    if let (serde_json::Value::Object(into), serde_json::Value::Object(from)) = (into, from) {
+        into.extend(from);
+    }
+}
+
+impl<M: ModelAdmin> Registered<M> {
+    fn new(admin: M) -> Self {
+        let opts = ModelOptions {
+            app_label: admin.app_label(),
+            app_verbose_name: admin.app_verbose_name(),
+            model_name: admin.model_name(),
+            verbose_name: admin.verbose_name(),
+            slug: admin.slug(),
+        };
+        let foreign_keys = <M::Entity as EntityTrait>::Relation::iter()
+            .filter_map(|rel| ForeignKey::from_relation(&rel.def()))
+            .collect();
+        let many_to_many = admin.many_to_many();
+        let mut actions: Vec<Box<dyn AdminAction<M::Entity>>> = vec![Box::new(DeleteSelected)];
+        actions.extend(admin.actions());
+        Registered { admin, opts, foreign_keys, many_to_many, actions }
+    }
+
+    fn label(&self, field: &str) -> String {
+        match self.admin.labels().iter().find(|(name, _)| *name == field) {
+            Some((_, label)) => (*label).to_owned(),
+            None if field == "id" => "ID".to_string(),
+            None => field.to_string(),
+        }
+    }
+
+    /// 一覧に表示するフィールド
+    fn list_display(&self) -> Vec<String> {
+        match self.admin.list_display() {
+            [] => ColumnOf::<M>::iter().map(|c| c.as_str().to_string()).collect(),
+            fields => fields.iter().map(|f| (*f).to_owned()).collect(),
+        }
+    }
+
+    /// 一覧の見出し。カラムのフィールドだけ並べ替えられる
+    fn columns(&self) -> serde_json::Value {
+        self.list_display()
+            .iter()
+            .map(|field| {
+                serde_json::json!({
+                    "name": field,
+                    "label": self.label(field),
+                    "sortable": column::<M>(field).is_some(),
+                })
+            })
+            .collect()
+    }
+
+    /// 一覧の絞り込みの選択肢
+    async fn filters(&self, db: &DatabaseConnection) -> Vec<AdminFilter> {
+        let mut filters = Vec::new();
+        for field in self.admin.list_filter() {
+            let Some(col) = column::<M>(field) else { continue };
+            let choices = if *col.def().get_column_type() == ColumnType::Boolean {
+                vec![("true".to_string(), "はい".to_string()), ("false".to_string(), "いいえ".to_string())]
+            } else {
+                M::Entity::find()
+                    .select_only()
+                    .column(col)
+                    .distinct()
+                    .order_by_asc(col)
+                    .into_json()
+                    .all(db)
+                    .await
+                    .unwrap_or_default()
+                    .into_iter()
+                    .filter_map(|row| match &row[*field] {
+                        serde_json::Value::Null => None,
+                        serde_json::Value::String(s) => Some((s.clone(), s.clone())),
+                        value => Some((value.to_string(), value.to_string())),
+                    })
+                    .collect()
+            };
+            filters.push(AdminFilter {
+                label: self.label(field),
+                parameter_name: (*field).to_owned(),
+                choices,
+            });
+        }
+        filters
+    }
+
+    fn foreign_key(&self, name: &str) -> Option<&ForeignKey> {
+        self.foreign_keys.iter().find(|fk| fk.column.to_string() == name)
+    }
+
+    fn m2m(&self, name: &str) -> Option<&ManyToMany> {
+        self.many_to_many.iter().find(|m2m| m2m.name == name)
+    }
+
+    /// 編集画面のフィールドのまとまり。入力欄はカラムの型とリレーションから決まります
+    fn fieldsets(&self) -> Vec<(Option<&'static str>, Vec<FormField>)> {
+        let fieldsets = match self.admin.fieldsets() {
+            fieldsets if !fieldsets.is_empty() => fieldsets,
+            _ => {
+                let primary_keys: Vec<String> = <M::Entity as EntityTrait>::PrimaryKey::iter()
+                    .map(|pk| pk.into_column().as_str().to_string())
+                    .collect();
+                let fields: Vec<String> = ColumnOf::<M>::iter()
+                    .map(|c| c.as_str().to_owned())
+                    .filter(|name| !primary_keys.contains(name))
+                    .chain(self.many_to_many.iter().map(|m2m| m2m.name.to_owned()))
+                    .collect();
+                vec![Fieldset { name: None, fields }]
+            }
+        };
+
+        fieldsets
+            .into_iter()
+            .map(|fieldset| {
+                let fields = fieldset.fields.iter().map(|name| self.form_field(name)).collect();
+                (fieldset.name, fields)
+            })
+            .collect()
+    }
+
+    /// フィールドの入力欄 (選択肢は `form_context` で読み込む)
+    fn form_field(&self, name: &str) -> FormField {
+        let readonly_field = self.admin.readonly_fields().contains(&name);
+        if self.m2m(name).is_some() {
+            return FormField {
+                name: name.to_string(),
+                label: self.label(name),
+                widget: Widget::SelectMultiple,
+                required: false,
+                readonly: readonly_field,
+                choices: Vec::new(),
+            };
+        }
+
+        let def = column::<M>(name).map(|c| c.def());
+        let widget = match self.foreign_key(name) {
+            Some(_) => Some(Widget::Select),
+            None => def.as_ref().and_then(|d| Widget::for_column_type(d.get_column_type())),
+        };
+        let readonly = widget.is_none() || readonly_field;
+        FormField {
+            name: name.to_string(),
+            label: self.label(name),
+            widget: widget.unwrap_or(Widget::Text),
+            required: !readonly && widget != Some(Widget::Checkbox) && def.is_some_and(|d| !d.is_null()),
+            readonly,
+            choices: Vec::new(),
+        }
+    }
+
+    /// フォームで変更するフィールド (多対多を含む)
+    fn editable_fields(&self) -> Vec<String> {
+        self.fieldsets()
+            .into_iter()
+            .flat_map(|(_, fields)| fields)
+            .filter(|f| !f.readonly)
+            .map(|f| f.name)
+            .collect()
+    }
+
+    /// 追加・編集画面の共通のコンテキスト。外部キーと多対多の選択肢はここで読み込みます
+    async fn form_context(&self, db: &DatabaseConnection) -> serde_json::Value {
+        let mut fieldsets = Vec::new();
+        for (name, mut fields) in self.fieldsets() {
+            for field in fields.iter_mut().filter(|f| !f.readonly) {
+                let choices = match (self.foreign_key(&field.name), self.m2m(&field.name)) {
+                    (Some(fk), _) => fk.choices(db).await,
+                    (_, Some(m2m)) => m2m.choices(db).await,
+                    _ => continue,
+                };
+                field.choices = choices.unwrap_or_default();
+            }
+            fieldsets.push(serde_json::json!({ "name": name, "fields": fields }));
+        }
+        serde_json::json!({
+            "opts": self.opts,
+            "base_url": self.opts.base_url(),
+            "active_nav": self.opts.slug,
+            "fieldsets": fieldsets,
+        })
+    }
+
+    /// 全てのフィールドの値を入力欄に表示する形にそろえる。
+    /// 送信された `data` がある場合は変更できるフィールドをその値 (未送信のチェックボックスは `null`) に、
+    /// 読み取り専用のフィールドと `data` がない場合 (GET) は `initial` の値にします
+    fn fill_form(&self, initial: serde_json::Value, data: serde_json::Value) -> serde_json::Value {
+        let mut form = serde_json::Map::new();
+        for (_, fields) in self.fieldsets() {
+            for field in fields {
+                let value = match (&data, field.readonly) {
+                    (serde_json::Value::Object(data), false) => data.get(&field.name).cloned().unwrap_or_default(),
+                    _ => initial.get(&field.name).cloned().unwrap_or_default(),
+                };
+                form.insert(field.name, fields::format_value(field.widget, value));
+            }
+        }
+        serde_json::Value::Object(form)
+    }
+
+    /// モデルのJSONに多対多の選択中のIDを追加する (編集画面の初期値)
+    async fn with_related(&self, db: &DatabaseConnection, id: i32, mut object: serde_json::Value) -> serde_json::Value {
+        for m2m in &self.many_to_many {
+            object[m2m.name] = m2m.selected(db, id).await.unwrap_or_default().into();
+        }
+        object
+    }
+
+    /// フォームの値を検証します (Djangoの `ModelForm.is_valid()`)。
+    /// 型の変換と必須のほか、外部キー・多対多の選択肢と `unique` のカラムの重複を確認します。
+    /// `id` は編集の場合のオブジェクトのID (重複の確認から除く)
+    async fn clean(&self, db: &DatabaseConnection, id: Option<i32>, data: &serde_json::Value) -> Result<(), FieldErrors> {
+        let fields = self.editable_fields();
+        let mut errors = match fields::construct_instance(&mut ActiveModelOf::<M>::default(), &fields, data) {
+            Ok(()) => FieldErrors::new(),
+            Err(errors) => errors,
+        };
+
+        for name in &fields {
+            if !errors.get(name).is_empty() {
+                continue;
+            }
+            if let Some(m2m) = self.m2m(name) {
+                if let Err(message) = m2m.clean(db, &data[name.as_str()]).await {
+                    errors.add(name, message);
+                }
+                continue;
+            }
+            // 空欄 (NULL) は確認しない
+            let raw = &data[name.as_str()];
+            let Some(col) = column::<M>(name).filter(|_| raw.as_str().is_some_and(|s| !s.trim().is_empty())) else {
+                continue;
+            };
+            let def = col.def();
+            let Ok(value) = fields::parse_value(&def, raw) else { continue };
+
+            if let Some(fk) = self.foreign_key(name) {
+                if !fk.exists(db, value.clone()).await.unwrap_or(false) {
+                    errors.add(name, fields::INVALID_CHOICE_MESSAGE);
+                }
+            }
+            if def.is_unique() {
+                let mut query = M::Entity::find().filter(col.eq(value));
+                if let (Some(id), Some(pk)) = (id, primary_key::<M>()) {
+                    query = query.filter(pk.ne(id));
+                }
+                if query.count(db).await.unwrap_or(0) > 0 {
+                    errors.add(name, format!("この {} を持った {} が既に存在します。", self.label(name), self.opts.verbose_name));
+                }
+            }
+        }
+        errors.into_result()
+    }
+
+    /// フォームの値でモデルを保存し、多対多の中間テーブルを更新します
+    async fn save_form(&self, db: &DatabaseConnection, mut active_model: ActiveModelOf<M>, data: &serde_json::Value, change: bool) -> Result<ModelOf<M>, DbErr> {
+        let fields = self.editable_fields();
+        fields::construct_instance(&mut active_model, &fields, data).map_err(|e| DbErr::Custom(e.to_string()))?;
+        let object = self.admin.save_model(db, active_model, change).await?;
+
+        let object_id = admin_log::object_id(&serde_json::to_value(&object).unwrap_or_default());
+        let object_id: i32 = object_id.parse().map_err(|_| DbErr::Custom(format!("Invalid primary key: {}", object_id)))?;
+        for m2m in self.many_to_many.iter().filter(|m2m| fields.iter().any(|f| f == m2m.name)) {
+            let ids = m2m.clean(db, &data[m2m.name]).await.map_err(DbErr::Custom)?;
+            m2m.save(db, object_id, &ids).await?;
+        }
+        Ok(object)
+    }
+
+    async fn get_object(&self, db: &DatabaseConnection, id: i32) -> Result<Option<ModelOf<M>>, DbErr> {
+        M::Entity::find_by_id(id).one(db).await
+    }
+}
+
+/// 一覧画面 (Djangoの `ChangeList`)。検索・絞り込み・並べ替え・ページ分割は `ListView` が行います
+struct ChangeList<'a, M: ModelAdmin> {
+    model: &'a Registered<M>,
+    per_page: u64,
+    filters: Vec<AdminFilter>,
+}
+
+#[rocket::async_trait]
+impl<M: ModelAdmin> ListView<M::Entity> for ChangeList<'_, M> {
+    fn template_name(&self) -> &'static str {
+        "admin/change_list"
+    }
+
+    fn per_page(&self) -> usize {
+        self.per_page as usize
+    }
+
+    fn get_filters(&self) -> Vec<AdminFilter> {
+        self.filters.clone()
+    }
+
+    fn filter_queryset(&self, query: Select<M::Entity>, q: &str) -> Select<M::Entity> {
+        let condition = self
+            .model
+            .admin
+            .search_fields()
+            .iter()
+            .filter_map(|field| column::<M>(field))
+            .fold(Condition::any(), |condition, col| condition.add(col.contains(q)));
+        query.filter(condition)
+    }
+
+    fn apply_filters(&self, mut query: Select<M::Entity>, params: &HashMap<String, String>) -> Select<M::Entity> {
+        for field in self.model.admin.list_filter() {
+            let (Some(col), Some(raw)) = (column::<M>(field), params.get(*field)) else { continue };
+            // 不正な値は無視する
+            if let Ok(value) = fields::parse_value(&col.def(), &serde_json::json!(raw)) {
+                query = query.filter(col.eq(value));
+            }
+        }
+        query
+    }
+
+    fn apply_sorting(&self, mut query: Select<M::Entity>, sort: Option<String>, dir: Option<String>) -> (Select<M::Entity>, String, String) {
+        let sortable = |field: &String| self.model.list_display().contains(field) && column::<M>(field).is_some();
+        if let (Some(field), Some(col)) = (sort.as_ref().filter(|s| sortable(s)), sort.as_deref().and_then(column::<M>)) {
+            let direction = dir.unwrap_or_else(|| "asc".to_string());
+            let order = if direction == "desc" { Order::Desc } else { Order::Asc };
+            return (query.order_by(col, order), field.clone(), direction);
+        }
+
+        // 指定がなければ `ordering` の順
+        let mut current = (String::new(), String::new());
+        for (i, field) in self.model.admin.ordering().iter().enumerate() {
+            let (name, order) = match field.strip_prefix('-') {
+                Some(name) => (name, Order::Desc),
+                None => (*field, Order::Asc),
+            };
+            let Some(col) = column::<M>(name) else { continue };
+            if i == 0 {
+                current = (name.to_string(), if order == Order::Desc { "desc" } else { "asc" }.to_string());
+            }
+            query = query.order_by(col, order);
+        }
+        (query, current.0, current.1)
+    }
+
+    async fn get_items(&self, db: &DatabaseConnection, items: Vec<ModelOf<M>>) -> Vec<serde_json::Value> {
+        let rows = self.model.admin.list_rows(db, items).await.unwrap_or_default();
+        let fields = self.model.list_display();
+        let links: Vec<String> = match self.model.admin.list_display_links() {
+            [] => fields.iter().take(1).cloned().collect(),
+            links => links.iter().map(|l| (*l).to_owned()).collect(),
+        };
+
+        rows.into_iter()
+            .map(|row| {
+                let cells: Vec<serde_json::Value> = fields
+                    .iter()
+                    .map(|field| {
+                        let value = row.get(field).cloned().unwrap_or_default();
+                        let kind = match value {
+                            serde_json::Value::Bool(_) => "boolean",
+                            serde_json::Value::Array(_) => "list",
+                            serde_json::Value::Null => "empty",
+                            _ => "text",
+                        };
+                        serde_json::json!({ "value": value, "kind": kind, "link": links.contains(field) })
+                    })
+                    .collect();
+                serde_json::json!({ "pk": admin_log::object_id(&row), "cells": cells })
+            })
+            .collect()
+    }
+}
+
+/// 追加画面。保存と操作履歴の記録は `CreateView` が行います
+struct AddView<'a, M: ModelAdmin>(&'a Registered<M>);
+
+#[rocket::async_trait]
+impl<M: ModelAdmin> CreateView<ActiveModelOf<M>> for AddView<'_, M> {
+    fn template_name(&self) -> &'static str {
+        "admin/change_form"
+    }
+
+    fn content_type(&self) -> String {
+        self.0.opts.model_name.clone()
+    }
+
+    fn success_url(&self) -> String {
+        self.0.opts.base_url()
+    }
+
+    async fn get_context_data(&self, db: &DatabaseConnection) -> serde_json::Value {
+        self.0.form_context(db).await
+    }
+
+    async fn clean(&self, db: &DatabaseConnection, data: &serde_json::Value) -> Result<(), FieldErrors> {
+        self.0.clean(db, None, data).await
+    }
+
+    async fn save(&self, db: &DatabaseConnection, data: &serde_json::Value) -> Result<ModelOf<M>, DbErr> {
+        self.0.save_form(db, ActiveModelOf::<M>::default(), data, false).await
+    }
+}
+
+/// 編集画面。保存と操作履歴の記録は `UpdateView` が行います
+struct ChangeView<'a, M: ModelAdmin> {
+    model: &'a Registered<M>,
+    /// 変更前の値 (多対多の変更を履歴に記録するため)
+    initial: serde_json::Value,
+}
+
+#[rocket::async_trait]
+impl<M: ModelAdmin> UpdateView<ActiveModelOf<M>> for ChangeView<'_, M> {
+    fn template_name(&self) -> &'static str {
+        "admin/change_form"
+    }
+
+    fn content_type(&self) -> String {
+        self.model.opts.model_name.clone()
+    }
+
+    fn success_url(&self) -> String {
+        self.model.opts.base_url()
+    }
+
+    async fn get_context_data(&self, db: &DatabaseConnection) -> serde_json::Value {
+        self.model.form_context(db).await
+    }
+
+    /// モデルのカラムに加えて、選択が変わった多対多のフィールドを記録する
+    fn changed_fields(&self, before: &serde_json::Value, after: &serde_json::Value, form_data: &serde_json::Value) -> Vec<String> {
+        let mut fields = admin_log::changed_fields(before, after);
+        let sorted = |value: &serde_json::Value| {
+            let mut ids: Vec<String> = value.as_array().into_iter().flatten().map(|id| id.to_string()).collect();
+            ids.sort();
+            ids
+        };
+        for m2m in &self.model.many_to_many {
+            if form_data.get(m2m.name).is_some() && sorted(&form_data[m2m.name]) != sorted(&self.initial[m2m.name]) {
+                fields.push(m2m.name.to_string());
+            }
+        }
+        fields
+    }
+
+    async fn get_object(&self, db: &DatabaseConnection, id: i32) -> Result<Option<ModelOf<M>>, DbErr> {
+        self.model.get_object(db, id).await
+    }
+
+    async fn clean(&self, db: &DatabaseConnection, id: i32, data: &serde_json::Value) -> Result<(), FieldErrors> {
+        self.model.clean(db, Some(id), data).await
+    }
+
+    async fn save(&self, db: &DatabaseConnection, id: i32, data: &serde_json::Value) -> Result<ModelOf<M>, DbErr> {
+        let object = self.model.get_object(db, id).await?.ok_or(DbErr::RecordNotFound(format!("{} (id={})", self.model.opts.verbose_name, id)))?;
+        self.model.save_form(db, object.into_active_model(), data, true).await
+    }
+}
+
+/// 削除の確認画面と削除処理。操作履歴の記録は `DeleteView` が行います
+struct RemoveView<'a, M: ModelAdmin> {
+    model: &'a Registered<M>,
+    site: &'a AdminSite,
+}
+
+#[rocket::async_trait]
+impl<M: ModelAdmin> DeleteView<M::Entity> for RemoveView<'_, M> {
+    fn success_url(&self) -> String {
+        self.model.opts.base_url()
+    }
+
+    fn content_type(&self) -> String {
+        self.model.opts.model_name.clone()
+    }
+
+    async fn get_context_data(&self, _db: &DatabaseConnection) -> serde_json::Value {
+        serde_json::json!({
+            "opts": self.model.opts,
+            "base_url": self.model.opts.base_url(),
+            "active_nav": self.model.opts.slug,
+        })
+    }
+
+    fn describe(&self, deleted: &mut DeletedObjects) {
+        self.site.describe(deleted);
+    }
+}
+
+#[rocket::async_trait]
+impl<M: ModelAdmin> AdminModel for Registered<M> {
+    fn opts(&self) -> &ModelOptions {
+        &self.opts
+    }
+
+    async fn changelist_view(
+        &self,
+        db: &DatabaseConnection,
+        per_page: u64,
+        params: ChangeListParams,
+        extra_context: serde_json::Value,
+    ) -> AppTemplate {
+        let view = ChangeList {
+            model: self,
+            per_page: self.admin.list_per_page().unwrap_or(per_page),
+            filters: self.filters(db).await,
+        };
+        let search_labels: Vec<String> = self.admin.search_fields().iter().map(|f| self.label(f)).collect();
+        let mut context = serde_json::json!({
+            "opts": self.opts,
+            "base_url": self.opts.base_url(),
+            "active_nav": self.opts.slug,
+            "columns": self.columns(),
+            "search_labels": search_labels,
+        });
+        merge_context(&mut context, extra_context);
+        view.list(db, params.page, params.q, params.sort, params.dir, &params.filters, context).await
+    }
+
+    async fn add_view(&self, db: &DatabaseConnection, extra_context: serde_json::Value) -> AppTemplate {
+        let mut context = serde_json::json!({ "form": self.fill_form(serde_json::Value::Null, serde_json::Value::Null) });
+        merge_context(&mut context, extra_context);
+        AddView(self).get(db, context).await
+    }
+
+    async fn add(
+        &self,
+        db: &DatabaseConnection,
+        actor: &user::Model,
+        data: serde_json::Value,
+        extra_context: serde_json::Value,
+    ) -> Result<Flash<Redirect>, AppTemplate> {
+        let data = self.fill_form(serde_json::Value::Null, data);
+        AddView(self).post(db, actor, &data, extra_context).await
+    }
+
+    async fn change_view(&self, db: &DatabaseConnection, id: i32, extra_context: serde_json::Value) -> Result<AppTemplate, Flash<Redirect>> {
+        let object = self
+            .get_object(db, id)
+            .await
+            .ok()
+            .flatten()
+            .ok_or_else(|| Flash::error(Redirect::to(self.opts.base_url()), format!("{}が見つかりません", self.opts.verbose_name)))?;
+        let object = self.with_related(db, id, serde_json::to_value(&object).unwrap_or_default()).await;
+        let mut context = serde_json::json!({
+            "object_repr": admin_log::object_repr(&object),
+            "form": self.fill_form(object, serde_json::Value::Null),
+            "is_edit": true,
+            "id": id,
+        });
+        merge_context(&mut context, self.form_context(db).await);
+        merge_context(&mut context, extra_context);
+        Ok(AppTemplate::new("admin/change_form", context))
+    }
+
+    async fn change(
+        &self,
+        db: &DatabaseConnection,
+        actor: &user::Model,
+        id: i32,
+        data: serde_json::Value,
+        extra_context: serde_json::Value,
+    ) -> Result<Flash<Redirect>, AppTemplate> {
+        let object = self.get_object(db, id).await.ok().flatten();
+        let object = self.with_related(db, id, serde_json::to_value(&object).unwrap_or_default()).await;
+        let mut context = serde_json::json!({ "object_repr": admin_log::object_repr(&object) });
+        merge_context(&mut context, extra_context);
+        let data = self.fill_form(object.clone(), data);
+        let view = ChangeView { model: self, initial: self.fill_form(object, serde_json::Value::Null) };
+        view.post(db, actor, id, &data, context).await
+    }
+
+    async fn delete_view(&self, db: &DatabaseConnection, site: &AdminSite, id: i32, extra_context: serde_json::Value) -> Result<AppTemplate, Flash<Redirect>> {
+        RemoveView { model: self, site }.get(db, id, extra_context).await
+    }
+
+    async fn delete(&self, db: &DatabaseConnection, site: &AdminSite, actor: &user::Model, id: i32) -> Result<Flash<Redirect>, Flash<Redirect>> {
+        RemoveView { model: self, site }.post(db, actor, id).await
+    }
+
+    fn actions(&self) -> Vec<ActionInfo> {
+        self.actions
+            .iter()
+            .map(|action| ActionInfo {
+                name: action.name(),
+                description: action.description(),
+                permission: action.permission(),
+            })
+            .collect()
+    }
+
+    async fn run_action(
+        &self,
+        db: &DatabaseConnection,
+        rocket: &Rocket<Orbit>,
+        site: &AdminSite,
+        actor: &user::Model,
+        name: &str,
+        ids: &[i32],
+        confirmed: bool,
+        data: &HashMap<String, String>,
+        extra_context: serde_json::Value,
+    ) -> ActionOutcome {
+        let redirect = |flash: fn(Redirect, String) -> Flash<Redirect>, message: String| {
+            ActionOutcome::Redirect(flash(Redirect::to(self.opts.base_url()), message))
+        };
+        let Some(action) = self.actions.iter().find(|a| a.name() == name) else {
+            return redirect(Flash::warning, "不明な操作です".to_string());
+        };
+        let objects = match primary_key::<M>() {
+            Some(pk) => M::Entity::find().filter(pk.is_in(ids.to_vec())).order_by_asc(pk).all(db).await,
+            None => Ok(Vec::new()),
+        };
+        let objects = match objects {
+            // Djangoと同じく、何も選択されていなければ実行しない
+            Ok(objects) if objects.is_empty() => {
+                return redirect(Flash::warning, format!("操作を実行するには{}を選択してください", self.opts.verbose_name));
+            }
+            Ok(objects) => objects,
+            Err(e) => return redirect(Flash::error, format!("操作に失敗しました: {}", e)),
+        };
+        let request = ActionRequest { rocket, site, opts: &self.opts, actor, objects, data };
+
+        if action.confirmation() && !confirmed {
+            let rows: Vec<serde_json::Value> = request.objects.iter().map(|o| serde_json::to_value(o).unwrap_or_default()).collect();
+            let mut context = serde_json::json!({
+                "opts": self.opts,
+                "base_url": self.opts.base_url(),
+                "active_nav": self.opts.slug,
+                "action": action.name(),
+                "action_description": action.description(),
+                "selected_ids": rows.iter().map(admin_log::object_id).collect::<Vec<_>>(),
+                "objects": rows.iter().map(admin_log::object_repr).collect::<Vec<_>>(),
+            });
+            match action.confirmation_context(db, &request).await {
+                Ok(extra) => merge_context(&mut context, extra),
+                Err(e) => return redirect(Flash::error, format!("操作に失敗しました: {}", e)),
+            }
+            merge_context(&mut context, extra_context);
+            return ActionOutcome::Page(AppTemplate::new(action.confirmation_template(), context));
+        }
+
+        match action.run(db, &request).await {
+            Ok(response) => ActionOutcome::from_response(response, self.opts.base_url()),
+            Err(e) => redirect(Flash::error, format!("操作に失敗しました: {}", e)),
+        }
+    }
+
+    async fn object_repr(&self, db: &DatabaseConnection, id: i32) -> Result<Option<String>, DbErr> {
+        let object = self.get_object(db, id).await?;
+        Ok(object.map(|o| admin_log::object_repr(&serde_json::to_value(&o).unwrap_or_default())))
+    }
+}
+
+/// 管理サイト。`ModelAdmin` を登録したモデルの管理画面を提供します。
+/// Djangoの `django.contrib.admin.AdminSite` (`admin.site`) に相当します。
+///
+/// ```ignore
+/// let admin_site = AdminSite::new()
+///     .register(UserAdmin)
+///     .register(TodoAdmin);
+/// ```
+#[derive(Default)]
+pub struct AdminSite {
+    models: Vec<Box<dyn AdminModel>>,
+}
+
+impl AdminSite {
+    pub fn new() -> Self {
+        AdminSite::default()
+    }
+
+    /// モデルを登録します (Djangoの `admin.site.register`)。
+    /// 同じURL (`slug`) のモデルを二重に登録するとパニックします (Djangoの `AlreadyRegistered`)
+    pub fn register<M: ModelAdmin>(mut self, admin: M) -> Self {
+        let model = Registered::new(admin);
+        if self.get(&model.opts.slug).is_some() {
+            panic!("The model {} is already registered", model.opts.slug);
+        }
+        self.models.push(Box::new(model));
+        self
+    }
+
+    /// URLのパスから登録したモデルを探します
+    pub fn get(&self, slug: &str) -> Option<&dyn AdminModel> {
+        self.models.iter().find(|m| m.opts().slug == slug).map(|m| m.as_ref())
+    }
+
+    /// 登録したモデル
+    pub fn models(&self) -> impl Iterator<Item = &ModelOptions> {
+        self.models.iter().map(|m| m.opts())
+    }
+
+    /// 操作履歴のモデル名 (`content_type`) とIDから編集画面のURLを作ります
+    pub fn admin_url(&self, model_name: &str, object_id: &str) -> Option<String> {
+        self.models()
+            .find(|opts| opts.model_name == model_name)
+            .map(|opts| format!("{}/edit/{}", opts.base_url(), object_id))
+    }
+
+    /// 削除されるオブジェクトに、登録したモデルの表示名と編集画面のURLを設定します
+    pub fn describe(&self, deleted: &mut DeletedObjects) {
+        deleted.for_each_mut(|object| {
+            if let Some(opts) = self.models().find(|opts| opts.model_name == object.model_name) {
+                object.verbose_name = opts.verbose_name.to_string();
+            }
+            object.url = self.admin_url(&object.model_name, &object.object_id);
+        });
+    }
+
+    /// 権限のあるモデルをアプリごとにまとめて返します (Djangoの `get_app_list`)。
+    /// 閲覧か変更の権限があるモデルを表示します
+    pub fn available_apps(&self, has_perm: impl Fn(&str) -> bool) -> Vec<AppInfo> {
+        let mut apps: Vec<AppInfo> = Vec::new();
+        for opts in self.models() {
+            if !has_perm(&opts.perm("view")) && !has_perm(&opts.perm("change")) {
+                continue;
+            }
+            let model = ModelInfo {
+                slug: opts.slug.clone(),
+                verbose_name: opts.verbose_name,
+                add: has_perm(&opts.perm("add")),
+            };
+            match apps.iter_mut().find(|app| app.app_label == opts.app_label) {
+                Some(app) => app.models.push(model),
+                None => apps.push(AppInfo {
+                    app_label: opts.app_label,
+                    name: opts.app_verbose_name,
+                    models: vec![model],
+                }),
+            }
+        }
+        apps
+    }
+
+    /// 登録したモデルの閲覧・追加・変更・削除の権限を作成します。
+    /// Djangoが `migrate` の後に行う `create_permissions` に相当し、既にある権限はそのままにします
+    pub async fn create_permissions(&self, db: &DatabaseConnection) -> Result<(), DbErr> {
+        for opts in self.models() {
+            for (action, label) in DEFAULT_PERMISSIONS {
+                let codename = opts.perm(action);
+                let perm = permission::ActiveModel {
+                    name: Set(format!("{}を{}", opts.verbose_name, label)),
+                    codename: Set(codename),
+                    ..Default::default()
+                };
+                // 複数のプロセスが同時に起動しても重複しないよう、既にあれば何もしない
+                Permission::insert(perm)
+                    .on_conflict(sea_query::OnConflict::column(permission::Column::Codename).do_nothing().to_owned())
+                    .do_nothing()
+                    .exec(db)
+                    .await?;
+            }
+        }
+        Ok(())
+    }
+}
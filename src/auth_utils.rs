@@ -1,31 +1,43 @@
-use argon2::{
-    password_hash::{rand_core::OsRng, PasswordHasher, PasswordVerifier, SaltString},
-    Argon2,
-};
 use anyhow::Result;
+use rand::Rng;
+use crate::hashers::{default_hashers, PasswordCheck};
 
 /// パスワードをArgon2でハッシュ化します。
 /// Djangoの `make_password` に相当する機能です。
 pub fn hash_password(password: &str) -> Result<String> {
-    let salt = SaltString::generate(&mut OsRng);
-    let argon2 = Argon2::default();
-    let hash = argon2
-        .hash_password(password.as_bytes(), &salt)
-        .map_err(|e| anyhow::anyhow!("Failed to hash password: {}", e))?
-        .to_string();
-    Ok(hash)
+    default_hashers().make_password(password)
+}
+
+/// 使用できないパスワードを表すハッシュの接頭辞 (Django: `UNUSABLE_PASSWORD_PREFIX`)
+pub const UNUSABLE_PASSWORD_PREFIX: &str = "!";
+
+/// どのパスワードとも一致しないハッシュ値を生成します。
+/// Djangoの `make_password(None)` に相当し、外部IdPでのみログインするユーザーに使用します。
+pub fn make_unusable_password() -> String {
+    let suffix: String = rand::thread_rng()
+        .sample_iter(&rand::distributions::Alphanumeric)
+        .take(40)
+        .map(char::from)
+        .collect();
+    format!("{}{}", UNUSABLE_PASSWORD_PREFIX, suffix)
 }
 
 /// パスワードを検証します。
 /// Djangoの `check_password` に相当する機能です。
+/// Djangoから移行したハッシュ (`pbkdf2_sha256` 等) も検証できます。
 pub fn verify_password(password: &str, hash: &str) -> bool {
-    let parsed_hash = match argon2::PasswordHash::new(hash) {
-        Ok(h) => h,
-        Err(_) => return false,
-    };
-    Argon2::default()
-        .verify_password(password.as_bytes(), &parsed_hash)
-        .is_ok()
+    check_password(password, hash).valid
+}
+
+/// パスワードを検証し、ハッシュを既定の方式で保存し直す必要があるかも返します。
+/// ログイン時のハッシュ更新には `UserService::check_password` を使用してください。
+pub fn check_password(password: &str, hash: &str) -> PasswordCheck {
+    default_hashers().check_password(password, hash)
+}
+
+/// 長さ以外の情報を漏らさないよう、タイミングを一定にしてバイト列を比較します。
+pub fn constant_time_eq(a: &[u8], b: &[u8]) -> bool {
+    a.len() == b.len() && a.iter().zip(b).fold(0u8, |acc, (x, y)| acc | (x ^ y)) == 0
 }
 
 #[cfg(test)]
@@ -49,5 +61,12 @@ mod tests {
         assert_ne!(hash1, hash2);
     }
 
+    #[test]
+    fn test_unusable_password_never_matches() {
+        let hash = make_unusable_password();
+        assert!(hash.starts_with(UNUSABLE_PASSWORD_PREFIX));
+        assert!(!verify_password("", &hash));
+        assert!(!verify_password(&hash, &hash));
+    }
 
 }
@@ -0,0 +1,139 @@
+//! Djangoの `manage.py` に相当する管理コマンド。
+//!
+//! ```bash
+//! cargo run --bin manage -- migrate
+//! cargo run --bin manage -- createsuperuser
+//! cargo run --bin manage -- changepassword admin
+//! cargo run --bin manage -- check --deploy
+//! ```
+
+use std::io::{self, Write};
+use std::process::ExitCode;
+use clap::{Parser, Subcommand};
+use migration::{Migrator, MigratorTrait};
+use rust_django_starter::settings::Settings;
+use rust_django_starter::checks::{CheckContext, CheckRegistry, Level};
+use rust_django_starter::{db, management};
+
+#[derive(Parser)]
+#[command(name = "manage", about = "Rust Django Starter の管理コマンド")]
+struct Cli {
+    #[command(subcommand)]
+    command: Command,
+}
+
+#[derive(Subcommand)]
+enum Command {
+    /// マイグレーションを適用する (Django: manage.py migrate)
+    Migrate,
+    /// スーパーユーザーを作成する (Django: manage.py createsuperuser)
+    Createsuperuser {
+        /// ユーザー名 (省略時は対話入力)
+        #[arg(long)]
+        username: Option<String>,
+        /// メールアドレス (省略時は対話入力)
+        #[arg(long)]
+        email: Option<String>,
+        /// 対話入力を行わない。パスワードは環境変数 `SUPERUSER_PASSWORD` から読み込む
+        #[arg(long)]
+        noinput: bool,
+    },
+    /// ユーザーのパスワードを変更する (Django: manage.py changepassword)
+    Changepassword {
+        /// 対象のユーザー名
+        username: String,
+    },
+    /// システムチェックを実行する (Django: manage.py check)
+    Check {
+        /// デプロイ用のチェックも実行する
+        #[arg(long)]
+        deploy: bool,
+        /// このレベル以上のメッセージがあれば失敗とする
+        #[arg(long, default_value = "ERROR")]
+        fail_level: Level,
+    },
+}
+
+#[tokio::main]
+async fn main() -> ExitCode {
+    dotenvy::dotenv().ok();
+    let cli = Cli::parse();
+
+    match run(cli.command).await {
+        Ok(()) => ExitCode::SUCCESS,
+        Err(e) => {
+            eprintln!("CommandError: {}", e);
+            ExitCode::FAILURE
+        }
+    }
+}
+
+async fn run(command: Command) -> Result<(), Box<dyn std::error::Error>> {
+    let settings = Settings::load()?;
+    let db = db::set_up_db(&settings.database_url).await?;
+
+    match command {
+        Command::Migrate => {
+            Migrator::up(&db, None).await?;
+            println!("マイグレーションを適用しました。");
+        }
+        Command::Createsuperuser { username, email, noinput } => {
+            let (username, email, password) = if noinput {
+                let username = username.ok_or("--noinput の場合は --username が必要です")?;
+                let password = std::env::var("SUPERUSER_PASSWORD")
+                    .map_err(|_| "--noinput の場合は環境変数 SUPERUSER_PASSWORD が必要です")?;
+                (username, email.unwrap_or_default(), password)
+            } else {
+                let username = match username {
+                    Some(u) => u,
+                    None => prompt("ユーザー名: ")?,
+                };
+                let email = match email {
+                    Some(e) => e,
+                    None => prompt("メールアドレス: ")?,
+                };
+                (username, email, prompt_new_password()?)
+            };
+
+            management::create_superuser(&db, &settings, &username, &email, &password).await?;
+            println!("スーパーユーザー '{}' を作成しました。", username);
+        }
+        Command::Changepassword { username } => {
+            println!("ユーザー '{}' のパスワードを変更します", username);
+            let password = prompt_new_password()?;
+            management::change_password(&db, &settings, &username, &password).await?;
+            println!("ユーザー '{}' のパスワードを変更しました。", username);
+        }
+        Command::Check { deploy, fail_level } => {
+            let report = CheckRegistry::default().run(&CheckContext::new(&settings, &db), deploy).await;
+            if report.fails_at(fail_level) {
+                return Err(report.to_string().into());
+            }
+            println!("{}", report);
+        }
+    }
+
+    Ok(())
+}
+
+/// 1行入力を受け付ける
+fn prompt(label: &str) -> io::Result<String> {
+    print!("{}", label);
+    io::stdout().flush()?;
+    let mut line = String::new();
+    io::stdin().read_line(&mut line)?;
+    Ok(line.trim().to_string())
+}
+
+/// パスワードを2回入力させ、一致した場合に返す (入力は画面に表示しない)
+fn prompt_new_password() -> Result<String, Box<dyn std::error::Error>> {
+    let password = rpassword::prompt_password("パスワード: ")?;
+    let confirmation = rpassword::prompt_password("パスワード (確認用): ")?;
+    if password != confirmation {
+        return Err("パスワードが一致しません".into());
+    }
+    if password.is_empty() {
+        return Err("パスワードを入力してください".into());
+    }
+    Ok(password)
+}
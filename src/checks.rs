@@ -0,0 +1,591 @@
+//! システムチェック。Djangoの `django.core.checks` と `manage.py check` に相当します。
+//!
+//! 危険な設定や環境の不備を起動前に検出します。チェックは `SystemCheck` トレイトを実装し、
+//! `CheckRegistry` に登録します。
+//!
+//! - 通常のチェック: 起動時と `manage check` で毎回実行します
+//! - デプロイ用のチェック (`deploy()` が `true`): `release` プロファイルでの起動時と `manage check --deploy` で実行します
+//!
+//! `ERROR` 以上のメッセージがあるとサーバーは起動しません。
+//! 個別のチェックは `SILENCED_SYSTEM_CHECKS` にIDを指定すると無視できます。
+//!
+//! ```ignore
+//! struct RequireAdminEmail;
+//!
+//! #[rocket::async_trait]
+//! impl SystemCheck for RequireAdminEmail {
+//!     async fn run(&self, ctx: &CheckContext<'_>) -> Vec<CheckMessage> { ... }
+//! }
+//!
+//! let registry = CheckRegistry::default().register(RequireAdminEmail);
+//! ```
+
+use std::fmt;
+use std::path::PathBuf;
+use std::str::FromStr;
+use migration::{Migrator, MigratorTrait};
+use sea_orm::*;
+use crate::auth_utils::verify_password;
+use crate::entities::{prelude::*, user};
+use crate::settings::Settings;
+
+/// `Rocket.toml` にコミットされている開発用の `secret_key`
+const COMMITTED_SECRET_KEY: &str = "hPRYyVRiMyxpw5sBB1XeCMN1kFsDCqKvBi2QJxBVHQo=";
+
+/// 以前のマイグレーションが作成していた初期管理者 (`admin` / `admin`) のパスワードハッシュ
+const SEEDED_ADMIN_PASSWORD_HASH: &str =
+    "$argon2id$v=19$m=19456,t=2,p=1$gbLN0HdzIAg3N/2UmMqJYQ$5xWILs4rN6xIJJE9uPQSAggsNMlPCFlRnS3iqv63Juk";
+
+/// 以前の初期管理者の既知のパスワード。マイグレーションは `admin`、削除した `/setup_admin` は `password` で作成していた
+const DEFAULT_ADMIN_PASSWORDS: [&str; 2] = ["admin", "password"];
+
+/// 署名に使う鍵の最小の長さ (バイト)
+const SECRET_KEY_MIN_LENGTH: usize = 32;
+
+/// ビューが参照しているテンプレート名と参照元のファイル (build.rs がビルド時に生成)
+const REFERENCED_TEMPLATES: &[(&str, &str)] = include!(concat!(env!("OUT_DIR"), "/referenced_templates.rs"));
+
+/// メッセージの重要度 (Djangoの `checks.DEBUG` 〜 `checks.CRITICAL`)
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord)]
+pub enum Level {
+    Debug,
+    Info,
+    Warning,
+    Error,
+    Critical,
+}
+
+impl Level {
+    /// レポートの見出し (Django: `ERRORS:` / `WARNINGS:` など)
+    fn heading(self) -> &'static str {
+        match self {
+            Level::Debug => "DEBUGS",
+            Level::Info => "INFOS",
+            Level::Warning => "WARNINGS",
+            Level::Error => "ERRORS",
+            Level::Critical => "CRITICALS",
+        }
+    }
+}
+
+impl FromStr for Level {
+    type Err = String;
+
+    /// `manage check --fail-level` の値 (大文字・小文字は区別しない)
+    fn from_str(value: &str) -> Result<Self, Self::Err> {
+        match value.to_uppercase().as_str() {
+            "DEBUG" => Ok(Level::Debug),
+            "INFO" => Ok(Level::Info),
+            "WARNING" => Ok(Level::Warning),
+            "ERROR" => Ok(Level::Error),
+            "CRITICAL" => Ok(Level::Critical),
+            _ => Err(format!("不明なレベルです: {} (DEBUG, INFO, WARNING, ERROR, CRITICAL)", value)),
+        }
+    }
+}
+
+/// チェック結果の1件。Djangoの `CheckMessage` に相当します。
+#[derive(Debug, Clone, PartialEq)]
+pub struct CheckMessage {
+    pub level: Level,
+    /// 一意なID (例: `security.W012`)。`SILENCED_SYSTEM_CHECKS` で指定します
+    pub id: &'static str,
+    pub msg: String,
+    /// 対処方法
+    pub hint: Option<String>,
+}
+
+impl CheckMessage {
+    pub fn new(level: Level, id: &'static str, msg: impl Into<String>) -> Self {
+        CheckMessage { level, id, msg: msg.into(), hint: None }
+    }
+
+    pub fn warning(id: &'static str, msg: impl Into<String>) -> Self {
+        CheckMessage::new(Level::Warning, id, msg)
+    }
+
+    pub fn error(id: &'static str, msg: impl Into<String>) -> Self {
+        CheckMessage::new(Level::Error, id, msg)
+    }
+
+    pub fn with_hint(mut self, hint: impl Into<String>) -> Self {
+        self.hint = Some(hint.into());
+        self
+    }
+}
+
+impl fmt::Display for CheckMessage {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "({}) {}", self.id, self.msg)?;
+        if let Some(hint) = &self.hint {
+            write!(f, "\n\tHINT: {}", hint)?;
+        }
+        Ok(())
+    }
+}
+
+/// チェックに渡す実行環境
+pub struct CheckContext<'a> {
+    pub settings: &'a Settings,
+    pub db: &'a DatabaseConnection,
+    /// Rocket 自体でTLSを終端しているか (Rocket.toml の `tls`)
+    pub tls_enabled: bool,
+    /// テンプレートのディレクトリ (Rocket.toml の `template_dir`)
+    pub template_dir: PathBuf,
+}
+
+impl<'a> CheckContext<'a> {
+    /// Rocketの設定 (Rocket.toml / `ROCKET_*` 環境変数) から構築します。
+    pub fn new(settings: &'a Settings, db: &'a DatabaseConnection) -> Self {
+        let figment = rocket::Config::figment();
+        CheckContext {
+            settings,
+            db,
+            tls_enabled: figment.find_value("tls").is_ok(),
+            template_dir: figment
+                .extract_inner::<PathBuf>("template_dir")
+                .unwrap_or_else(|_| PathBuf::from("templates")),
+        }
+    }
+}
+
+/// 1つのチェック。Djangoの `@register` で登録するチェック関数に相当します。
+#[rocket::async_trait]
+pub trait SystemCheck: Send + Sync {
+    /// デプロイ時にのみ実行するか (Django: `@register(deploy=True)`)
+    fn deploy(&self) -> bool {
+        false
+    }
+
+    async fn run(&self, ctx: &CheckContext<'_>) -> Vec<CheckMessage>;
+}
+
+/// チェックの実行結果
+#[derive(Debug, Default)]
+pub struct CheckReport {
+    pub messages: Vec<CheckMessage>,
+    /// `SILENCED_SYSTEM_CHECKS` で除外した件数
+    pub silenced: usize,
+}
+
+impl CheckReport {
+    /// `level` 以上のメッセージがあるか (Djangoの `--fail-level`)
+    pub fn fails_at(&self, level: Level) -> bool {
+        self.messages.iter().any(|message| message.level >= level)
+    }
+}
+
+impl fmt::Display for CheckReport {
+    /// `manage.py check` と同じ形式で出力します
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        if !self.messages.is_empty() {
+            writeln!(f, "System check identified some issues:")?;
+        }
+        for level in [Level::Critical, Level::Error, Level::Warning, Level::Info, Level::Debug] {
+            let messages: Vec<_> = self.messages.iter().filter(|m| m.level == level).collect();
+            if messages.is_empty() {
+                continue;
+            }
+            writeln!(f, "\n{}:", level.heading())?;
+            for message in messages {
+                writeln!(f, "{}", message)?;
+            }
+        }
+        let count = self.messages.len();
+        let issues = match count {
+            0 => "no issues".to_string(),
+            1 => "1 issue".to_string(),
+            n => format!("{} issues", n),
+        };
+        if count > 0 {
+            writeln!(f)?;
+        }
+        write!(f, "System check identified {} ({} silenced).", issues, self.silenced)
+    }
+}
+
+/// チェックの一覧。Djangoの `django.core.checks.registry` に相当します。
+pub struct CheckRegistry {
+    checks: Vec<Box<dyn SystemCheck>>,
+}
+
+impl Default for CheckRegistry {
+    /// 標準のチェックを全て登録した状態
+    fn default() -> Self {
+        CheckRegistry::new()
+            .register(MigrationsCheck)
+            .register(TemplatesCheck::default())
+            .register(SecretKeyCheck)
+            .register(DefaultAdminCheck)
+            .register(SecureCookiesCheck)
+            .register(HttpsCheck)
+            .register(HstsCheck)
+            .register(SiteUrlCheck)
+    }
+}
+
+impl CheckRegistry {
+    pub fn new() -> Self {
+        CheckRegistry { checks: Vec::new() }
+    }
+
+    /// チェックを追加します
+    pub fn register(mut self, check: impl SystemCheck + 'static) -> Self {
+        self.checks.push(Box::new(check));
+        self
+    }
+
+    /// チェックを実行します。`deploy` が `true` の場合はデプロイ用のチェックも実行します。
+    pub async fn run(&self, ctx: &CheckContext<'_>, deploy: bool) -> CheckReport {
+        let mut report = CheckReport::default();
+        for check in self.checks.iter().filter(|check| deploy || !check.deploy()) {
+            for message in check.run(ctx).await {
+                if ctx.settings.silenced_system_checks.iter().any(|id| id == message.id) {
+                    report.silenced += 1;
+                } else {
+                    report.messages.push(message);
+                }
+            }
+        }
+        report
+    }
+}
+
+// ---------------------------------------------------------------------------
+// 標準のチェック
+// ---------------------------------------------------------------------------
+
+/// 未適用のマイグレーション (Djangoの `runserver` が表示する警告に相当)
+pub struct MigrationsCheck;
+
+#[rocket::async_trait]
+impl SystemCheck for MigrationsCheck {
+    async fn run(&self, ctx: &CheckContext<'_>) -> Vec<CheckMessage> {
+        match Migrator::get_pending_migrations(ctx.db).await {
+            Ok(pending) if pending.is_empty() => Vec::new(),
+            Ok(pending) => {
+                let names: Vec<&str> = pending.iter().map(|m| m.name()).collect();
+                vec![CheckMessage::warning(
+                    "migrations.W001",
+                    format!("未適用のマイグレーションが {} 件あります: {}", names.len(), names.join(", ")),
+                )
+                .with_hint("`manage migrate` を実行してください。")]
+            }
+            Err(e) => vec![CheckMessage::error(
+                "migrations.E001",
+                format!("マイグレーションの状態を取得できません: {}", e),
+            )],
+        }
+    }
+}
+
+/// ビューが参照しているテンプレートが存在するか
+pub struct TemplatesCheck {
+    /// テンプレート名と参照元のファイル
+    pub referenced: Vec<(&'static str, &'static str)>,
+}
+
+impl Default for TemplatesCheck {
+    fn default() -> Self {
+        TemplatesCheck { referenced: REFERENCED_TEMPLATES.to_vec() }
+    }
+}
+
+#[rocket::async_trait]
+impl SystemCheck for TemplatesCheck {
+    async fn run(&self, ctx: &CheckContext<'_>) -> Vec<CheckMessage> {
+        self.referenced
+            .iter()
+            .filter(|(name, _)| !template_exists(&ctx.template_dir, name))
+            .map(|(name, file)| {
+                CheckMessage::error(
+                    "templates.E001",
+                    format!("テンプレート '{}' ({} で使用) が {} にありません。", name, file, ctx.template_dir.display()),
+                )
+            })
+            .collect()
+    }
+}
+
+/// Rocket のテンプレート名 (`admin/list`) に対応するファイル (`admin/list.html.tera` など) があるか
+fn template_exists(dir: &std::path::Path, name: &str) -> bool {
+    let path = dir.join(name);
+    let (Some(parent), Some(stem)) = (path.parent(), path.file_name()) else {
+        return false;
+    };
+    let prefix = format!("{}.", stem.to_string_lossy());
+    std::fs::read_dir(parent)
+        .map(|entries| {
+            entries.flatten().any(|entry| {
+                let file_name = entry.file_name().to_string_lossy().into_owned();
+                file_name.starts_with(&prefix) && file_name.ends_with(".tera")
+            })
+        })
+        .unwrap_or(false)
+}
+
+/// 署名に使う鍵 (Django: `security.W009`)
+pub struct SecretKeyCheck;
+
+#[rocket::async_trait]
+impl SystemCheck for SecretKeyCheck {
+    fn deploy(&self) -> bool {
+        true
+    }
+
+    async fn run(&self, ctx: &CheckContext<'_>) -> Vec<CheckMessage> {
+        let secret_key = ctx.settings.secret_key();
+        let hint = "`openssl rand -base64 32` で生成した値を環境変数 `ROCKET_SECRET_KEY` に設定してください。";
+        if secret_key == COMMITTED_SECRET_KEY.as_bytes() {
+            return vec![CheckMessage::error(
+                "security.E001",
+                "secret_key がリポジトリの Rocket.toml に含まれている値のままです。セッションやトークンの署名を偽造される恐れがあります。",
+            )
+            .with_hint(hint)];
+        }
+        if secret_key.len() < SECRET_KEY_MIN_LENGTH {
+            return vec![CheckMessage::warning(
+                "security.W009",
+                format!("secret_key が短すぎます ({} 文字未満)。", SECRET_KEY_MIN_LENGTH),
+            )
+            .with_hint(hint)];
+        }
+        Vec::new()
+    }
+}
+
+/// 以前のマイグレーションが作成した既知のパスワードの管理者
+pub struct DefaultAdminCheck;
+
+#[rocket::async_trait]
+impl SystemCheck for DefaultAdminCheck {
+    fn deploy(&self) -> bool {
+        true
+    }
+
+    async fn run(&self, ctx: &CheckContext<'_>) -> Vec<CheckMessage> {
+        let users = User::find()
+            .filter(user::Column::IsActive.eq(true))
+            .filter(
+                Condition::any()
+                    .add(user::Column::PasswordHash.eq(SEEDED_ADMIN_PASSWORD_HASH))
+                    .add(user::Column::Username.eq("admin")),
+            )
+            .all(ctx.db)
+            .await;
+        let users = match users {
+            Ok(users) => users,
+            Err(e) => return vec![CheckMessage::error("auth.E002", format!("ユーザーを確認できません: {}", e))],
+        };
+
+        users
+            .iter()
+            .filter(|u| {
+                u.password_hash == SEEDED_ADMIN_PASSWORD_HASH
+                    || DEFAULT_ADMIN_PASSWORDS.iter().any(|password| verify_password(password, &u.password_hash))
+            })
+            .map(|u| {
+                CheckMessage::error(
+                    "auth.E001",
+                    format!("ユーザー '{}' のパスワードが初期値 ({}) のままです。", u.username, DEFAULT_ADMIN_PASSWORDS.join(" / ")),
+                )
+                .with_hint(format!("`manage changepassword {}` でパスワードを変更してください。", u.username))
+            })
+            .collect()
+    }
+}
+
+/// Cookieの `Secure` 属性 (Django: `security.W012` / `security.W016`)
+pub struct SecureCookiesCheck;
+
+#[rocket::async_trait]
+impl SystemCheck for SecureCookiesCheck {
+    fn deploy(&self) -> bool {
+        true
+    }
+
+    async fn run(&self, ctx: &CheckContext<'_>) -> Vec<CheckMessage> {
+        let mut messages = Vec::new();
+        if !ctx.settings.session_cookie_secure {
+            messages.push(
+                CheckMessage::warning(
+                    "security.W012",
+                    "SESSION_COOKIE_SECURE が有効になっていません。HTTPの通信でセッションのCookieを盗まれる恐れがあります。",
+                )
+                .with_hint("HTTPSで運用し、SESSION_COOKIE_SECURE=true を設定してください。"),
+            );
+        }
+        if !ctx.settings.csrf_cookie_secure {
+            messages.push(
+                CheckMessage::warning("security.W016", "CSRF_COOKIE_SECURE が有効になっていません。")
+                    .with_hint("HTTPSで運用し、CSRF_COOKIE_SECURE=true を設定してください。"),
+            );
+        }
+        messages
+    }
+}
+
+/// HTTPSの設定 (Django: `security.W008`)
+pub struct HttpsCheck;
+
+#[rocket::async_trait]
+impl SystemCheck for HttpsCheck {
+    fn deploy(&self) -> bool {
+        true
+    }
+
+    async fn run(&self, ctx: &CheckContext<'_>) -> Vec<CheckMessage> {
+        if ctx.tls_enabled {
+            return Vec::new();
+        }
+        vec![CheckMessage::warning("security.W008", "TLS が設定されていないため、通信が暗号化されません。")
+            .with_hint(
+                "Rocket.toml の [release.tls] を設定してください。HTTPSを終端するリバースプロキシの背後で運用する場合は、\
+                 SILENCED_SYSTEM_CHECKS に security.W008 を追加してください。",
+            )]
+    }
+}
+
+/// HSTSの設定 (Django: `security.W004`)
+pub struct HstsCheck;
+
+#[rocket::async_trait]
+impl SystemCheck for HstsCheck {
+    fn deploy(&self) -> bool {
+        true
+    }
+
+    async fn run(&self, ctx: &CheckContext<'_>) -> Vec<CheckMessage> {
+        if ctx.settings.secure_hsts_seconds > 0 {
+            return Vec::new();
+        }
+        vec![CheckMessage::warning("security.W004", "SECURE_HSTS_SECONDS が設定されていません。")
+            .with_hint(
+                "HTTPSのみで運用する場合は SECURE_HSTS_SECONDS を設定してください。\
+                 値を誤るとサイトに長期間アクセスできなくなるため、短い値から始めてください。",
+            )]
+    }
+}
+
+/// メール内のリンクに使うサイトURL (Django: `security.W020` の `ALLOWED_HOSTS`)
+pub struct SiteUrlCheck;
+
+#[rocket::async_trait]
+impl SystemCheck for SiteUrlCheck {
+    fn deploy(&self) -> bool {
+        true
+    }
+
+    async fn run(&self, ctx: &CheckContext<'_>) -> Vec<CheckMessage> {
+        if ctx.settings.site_url.is_some() {
+            return Vec::new();
+        }
+        let hint = "SITE_URL にサイトのURL (例: https://example.com) を設定してください。";
+        if ctx.settings.allowed_hosts.iter().any(|host| host == "*") {
+            return vec![CheckMessage::error(
+                "security.E020",
+                "SITE_URL が未設定で ALLOWED_HOSTS が `*` のため、パスワードリセットのリンクを任意のドメインに向けられます。",
+            )
+            .with_hint(hint)];
+        }
+        vec![CheckMessage::warning(
+            "security.W020",
+            "SITE_URL が設定されていません。メール内のリンクはリクエストの Host ヘッダーから組み立てられ、常に http になります。",
+        )
+        .with_hint(hint)]
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use rocket::figment::providers::Serialized;
+    use rocket::figment::Figment;
+
+    fn settings(values: rocket::serde::json::Value) -> Settings {
+        let figment = Figment::from(Serialized::defaults(rocket::serde::json::json!({
+            "database_url": "postgres://localhost/app",
+        })))
+        .merge(Serialized::defaults(values));
+        Settings::from_figment(&figment).unwrap()
+    }
+
+    fn ids(report: &CheckReport) -> Vec<&'static str> {
+        report.messages.iter().map(|m| m.id).collect()
+    }
+
+    fn run(registry: CheckRegistry, settings: &Settings, deploy: bool) -> CheckReport {
+        let db = DatabaseConnection::Disconnected;
+        let ctx = CheckContext { settings, db: &db, tls_enabled: false, template_dir: PathBuf::from("templates") };
+        rocket::async_test(registry.run(&ctx, deploy))
+    }
+
+    fn security_checks() -> CheckRegistry {
+        CheckRegistry::new().register(SecretKeyCheck).register(SecureCookiesCheck).register(HttpsCheck)
+    }
+
+    #[test]
+    fn test_deploy_checks_run_only_in_deploy_mode() {
+        let settings = settings(rocket::serde::json::json!({ "secret_key": COMMITTED_SECRET_KEY }));
+        assert!(run(security_checks(), &settings, false).messages.is_empty());
+
+        let report = run(security_checks(), &settings, true);
+        assert_eq!(ids(&report), vec!["security.E001", "security.W012", "security.W016", "security.W008"]);
+        assert!(report.fails_at(Level::Error));
+    }
+
+    #[test]
+    fn test_secure_settings_pass_and_silenced_checks_are_counted() {
+        let settings = settings(rocket::serde::json::json!({
+            "secret_key": "a-long-and-random-secret-key-for-production-use",
+            "session_cookie_secure": true,
+            "csrf_cookie_secure": true,
+            "silenced_system_checks": ["security.W008"],
+        }));
+        let report = run(security_checks(), &settings, true);
+        assert!(report.messages.is_empty());
+        assert_eq!(report.silenced, 1);
+        assert_eq!(report.to_string(), "System check identified no issues (1 silenced).");
+    }
+
+    #[test]
+    fn test_site_url_check() {
+        let registry = || CheckRegistry::new().register(SiteUrlCheck);
+        let report = run(registry(), &settings(rocket::serde::json::json!({})), true);
+        assert_eq!(ids(&report), vec!["security.W020"]);
+        let report = run(registry(), &settings(rocket::serde::json::json!({ "allowed_hosts": "*" })), true);
+        assert_eq!(ids(&report), vec!["security.E020"]);
+        let report = run(registry(), &settings(rocket::serde::json::json!({ "site_url": "https://example.com" })), true);
+        assert!(report.messages.is_empty());
+    }
+
+    #[test]
+    fn test_missing_templates_are_reported() {
+        let settings = settings(rocket::serde::json::json!({}));
+        let check = TemplatesCheck { referenced: vec![("login", "src/a.rs"), ("admin/change_list", "src/b.rs"), ("admin/nope", "src/c.rs")] };
+        let report = run(CheckRegistry::new().register(check), &settings, false);
+        assert_eq!(ids(&report), vec!["templates.E001"]);
+        assert!(report.messages[0].msg.contains("admin/nope"));
+
+        // ビューが参照しているテンプレートは全て存在する
+        let report = run(CheckRegistry::new().register(TemplatesCheck::default()), &settings, false);
+        assert!(report.messages.is_empty(), "{}", report);
+        assert!(REFERENCED_TEMPLATES.iter().any(|(name, _)| *name == "admin/change_form"));
+    }
+
+    #[test]
+    fn test_report_format() {
+        let report = CheckReport {
+            messages: vec![
+                CheckMessage::warning("security.W012", "cookie").with_hint("fix it"),
+                CheckMessage::error("auth.E001", "admin"),
+            ],
+            silenced: 0,
+        };
+        assert_eq!(
+            report.to_string(),
+            "System check identified some issues:\n\nERRORS:\n(auth.E001) admin\n\nWARNINGS:\n(security.W012) cookie\n\tHINT: fix it\n\nSystem check identified 2 issues (0 silenced)."
+        );
+        assert_eq!("warning".parse::<Level>(), Ok(Level::Warning));
+    }
+}
@@ -0,0 +1,349 @@
+use rocket::form::Form;
+use rocket::http::CookieJar;
+use rocket::response::{Flash, Redirect};
+use rocket::serde::json::serde_json;
+use rocket::State;
+use rocket_dyn_templates::context;
+use sea_orm::*;
+use chrono::{Duration, Utc};
+use crate::entities::{prelude::*, group, permission, user};
+use crate::guards::auth::AuthenticatedUser;
+use crate::csrf::CsrfToken;
+use crate::password_validation::PasswordValidators;
+use crate::services::api_tokens::ApiTokenService;
+use crate::services::user_service::UserService;
+use crate::sessions::{ClientInfo, SessionInfo, SessionManager};
+use crate::validation::{clean_email, FieldErrors, UserFormValidation};
+use crate::views::app_template::AppTemplate;
+
+/// プロフィールの編集フォーム
+#[derive(FromForm)]
+pub struct ProfileForm<'r> {
+    pub username: &'r str,
+    #[field(default = "")]
+    pub email: &'r str,
+}
+
+/// パスワード変更フォーム。
+/// Djangoの `PasswordChangeForm` に相当します。
+#[derive(FromForm)]
+pub struct PasswordChangeForm<'r> {
+    pub old_password: &'r str,
+    pub new_password1: &'r str,
+    pub new_password2: &'r str,
+}
+
+/// APIトークンの発行フォーム
+#[derive(FromForm)]
+pub struct ApiTokenForm<'r> {
+    pub name: &'r str,
+    /// 有効期間 (日)。0 の場合は無期限
+    #[field(default = 0)]
+    pub expires_days: i64,
+    #[field(default = Vec::new())]
+    pub permissions: Vec<String>,
+}
+
+async fn render_profile(
+    db: &DatabaseConnection,
+    user: &user::Model,
+    form: serde_json::Value,
+    field_errors: FieldErrors,
+) -> AppTemplate {
+    let groups: Vec<group::Model> = user.find_related(Group).all(db).await.unwrap_or_default();
+    AppTemplate::new("account/profile", context! {
+        title: "プロフィール",
+        active_nav: "profile",
+        form: form,
+        groups: groups,
+        field_errors: field_errors,
+    })
+}
+
+/// プロフィール画面 (GET)。
+/// ユーザー名・メールアドレスと所属グループを表示します。
+#[get("/")]
+pub async fn profile(db: &State<DatabaseConnection>, user: AuthenticatedUser, _csrf: CsrfToken) -> AppTemplate {
+    let form = serde_json::json!({ "username": user.user.username, "email": user.user.email });
+    render_profile(db.inner(), &user.user, form, FieldErrors::new()).await
+}
+
+/// プロフィールの更新 (POST)。
+/// ユーザー名の検証は管理画面と同じ `UserFormValidation` を使用します。
+#[post("/", data = "<form>")]
+pub async fn update_profile(
+    db: &State<DatabaseConnection>,
+    validators: &State<PasswordValidators>,
+    user: AuthenticatedUser,
+    form: Form<ProfileForm<'_>>,
+) -> Result<Flash<Redirect>, AppTemplate> {
+    let username = form.username.trim();
+    let submitted = serde_json::json!({ "username": username, "email": form.email });
+
+    let mut errors = FieldErrors::new();
+    if let Err(form_errors) = UserFormValidation::new(username, None).validate_fields(validators) {
+        errors.merge(form_errors);
+    } else if username != user.user.username {
+        match UserService::find_by_username(db.inner(), username).await {
+            Ok(Some(_)) => errors.add("username", "このユーザー名は既に使用されています"),
+            Ok(None) => {}
+            Err(e) => errors.add(FieldErrors::NON_FIELD, e.to_string()),
+        }
+    }
+    let email = clean_email(form.email).unwrap_or_else(|message| {
+        errors.add("email", message);
+        String::new()
+    });
+    if !errors.is_empty() {
+        return Err(render_profile(db.inner(), &user.user, submitted, errors).await);
+    }
+
+    let mut active: user::ActiveModel = user.user.clone().into();
+    active.username = Set(username.to_string());
+    active.email = Set(email);
+    if let Err(e) = active.update(db.inner()).await {
+        errors.add(FieldErrors::NON_FIELD, format!("更新に失敗しました: {}", e));
+        return Err(render_profile(db.inner(), &user.user, submitted, errors).await);
+    }
+
+    Ok(Flash::success(Redirect::to("/account"), "プロフィールを更新しました"))
+}
+
+fn render_password_change(validators: &PasswordValidators, field_errors: FieldErrors) -> AppTemplate {
+    AppTemplate::new("account/password_change", context! {
+        title: "パスワードの変更",
+        active_nav: "password",
+        field_errors: field_errors,
+        password_help_texts: validators.help_texts(),
+    })
+}
+
+/// パスワード変更画面 (GET)。
+/// Djangoの `PasswordChangeView` に相当します。
+#[get("/password")]
+pub fn password_change_form(
+    validators: &State<PasswordValidators>,
+    _user: AuthenticatedUser,
+    _csrf: CsrfToken,
+) -> AppTemplate {
+    render_password_change(validators, FieldErrors::new())
+}
+
+/// パスワード変更処理 (POST)。
+/// 現在のパスワードを確認してから変更し、他の端末のセッションを無効化してセッションキーを再発行します。
+#[post("/password", data = "<form>")]
+#[allow(clippy::too_many_arguments)]
+pub async fn password_change(
+    db: &State<DatabaseConnection>,
+    sessions: &State<SessionManager>,
+    validators: &State<PasswordValidators>,
+    user: AuthenticatedUser,
+    client: ClientInfo,
+    cookies: &CookieJar<'_>,
+    form: Form<PasswordChangeForm<'_>>,
+) -> Result<Redirect, AppTemplate> {
+    let mut errors = FieldErrors::new();
+    // Django: PasswordChangeForm.clean_old_password()
+    match UserService::check_password(db.inner(), &user.user, form.old_password).await {
+        Ok(true) => {}
+        Ok(false) => errors.add("old_password", "現在のパスワードが間違っています。もう一度入力してください。"),
+        Err(e) => errors.add(FieldErrors::NON_FIELD, e.to_string()),
+    }
+
+    // Django: SetPasswordForm.clean_new_password2()
+    if form.new_password1.is_empty() {
+        errors.add("new_password1", "パスワードを入力してください");
+    } else if form.new_password1 != form.new_password2 {
+        errors.add("new_password2", "確認用パスワードが一致しません");
+    } else if let Err(form_errors) = UserFormValidation::new(&user.user.username, Some(form.new_password1))
+        .with_email(&user.user.email)
+        .validate_fields(validators)
+    {
+        errors.extend("new_password2", form_errors.get("password").to_vec());
+    }
+    if !errors.is_empty() {
+        return Err(render_password_change(validators, errors));
+    }
+
+    let user_id = user.user.id;
+    if let Err(e) = UserService::set_password(db.inner(), user.user, form.new_password1).await {
+        errors.add(FieldErrors::NON_FIELD, format!("変更に失敗しました: {}", e));
+        return Err(render_password_change(validators, errors));
+    }
+
+    // Django: update_session_auth_hash(request, form.user)
+    if let Err(e) = sessions.update_session_auth_hash(cookies, user_id, &client).await {
+        errors.add(FieldErrors::NON_FIELD, e.to_string());
+        return Err(render_password_change(validators, errors));
+    }
+
+    Ok(Redirect::to("/account/password/done"))
+}
+
+/// パスワード変更完了画面 (GET)。
+/// Djangoの `PasswordChangeDoneView` に相当します。
+#[get("/password/done")]
+pub fn password_change_done(_user: AuthenticatedUser) -> AppTemplate {
+    AppTemplate::new("account/password_change_done", context! {
+        title: "パスワードの変更完了",
+        active_nav: "password",
+    })
+}
+
+/// セキュリティ設定画面 (GET)。
+/// ログイン中のセッション (端末) 一覧を表示します。
+#[get("/security")]
+pub async fn security(
+    sessions: &State<SessionManager>,
+    user: AuthenticatedUser,
+    _csrf: CsrfToken,
+) -> AppTemplate {
+    let current_key = user.session.session_key.as_str();
+    let active_sessions: Vec<SessionInfo> = sessions
+        .list_for_user(user.user.id)
+        .await
+        .unwrap_or_default()
+        .iter()
+        .map(|s| SessionInfo::new(s, Some(current_key)))
+        .collect();
+
+    AppTemplate::new("account/security", context! {
+        title: "セキュリティ",
+        active_nav: "security",
+        sessions: active_sessions,
+    })
+}
+
+/// セッションを1件無効化 (POST)。
+/// 現在のセッションを指定した場合はログアウトになります。
+#[post("/security/revoke/<session_id>")]
+pub async fn revoke_session(
+    sessions: &State<SessionManager>,
+    user: AuthenticatedUser,
+    cookies: &CookieJar<'_>,
+    session_id: &str,
+) -> Flash<Redirect> {
+    if user.session.public_id() == session_id {
+        return match sessions.logout(cookies).await {
+            Ok(_) => Flash::success(Redirect::to("/auth/login"), "ログアウトしました"),
+            Err(e) => Flash::error(Redirect::to("/account/security"), format!("ログアウトに失敗しました: {}", e)),
+        };
+    }
+
+    match sessions.revoke(user.user.id, session_id).await {
+        Ok(true) => Flash::success(Redirect::to("/account/security"), "セッションを無効化しました"),
+        Ok(false) => Flash::warning(Redirect::to("/account/security"), "セッションが見つかりません"),
+        Err(e) => Flash::error(Redirect::to("/account/security"), format!("無効化に失敗しました: {}", e)),
+    }
+}
+
+/// 現在のセッション以外を全て無効化 (POST)。
+/// 「他の全ての端末からログアウト」に相当します。
+#[post("/security/revoke_others")]
+pub async fn revoke_other_sessions(
+    sessions: &State<SessionManager>,
+    user: AuthenticatedUser,
+) -> Flash<Redirect> {
+    match sessions.invalidate_user(user.user.id, Some(&user.session.session_key)).await {
+        Ok(count) => Flash::success(Redirect::to("/account/security"), format!("{} 件のセッションを無効化しました", count)),
+        Err(e) => Flash::error(Redirect::to("/account/security"), format!("無効化に失敗しました: {}", e)),
+    }
+}
+
+/// トークンの範囲として選択できる権限 (ユーザー自身が持つ権限)
+async fn grantable_permissions(db: &DatabaseConnection, user: &user::Model) -> Result<Vec<permission::Model>, DbErr> {
+    let codenames = user.get_all_permissions(db).await?;
+    Permission::find()
+        .filter(permission::Column::Codename.is_in(codenames))
+        .order_by_asc(permission::Column::Codename)
+        .all(db)
+        .await
+}
+
+async fn render_api_tokens(
+    db: &DatabaseConnection,
+    user: &user::Model,
+    new_token: Option<String>,
+    form: serde_json::Value,
+    field_errors: FieldErrors,
+) -> AppTemplate {
+    let tokens = ApiTokenService::list_for_user(db, user.id).await.unwrap_or_default();
+    let permissions = grantable_permissions(db, user).await.unwrap_or_default();
+    AppTemplate::new("account/api_tokens", context! {
+        title: "APIトークン",
+        active_nav: "api_tokens",
+        tokens: tokens,
+        permissions: permissions,
+        new_token: new_token,
+        form: form,
+        field_errors: field_errors,
+    })
+}
+
+/// APIトークン一覧・発行画面 (GET)。
+#[get("/tokens")]
+pub async fn api_tokens(db: &State<DatabaseConnection>, user: AuthenticatedUser, _csrf: CsrfToken) -> AppTemplate {
+    render_api_tokens(db.inner(), &user.user, None, serde_json::json!({}), FieldErrors::new()).await
+}
+
+/// APIトークンの発行 (POST)。
+/// 発行したトークンはこのレスポンスでのみ表示され、以降は再表示できません。
+#[post("/tokens", data = "<form>")]
+pub async fn create_api_token(
+    db: &State<DatabaseConnection>,
+    user: AuthenticatedUser,
+    form: Form<ApiTokenForm<'_>>,
+) -> AppTemplate {
+    let submitted = serde_json::json!({
+        "name": form.name,
+        "expires_days": form.expires_days,
+        "permissions": form.permissions,
+    });
+
+    let mut errors = FieldErrors::new();
+    if form.expires_days < 0 {
+        errors.add("expires_days", "有効期間が不正です");
+        return render_api_tokens(db.inner(), &user.user, None, submitted, errors).await;
+    }
+
+    let expires_at = (form.expires_days > 0).then(|| (Utc::now() + Duration::days(form.expires_days)).into());
+    match ApiTokenService::create(db.inner(), &user.user, form.name, expires_at, &form.permissions).await {
+        Ok((_, plaintext)) => {
+            render_api_tokens(db.inner(), &user.user, Some(plaintext), serde_json::json!({}), errors).await
+        }
+        Err(e) => {
+            errors.add(FieldErrors::NON_FIELD, e.to_string());
+            render_api_tokens(db.inner(), &user.user, None, submitted, errors).await
+        }
+    }
+}
+
+/// APIトークンの削除 (POST)
+#[post("/tokens/revoke/<token_id>")]
+pub async fn revoke_api_token(
+    db: &State<DatabaseConnection>,
+    user: AuthenticatedUser,
+    token_id: i32,
+) -> Flash<Redirect> {
+    match ApiTokenService::revoke(db.inner(), user.user.id, token_id).await {
+        Ok(true) => Flash::success(Redirect::to("/account/tokens"), "トークンを削除しました"),
+        Ok(false) => Flash::warning(Redirect::to("/account/tokens"), "トークンが見つかりません"),
+        Err(e) => Flash::error(Redirect::to("/account/tokens"), format!("削除に失敗しました: {}", e)),
+    }
+}
+
+pub fn routes() -> Vec<rocket::Route> {
+    routes![
+        profile,
+        update_profile,
+        password_change_form,
+        password_change,
+        password_change_done,
+        security,
+        revoke_session,
+        revoke_other_sessions,
+        api_tokens,
+        create_api_token,
+        revoke_api_token
+    ]
+}
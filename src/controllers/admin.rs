@@ -5,12 +5,20 @@ use rocket_dyn_templates::context;
 use rocket::serde::json::serde_json;
 use sea_orm::*;
 use serde::{Deserialize, Serialize};
-use crate::entities::{prelude::*, user, group_user, group};
-use crate::guards::auth::AdminUser;
-use crate::auth_utils::hash_password;
+use crate::admin::actions::{ActionField, ExportCsv};
+use crate::admin::{ActionRequest, ActionResponse, AdminAction, AdminSite, Fieldset, ModelAdmin};
+use crate::entities::{prelude::*, user, group, group_user};
+use crate::guards::permission::{PermissionRequired, StaffUser, AddUser, ChangeUser};
+use crate::auth_utils::{hash_password, make_unusable_password};
 use crate::csrf::CsrfToken;
-use crate::views::list::ListView;
-use crate::views::edit::{CreateView, UpdateView, DeleteView};
+use crate::errors::AppError;
+use crate::password_validation::PasswordValidators;
+use crate::validation::{clean_email, FieldErrors, UserFormValidation};
+use crate::services::admin_log::{self, AdminLogService};
+use crate::services::login_throttle::{LoginThrottle, LOCKOUT_FIELD};
+use crate::services::two_factor::TwoFactorService;
+use crate::sessions::{SessionInfo, SessionManager};
+use crate::views::edit::{CreateView, UpdateView};
 use crate::views::app_template::AppTemplate;
 
 /// ユーザー作成・編集フォームのデータ構造
@@ -19,6 +27,8 @@ use crate::views::app_template::AppTemplate;
 pub struct UserForm<'r> {
     pub username: &'r str,
     pub password: &'r str,
+    #[field(default = "")]
+    pub email: &'r str,
     #[field(default = false)]
     pub is_admin: bool,
     #[field(default = false)]
@@ -26,148 +36,141 @@ pub struct UserForm<'r> {
     /// 所属グループIDリスト
     #[field(default = Vec::new())]
     pub group_ids: Vec<i32>,
-    /// CSRFトークン（フォームから受け取る）
-    #[field(default = "")]
-    pub csrf_token: &'r str,
 }
 
+/// ダッシュボードの「最近のアクション」に表示する件数 (Djangoと同じ)
+const RECENT_ACTIONS_LIMIT: u64 = 10;
+
+/// ダッシュボード。登録したモデルの一覧 (`available_apps`) は `ContextFairing` が渡します。
+/// Djangoの `AdminSite.index` に相当。
 #[get("/")]
-pub fn dashboard(_admin: AdminUser) -> AppTemplate {
+pub async fn dashboard(db: &State<DatabaseConnection>, site: &State<AdminSite>, staff: StaffUser) -> AppTemplate {
+    let recent_actions = AdminLogService::recent_for_user(db.inner(), site, staff.user.user.id, RECENT_ACTIONS_LIMIT)
+        .await
+        .unwrap_or_default();
     AppTemplate::new("admin/dashboard", context! {
         active_nav: "dashboard",
+        recent_actions: recent_actions,
     })
 }
 
-pub struct UserListView;
+/// ユーザーの管理画面の設定 (Djangoの `UserAdmin`)。
+/// 一覧・削除・履歴は `AdminSite` の画面を使い、追加・編集はパスワードやグループを扱うため独自のルートで処理します。
+pub struct UserAdmin;
 
-impl ListView<User> for UserListView {
-    fn template_name(&self) -> &'static str {
-        "admin/list"
+#[rocket::async_trait]
+impl ModelAdmin for UserAdmin {
+    type Entity = User;
+
+    fn app_label(&self) -> &'static str {
+        "auth"
     }
 
-    fn filter_queryset(&self, query: Select<User>, q: &str) -> Select<User> {
-         query.filter(user::Column::Username.contains(q))
+    fn app_verbose_name(&self) -> &'static str {
+        "認証と認可"
     }
 
-    fn get_context_data(&self, _db: &DatabaseConnection) -> serde_json::Value {
-        serde_json::json!({
-            "active_nav": "users",
-        })
+    fn verbose_name(&self) -> &'static str {
+        "ユーザー"
     }
-}
 
-#[derive(Serialize)]
-struct UserWithGroups {
-    user: user::Model,
-    groups: Vec<group::Model>,
-}
+    fn labels(&self) -> &'static [(&'static str, &'static str)] {
+        &[
+            ("username", "ユーザー名"),
+            ("email", "メールアドレス"),
+            ("groups", "グループ"),
+            ("is_active", "アクティブ"),
+            ("is_admin", "スタッフ"),
+            ("two_factor", "2要素認証"),
+        ]
+    }
 
-/// ユーザー一覧を表示する管理画面。
-/// Generic View (`ListView`) を使用せず、グループ情報を取得するためにカスタム実装。
-#[get("/users?<page>&<q>&<sort>&<dir>&<is_active>&<is_admin>")]
-pub async fn list_users(
-    db: &State<DatabaseConnection>,
-    _admin: AdminUser,
-    _csrf: CsrfToken,
-    page: Option<usize>,
-    q: Option<String>,
-    sort: Option<String>,
-    dir: Option<String>,
-    is_active: Option<bool>,
-    is_admin: Option<bool>,
-) -> AppTemplate {
-    let page = if page.unwrap_or(1) < 1 { 1 } else { page.unwrap_or(1) };
-    let per_page = 10;
+    fn list_display(&self) -> &'static [&'static str] {
+        &["id", "username", "email", "groups", "is_active", "is_admin", "two_factor"]
+    }
 
-    // 1. クエリ構築
-    let mut query = User::find();
+    fn list_display_links(&self) -> &'static [&'static str] {
+        &["username"]
+    }
 
-    // 2. 検索適用
-    let search_query = q.clone().unwrap_or_default();
-    if !search_query.trim().is_empty() {
-         query = query.filter(user::Column::Username.contains(&search_query));
+    fn search_fields(&self) -> &'static [&'static str] {
+        &["username", "email"]
     }
 
-    // 3. フィルタ適用
-    if let Some(active) = is_active {
-        query = query.filter(user::Column::IsActive.eq(active));
+    fn list_filter(&self) -> &'static [&'static str] {
+        &["is_active", "is_admin"]
     }
-    if let Some(admin) = is_admin {
-        query = query.filter(user::Column::IsAdmin.eq(admin));
+
+    /// `password_hash` / `totp_secret` / `totp_last_counter` は汎用のフォームに含めない。
+    /// パスワードと2要素認証は専用の画面と操作で変更します
+    fn fieldsets(&self) -> Vec<Fieldset> {
+        vec![
+            Fieldset::new(None, &["username", "email"]),
+            Fieldset::new(Some("権限"), &["is_active", "is_admin"]),
+        ]
     }
 
-    // 4. ソート適用
-    let sort_col = sort.clone().unwrap_or_else(|| "id".to_string());
-    let direction = dir.clone().unwrap_or_else(|| "desc".to_string());
-    
-    let order = if direction.to_lowercase() == "asc" {
-        Order::Asc
-    } else {
-        Order::Desc
-    };
+    fn actions(&self) -> Vec<Box<dyn AdminAction<User>>> {
+        vec![
+            Box::new(SetActive { active: true }),
+            Box::new(SetActive { active: false }),
+            Box::new(GroupMembership { add: true }),
+            Box::new(GroupMembership { add: false }),
+            Box::new(ResetPasswords),
+            Box::new(ResetTwoFactor),
+            Box::new(ExportCsv { fields: &["id", "username", "email", "is_active", "is_admin"] }),
+        ]
+    }
 
-    match sort_col.as_str() {
-        "username" => query = query.order_by(user::Column::Username, order),
-        "is_active" => query = query.order_by(user::Column::IsActive, order),
-        "is_admin" => query = query.order_by(user::Column::IsAdmin, order),
-        _ => query = query.order_by(user::Column::Id, order), // Default to ID
-    }
-
-    // 5. ページネーション (Userのみ)
-    let paginator = query.paginate(db.inner(), per_page);
-    let num_pages = paginator.num_pages().await.unwrap_or(0);
-    let users = paginator.fetch_page((page - 1) as u64).await.unwrap_or_default();
-
-    // 6. グループ情報の取得
-    let mut items = Vec::new();
-    for u in users {
-        let groups: Vec<group::Model> = u.find_related(Group).all(db.inner()).await.unwrap_or_default();
-        items.push(UserWithGroups {
-            user: u,
-            groups,
-        });
-    }
-
-    // フィルタ定義と現在の状態
-    let filters = serde_json::json!([
-        {
-            "label": "アクティブ",
-            "parameter_name": "is_active",
-            "current_value": is_active.map(|b| b.to_string()).unwrap_or_default(),
-            "choices": [
-                ["true", "はい"],
-                ["false", "いいえ"]
-            ]
-        },
-        {
-            "label": "スタッフ",
-            "parameter_name": "is_admin",
-            "current_value": is_admin.map(|b| b.to_string()).unwrap_or_default(),
-            "choices": [
-                ["true", "はい"],
-                ["false", "いいえ"]
-            ]
+    /// 所属グループ名と2要素認証の状態 (`totp_secret` はシリアライズされないため) を追加する
+    async fn list_rows(&self, db: &DatabaseConnection, users: Vec<user::Model>) -> Result<Vec<serde_json::Value>, DbErr> {
+        let mut rows = Vec::new();
+        for (u, groups) in users.iter().zip(users.load_many_to_many(Group, GroupUser, db).await?) {
+            let mut row = serde_json::to_value(u).unwrap_or_default();
+            row["groups"] = groups.into_iter().map(|g| g.name).collect();
+            row["two_factor"] = TwoFactorService::is_enabled(u).into();
+            rows.push(row);
         }
-    ]);
-
-    // 7. コンテキスト構築
-    AppTemplate::new("admin/list", context! {
-        items: items,
-        current_page: page,
-        num_pages: num_pages,
-        search_query: search_query,
-        sort: sort_col,
-        dir: direction,
-        base_url: "/admin/users",
-        active_nav: "users",
-        admin_filters: filters,
-    })
+        Ok(rows)
+    }
+}
+
+/// ユーザー名・メールアドレス・パスワードの検証。
+/// Djangoの `UserCreationForm` / `UserChangeForm` の `clean_*` に相当します。
+/// パスワードは `AUTH_PASSWORD_VALIDATORS` 相当のバリデーターで検証します (編集時の空欄は変更なし)。
+fn clean_user_form(
+    data: &serde_json::Value,
+    validators: &PasswordValidators,
+    password_required: bool,
+) -> Result<(), FieldErrors> {
+    let username = data["username"].as_str().unwrap_or("").trim();
+    let email = data["email"].as_str().unwrap_or("");
+    let password = data["password"].as_str().unwrap_or("");
+
+    let mut errors = FieldErrors::new();
+    if let Err(message) = clean_email(email) {
+        errors.add("email", message);
+    }
+    if password_required && password.is_empty() {
+        errors.add("password", "パスワードは必須です");
+    }
+
+    let password = if password.is_empty() { None } else { Some(password) };
+    if let Err(form_errors) = UserFormValidation::new(username, password)
+        .with_email(email.trim())
+        .validate_fields(validators)
+    {
+        errors.merge(form_errors);
+    }
+    errors.into_result()
 }
 
-pub struct UserCreateView;
+pub struct UserCreateView<'a> {
+    pub validators: &'a PasswordValidators,
+}
 
 #[rocket::async_trait]
-impl CreateView<user::ActiveModel> for UserCreateView {
+impl CreateView<user::ActiveModel> for UserCreateView<'_> {
     fn success_url(&self) -> String {
         "/admin/users".to_string()
     }
@@ -175,9 +178,14 @@ impl CreateView<user::ActiveModel> for UserCreateView {
     async fn get_context_data(&self, db: &DatabaseConnection) -> serde_json::Value {
         let all_groups = Group::find().all(db).await.unwrap_or_default();
         serde_json::json!({
-            "all_groups": all_groups
+            "all_groups": all_groups,
+            "password_help_texts": self.validators.help_texts(),
         })
     }
+
+    async fn clean(&self, _db: &DatabaseConnection, data: &serde_json::Value) -> Result<(), FieldErrors> {
+        clean_user_form(data, self.validators, true)
+    }
     
     async fn save(&self, db: &DatabaseConnection, data: &serde_json::Value) -> Result<user::Model, DbErr> {
          let username = data["username"].as_str().ok_or(DbErr::Custom("ユーザー名は必須です".into()))?;
@@ -187,6 +195,7 @@ impl CreateView<user::ActiveModel> for UserCreateView {
              return Err(DbErr::Custom("ユーザー名は必須です".into()));
          }
          
+         let email = clean_email(data["email"].as_str().unwrap_or("")).map_err(DbErr::Custom)?;
          let password_hash = hash_password(password).map_err(|e| DbErr::Custom(e.to_string()))?;
          
          let is_admin = data["is_admin"].as_bool().unwrap_or(false);
@@ -195,6 +204,7 @@ impl CreateView<user::ActiveModel> for UserCreateView {
          let active_model = user::ActiveModel {
             username: Set(username.to_owned()),
             password_hash: Set(password_hash),
+            email: Set(email),
             is_admin: Set(is_admin),
             is_active: Set(is_active),
             ..Default::default()
@@ -231,8 +241,13 @@ impl CreateView<user::ActiveModel> for UserCreateView {
 /// ユーザー作成フォーム (GET)。
 /// Djangoの `CreateView` (GET) に相当。
 #[get("/users/create")]
-pub async fn create_user_form(db: &State<DatabaseConnection>, _admin: AdminUser, _csrf: CsrfToken) -> AppTemplate {
-    let view = UserCreateView;
+pub async fn create_user_form(
+    db: &State<DatabaseConnection>,
+    validators: &State<PasswordValidators>,
+    _perm: PermissionRequired<AddUser>,
+    _csrf: CsrfToken,
+) -> AppTemplate {
+    let view = UserCreateView { validators };
     let context = serde_json::json!({
         "active_nav": "users",
         "base_url": "/admin/users",
@@ -245,30 +260,28 @@ pub async fn create_user_form(db: &State<DatabaseConnection>, _admin: AdminUser,
 #[post("/users/create", data = "<form>")]
 pub async fn create_user(
     db: &State<DatabaseConnection>,
-    _admin: AdminUser,
-    csrf: CsrfToken,
+    validators: &State<PasswordValidators>,
+    perm: PermissionRequired<AddUser>,
     form: Form<UserForm<'_>>,
 ) -> Result<Flash<Redirect>, AppTemplate> {
-    if !csrf.verify(form.csrf_token) {
-        return Ok(Flash::error(Redirect::to("/admin/users/create"), "CSRF検証に失敗しました"));
-    }
-    
     // Convert form to JSON Value
     let form_data = serde_json::to_value(form.into_inner()).unwrap();
-    let view = UserCreateView;
+    let view = UserCreateView { validators };
     
     // Error case needs context
     let context = serde_json::json!({
         "active_nav": "users",
     });
 
-    view.post(db, &form_data, context).await
+    view.post(db, &perm.user.user, &form_data, context).await
 }
 
-pub struct UserUpdateView;
+pub struct UserUpdateView<'a> {
+    pub validators: &'a PasswordValidators,
+}
 
 #[rocket::async_trait]
-impl UpdateView<user::ActiveModel> for UserUpdateView {
+impl UpdateView<user::ActiveModel> for UserUpdateView<'_> {
     fn success_url(&self) -> String {
         "/admin/users".to_string()
     }
@@ -276,14 +289,27 @@ impl UpdateView<user::ActiveModel> for UserUpdateView {
     async fn get_context_data(&self, db: &DatabaseConnection) -> serde_json::Value {
         let all_groups = Group::find().all(db).await.unwrap_or_default();
         serde_json::json!({
-            "all_groups": all_groups
+            "all_groups": all_groups,
+            "password_help_texts": self.validators.help_texts(),
         })
     }
 
+    async fn clean(&self, _db: &DatabaseConnection, _id: i32, data: &serde_json::Value) -> Result<(), FieldErrors> {
+        clean_user_form(data, self.validators, false)
+    }
+
     async fn get_object(&self, db: &DatabaseConnection, id: i32) -> Result<Option<user::Model>, DbErr> {
         User::find_by_id(id).one(db).await
     }
 
+    /// パスワードのハッシュはフォームの項目名 (`password`) で記録する
+    fn changed_fields(&self, before: &serde_json::Value, after: &serde_json::Value, _form_data: &serde_json::Value) -> Vec<String> {
+        admin_log::changed_fields(before, after)
+            .into_iter()
+            .map(|field| if field == "password_hash" { "password".to_string() } else { field })
+            .collect()
+    }
+
     async fn save(&self, db: &DatabaseConnection, id: i32, data: &serde_json::Value) -> Result<user::Model, DbErr> {
          let existing = User::find_by_id(id).one(db).await?.ok_or(DbErr::Custom("NotFound".into()))?;
          let mut active_model: user::ActiveModel = existing.into();
@@ -294,6 +320,10 @@ impl UpdateView<user::ActiveModel> for UserUpdateView {
              }
          }
          
+         if let Some(e) = data["email"].as_str() {
+             active_model.email = Set(clean_email(e).map_err(DbErr::Custom)?);
+         }
+
          if let Some(p) = data["password"].as_str() {
              if !p.is_empty() {
                  let hash = hash_password(p).map_err(|e| DbErr::Custom(e.to_string()))?;
@@ -335,25 +365,19 @@ impl UpdateView<user::ActiveModel> for UserUpdateView {
     }
 }
 
-pub struct UserDeleteView;
-
-#[rocket::async_trait]
-impl DeleteView<User> for UserDeleteView {
-    fn success_url(&self) -> String {
-        "/admin/users".to_string()
-    }
-}
-
 /// ユーザー編集フォーム (GET)。
 /// Djangoの `UpdateView` (GET) に相当。
 #[get("/users/edit/<id>")]
 pub async fn edit_user_form(
     db: &State<DatabaseConnection>,
-    _admin: AdminUser,
+    sessions: &State<SessionManager>,
+    throttle: &State<LoginThrottle>,
+    validators: &State<PasswordValidators>,
+    _perm: PermissionRequired<ChangeUser>,
     _csrf: CsrfToken,
     id: i32,
 ) -> Result<AppTemplate, Flash<Redirect>> {
-    let view = UserUpdateView;
+    let view = UserUpdateView { validators };
     // ユーザーとグループ情報を取得して初期値として渡す必要がある
     // `view.get` は `get_object` を呼ぶが、それは `form` 初期値（Model）のため。
     // グループIDリスト（M2M）は Model に含まれないため、手動で取得して context に入れる
@@ -368,9 +392,38 @@ pub async fn edit_user_form(
         .await
         .unwrap_or_default();
 
+    // ログイン中のセッション一覧 (Djangoにはないが、強制ログアウト用に表示する)
+    let user_sessions: Vec<SessionInfo> = sessions
+        .list_for_user(id)
+        .await
+        .unwrap_or_default()
+        .iter()
+        .map(|s| SessionInfo::new(s, None))
+        .collect();
+
+    // ログイン失敗によるロック状態と2要素認証の状態
+    let target = User::find_by_id(id).one(db.inner()).await.unwrap_or_default();
+    let login_failure = match &target {
+        Some(u) => throttle.status(db.inner(), &u.username).await.unwrap_or_default(),
+        None => None,
+    };
+    let two_factor_enabled = target.as_ref().is_some_and(TwoFactorService::is_enabled);
+    let recovery_codes_remaining = TwoFactorService::remaining_recovery_codes(db.inner(), id)
+        .await
+        .unwrap_or_default();
+    let is_locked = login_failure
+        .as_ref()
+        .and_then(|f| f.locked_until)
+        .is_some_and(|until| until > chrono::Utc::now());
+
     let context = serde_json::json!({
         "active_nav": "users",
         "user_group_ids": group_ids,
+        "user_sessions": user_sessions,
+        "login_failure": login_failure,
+        "is_locked": is_locked,
+        "two_factor_enabled": two_factor_enabled,
+        "recovery_codes_remaining": recovery_codes_remaining,
         "base_url": "/admin/users",
     });
 
@@ -382,85 +435,315 @@ pub async fn edit_user_form(
 #[post("/users/edit/<id>", data = "<form>")]
 pub async fn edit_user(
     db: &State<DatabaseConnection>,
-    _admin: AdminUser,
-    csrf: CsrfToken,
+    sessions: &State<SessionManager>,
+    validators: &State<PasswordValidators>,
+    perm: PermissionRequired<ChangeUser>,
     id: i32,
     form: Form<UserForm<'_>>,
 ) -> Result<Flash<Redirect>, AppTemplate> {
-    if !csrf.verify(form.csrf_token) {
-        return Ok(Flash::error(Redirect::to(format!("/admin/users/edit/{}", id)), "CSRF検証に失敗しました"));
-    }
-    
+    let password_changed = !form.password.is_empty();
     let form_data = serde_json::to_value(form.into_inner()).unwrap();
-    let view = UserUpdateView;
+    let view = UserUpdateView { validators };
     
     let context = serde_json::json!({
         "active_nav": "users",
     });
 
-    view.post(db, id, &form_data, context).await
+    let result = view.post(db, &perm.user.user, id, &form_data, context).await;
+
+    // パスワードが変更された場合は既存のセッションを全て無効化する
+    // (Djangoの update_session_auth_hash を使わない場合の挙動)
+    if result.is_ok() && password_changed {
+        if let Err(e) = sessions.invalidate_user(id, None).await {
+            return Ok(Flash::warning(Redirect::to("/admin/users"), format!("更新しましたが、セッションの無効化に失敗しました: {}", e)));
+        }
+    }
+
+    result
 }
 
-/// ユーザー削除処理 (POST)。
-/// Djangoの `DeleteView` に相当。
-#[post("/users/delete/<id>")]
-pub async fn delete_user(
-    db: &State<DatabaseConnection>,
-    _admin: AdminUser,
+/// ユーザーのセッションを1件無効化 (POST)。
+#[post("/users/<id>/sessions/revoke/<session_id>")]
+pub async fn revoke_user_session(
+    sessions: &State<SessionManager>,
+    _perm: PermissionRequired<ChangeUser>,
     id: i32,
-) -> Result<Flash<Redirect>, Flash<Redirect>> {
-    let view = UserDeleteView;
-    view.post(db, id).await
+    session_id: &str,
+) -> Flash<Redirect> {
+    let redirect = Redirect::to(format!("/admin/users/edit/{}", id));
+    match sessions.revoke(id, session_id).await {
+        Ok(true) => Flash::success(redirect, "セッションを無効化しました"),
+        Ok(false) => Flash::warning(redirect, "セッションが見つかりません"),
+        Err(e) => Flash::error(redirect, format!("無効化に失敗しました: {}", e)),
+    }
 }
 
-#[derive(FromForm)]
-pub struct UserActionForm {
-    pub action: String,
-    pub selected_ids: Vec<i32>,
-    pub csrf_token: String,
+/// ユーザーの全セッションを無効化 (POST)。強制ログアウトに相当します。
+#[post("/users/<id>/sessions/revoke_all")]
+pub async fn revoke_all_user_sessions(
+    sessions: &State<SessionManager>,
+    _perm: PermissionRequired<ChangeUser>,
+    id: i32,
+) -> Flash<Redirect> {
+    let redirect = Redirect::to(format!("/admin/users/edit/{}", id));
+    match sessions.invalidate_user(id, None).await {
+        Ok(count) => Flash::success(redirect, format!("{} 件のセッションを無効化しました", count)),
+        Err(e) => Flash::error(redirect, format!("無効化に失敗しました: {}", e)),
+    }
 }
 
-/// ユーザー一括操作 (POST)。
-#[post("/users/action", data = "<form>")]
-pub async fn user_action(
+/// ログイン失敗によるロックを解除 (POST)。
+/// django-axes の `axes_reset` に相当します。
+#[post("/users/unlock/<id>")]
+pub async fn unlock_user(
     db: &State<DatabaseConnection>,
-    _admin: AdminUser,
-    csrf: CsrfToken,
-    form: Form<UserActionForm>,
-) -> Result<Flash<Redirect>, Flash<Redirect>> {
-    if !csrf.verify(&form.csrf_token) {
-        return Err(Flash::error(Redirect::to("/admin/users"), "CSRF検証に失敗しました"));
-    }
-
-    match form.action.as_str() {
-        "delete_selected" => {
-            if form.selected_ids.is_empty() {
-                return Ok(Flash::warning(Redirect::to("/admin/users"), "ユーザーが選択されていません"));
+    throttle: &State<LoginThrottle>,
+    perm: PermissionRequired<ChangeUser>,
+    id: i32,
+) -> Flash<Redirect> {
+    let redirect = Redirect::to(format!("/admin/users/edit/{}", id));
+    let user = match User::find_by_id(id).one(db.inner()).await {
+        Ok(Some(u)) => u,
+        Ok(None) => return Flash::error(Redirect::to("/admin/users"), "ユーザーが見つかりません"),
+        Err(e) => return Flash::error(redirect, format!("ロック解除に失敗しました: {}", e)),
+    };
+
+    let object = serde_json::to_value(&user).unwrap_or_default();
+    let result = match throttle.reset(db.inner(), &user.username).await {
+        Ok(()) => AdminLogService::log_change(db.inner(), perm.user.user.id, "user", &object, &[LOCKOUT_FIELD.to_string()])
+            .await
+            .map_err(AppError::from),
+        Err(e) => Err(e),
+    };
+    match result {
+        Ok(_) => Flash::success(redirect, "ロックを解除しました"),
+        Err(e) => Flash::error(redirect, format!("ロック解除に失敗しました: {}", e)),
+    }
+}
+
+/// 2要素認証をリセット (POST)。
+/// 認証アプリを紛失したユーザーのために、TOTPの登録とリカバリーコードを削除します。
+#[post("/users/otp_reset/<id>")]
+pub async fn reset_user_two_factor(
+    db: &State<DatabaseConnection>,
+    perm: PermissionRequired<ChangeUser>,
+    id: i32,
+) -> Flash<Redirect> {
+    let redirect = Redirect::to(format!("/admin/users/edit/{}", id));
+    let user = match User::find_by_id(id).one(db.inner()).await {
+        Ok(Some(u)) => u,
+        Ok(None) => return Flash::error(Redirect::to("/admin/users"), "ユーザーが見つかりません"),
+        Err(e) => return Flash::error(redirect, format!("リセットに失敗しました: {}", e)),
+    };
+
+    match reset_two_factor(db.inner(), &perm.user.user, user).await {
+        Ok(_) => Flash::success(redirect, "2要素認証をリセットしました"),
+        Err(e) => Flash::error(redirect, format!("リセットに失敗しました: {}", e)),
+    }
+}
+
+/// 2要素認証をリセットし、操作履歴に記録する
+async fn reset_two_factor(db: &DatabaseConnection, actor: &user::Model, user: user::Model) -> Result<(), AppError> {
+    let object = serde_json::to_value(&user).unwrap_or_default();
+    TwoFactorService::disable(db, user).await?;
+    AdminLogService::log_change(db, actor.id, "user", &object, &["totp_secret".to_string()]).await?;
+    Ok(())
+}
+
+/// `DbErr` 以外のエラーを一括操作のエラーにする
+fn action_error(e: AppError) -> DbErr {
+    match e {
+        AppError::Database(e) => e,
+        e => DbErr::Custom(e.to_string()),
+    }
+}
+
+/// 選択したユーザーを有効・無効にする。自分自身は無効にできません
+struct SetActive {
+    active: bool,
+}
+
+#[rocket::async_trait]
+impl AdminAction<User> for SetActive {
+    fn name(&self) -> &'static str {
+        if self.active { "activate_users" } else { "deactivate_users" }
+    }
+
+    fn description(&self) -> &'static str {
+        if self.active { "選択されたユーザーを有効にする" } else { "選択されたユーザーを無効にする" }
+    }
+
+    async fn run(&self, db: &DatabaseConnection, request: &ActionRequest<'_, User>) -> Result<ActionResponse, DbErr> {
+        let mut count = 0;
+        for u in request.objects.iter().filter(|u| u.is_active != self.active) {
+            if !self.active && u.id == request.actor.id {
+                continue;
             }
-            
-            let result = User::delete_many()
-                .filter(user::Column::Id.is_in(form.selected_ids.clone()))
-                .exec(db.inner())
-                .await;
-
-            match result {
-                Ok(res) => Ok(Flash::success(Redirect::to("/admin/users"), format!("{} 件のユーザーを削除しました", res.rows_affected))),
-                Err(e) => Err(Flash::error(Redirect::to("/admin/users"), format!("削除に失敗しました: {}", e))),
+            let mut active_model = u.clone().into_active_model();
+            active_model.is_active = Set(self.active);
+            let updated = active_model.update(db).await?;
+            let object = serde_json::to_value(&updated).unwrap_or_default();
+            AdminLogService::log_change(db, request.actor.id, &request.opts.model_name, &object, &["is_active".to_string()]).await?;
+            count += 1;
+        }
+        let state = if self.active { "有効" } else { "無効" };
+        Ok(ActionResponse::Success(format!("{} 件のユーザーを{}にしました", count, state)))
+    }
+}
+
+/// 選択したユーザーをグループに追加する・グループから外す。グループは確認画面で選択します
+struct GroupMembership {
+    add: bool,
+}
+
+#[rocket::async_trait]
+impl AdminAction<User> for GroupMembership {
+    fn name(&self) -> &'static str {
+        if self.add { "add_to_group" } else { "remove_from_group" }
+    }
+
+    fn description(&self) -> &'static str {
+        if self.add { "選択されたユーザーをグループに追加" } else { "選択されたユーザーをグループから外す" }
+    }
+
+    fn confirmation(&self) -> bool {
+        true
+    }
+
+    async fn confirmation_context(&self, db: &DatabaseConnection, _request: &ActionRequest<'_, User>) -> Result<serde_json::Value, DbErr> {
+        let groups = Group::find().order_by_asc(group::Column::Name).all(db).await?;
+        let field = ActionField {
+            name: "group",
+            label: "グループ",
+            choices: groups.into_iter().map(|g| (g.id.to_string(), g.name)).collect(),
+        };
+        Ok(serde_json::json!({ "fields": [field] }))
+    }
+
+    async fn run(&self, db: &DatabaseConnection, request: &ActionRequest<'_, User>) -> Result<ActionResponse, DbErr> {
+        let group_id = request.data.get("group").and_then(|id| id.parse::<i32>().ok());
+        let target = match group_id {
+            Some(id) => Group::find_by_id(id).one(db).await?,
+            None => None,
+        };
+        let Some(target) = target else {
+            return Ok(ActionResponse::Warning("グループを選択してください".to_string()));
+        };
+
+        let ids: Vec<i32> = request.objects.iter().map(|u| u.id).collect();
+        let members: Vec<i32> = GroupUser::find()
+            .filter(group_user::Column::GroupId.eq(target.id))
+            .filter(group_user::Column::UserId.is_in(ids.clone()))
+            .all(db)
+            .await?
+            .into_iter()
+            .map(|gu| gu.user_id)
+            .collect();
+        // 追加は未所属のユーザー、外す場合は所属しているユーザーが対象
+        let changed: Vec<&user::Model> = request.objects.iter().filter(|u| members.contains(&u.id) != self.add).collect();
+
+        let txn = db.begin().await?;
+        if self.add {
+            for u in &changed {
+                group_user::ActiveModel {
+                    group_id: Set(target.id),
+                    user_id: Set(u.id),
+                    ..Default::default()
+                }
+                .insert(&txn)
+                .await?;
+            }
+        } else {
+            GroupUser::delete_many()
+                .filter(group_user::Column::GroupId.eq(target.id))
+                .filter(group_user::Column::UserId.is_in(ids))
+                .exec(&txn)
+                .await?;
+        }
+        for u in &changed {
+            let object = serde_json::to_value(u).unwrap_or_default();
+            AdminLogService::log_change(&txn, request.actor.id, &request.opts.model_name, &object, &["groups".to_string()]).await?;
+        }
+        txn.commit().await?;
+
+        let message = if self.add {
+            format!("{} 件のユーザーをグループ「{}」に追加しました", changed.len(), target.name)
+        } else {
+            format!("{} 件のユーザーをグループ「{}」から外しました", changed.len(), target.name)
+        };
+        Ok(ActionResponse::Success(message))
+    }
+}
+
+/// 選択したユーザーのパスワードを使用できないものにし、全てのセッションを無効化する。
+/// ユーザーはパスワードのリセット画面から新しいパスワードを設定します。自分自身は対象外です
+struct ResetPasswords;
+
+#[rocket::async_trait]
+impl AdminAction<User> for ResetPasswords {
+    fn name(&self) -> &'static str {
+        "reset_passwords"
+    }
+
+    fn description(&self) -> &'static str {
+        "選択されたユーザーのパスワードをリセット"
+    }
+
+    fn confirmation(&self) -> bool {
+        true
+    }
+
+    async fn run(&self, db: &DatabaseConnection, request: &ActionRequest<'_, User>) -> Result<ActionResponse, DbErr> {
+        let sessions = request.rocket.state::<SessionManager>();
+        let mut count = 0;
+        for u in request.objects.iter().filter(|u| u.id != request.actor.id) {
+            let mut active_model = u.clone().into_active_model();
+            active_model.password_hash = Set(make_unusable_password());
+            let updated = active_model.update(db).await?;
+            if let Some(sessions) = sessions {
+                sessions.invalidate_user(u.id, None).await.map_err(action_error)?;
             }
+            let object = serde_json::to_value(&updated).unwrap_or_default();
+            AdminLogService::log_change(db, request.actor.id, &request.opts.model_name, &object, &["password".to_string()]).await?;
+            count += 1;
+        }
+        Ok(ActionResponse::Success(format!("{} 件のユーザーのパスワードをリセットしました", count)))
+    }
+}
+
+/// 選択したユーザーの2要素認証をリセットする
+struct ResetTwoFactor;
+
+#[rocket::async_trait]
+impl AdminAction<User> for ResetTwoFactor {
+    fn name(&self) -> &'static str {
+        "reset_two_factor"
+    }
+
+    fn description(&self) -> &'static str {
+        "選択されたユーザーの2要素認証をリセット"
+    }
+
+    async fn run(&self, db: &DatabaseConnection, request: &ActionRequest<'_, User>) -> Result<ActionResponse, DbErr> {
+        let mut count = 0;
+        for u in request.objects.iter().filter(|u| TwoFactorService::is_enabled(u)) {
+            reset_two_factor(db, request.actor, u.clone()).await.map_err(action_error)?;
+            count += 1;
         }
-        _ => Ok(Flash::warning(Redirect::to("/admin/users"), "不明な操作です")),
+        Ok(ActionResponse::Success(format!("{} 件のユーザーの2要素認証をリセットしました", count)))
     }
 }
 
 pub fn routes() -> Vec<rocket::Route> {
     routes![
         dashboard,
-        list_users,
         create_user_form,
         create_user,
         edit_user_form,
         edit_user,
-        delete_user,
-        user_action
+        revoke_user_session,
+        revoke_all_user_sessions,
+        unlock_user,
+        reset_user_two_factor
     ]
 }
@@ -0,0 +1,218 @@
+use std::collections::HashMap;
+use rocket::form::Form;
+use rocket::http::Status;
+use rocket::response::{Flash, Redirect};
+use rocket::State;
+use rocket::serde::json::serde_json;
+use sea_orm::DatabaseConnection;
+use crate::admin::actions::{ActionInfo, ActionOutcome, App};
+use crate::admin::sites::{AdminModel, ChangeListParams};
+use crate::admin::AdminSite;
+use crate::guards::permission::StaffUser;
+use crate::services::admin_log::AdminLogService;
+use crate::settings::Settings;
+use crate::views::app_template::AppTemplate;
+
+// `AdminSite` に登録したモデルの管理画面。
+// Djangoの `AdminSite.get_urls()` が作るURLに相当します。
+// 独自の画面を持つモデル (ユーザーの追加・編集など) は、同じパスの静的なルートが優先されます。
+
+/// URLのモデルを探し、権限を確認する。
+/// 登録されていないモデルは 404、権限がなければ 403 を返します
+fn model_admin<'a>(site: &'a AdminSite, staff: &StaffUser, model: &str, action: &str) -> Result<&'a dyn AdminModel, Status> {
+    let admin = site.get(model).ok_or(Status::NotFound)?;
+    if staff.has_perm(&admin.opts().perm(action)) {
+        Ok(admin)
+    } else {
+        Err(Status::Forbidden)
+    }
+}
+
+/// フォームの値をJSONにする。複数選択 (同じ名前で複数送信) の値は配列になります
+fn form_data(form: HashMap<String, Vec<String>>) -> serde_json::Value {
+    form.into_iter()
+        .map(|(name, mut values)| {
+            let value = match values.len() {
+                1 => serde_json::Value::String(values.remove(0)),
+                _ => values.into_iter().map(serde_json::Value::String).collect(),
+            };
+            (name, value)
+        })
+        .collect()
+}
+
+/// テンプレートでボタンやリンクを出し分けるための権限と、実行できる一括操作
+fn permission_context(admin: &dyn AdminModel, staff: &StaffUser) -> serde_json::Value {
+    let opts = admin.opts();
+    let actions: Vec<ActionInfo> = admin.actions().into_iter().filter(|a| staff.has_perm(&opts.perm(a.permission))).collect();
+    serde_json::json!({
+        "has_add_permission": staff.has_perm(&opts.perm("add")),
+        "has_change_permission": staff.has_perm(&opts.perm("change")),
+        "has_delete_permission": staff.has_perm(&opts.perm("delete")),
+        "actions": actions,
+    })
+}
+
+/// 一覧 (Djangoの `changelist_view`)
+#[get("/<model>?<page>&<q>&<sort>&<dir>&<filters..>", rank = 20)]
+#[allow(clippy::too_many_arguments)]
+pub async fn changelist(
+    db: &State<DatabaseConnection>,
+    settings: &State<Settings>,
+    site: &State<AdminSite>,
+    staff: StaffUser,
+    model: &str,
+    page: Option<usize>,
+    q: Option<String>,
+    sort: Option<String>,
+    dir: Option<String>,
+    filters: HashMap<String, String>,
+) -> Result<AppTemplate, Status> {
+    let admin = model_admin(site, &staff, model, "view")?;
+    let params = ChangeListParams { page: page.unwrap_or(1), q, sort, dir, filters };
+    Ok(admin.changelist_view(db, settings.list_per_page, params, permission_context(admin, &staff)).await)
+}
+
+/// 追加フォーム (Djangoの `add_view`)
+#[get("/<model>/create", rank = 20)]
+pub async fn add_view(
+    db: &State<DatabaseConnection>,
+    site: &State<AdminSite>,
+    staff: StaffUser,
+    model: &str,
+) -> Result<AppTemplate, Status> {
+    let admin = model_admin(site, &staff, model, "add")?;
+    Ok(admin.add_view(db, permission_context(admin, &staff)).await)
+}
+
+/// 追加処理
+#[post("/<model>/create", data = "<form>", rank = 20)]
+pub async fn add(
+    db: &State<DatabaseConnection>,
+    site: &State<AdminSite>,
+    staff: StaffUser,
+    model: &str,
+    form: Form<HashMap<String, Vec<String>>>,
+) -> Result<Result<Flash<Redirect>, AppTemplate>, Status> {
+    let admin = model_admin(site, &staff, model, "add")?;
+    let data = form_data(form.into_inner());
+    Ok(admin.add(db, &staff.user.user, data, permission_context(admin, &staff)).await)
+}
+
+/// 編集フォーム (Djangoの `change_view`)
+#[get("/<model>/edit/<id>", rank = 20)]
+pub async fn change_view(
+    db: &State<DatabaseConnection>,
+    site: &State<AdminSite>,
+    staff: StaffUser,
+    model: &str,
+    id: i32,
+) -> Result<Result<AppTemplate, Flash<Redirect>>, Status> {
+    let admin = model_admin(site, &staff, model, "change")?;
+    Ok(admin.change_view(db, id, permission_context(admin, &staff)).await)
+}
+
+/// 編集処理
+#[post("/<model>/edit/<id>", data = "<form>", rank = 20)]
+pub async fn change(
+    db: &State<DatabaseConnection>,
+    site: &State<AdminSite>,
+    staff: StaffUser,
+    model: &str,
+    id: i32,
+    form: Form<HashMap<String, Vec<String>>>,
+) -> Result<Result<Flash<Redirect>, AppTemplate>, Status> {
+    let admin = model_admin(site, &staff, model, "change")?;
+    let data = form_data(form.into_inner());
+    Ok(admin.change(db, &staff.user.user, id, data, permission_context(admin, &staff)).await)
+}
+
+/// 削除の確認画面 (Djangoの `delete_view`)
+#[get("/<model>/delete/<id>", rank = 20)]
+pub async fn delete_view(
+    db: &State<DatabaseConnection>,
+    site: &State<AdminSite>,
+    staff: StaffUser,
+    model: &str,
+    id: i32,
+) -> Result<Result<AppTemplate, Flash<Redirect>>, Status> {
+    let admin = model_admin(site, &staff, model, "delete")?;
+    Ok(admin.delete_view(db, site, id, permission_context(admin, &staff)).await)
+}
+
+/// 削除処理
+#[post("/<model>/delete/<id>", rank = 20)]
+pub async fn delete(
+    db: &State<DatabaseConnection>,
+    site: &State<AdminSite>,
+    staff: StaffUser,
+    model: &str,
+    id: i32,
+) -> Result<Result<Flash<Redirect>, Flash<Redirect>>, Status> {
+    let admin = model_admin(site, &staff, model, "delete")?;
+    Ok(admin.delete(db, site, &staff.user.user, id).await)
+}
+
+/// 操作履歴 (Djangoの `history_view`)
+#[get("/<model>/history/<id>", rank = 20)]
+pub async fn history(
+    db: &State<DatabaseConnection>,
+    site: &State<AdminSite>,
+    staff: StaffUser,
+    model: &str,
+    id: i32,
+) -> Result<Result<AppTemplate, Flash<Redirect>>, Status> {
+    let admin = model_admin(site, &staff, model, "view")?;
+    let opts = admin.opts();
+    let Some(object_repr) = admin.object_repr(db, id).await.ok().flatten() else {
+        return Ok(Err(Flash::error(Redirect::to(opts.base_url()), format!("{}が見つかりません", opts.verbose_name))));
+    };
+    let entries = AdminLogService::for_object(db, site, &opts.model_name, &id.to_string()).await.unwrap_or_default();
+
+    Ok(Ok(AppTemplate::new("admin/object_history", serde_json::json!({
+        "active_nav": opts.slug,
+        "opts": opts,
+        "model_name": opts.verbose_name,
+        "base_url": opts.base_url(),
+        "object_id": id,
+        "object_repr": object_repr,
+        "entries": entries,
+    }))))
+}
+
+/// 一括操作 (POST)。操作ごとの権限を確認し、確認画面・実行結果・ダウンロードのいずれかを返します。
+/// 確認画面からは `post=yes` と、確認画面で入力した値が送信されます
+#[post("/<model>/action", data = "<form>", rank = 20)]
+pub async fn action(
+    db: &State<DatabaseConnection>,
+    app: App<'_>,
+    site: &State<AdminSite>,
+    staff: StaffUser,
+    model: &str,
+    form: Form<HashMap<String, Vec<String>>>,
+) -> Result<ActionOutcome, Status> {
+    let admin = model_admin(site, &staff, model, "view")?;
+    let opts = admin.opts();
+    let mut form = form.into_inner();
+    let name = form.remove("action").and_then(|values| values.into_iter().next()).unwrap_or_default();
+    let ids: Vec<i32> = form.remove("selected_ids").unwrap_or_default().iter().filter_map(|id| id.parse().ok()).collect();
+    let confirmed = form.remove("post").is_some();
+    form.remove("csrf_token");
+    let data: HashMap<String, String> = form
+        .into_iter()
+        .filter_map(|(key, values)| values.into_iter().next().map(|value| (key, value)))
+        .collect();
+
+    let redirect = || Redirect::to(opts.base_url());
+    let Some(action) = admin.actions().into_iter().find(|a| a.name == name) else {
+        return Ok(ActionOutcome::Redirect(Flash::warning(redirect(), "不明な操作です")));
+    };
+    if !staff.has_perm(&opts.perm(action.permission)) {
+        return Ok(ActionOutcome::Redirect(Flash::error(redirect(), "この操作を実行する権限がありません")));
+    }
+    Ok(admin.run_action(db, app.0, site, &staff.user.user, &name, &ids, confirmed, &data, permission_context(admin, &staff)).await)
+}
+
+pub fn routes() -> Vec<rocket::Route> {
+    routes![changelist, add_view, add, change_view, change, delete_view, delete, history, action]
+}
@@ -0,0 +1,74 @@
+use rocket::serde::json::Json;
+use rocket::serde::Serialize;
+use rocket::State;
+use sea_orm::*;
+use crate::entities::{prelude::*, user};
+use crate::errors::AppError;
+use crate::guards::auth::ApiUser;
+use crate::guards::permission::{ApiPermissionRequired, ViewUser};
+
+/// APIで返すユーザー情報 (パスワードハッシュなどは含めない)
+#[derive(Serialize)]
+#[serde(crate = "rocket::serde")]
+pub struct UserSummary {
+    pub id: i32,
+    pub username: String,
+    pub email: String,
+    pub is_active: bool,
+    pub is_admin: bool,
+}
+
+impl From<&user::Model> for UserSummary {
+    fn from(user: &user::Model) -> Self {
+        UserSummary {
+            id: user.id,
+            username: user.username.clone(),
+            email: user.email.clone(),
+            is_active: user.is_active,
+            is_admin: user.is_admin,
+        }
+    }
+}
+
+/// 認証中のユーザー情報
+#[derive(Serialize)]
+#[serde(crate = "rocket::serde")]
+pub struct MeResponse {
+    #[serde(flatten)]
+    pub user: UserSummary,
+    /// 認証方式 (`session` または `token`)
+    pub auth: &'static str,
+    /// トークンの範囲 (セッション認証の場合は `None`)
+    pub scopes: Option<Vec<String>>,
+}
+
+/// 認証中のユーザー情報 (GET)。セッションとAPIトークンのどちらでも認証できます。
+#[get("/me")]
+pub fn me(auth: ApiUser) -> Json<MeResponse> {
+    let (kind, scopes) = match &auth {
+        ApiUser::Session(_) => ("session", None),
+        ApiUser::Token(token) => ("token", Some(token.scopes.clone())),
+    };
+    Json(MeResponse {
+        user: UserSummary::from(auth.user()),
+        auth: kind,
+        scopes,
+    })
+}
+
+/// ユーザー一覧 (GET)。`auth.view_user` 権限が必要です。
+#[get("/users")]
+pub async fn users(
+    db: &State<DatabaseConnection>,
+    _perm: ApiPermissionRequired<ViewUser>,
+) -> Result<Json<Vec<UserSummary>>, AppError> {
+    let users = User::find()
+        .order_by_asc(user::Column::Id)
+        .all(db.inner())
+        .await?;
+    Ok(Json(users.iter().map(UserSummary::from).collect()))
+}
+
+pub fn routes() -> Vec<rocket::Route> {
+    routes![me, users]
+}
@@ -1,11 +1,22 @@
-use rocket::http::{Cookie, CookieJar, Status};
+use rocket::http::{CookieJar, Status};
 use rocket::response::Redirect;
 use rocket::form::Form;
 use rocket::State;
 use sea_orm::*;
 use serde::Deserialize;
-use crate::entities::{prelude::*, user};
-use crate::auth_utils::verify_password;
+use crate::csrf::CsrfToken;
+use crate::entities::user;
+use crate::controllers::two_factor::begin_pending_login;
+use crate::errors::AppError;
+use crate::oidc::OidcProviders;
+use crate::otp::TwoFactorConfig;
+use crate::services::login_throttle::LoginThrottle;
+use crate::services::registration::SignupConfig;
+use crate::services::two_factor::TwoFactorService;
+use crate::services::user_service::UserService;
+use crate::sessions::{ClientInfo, SessionManager};
+use crate::settings::Settings;
+use crate::validation::{is_safe_redirect_url, FieldErrors};
 
 use crate::views::app_template::AppTemplate;
 use rocket_dyn_templates::context;
@@ -14,53 +25,191 @@ use rocket_dyn_templates::context;
 pub struct LoginForm<'r> {
     pub username: &'r str,
     pub password: &'r str,
+    /// ログイン後のリダイレクト先 (Djangoの `next` パラメーター)
+    #[field(default = "")]
+    #[serde(default)]
+    pub next: &'r str,
 }
 
-/// ログイン画面 (GET)
-/// `templates/login.html.tera` (旧 index.html.tera) を表示
-#[get("/login")]
-pub fn login_form() -> AppTemplate {
+/// ユーザー名とパスワードによる認証フォーム。
+/// Djangoの `AuthenticationForm` に相当し、`UserService::authenticate` にログイン試行の制限を組み合わせます。
+pub struct AuthenticationForm<'a> {
+    pub username: &'a str,
+    pub password: &'a str,
+}
+
+impl<'a> AuthenticationForm<'a> {
+    /// 認証に失敗した場合のメッセージ (ユーザーの存在を推測されないよう、理由を区別しない)
+    pub const INVALID_LOGIN: &'static str =
+        "正しいユーザー名とパスワードを入力してください。どちらのフィールドも大文字と小文字は区別されます。";
+    /// 無効なアカウントの場合のメッセージ
+    pub const INACTIVE: &'static str = "このアカウントは無効です。";
+    /// ロック中の場合のメッセージ
+    pub const LOCKED: &'static str = "ログインの試行回数が多すぎます。しばらくしてから再度お試しください。";
+
+    pub fn new(form: &'a LoginForm<'a>) -> Self {
+        AuthenticationForm { username: form.username.trim(), password: form.password }
+    }
+
+    /// 認証を行い、ログインを許可するユーザーを返します (Djangoの `AuthenticationForm.clean()` に相当)。
+    /// 失敗した場合は、応答のステータスとフォームに表示するエラーを返します。
+    pub async fn clean(
+        &self,
+        db: &DatabaseConnection,
+        throttle: &LoginThrottle,
+        ip_address: Option<&str>,
+    ) -> Result<user::Model, (Status, FieldErrors)> {
+        let error = |status: Status, message: &str| {
+            let mut errors = FieldErrors::new();
+            errors.add(FieldErrors::NON_FIELD, message);
+            (status, errors)
+        };
+        let internal = |e: AppError| error(Status::InternalServerError, &e.to_string());
+
+        // ロック中のユーザー名・IPアドレスはパスワードを検証せずに拒否する
+        let locked = throttle.check(db, self.username, ip_address).await.map_err(internal)?;
+        if locked.is_some() {
+            return Err(error(Status::TooManyRequests, Self::LOCKED));
+        }
+
+        // パスワード検証 (Djangoの authenticate() に相当)。古い方式のハッシュはここで更新される
+        // 存在しないユーザー名も失敗として記録する (ユーザーの存在を推測されないように)
+        match UserService::authenticate(db, self.username, self.password).await {
+            Ok(user) => {
+                // 2要素認証が有効な場合は認証コードの確認後に解除する (`login_otp`)。
+                // ここで解除すると、パスワードを知っている攻撃者が再ログインするたびに認証コードを試せてしまう
+                if !TwoFactorService::is_enabled(&user) {
+                    throttle.reset(db, &user.username).await.map_err(internal)?;
+                }
+                Ok(user)
+            }
+            Err(AppError::Unauthorized) => {
+                throttle
+                    .record_failure(db, self.username, ip_address)
+                    .await
+                    .map_err(internal)?;
+                Err(error(Status::Ok, Self::INVALID_LOGIN))
+            }
+            // Django: AuthenticationForm.confirm_login_allowed()
+            Err(AppError::Forbidden) => Err(error(Status::Ok, Self::INACTIVE)),
+            Err(e) => Err(internal(e)),
+        }
+    }
+}
+
+/// ログイン画面を描画する (GET と、エラー時の再表示で共通)
+fn render_login(
+    signup: &SignupConfig,
+    oidc: &OidcProviders,
+    username: &str,
+    next: Option<&str>,
+    field_errors: FieldErrors,
+) -> AppTemplate {
+    let oidc_providers: Vec<_> = oidc
+        .iter()
+        .map(|p| context! { name: &p.config.name, display_name: &p.config.display_name })
+        .collect();
     AppTemplate::new("login", context! {
         title: "Login",
+        signup_enabled: signup.enabled,
+        oidc_providers: oidc_providers,
+        next: next.filter(|url| is_safe_redirect_url(url)),
+        form: context! { username: username },
+        field_errors: field_errors,
     })
 }
 
+/// ログイン画面 (GET)
+/// `templates/login.html.tera` (旧 index.html.tera) を表示
+#[get("/login?<next>")]
+pub fn login_form(
+    signup: &State<SignupConfig>,
+    oidc: &State<OidcProviders>,
+    _csrf: CsrfToken,
+    next: Option<&str>,
+) -> AppTemplate {
+    render_login(signup, oidc, "", next, FieldErrors::new())
+}
+
 /// ログイン処理を行うビュー。
-/// Djangoの `LoginView` に相当します。
+/// Djangoの `LoginView` に相当します。認証に失敗した場合は、入力したユーザー名とエラーを添えてフォームを再表示します。
 #[post("/login", data = "<login_form>")]
+#[allow(clippy::too_many_arguments)]
 pub async fn login(
     db: &State<DatabaseConnection>,
+    sessions: &State<SessionManager>,
+    throttle: &State<LoginThrottle>,
+    two_factor: &State<TwoFactorConfig>,
+    signup: &State<SignupConfig>,
+    oidc: &State<OidcProviders>,
+    settings: &State<Settings>,
+    client: ClientInfo,
     login_form: Form<LoginForm<'_>>,
     cookies: &CookieJar<'_>,
-) -> Result<Redirect, Status> {
-    // ユーザーをDBから取得 (Djangoの User.objects.get(username=...) に相当)
-    let user_result = User::find()
-        .filter(user::Column::Username.eq(login_form.username))
-        .one(db.inner())
+) -> Result<Redirect, (Status, AppTemplate)> {
+    let form = AuthenticationForm::new(&login_form);
+    let next = Some(login_form.next);
+    let rerender = |status: Status, errors: FieldErrors| {
+        (status, render_login(signup, oidc, form.username, next, errors))
+    };
+
+    let user = form
+        .clean(db.inner(), throttle.inner(), client.ip_address.as_deref())
+        .await
+        .map_err(|(status, errors)| rerender(status, errors))?;
+
+    let redirect_url = success_url(settings, next);
+    complete_login(sessions, two_factor, cookies, &client, &user, redirect_url)
         .await
-        .map_err(|_| Status::InternalServerError)?
-        .ok_or(Status::Unauthorized)?;
+        .map_err(|e| {
+            let mut errors = FieldErrors::new();
+            errors.add(FieldErrors::NON_FIELD, e.to_string());
+            rerender(Status::InternalServerError, errors)
+        })
+}
 
-    // パスワード検証 (Djangoの check_password に相当)
-    if !verify_password(login_form.password, &user_result.password_hash) {
-        return Err(Status::Unauthorized);
+/// ログイン後のリダイレクト先。
+/// `next` が同一サイト内の安全なURLならそれを、そうでなければ `LOGIN_REDIRECT_URL` を返します
+/// (Djangoの `LoginView.get_success_url()` に相当)。
+pub fn success_url(settings: &Settings, next: Option<&str>) -> String {
+    match next {
+        Some(next) if is_safe_redirect_url(next) => next.to_string(),
+        _ => settings.login_redirect_url.clone(),
     }
+}
 
-    // アクティブユーザーかチェック (Djangoの user.is_active に相当)
-    if !user_result.is_active {
-        return Err(Status::Forbidden);
+/// 本人確認が済んだユーザーをログインさせます。
+/// パスワードログインと外部IdP (OIDC) ログインで共通の処理で、
+/// 2要素認証が有効 (または必須で未設定) の場合はセッションを作成せずに2段階目へ進めます。
+pub async fn complete_login(
+    sessions: &SessionManager,
+    two_factor: &TwoFactorConfig,
+    cookies: &CookieJar<'_>,
+    client: &ClientInfo,
+    user: &user::Model,
+    redirect_url: String,
+) -> Result<Redirect, AppError> {
+    if TwoFactorService::is_enabled(user) {
+        begin_pending_login(cookies, user.id, &redirect_url);
+        return Ok(Redirect::to("/auth/login/otp"));
+    }
+    if two_factor.needs_setup(user) {
+        begin_pending_login(cookies, user.id, &redirect_url);
+        return Ok(Redirect::to("/auth/login/otp/setup"));
     }
 
-    // セッションクッキーをセット (Djangoの login(request, user) に相当)
-    cookies.add_private(Cookie::new("user_id", user_result.id.to_string()));
-    let redirect_url = std::env::var("LOGIN_REDIRECT_URL").unwrap_or_else(|_| "/".to_string());
+    // セッションを作成してクッキーをセット (Djangoの login(request, user) に相当)
+    sessions.login(cookies, user.id, client).await?;
     Ok(Redirect::to(redirect_url))
 }
 
 /// ログアウト処理。
 /// Djangoの `LogoutView` に相当します。
 #[post("/logout")]
-pub fn logout(cookies: &CookieJar<'_>) -> Redirect {
-    cookies.remove_private(Cookie::from("user_id"));
-    Redirect::to("/")
+pub async fn logout(sessions: &State<SessionManager>, cookies: &CookieJar<'_>) -> Result<Redirect, Status> {
+    sessions
+        .logout(cookies)
+        .await
+        .map_err(|_| Status::InternalServerError)?;
+    Ok(Redirect::to("/"))
 }
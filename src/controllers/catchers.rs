@@ -0,0 +1,81 @@
+use rocket::http::{RawStr, Status};
+use rocket::response::Redirect;
+use rocket::serde::json::Json;
+use rocket::serde::Serialize;
+use rocket::{Catcher, Request};
+use rocket_dyn_templates::context;
+use crate::settings::Settings;
+use crate::views::app_template::AppTemplate;
+
+/// JSONで返すエラー
+#[derive(Serialize)]
+#[serde(crate = "rocket::serde")]
+pub struct ErrorBody {
+    pub status: u16,
+    pub error: &'static str,
+    pub message: &'static str,
+}
+
+/// エラー時のレスポンス。リクエストの種類に応じて形式を切り替えます。
+#[derive(Responder)]
+#[allow(clippy::large_enum_variant)]
+pub enum ErrorResponse {
+    Redirect(Redirect),
+    Json((Status, Json<ErrorBody>)),
+    Html((Status, AppTemplate)),
+}
+
+/// API・HTMX からのリクエストか (HTMLではなくJSONでエラーを返す)
+pub fn wants_json(request: &Request<'_>) -> bool {
+    request.uri().path().starts_with("/api/")
+        || request.headers().contains("HX-Request")
+        || request.accept().is_some_and(|accept| accept.preferred().media_type().is_json())
+}
+
+/// ログイン画面のURL (`LOGIN_URL` に元のURLを `next` として付ける)。
+/// Djangoの `redirect_to_login()` に相当します。
+pub fn login_url(request: &Request<'_>) -> String {
+    let base = request.rocket().state::<Settings>().map_or("/auth/login", |s| s.login_url.as_str());
+    format!("{}?next={}", base, RawStr::new(&request.uri().to_string()).percent_encode())
+}
+
+fn json_error(status: Status, error: &'static str, message: &'static str) -> ErrorResponse {
+    ErrorResponse::Json((status, Json(ErrorBody { status: status.code, error, message })))
+}
+
+/// 401: 未ログインの場合は、ログイン画面へリダイレクトします。
+/// Djangoの `login_required` (未ログイン時に `LOGIN_URL?next=...` へリダイレクト) に相当します。
+/// GET 以外のリクエストはリダイレクトしても再送できないため、エラー画面を表示します。
+#[catch(401)]
+pub fn unauthorized(request: &Request<'_>) -> ErrorResponse {
+    if wants_json(request) {
+        return json_error(Status::Unauthorized, "unauthorized", "認証が必要です");
+    }
+    if request.method().as_str() == "GET" {
+        return ErrorResponse::Redirect(Redirect::to(login_url(request)));
+    }
+    ErrorResponse::Html((Status::Unauthorized, AppTemplate::new("error", context! {
+        title: "ログインが必要です",
+        status: 401,
+        message: "このページを表示するにはログインしてください。",
+        login_url: login_url(request),
+    })))
+}
+
+/// 403: ログイン済みだが権限がない場合は、エラー画面を表示します (Djangoの `PermissionDenied`)。
+#[catch(403)]
+pub fn forbidden(request: &Request<'_>) -> ErrorResponse {
+    if wants_json(request) {
+        return json_error(Status::Forbidden, "forbidden", "この操作を行う権限がありません");
+    }
+    ErrorResponse::Html((Status::Forbidden, AppTemplate::new("error", context! {
+        title: "アクセス権がありません",
+        status: 403,
+        message: "このページを表示する権限がありません。別のユーザーでログインしてください。",
+        login_url: login_url(request),
+    })))
+}
+
+pub fn catchers() -> Vec<Catcher> {
+    catchers![unauthorized, forbidden]
+}
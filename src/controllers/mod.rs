@@ -1,4 +1,12 @@
 pub mod auth;
+pub mod catchers;
+pub mod oidc;
+pub mod password_reset;
+pub mod registration;
 pub mod admin;
 pub mod admin_groups;
+pub mod admin_site;
+pub mod account;
+pub mod api;
 pub mod todo;
+pub mod two_factor;
@@ -0,0 +1,143 @@
+use rocket::http::{Cookie, CookieJar, SameSite, Status};
+use rocket::response::Redirect;
+use rocket::serde::json::serde_json;
+use rocket::State;
+use rocket_dyn_templates::context;
+use sea_orm::DatabaseConnection;
+use crate::controllers::auth::{complete_login, success_url};
+use crate::controllers::password_reset::CurrentSite;
+use crate::errors::AppError;
+use crate::oidc::{AuthorizationRequest, OidcProvider, OidcProviders};
+use crate::otp::TwoFactorConfig;
+use crate::services::oidc::OidcUserService;
+use crate::sessions::{ClientInfo, SessionManager};
+use crate::settings::Settings;
+use crate::validation::is_safe_redirect_url;
+use crate::views::app_template::AppTemplate;
+
+/// 認可リクエストの `state` / `nonce` / `code_verifier` を保持するCookie名
+const OIDC_AUTH_COOKIE: &str = "oidc_auth";
+
+/// IdPでのログインを待つ時間 (秒)
+const OIDC_AUTH_AGE: i64 = 60 * 10;
+
+type ErrorPage = (Status, AppTemplate);
+
+fn error_page(status: Status, message: &str) -> ErrorPage {
+    (status, AppTemplate::new("registration/oidc_error", context! {
+        title: "ログインできません",
+        message: message,
+    }))
+}
+
+fn find_provider<'a>(providers: &'a OidcProviders, name: &str) -> Result<&'a OidcProvider, ErrorPage> {
+    providers
+        .get(name)
+        .ok_or_else(|| error_page(Status::NotFound, "指定されたログイン方法は利用できません。"))
+}
+
+fn redirect_uri(provider: &OidcProvider, site: &CurrentSite) -> String {
+    provider.config.redirect_uri.clone().unwrap_or_else(|| {
+        format!("{}://{}/auth/oidc/{}/callback", site.protocol, site.domain, provider.config.name)
+    })
+}
+
+/// IdPの認可エンドポイントへリダイレクトします (GET)。
+/// mozilla-django-oidc の `OIDCAuthenticationRequestView` に相当します。
+#[get("/oidc/<provider>/login?<next>")]
+pub async fn oidc_login(
+    providers: &State<OidcProviders>,
+    site: CurrentSite,
+    cookies: &CookieJar<'_>,
+    provider: &str,
+    next: Option<&str>,
+) -> Result<Redirect, ErrorPage> {
+    let provider = find_provider(providers, provider)?;
+    let next = next.filter(|url| is_safe_redirect_url(url)).map(str::to_string);
+    let request = AuthorizationRequest::new(&provider.config.name, next);
+
+    let url = provider
+        .authorization_url(&request, &redirect_uri(provider, &site))
+        .await
+        .map_err(|_| error_page(Status::BadGateway, "認証サーバーに接続できません。"))?;
+
+    // IdPからのリダイレクト (別サイトからの遷移) でも送信されるよう SameSite=Lax にする
+    let value = serde_json::to_string(&request).map_err(|_| error_page(Status::InternalServerError, ""))?;
+    cookies.add_private(
+        Cookie::build((OIDC_AUTH_COOKIE, value))
+            .path("/auth/oidc")
+            .same_site(SameSite::Lax)
+            .http_only(true)
+            .max_age(rocket::time::Duration::seconds(OIDC_AUTH_AGE))
+            .build(),
+    );
+    Ok(Redirect::to(url))
+}
+
+/// IdPからのコールバック (GET)。
+/// 認可コードをIDトークンと交換・検証し、対応するユーザーでログインします。
+/// mozilla-django-oidc の `OIDCAuthenticationCallbackView` に相当します。
+#[get("/oidc/<provider>/callback?<code>&<state>&<error>")]
+#[allow(clippy::too_many_arguments)]
+pub async fn oidc_callback(
+    db: &State<DatabaseConnection>,
+    providers: &State<OidcProviders>,
+    sessions: &State<SessionManager>,
+    two_factor: &State<TwoFactorConfig>,
+    settings: &State<Settings>,
+    site: CurrentSite,
+    client: ClientInfo,
+    cookies: &CookieJar<'_>,
+    provider: &str,
+    code: Option<&str>,
+    state: Option<&str>,
+    error: Option<&str>,
+) -> Result<Redirect, ErrorPage> {
+    let provider = find_provider(providers, provider)?;
+
+    // 認可リクエストは1回限り有効
+    let request: Option<AuthorizationRequest> = cookies
+        .get_private(OIDC_AUTH_COOKIE)
+        .and_then(|cookie| serde_json::from_str(cookie.value()).ok());
+    cookies.remove_private(Cookie::build(OIDC_AUTH_COOKIE).path("/auth/oidc"));
+
+    if error.is_some() {
+        return Err(error_page(Status::Unauthorized, "認証サーバーでログインが拒否されました。"));
+    }
+    let (request, code) = match (request, code, state) {
+        (Some(request), Some(code), Some(state))
+            if request.provider == provider.config.name && request.state == state =>
+        {
+            (request, code)
+        }
+        _ => {
+            return Err(error_page(
+                Status::BadRequest,
+                "ログインの有効期限が切れたか、不正なリクエストです。もう一度ログインしてください。",
+            ))
+        }
+    };
+
+    let claims = provider
+        .exchange_code(code, &request, &redirect_uri(provider, &site))
+        .await
+        .map_err(|_| error_page(Status::Unauthorized, "認証サーバーからの応答を検証できませんでした。"))?;
+
+    let user = match OidcUserService::authenticate(db.inner(), &provider.config, &claims).await {
+        Ok(user) if user.is_active => user,
+        Ok(_) => return Err(error_page(Status::Forbidden, "このアカウントは無効です。")),
+        Err(AppError::Forbidden) => {
+            return Err(error_page(Status::Forbidden, "このアカウントはまだ登録されていません。管理者に連絡してください。"))
+        }
+        Err(_) => return Err(error_page(Status::InternalServerError, "ログインに失敗しました。")),
+    };
+
+    let redirect_url = success_url(settings, request.next.as_deref());
+    complete_login(sessions, two_factor, cookies, &client, &user, redirect_url)
+        .await
+        .map_err(|_| error_page(Status::InternalServerError, "ログインに失敗しました。"))
+}
+
+pub fn routes() -> Vec<rocket::Route> {
+    routes![oidc_login, oidc_callback]
+}
@@ -0,0 +1,258 @@
+use rocket::form::Form;
+use rocket::http::Status;
+use rocket::request::{FromRequest, Outcome, Request};
+use rocket::response::{Flash, Redirect};
+use rocket::State;
+use rocket_dyn_templates::{context, Metadata};
+use sea_orm::*;
+use sea_orm::sea_query::{Expr, Func};
+use crate::entities::{prelude::*, user};
+use crate::auth_utils::hash_password;
+use crate::csrf::CsrfToken;
+use crate::mail::Mailer;
+use crate::password_validation::{PasswordValidators, UserAttributes};
+use crate::sessions::SessionManager;
+use crate::settings::{split_site_url, Settings};
+use crate::tokens::{decode_uid, encode_uid, PasswordResetTokenGenerator};
+use crate::validation::{is_allowed_host, FieldErrors};
+use crate::views::app_template::AppTemplate;
+
+/// パスワードリセット申請フォーム
+/// Djangoの `PasswordResetForm` に相当
+#[derive(FromForm)]
+pub struct PasswordResetForm<'r> {
+    pub email: &'r str,
+}
+
+/// 新しいパスワードの設定フォーム
+/// Djangoの `SetPasswordForm` に相当
+#[derive(FromForm)]
+pub struct SetPasswordForm<'r> {
+    pub new_password1: &'r str,
+    pub new_password2: &'r str,
+}
+
+/// メール内のリンクに使うサイトURLを決めるガード。
+/// Djangoの `get_current_site(request)` と `request.is_secure()` に相当します。
+///
+/// `SITE_URL` を設定している場合はその値を使います。未設定の場合はリクエストの `Host` ヘッダーを使い、
+/// `ALLOWED_HOSTS` にないホストは `400 Bad Request` にします (偽の `Host` でリセット用リンクを
+/// 攻撃者のドメインに向けられるのを防ぐため。Djangoの `DisallowedHost`)。
+pub struct CurrentSite {
+    pub protocol: &'static str,
+    pub domain: String,
+}
+
+#[rocket::async_trait]
+impl<'r> FromRequest<'r> for CurrentSite {
+    type Error = ();
+
+    async fn from_request(request: &'r Request<'_>) -> Outcome<Self, Self::Error> {
+        let Some(settings) = request.rocket().state::<Settings>() else {
+            return Outcome::Error((Status::InternalServerError, ()));
+        };
+        if let Some((protocol, domain)) = settings.site_url.as_deref().and_then(split_site_url) {
+            return Outcome::Success(CurrentSite { protocol, domain: domain.to_string() });
+        }
+
+        let (host, domain) = match request.host() {
+            Some(host) => (host.domain().to_string(), host.to_string()),
+            None => ("localhost".to_string(), "localhost".to_string()),
+        };
+        if !is_allowed_host(&host, &settings.allowed_hosts) {
+            warn!("ALLOWED_HOSTS にないホストへのリクエストです: {}", host);
+            return Outcome::Error((Status::BadRequest, ()));
+        }
+        // プロキシの `X-Forwarded-Proto` は偽装できるため、TLSを終端している場合のみ https とする
+        let protocol = if request.rocket().config().tls_enabled() { "https" } else { "http" };
+        Outcome::Success(CurrentSite { protocol, domain })
+    }
+}
+
+/// パスワードリセット申請画面 (GET)。
+/// Djangoの `PasswordResetView` に相当。
+#[get("/password_reset")]
+pub fn password_reset_form(_csrf: CsrfToken) -> AppTemplate {
+    AppTemplate::new("registration/password_reset_form", context! {
+        title: "パスワードのリセット",
+    })
+}
+
+/// パスワードリセット申請処理 (POST)。
+/// 入力されたメールアドレスを持つアクティブなユーザーにリセット用リンクを送信します。
+/// ユーザーの存在を推測されないよう、該当ユーザーがいない場合やメールを送信できない場合も完了画面へ遷移します。
+#[post("/password_reset", data = "<form>")]
+pub async fn password_reset(
+    db: &State<DatabaseConnection>,
+    mailer: &State<Mailer>,
+    tokens: &State<PasswordResetTokenGenerator>,
+    templates: Metadata<'_>,
+    site: CurrentSite,
+    form: Form<PasswordResetForm<'_>>,
+) -> Result<Redirect, Flash<Redirect>> {
+    let email = form.email.trim();
+    if email.is_empty() {
+        return Err(Flash::error(Redirect::to("/auth/password_reset"), "メールアドレスを入力してください"));
+    }
+
+    // Django: PasswordResetForm.get_users() (メールアドレスは大文字小文字を区別しない)
+    let users = User::find()
+        .filter(Expr::expr(Func::lower(Expr::col(user::Column::Email))).eq(email.to_lowercase()))
+        .filter(user::Column::IsActive.eq(true))
+        .all(db.inner())
+        .await
+        .unwrap_or_default();
+
+    for u in users {
+        let ctx = context! {
+            protocol: site.protocol,
+            domain: &site.domain,
+            uid: encode_uid(u.id),
+            token: tokens.make_token(&u),
+            username: &u.username,
+        };
+        let render = |name: &'static str| templates.render(name, &ctx).map(|(_, s)| s).unwrap_or_default();
+        let subject = render("registration/password_reset_subject");
+        let body = render("registration/password_reset_email");
+
+        // 送信の失敗を画面に出すと登録済みのアドレスが分かってしまうため、サーバーのログにのみ記録する
+        if let Err(e) = mailer.send_mail(subject.trim(), &body, vec![u.email.clone()]).await {
+            error!("パスワードリセットのメールを送信できません (user_id={}): {}", u.id, e);
+        }
+    }
+
+    Ok(Redirect::to("/auth/password_reset/done"))
+}
+
+/// リセット用メール送信完了画面 (GET)。
+/// Djangoの `PasswordResetDoneView` に相当。
+#[get("/password_reset/done")]
+pub fn password_reset_done() -> AppTemplate {
+    AppTemplate::new("registration/password_reset_done", context! {
+        title: "パスワードリセットのメールを送信しました",
+    })
+}
+
+/// uidb64 とトークンを検証し、対象ユーザーを返す
+async fn get_user_for_token(
+    db: &DatabaseConnection,
+    tokens: &PasswordResetTokenGenerator,
+    uidb64: &str,
+    token: &str,
+) -> Option<user::Model> {
+    let id = decode_uid(uidb64)?;
+    let user = User::find_by_id(id).one(db).await.ok()??;
+    if user.is_active && tokens.check_token(&user, token) {
+        Some(user)
+    } else {
+        None
+    }
+}
+
+/// 新しいパスワードの入力画面 (GET)。
+/// Djangoの `PasswordResetConfirmView` に相当。リンクが無効な場合はその旨を表示します。
+#[get("/reset/<uidb64>/<token>")]
+pub async fn password_reset_confirm_form(
+    db: &State<DatabaseConnection>,
+    tokens: &State<PasswordResetTokenGenerator>,
+    validators: &State<PasswordValidators>,
+    _csrf: CsrfToken,
+    uidb64: &str,
+    token: &str,
+) -> AppTemplate {
+    let validlink = get_user_for_token(db, tokens, uidb64, token).await.is_some();
+
+    AppTemplate::new("registration/password_reset_confirm", context! {
+        title: "新しいパスワードを入力してください",
+        validlink: validlink,
+        uidb64: uidb64,
+        token: token,
+        password_help_texts: validators.help_texts(),
+    })
+}
+
+/// 新しいパスワードの設定処理 (POST)。
+/// パスワード変更後は既存のセッションを全て無効化します。
+#[post("/reset/<uidb64>/<token>", data = "<form>")]
+#[allow(clippy::too_many_arguments)]
+pub async fn password_reset_confirm(
+    db: &State<DatabaseConnection>,
+    sessions: &State<SessionManager>,
+    tokens: &State<PasswordResetTokenGenerator>,
+    validators: &State<PasswordValidators>,
+    uidb64: &str,
+    token: &str,
+    form: Form<SetPasswordForm<'_>>,
+) -> Result<Redirect, AppTemplate> {
+    let render = |validlink: bool, errors: Vec<String>, field_errors: FieldErrors| {
+        AppTemplate::new("registration/password_reset_confirm", context! {
+            title: "新しいパスワードを入力してください",
+            validlink: validlink,
+            uidb64: uidb64,
+            token: token,
+            errors: errors,
+            field_errors: field_errors,
+            password_help_texts: validators.help_texts(),
+        })
+    };
+
+    let user = match get_user_for_token(db, tokens, uidb64, token).await {
+        Some(u) => u,
+        None => return Err(render(false, vec![], FieldErrors::new())),
+    };
+
+    // Django: SetPasswordForm.clean_new_password2()
+    let mut field_errors = FieldErrors::new();
+    if form.new_password1.is_empty() {
+        field_errors.add("new_password1", "パスワードを入力してください");
+    } else if form.new_password1 != form.new_password2 {
+        field_errors.add("new_password2", "確認用パスワードが一致しません");
+    } else if let Err(messages) = validators.validate(
+        form.new_password1,
+        Some(&UserAttributes::new(&user.username, &user.email)),
+    ) {
+        field_errors.extend("new_password2", messages);
+    }
+    if !field_errors.is_empty() {
+        return Err(render(true, vec![], field_errors));
+    }
+
+    let password_hash = match hash_password(form.new_password1) {
+        Ok(hash) => hash,
+        Err(e) => return Err(render(true, vec![e.to_string()], FieldErrors::new())),
+    };
+
+    let user_id = user.id;
+    let mut active: user::ActiveModel = user.into();
+    active.password_hash = Set(password_hash);
+    if let Err(e) = active.update(db.inner()).await {
+        return Err(render(true, vec![e.to_string()], FieldErrors::new()));
+    }
+
+    // 他の端末に残っているセッションを破棄する
+    if let Err(e) = sessions.invalidate_user(user_id, None).await {
+        return Err(render(true, vec![e.to_string()], FieldErrors::new()));
+    }
+
+    Ok(Redirect::to("/auth/reset/done"))
+}
+
+/// パスワードリセット完了画面 (GET)。
+/// Djangoの `PasswordResetCompleteView` に相当。
+#[get("/reset/done")]
+pub fn password_reset_complete() -> AppTemplate {
+    AppTemplate::new("registration/password_reset_complete", context! {
+        title: "パスワードのリセットが完了しました",
+    })
+}
+
+pub fn routes() -> Vec<rocket::Route> {
+    routes![
+        password_reset_form,
+        password_reset,
+        password_reset_done,
+        password_reset_confirm_form,
+        password_reset_confirm,
+        password_reset_complete
+    ]
+}
@@ -0,0 +1,202 @@
+use rocket::form::Form;
+use rocket::http::Status;
+use rocket::request::{FromRequest, Outcome, Request};
+use rocket::response::Redirect;
+use rocket::serde::json::serde_json;
+use rocket::State;
+use rocket_dyn_templates::{context, Metadata};
+use sea_orm::*;
+use crate::controllers::password_reset::CurrentSite;
+use crate::csrf::CsrfToken;
+use crate::entities::prelude::*;
+use crate::mail::Mailer;
+use crate::password_validation::PasswordValidators;
+use crate::services::registration::{RegistrationService, SignupConfig};
+use crate::services::user_service::UserService;
+use crate::tokens::{decode_uid, encode_uid, AccountActivationTokenGenerator};
+use crate::validation::{clean_email, FieldErrors, UserFormValidation};
+use crate::views::app_template::AppTemplate;
+
+/// ユーザー登録フォーム。
+/// Djangoの `UserCreationForm` (+ django-registration の `RegistrationForm`) に相当します。
+#[derive(FromForm)]
+pub struct SignupForm<'r> {
+    pub username: &'r str,
+    pub email: &'r str,
+    pub password1: &'r str,
+    pub password2: &'r str,
+}
+
+/// ユーザー登録が有効な場合のみ通過するガード。
+/// 無効な場合は `404 Not Found` を返します (django-registration の `REGISTRATION_OPEN = False` に相当)。
+pub struct SignupOpen<'r>(pub &'r SignupConfig);
+
+#[rocket::async_trait]
+impl<'r> FromRequest<'r> for SignupOpen<'r> {
+    type Error = ();
+
+    async fn from_request(request: &'r Request<'_>) -> Outcome<Self, Self::Error> {
+        match request.rocket().state::<SignupConfig>() {
+            Some(config) if config.enabled => Outcome::Success(SignupOpen(config)),
+            _ => Outcome::Error((Status::NotFound, ())),
+        }
+    }
+}
+
+fn render_signup(validators: &PasswordValidators, form: serde_json::Value, field_errors: FieldErrors) -> AppTemplate {
+    AppTemplate::new("registration/registration_form", context! {
+        title: "ユーザー登録",
+        form: form,
+        field_errors: field_errors,
+        password_help_texts: validators.help_texts(),
+    })
+}
+
+/// ユーザー登録画面 (GET)。
+/// django-registration の `RegistrationView` に相当。
+#[get("/signup")]
+pub fn signup_form(_open: SignupOpen<'_>, validators: &State<PasswordValidators>, _csrf: CsrfToken) -> AppTemplate {
+    render_signup(validators, serde_json::json!({}), FieldErrors::new())
+}
+
+/// ユーザー登録処理 (POST)。
+/// 無効状態のユーザーを作成し、有効化用のリンクをメールで送信します。
+#[post("/signup", data = "<form>")]
+#[allow(clippy::too_many_arguments)]
+pub async fn signup(
+    open: SignupOpen<'_>,
+    db: &State<DatabaseConnection>,
+    mailer: &State<Mailer>,
+    tokens: &State<AccountActivationTokenGenerator>,
+    validators: &State<PasswordValidators>,
+    templates: Metadata<'_>,
+    site: CurrentSite,
+    form: Form<SignupForm<'_>>,
+) -> Result<Redirect, AppTemplate> {
+    let username = form.username.trim();
+    let submitted = serde_json::json!({ "username": username, "email": form.email });
+
+    let mut errors = FieldErrors::new();
+    // Django: UserCreationForm.clean_password2() + _post_clean() (パスワードバリデーター)
+    let email = match clean_email(form.email) {
+        Ok(email) if email.is_empty() => {
+            errors.add("email", "メールアドレスを入力してください");
+            email
+        }
+        Ok(email) => email,
+        Err(message) => {
+            errors.add("email", message);
+            String::new()
+        }
+    };
+    if form.password1.is_empty() {
+        errors.add("password1", "パスワードを入力してください");
+    } else if form.password1 != form.password2 {
+        errors.add("password2", "確認用パスワードが一致しません");
+    }
+    let password = if errors.get("password1").is_empty() && errors.get("password2").is_empty() {
+        Some(form.password1)
+    } else {
+        None
+    };
+    if let Err(form_errors) = UserFormValidation::new(username, password)
+        .with_email(&email)
+        .validate_fields(validators)
+    {
+        errors.extend("username", form_errors.get("username").to_vec());
+        errors.extend("password2", form_errors.get("password").to_vec());
+    }
+    if errors.get("username").is_empty() {
+        match UserService::find_by_username(db.inner(), username).await {
+            Ok(Some(_)) => errors.add("username", "このユーザー名は既に使用されています"),
+            Ok(None) => {}
+            Err(e) => errors.add(FieldErrors::NON_FIELD, e.to_string()),
+        }
+    }
+    if !errors.is_empty() {
+        return Err(render_signup(validators, submitted, errors));
+    }
+
+    let user = match RegistrationService::register(db.inner(), open.0, username, &email, form.password1).await {
+        Ok(user) => user,
+        Err(e) => {
+            errors.add(FieldErrors::NON_FIELD, format!("登録に失敗しました: {}", e));
+            return Err(render_signup(validators, submitted, errors));
+        }
+    };
+
+    // Django: RegistrationView.send_activation_email()
+    let ctx = context! {
+        protocol: site.protocol,
+        domain: &site.domain,
+        uid: encode_uid(user.id),
+        token: tokens.make_token(&user),
+        username: &user.username,
+        expiration_days: open.0.activation_days,
+    };
+    let render = |name: &'static str| templates.render(name, &ctx).map(|(_, s)| s).unwrap_or_default();
+    let subject = render("registration/activation_email_subject");
+    let body = render("registration/activation_email");
+
+    if let Err(e) = mailer.send_mail(subject.trim(), &body, vec![user.email.clone()]).await {
+        // 有効化できないユーザーが残らないよう削除し、再登録できるようにする
+        let _ = User::delete_by_id(user.id).exec(db.inner()).await;
+        errors.add(FieldErrors::NON_FIELD, format!("有効化メールの送信に失敗しました: {}", e));
+        return Err(render_signup(validators, submitted, errors));
+    }
+
+    Ok(Redirect::to("/auth/signup/done"))
+}
+
+/// 有効化メール送信完了画面 (GET)。
+/// django-registration の `registration_complete` に相当。
+#[get("/signup/done")]
+pub fn signup_done(open: SignupOpen<'_>) -> AppTemplate {
+    AppTemplate::new("registration/registration_complete", context! {
+        title: "確認メールを送信しました",
+        expiration_days: open.0.activation_days,
+    })
+}
+
+/// アカウントの有効化 (GET)。
+/// django-registration の `ActivationView` に相当。リンクが無効な場合はその旨を表示します。
+/// 登録の受付を停止した後も、送信済みのリンクは有効期間内であれば使用できます。
+/// 一度有効化したユーザーは、管理者が無効化した後もリンクで再び有効化することはできません。
+#[get("/activate/<uidb64>/<token>")]
+pub async fn activate(
+    db: &State<DatabaseConnection>,
+    tokens: &State<AccountActivationTokenGenerator>,
+    uidb64: &str,
+    token: &str,
+) -> AppTemplate {
+    let user = match decode_uid(uidb64) {
+        Some(id) => UserService::find_by_id(db.inner(), id).await.ok().flatten(),
+        None => None,
+    };
+
+    let activated = match user {
+        Some(user) if !user.is_active && user.activated_at.is_none() && tokens.check_token(&user, token) => {
+            RegistrationService::activate(db.inner(), user).await.is_ok()
+        }
+        _ => false,
+    };
+
+    if activated {
+        AppTemplate::new("registration/activation_complete", context! {
+            title: "アカウントを有効化しました",
+        })
+    } else {
+        AppTemplate::new("registration/activation_failed", context! {
+            title: "アカウントを有効化できません",
+        })
+    }
+}
+
+pub fn routes() -> Vec<rocket::Route> {
+    routes![
+        signup_form,
+        signup,
+        signup_done,
+        activate
+    ]
+}
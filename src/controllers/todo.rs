@@ -8,6 +8,7 @@ use chrono::Utc;
 use crate::entities::{prelude::*, todo};
 use crate::guards::auth::AuthenticatedUser;
 use crate::csrf::CsrfToken;
+use crate::admin::{Fieldset, ModelAdmin};
 
 /// TODOフォームのデータ構造
 /// Djangoの `forms.ModelForm` に相当
@@ -20,8 +21,6 @@ pub struct TodoForm<'r> {
     pub priority: i32,
     #[field(default = false)]
     pub completed: bool,
-    #[field(default = "")]
-    pub csrf_token: &'r str,
     pub group_id: Option<i32>,
 }
 
@@ -71,14 +70,8 @@ pub async fn create_todo_form(db: &State<DatabaseConnection>, user: Authenticate
 pub async fn create_todo(
     db: &State<DatabaseConnection>,
     user: AuthenticatedUser,
-    csrf: CsrfToken,
     form: Form<TodoForm<'_>>,
 ) -> Result<Flash<Redirect>, Flash<Redirect>> {
-    // CSRF検証
-    if !csrf.verify(form.csrf_token) {
-        return Err(Flash::error(Redirect::to("/todo/create"), "CSRF検証に失敗しました"));
-    }
-
     // バリデーション
     if form.title.trim().is_empty() {
         return Err(Flash::error(Redirect::to("/todo/create"), "タイトルは必須です"));
@@ -136,15 +129,9 @@ pub async fn edit_todo_form(
 pub async fn edit_todo(
     db: &State<DatabaseConnection>,
     user: AuthenticatedUser,
-    csrf: CsrfToken,
     id: i32,
     form: Form<TodoForm<'_>>,
 ) -> Result<Flash<Redirect>, Flash<Redirect>> {
-    // CSRF検証
-    if !csrf.verify(form.csrf_token) {
-        return Err(Flash::error(Redirect::to(format!("/todo/edit/{}", id)), "CSRF検証に失敗しました"));
-    }
-
     // 既存のTODOを取得（自分のもののみ）
     let existing = Todo::find_by_id(id)
         .filter(todo::Column::UserId.eq(user.user.id))
@@ -233,3 +220,82 @@ pub async fn delete_todo(
     // HTMX request expects empty content to remove the element
     Ok("".to_string())
 }
+
+/// TODOの管理画面の設定 (`admin.py` の `TodoAdmin`)。
+/// 全ユーザーのTODOを `/admin/todos` で一覧・編集できます。
+pub struct TodoAdmin;
+
+#[rocket::async_trait]
+impl ModelAdmin for TodoAdmin {
+    type Entity = Todo;
+
+    fn app_label(&self) -> &'static str {
+        "todo"
+    }
+
+    fn verbose_name(&self) -> &'static str {
+        "TODO"
+    }
+
+    fn labels(&self) -> &'static [(&'static str, &'static str)] {
+        &[
+            ("title", "タイトル"),
+            ("description", "説明"),
+            ("completed", "完了"),
+            ("priority", "優先度"),
+            ("user_id", "作成者"),
+            ("group_id", "グループ"),
+            ("created_at", "作成日時"),
+            ("updated_at", "更新日時"),
+        ]
+    }
+
+    fn list_display(&self) -> &'static [&'static str] {
+        &["id", "title", "user_id", "group_id", "priority", "completed", "updated_at"]
+    }
+
+    fn list_display_links(&self) -> &'static [&'static str] {
+        &["title"]
+    }
+
+    fn search_fields(&self) -> &'static [&'static str] {
+        &["title", "description"]
+    }
+
+    fn list_filter(&self) -> &'static [&'static str] {
+        &["completed", "priority"]
+    }
+
+    fn ordering(&self) -> &'static [&'static str] {
+        &["-updated_at"]
+    }
+
+    fn readonly_fields(&self) -> &'static [&'static str] {
+        &["created_at", "updated_at"]
+    }
+
+    fn fieldsets(&self) -> Vec<Fieldset> {
+        vec![
+            Fieldset::new(None, &["title", "description", "user_id", "group_id"]),
+            Fieldset::new(Some("状態"), &["priority", "completed"]),
+            Fieldset::new(Some("日時"), &["created_at", "updated_at"]),
+        ]
+    }
+
+    /// 作成日時・更新日時はフォームではなくここで設定する
+    async fn save_model(
+        &self,
+        db: &DatabaseConnection,
+        mut active_model: todo::ActiveModel,
+        change: bool,
+    ) -> Result<todo::Model, DbErr> {
+        let now: sea_orm::prelude::DateTimeWithTimeZone = Utc::now().into();
+        active_model.updated_at = Set(now);
+        if change {
+            active_model.update(db).await
+        } else {
+            active_model.created_at = Set(now);
+            active_model.insert(db).await
+        }
+    }
+}
@@ -0,0 +1,426 @@
+use chrono::Utc;
+use rocket::form::Form;
+use rocket::http::{Cookie, CookieJar, SameSite, Status};
+use rocket::response::{Flash, Redirect};
+use rocket::State;
+use rocket_dyn_templates::context;
+use sea_orm::*;
+use crate::csrf::CsrfToken;
+use crate::entities::user;
+use crate::errors::AppError;
+use crate::guards::auth::AuthenticatedUser;
+use crate::otp::{Totp, TwoFactorConfig};
+use crate::services::login_throttle::LoginThrottle;
+use crate::services::two_factor::TwoFactorService;
+use crate::services::user_service::UserService;
+use crate::sessions::{ClientInfo, SessionManager};
+use crate::settings::Settings;
+use crate::views::app_template::AppTemplate;
+
+/// パスワード認証を通過し、2要素認証を待っているユーザーを保持するCookie名
+const OTP_PENDING_COOKIE: &str = "otp_pending";
+
+/// 2要素認証の入力を待つ時間 (秒)
+const OTP_PENDING_AGE: i64 = 60 * 5;
+
+/// 2要素認証の完了後のリダイレクト先を保持するCookie名
+const OTP_NEXT_COOKIE: &str = "otp_next";
+
+/// 登録中のTOTPシークレットを保持するCookie名
+const OTP_SETUP_COOKIE: &str = "otp_setup_secret";
+
+/// 発行直後のリカバリーコードを一度だけ表示するためのCookie名
+const RECOVERY_CODES_COOKIE: &str = "otp_recovery_codes";
+
+/// 認証コードの入力フォーム
+/// django-otp の `OTPTokenForm` に相当
+#[derive(FromForm)]
+pub struct OtpTokenForm<'r> {
+    pub otp_token: &'r str,
+}
+
+fn private_cookie(name: &'static str, value: String, max_age: i64) -> Cookie<'static> {
+    Cookie::build((name, value))
+        .path("/")
+        .same_site(SameSite::Lax)
+        .http_only(true)
+        .max_age(rocket::time::Duration::seconds(max_age))
+        .build()
+}
+
+/// パスワード認証済みのユーザーを記録し、2要素認証の画面に進めます。
+/// (この時点ではまだセッションを作成しないため `AuthenticatedUser` は失敗します)
+/// `redirect_url` は2要素認証の完了後のリダイレクト先です。
+pub fn begin_pending_login(cookies: &CookieJar<'_>, user_id: i32, redirect_url: &str) {
+    let expires = Utc::now().timestamp() + OTP_PENDING_AGE;
+    cookies.add_private(private_cookie(OTP_PENDING_COOKIE, format!("{}:{}", user_id, expires), OTP_PENDING_AGE));
+    cookies.add_private(private_cookie(OTP_NEXT_COOKIE, redirect_url.to_string(), OTP_PENDING_AGE));
+}
+
+/// 2要素認証を待っているユーザーを取得します (期限切れの場合は `None`)。
+async fn pending_user(db: &DatabaseConnection, cookies: &CookieJar<'_>) -> Result<Option<user::Model>, AppError> {
+    let value = match cookies.get_private(OTP_PENDING_COOKIE) {
+        Some(cookie) => cookie.value().to_string(),
+        None => return Ok(None),
+    };
+    let (user_id, expires) = match value.split_once(':') {
+        Some((id, exp)) => (id.parse::<i32>().ok(), exp.parse::<i64>().ok()),
+        None => (None, None),
+    };
+    match (user_id, expires) {
+        (Some(user_id), Some(expires)) if expires > Utc::now().timestamp() => {
+            Ok(UserService::find_by_id(db, user_id).await?.filter(|u| u.is_active))
+        }
+        _ => Ok(None),
+    }
+}
+
+/// 2要素認証を完了し、セッションを作成します。戻り値はログイン後のリダイレクト先です。
+async fn complete_login(
+    sessions: &SessionManager,
+    settings: &Settings,
+    cookies: &CookieJar<'_>,
+    user: &user::Model,
+    client: &ClientInfo,
+) -> Result<String, AppError> {
+    let redirect_url = cookies
+        .get_private(OTP_NEXT_COOKIE)
+        .map(|cookie| cookie.value().to_string())
+        .unwrap_or_else(|| settings.login_redirect_url.clone());
+    cookies.remove_private(Cookie::build(OTP_PENDING_COOKIE).path("/"));
+    cookies.remove_private(Cookie::build(OTP_NEXT_COOKIE).path("/"));
+    cookies.remove_private(Cookie::build(OTP_SETUP_COOKIE).path("/"));
+    sessions.login(cookies, user.id, client).await?;
+    Ok(redirect_url)
+}
+
+/// 登録中のシークレットを取得します。なければ新しく生成してCookieに保存します。
+fn setup_secret(cookies: &CookieJar<'_>) -> String {
+    if let Some(cookie) = cookies.get_private(OTP_SETUP_COOKIE) {
+        if Totp::from_base32(cookie.value()).is_some() {
+            return cookie.value().to_string();
+        }
+    }
+    let secret = Totp::generate_secret();
+    cookies.add_private(private_cookie(OTP_SETUP_COOKIE, secret.clone(), 60 * 30));
+    secret
+}
+
+fn setup_context(config: &TwoFactorConfig, user: &user::Model, secret: &str) -> (String, String) {
+    let account = if user.email.is_empty() { &user.username } else { &user.email };
+    (Totp::provisioning_uri(secret, &config.issuer, account), secret.to_string())
+}
+
+fn store_recovery_codes(cookies: &CookieJar<'_>, codes: &[String]) {
+    cookies.add_private(private_cookie(RECOVERY_CODES_COOKIE, codes.join(" "), 60 * 10));
+}
+
+// ---------------------------------------------------------------------------
+// ログインの2段階目 (/auth)
+// ---------------------------------------------------------------------------
+
+/// 認証コードの入力画面 (GET)。
+/// django-two-factor-auth の `LoginView` (token ステップ) に相当します。
+#[get("/login/otp")]
+pub async fn login_otp_form(
+    db: &State<DatabaseConnection>,
+    _csrf: CsrfToken,
+    cookies: &CookieJar<'_>,
+) -> Result<AppTemplate, Redirect> {
+    match pending_user(db.inner(), cookies).await {
+        Ok(Some(user)) if TwoFactorService::is_enabled(&user) => Ok(AppTemplate::new("registration/login_otp", context! {
+            title: "2要素認証",
+            username: user.username,
+        })),
+        _ => Err(Redirect::to("/auth/login")),
+    }
+}
+
+/// 認証コードの検証 (POST)。
+/// TOTPコードとリカバリーコードのどちらでもログインできます。
+#[post("/login/otp", data = "<form>")]
+pub async fn login_otp(
+    db: &State<DatabaseConnection>,
+    sessions: &State<SessionManager>,
+    settings: &State<Settings>,
+    throttle: &State<LoginThrottle>,
+    client: ClientInfo,
+    cookies: &CookieJar<'_>,
+    form: Form<OtpTokenForm<'_>>,
+) -> Result<Redirect, Result<AppTemplate, Status>> {
+    let user = match pending_user(db.inner(), cookies).await {
+        Ok(Some(user)) if TwoFactorService::is_enabled(&user) => user,
+        Ok(_) => return Ok(Redirect::to("/auth/login")),
+        Err(_) => return Err(Err(Status::InternalServerError)),
+    };
+
+    let render_error = |message: &str| {
+        Err(Ok(AppTemplate::new("registration/login_otp", context! {
+            title: "2要素認証",
+            username: user.username.clone(),
+            errors: vec![message.to_string()],
+        })))
+    };
+
+    // 認証コードの総当たりもパスワードと同じく試行制限の対象にする
+    let ip_address = client.ip_address.as_deref();
+    let locked = throttle
+        .check(db.inner(), &user.username, ip_address)
+        .await
+        .map_err(|_| Err(Status::InternalServerError))?;
+    if locked.is_some() {
+        return Err(Err(Status::TooManyRequests));
+    }
+
+    let verified = TwoFactorService::verify(db.inner(), &user, form.otp_token)
+        .await
+        .map_err(|_| Err(Status::InternalServerError))?;
+    if !verified {
+        throttle
+            .record_failure(db.inner(), &user.username, ip_address)
+            .await
+            .map_err(|_| Err(Status::InternalServerError))?;
+        return render_error("認証コードが正しくありません");
+    }
+
+    throttle
+        .reset(db.inner(), &user.username)
+        .await
+        .map_err(|_| Err(Status::InternalServerError))?;
+    let redirect_url = complete_login(sessions, settings, cookies, &user, &client)
+        .await
+        .map_err(|_| Err(Status::InternalServerError))?;
+    Ok(Redirect::to(redirect_url))
+}
+
+/// 2要素認証が必須のユーザーが未登録の場合の登録画面 (GET)。
+/// 登録を完了するまでログインできません。
+#[get("/login/otp/setup")]
+pub async fn login_otp_setup_form(
+    db: &State<DatabaseConnection>,
+    config: &State<TwoFactorConfig>,
+    _csrf: CsrfToken,
+    cookies: &CookieJar<'_>,
+) -> Result<AppTemplate, Redirect> {
+    let user = match pending_user(db.inner(), cookies).await {
+        Ok(Some(user)) if config.needs_setup(&user) => user,
+        _ => return Err(Redirect::to("/auth/login")),
+    };
+
+    let (otpauth_uri, secret) = setup_context(config, &user, &setup_secret(cookies));
+    Ok(AppTemplate::new("registration/login_otp_setup", context! {
+        title: "2要素認証の設定",
+        username: user.username,
+        otpauth_uri: otpauth_uri,
+        secret: secret,
+        setup_action: "/auth/login/otp/setup",
+    }))
+}
+
+/// 2要素認証の登録を完了してログインする (POST)。
+#[post("/login/otp/setup", data = "<form>")]
+pub async fn login_otp_setup(
+    db: &State<DatabaseConnection>,
+    sessions: &State<SessionManager>,
+    settings: &State<Settings>,
+    config: &State<TwoFactorConfig>,
+    client: ClientInfo,
+    cookies: &CookieJar<'_>,
+    form: Form<OtpTokenForm<'_>>,
+) -> Result<Redirect, Result<AppTemplate, Status>> {
+    let user = match pending_user(db.inner(), cookies).await {
+        Ok(Some(user)) if config.needs_setup(&user) => user,
+        Ok(_) => return Ok(Redirect::to("/auth/login")),
+        Err(_) => return Err(Err(Status::InternalServerError)),
+    };
+
+    let secret = setup_secret(cookies);
+    let error = match TwoFactorService::enable(db.inner(), user.clone(), &secret, form.otp_token).await {
+        Ok(codes) => {
+            complete_login(sessions, settings, cookies, &user, &client)
+                .await
+                .map_err(|_| Err(Status::InternalServerError))?;
+            store_recovery_codes(cookies, &codes);
+            return Ok(Redirect::to("/account/two_factor/recovery_codes"));
+        }
+        Err(AppError::BadRequest(message)) => message,
+        Err(_) => return Err(Err(Status::InternalServerError)),
+    };
+
+    let (otpauth_uri, secret) = setup_context(config, &user, &secret);
+    Err(Ok(AppTemplate::new("registration/login_otp_setup", context! {
+        title: "2要素認証の設定",
+        username: user.username,
+        otpauth_uri: otpauth_uri,
+        secret: secret,
+        setup_action: "/auth/login/otp/setup",
+        errors: vec![error],
+    })))
+}
+
+// ---------------------------------------------------------------------------
+// アカウント設定 (/account)
+// ---------------------------------------------------------------------------
+
+/// 2要素認証の設定画面 (GET)。
+#[get("/two_factor")]
+pub async fn two_factor(
+    db: &State<DatabaseConnection>,
+    config: &State<TwoFactorConfig>,
+    user: AuthenticatedUser,
+    _csrf: CsrfToken,
+) -> AppTemplate {
+    let remaining = TwoFactorService::remaining_recovery_codes(db.inner(), user.user.id)
+        .await
+        .unwrap_or_default();
+
+    AppTemplate::new("account/two_factor", context! {
+        title: "2要素認証",
+        active_nav: "two_factor",
+        enabled: TwoFactorService::is_enabled(&user.user),
+        required: config.is_required_for(&user.user),
+        recovery_codes_remaining: remaining,
+    })
+}
+
+/// 認証アプリの登録画面 (GET)。
+/// otpauth URI とシークレットを表示します。
+#[get("/two_factor/setup")]
+pub async fn two_factor_setup_form(
+    config: &State<TwoFactorConfig>,
+    user: AuthenticatedUser,
+    _csrf: CsrfToken,
+    cookies: &CookieJar<'_>,
+) -> Result<AppTemplate, Flash<Redirect>> {
+    if TwoFactorService::is_enabled(&user.user) {
+        return Err(Flash::warning(Redirect::to("/account/two_factor"), "2要素認証は既に有効です"));
+    }
+
+    let (otpauth_uri, secret) = setup_context(config, &user.user, &setup_secret(cookies));
+    Ok(AppTemplate::new("account/two_factor_setup", context! {
+        title: "2要素認証の設定",
+        active_nav: "two_factor",
+        otpauth_uri: otpauth_uri,
+        secret: secret,
+        setup_action: "/account/two_factor/setup",
+    }))
+}
+
+/// 確認コードを検証して2要素認証を有効にする (POST)。
+#[post("/two_factor/setup", data = "<form>")]
+pub async fn two_factor_setup(
+    db: &State<DatabaseConnection>,
+    config: &State<TwoFactorConfig>,
+    user: AuthenticatedUser,
+    cookies: &CookieJar<'_>,
+    form: Form<OtpTokenForm<'_>>,
+) -> Result<Flash<Redirect>, AppTemplate> {
+    if TwoFactorService::is_enabled(&user.user) {
+        return Ok(Flash::warning(Redirect::to("/account/two_factor"), "2要素認証は既に有効です"));
+    }
+
+    let secret = setup_secret(cookies);
+    let error = match TwoFactorService::enable(db.inner(), user.user.clone(), &secret, form.otp_token).await {
+        Ok(codes) => {
+            cookies.remove_private(Cookie::build(OTP_SETUP_COOKIE).path("/"));
+            store_recovery_codes(cookies, &codes);
+            return Ok(Flash::success(Redirect::to("/account/two_factor/recovery_codes"), "2要素認証を有効にしました"));
+        }
+        Err(AppError::BadRequest(message)) => message,
+        Err(e) => format!("設定に失敗しました: {}", e),
+    };
+
+    let (otpauth_uri, secret) = setup_context(config, &user.user, &secret);
+    Err(AppTemplate::new("account/two_factor_setup", context! {
+        title: "2要素認証の設定",
+        active_nav: "two_factor",
+        otpauth_uri: otpauth_uri,
+        secret: secret,
+        setup_action: "/account/two_factor/setup",
+        errors: vec![error],
+    }))
+}
+
+/// 発行したリカバリーコードの表示 (GET)。
+/// コードは発行直後の1回だけ表示します。
+#[get("/two_factor/recovery_codes")]
+pub fn recovery_codes(user: AuthenticatedUser, _csrf: CsrfToken, cookies: &CookieJar<'_>) -> AppTemplate {
+    let codes: Vec<String> = match cookies.get_private(RECOVERY_CODES_COOKIE) {
+        Some(cookie) => cookie.value().split_whitespace().map(str::to_string).collect(),
+        None => Vec::new(),
+    };
+    cookies.remove_private(Cookie::build(RECOVERY_CODES_COOKIE).path("/"));
+
+    AppTemplate::new("account/recovery_codes", context! {
+        title: "リカバリーコード",
+        active_nav: "two_factor",
+        enabled: TwoFactorService::is_enabled(&user.user),
+        codes: codes,
+    })
+}
+
+/// リカバリーコードを再発行する (POST)。
+#[post("/two_factor/recovery_codes")]
+pub async fn regenerate_recovery_codes(
+    db: &State<DatabaseConnection>,
+    user: AuthenticatedUser,
+    cookies: &CookieJar<'_>,
+) -> Flash<Redirect> {
+    if !TwoFactorService::is_enabled(&user.user) {
+        return Flash::warning(Redirect::to("/account/two_factor"), "2要素認証が有効になっていません");
+    }
+
+    match TwoFactorService::regenerate_recovery_codes(db.inner(), user.user.id).await {
+        Ok(codes) => {
+            store_recovery_codes(cookies, &codes);
+            Flash::success(Redirect::to("/account/two_factor/recovery_codes"), "リカバリーコードを再発行しました")
+        }
+        Err(e) => Flash::error(Redirect::to("/account/two_factor"), format!("再発行に失敗しました: {}", e)),
+    }
+}
+
+/// 2要素認証を無効にする (POST)。
+/// 本人確認のため認証コード (またはリカバリーコード) の入力を求めます。
+#[post("/two_factor/disable", data = "<form>")]
+pub async fn disable_two_factor(
+    db: &State<DatabaseConnection>,
+    config: &State<TwoFactorConfig>,
+    user: AuthenticatedUser,
+    form: Form<OtpTokenForm<'_>>,
+) -> Flash<Redirect> {
+    let redirect = Redirect::to("/account/two_factor");
+    if config.is_required_for(&user.user) {
+        return Flash::error(redirect, "管理者は2要素認証を無効にできません");
+    }
+
+    match TwoFactorService::verify(db.inner(), &user.user, form.otp_token).await {
+        Ok(true) => match TwoFactorService::disable(db.inner(), user.user).await {
+            Ok(_) => Flash::success(redirect, "2要素認証を無効にしました"),
+            Err(e) => Flash::error(redirect, format!("無効化に失敗しました: {}", e)),
+        },
+        Ok(false) => Flash::error(redirect, "認証コードが正しくありません"),
+        Err(e) => Flash::error(redirect, format!("無効化に失敗しました: {}", e)),
+    }
+}
+
+/// ログインの2段階目のルート (/auth にマウント)
+pub fn login_routes() -> Vec<rocket::Route> {
+    routes![
+        login_otp_form,
+        login_otp,
+        login_otp_setup_form,
+        login_otp_setup
+    ]
+}
+
+/// アカウント設定のルート (/account にマウント)
+pub fn account_routes() -> Vec<rocket::Route> {
+    routes![
+        two_factor,
+        two_factor_setup_form,
+        two_factor_setup,
+        recovery_codes,
+        regenerate_recovery_codes,
+        disable_two_factor
+    ]
+}
@@ -1,97 +1,269 @@
 use rand::Rng;
 use base64::{Engine as _, engine::general_purpose::URL_SAFE_NO_PAD};
+use hmac::{Hmac, Mac};
+use rocket::http::uri::Absolute;
 use rocket::http::{Cookie, SameSite};
 use rocket::request::{FromRequest, Outcome, Request};
 use rocket::http::Status;
+use sha2::{Digest, Sha256};
 use std::time::{SystemTime, UNIX_EPOCH};
+use crate::auth_utils::constant_time_eq;
+use crate::settings::Settings;
 
-/// CSRFトークンの有効期限（秒）
-const CSRF_TOKEN_EXPIRY: u64 = 3600; // 1時間
+type HmacSha256 = Hmac<Sha256>;
+
+/// CSRFトークンの有効期限のデフォルト値 (秒)
+pub(crate) const DEFAULT_CSRF_COOKIE_AGE: u64 = 3600; // 1時間
+
+/// シークレットの長さ (発行時刻8バイト + 乱数32バイト)
+const SECRET_LENGTH: usize = 40;
+
+/// HMACの鍵を用途ごとに分けるためのソルト (Django: `key_salt`)
+const KEY_SALT: &str = "rust_django_starter.csrf.CsrfToken";
+
+/// フォームでトークンを送信するフィールド名
+pub const CSRF_FIELD_NAME: &str = "csrf_token";
+
+/// シークレットを保存するCookie名 (Django: `CSRF_COOKIE_NAME`)
+pub const CSRF_COOKIE_NAME: &str = "csrf_token";
+
+/// HTMX などがトークンを送信するヘッダー名
+pub const CSRF_HEADER_NAME: &str = "X-CSRF-Token";
+
+fn now() -> u64 {
+    SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .unwrap()
+        .as_secs()
+}
+
+/// CSRF対策の設定。
+/// Cookieの署名に使う鍵と、Djangoの `CSRF_TRUSTED_ORIGINS` に相当する信頼済みオリジンを持ちます。
+/// `CsrfProtection` Fairing が管理します。
+#[derive(Clone)]
+pub struct CsrfConfig {
+    key: Vec<u8>,
+    /// 別オリジンからの送信を許可するオリジン (例: `https://example.com`, `https://*.example.com`)
+    pub trusted_origins: Vec<String>,
+    /// トークンの有効期間 (秒)
+    pub max_age: u64,
+    /// HTTPのリクエストでも Cookie に `Secure` 属性を付ける
+    pub cookie_secure: bool,
+}
+
+impl CsrfConfig {
+    pub fn new(secret: &[u8], trusted_origins: Vec<String>) -> Self {
+        // Djangoの `salted_hmac` と同様に、ソルトと秘密鍵から用途別の鍵を派生させる
+        let key = Sha256::new()
+            .chain_update(KEY_SALT.as_bytes())
+            .chain_update(secret)
+            .finalize()
+            .to_vec();
+        CsrfConfig { key, trusted_origins, max_age: DEFAULT_CSRF_COOKIE_AGE, cookie_secure: false }
+    }
+
+    /// 設定から構築します。
+    ///
+    /// - `secret_key`: Rocket.toml / `ROCKET_SECRET_KEY`。未設定の場合は起動ごとにランダムな鍵を使用します
+    /// - `CSRF_TRUSTED_ORIGINS`: 信頼済みオリジン (カンマ区切り)
+    /// - `CSRF_COOKIE_AGE`: トークンの有効期間 (秒)
+    /// - `CSRF_COOKIE_SECURE`: `true` の場合、HTTPのリクエストでも Cookie をHTTPSでのみ送信させます
+    pub fn from_settings(settings: &Settings) -> Self {
+        CsrfConfig {
+            max_age: settings.csrf_cookie_age,
+            cookie_secure: settings.csrf_cookie_secure,
+            ..CsrfConfig::new(settings.secret_key(), settings.csrf_trusted_origins.clone())
+        }
+    }
+
+    fn mac(&self, secret: &[u8]) -> HmacSha256 {
+        let mut mac = HmacSha256::new_from_slice(&self.key).expect("HMAC can take a key of any size");
+        mac.update(secret);
+        mac
+    }
+
+    /// `CSRF_TRUSTED_ORIGINS` に含まれるオリジンか。`https://*.example.com` はサブドメインにも一致します。
+    pub fn is_trusted_origin(&self, origin: &str) -> bool {
+        self.trusted_origins.iter().any(|trusted| {
+            if trusted == origin {
+                return true;
+            }
+            match (trusted.split_once("://*."), origin.split_once("://")) {
+                (Some((scheme, domain)), Some((origin_scheme, host))) => {
+                    scheme == origin_scheme && host.ends_with(&format!(".{}", domain))
+                }
+                _ => false,
+            }
+        })
+    }
+}
 
 /// CSRFトークン。
 /// Djangoの {% csrf_token %} に相当します。
+///
+/// Cookieにはシークレットを署名付き (`<シークレット>.<HMAC>`) で保存し、フォームには
+/// レスポンスごとに異なるマスクをかけたトークンを埋め込みます (BREACH攻撃対策)。
 #[derive(Debug, Clone)]
-pub struct CsrfToken(pub String);
+pub struct CsrfToken {
+    secret: [u8; SECRET_LENGTH],
+}
 
 impl CsrfToken {
     /// 新しいCSRFトークンを生成します。
     pub fn generate() -> Self {
         let mut rng = rand::thread_rng();
         let random_bytes: [u8; 32] = rng.gen();
-        
+
         // タイムスタンプを含めて有効期限を管理
-        let timestamp = SystemTime::now()
-            .duration_since(UNIX_EPOCH)
-            .unwrap()
-            .as_secs();
-        
-        let mut token_data = timestamp.to_be_bytes().to_vec();
-        token_data.extend_from_slice(&random_bytes);
-        
-        CsrfToken(URL_SAFE_NO_PAD.encode(&token_data))
-    }
-
-    /// トークンを検証します。
-    pub fn verify(&self, submitted: &str) -> bool {
-        if self.0 != submitted {
+        let mut secret = [0u8; SECRET_LENGTH];
+        secret[..8].copy_from_slice(&now().to_be_bytes());
+        secret[8..].copy_from_slice(&random_bytes);
+
+        CsrfToken { secret }
+    }
+
+    /// 発行から `max_age` 秒以上経過しているか
+    pub fn is_expired(&self, max_age: u64) -> bool {
+        let issued_at = u64::from_be_bytes(self.secret[..8].try_into().unwrap());
+        now().saturating_sub(issued_at) >= max_age
+    }
+
+    /// Cookieに保存する値 (署名付きのシークレット)
+    pub fn cookie_value(&self, config: &CsrfConfig) -> String {
+        let signature = config.mac(&self.secret).finalize().into_bytes();
+        format!("{}.{}", URL_SAFE_NO_PAD.encode(self.secret), URL_SAFE_NO_PAD.encode(signature))
+    }
+
+    /// Cookieの値からトークンを復元します。署名が一致しない場合は `None` を返します。
+    pub fn from_cookie_value(value: &str, config: &CsrfConfig) -> Option<Self> {
+        let (secret, signature) = value.split_once('.')?;
+        let secret = URL_SAFE_NO_PAD.decode(secret).ok()?;
+        let signature = URL_SAFE_NO_PAD.decode(signature).ok()?;
+
+        // 定数時間で署名を比較する
+        config.mac(&secret).verify_slice(&signature).ok()?;
+        Some(CsrfToken { secret: secret.try_into().ok()? })
+    }
+
+    /// フォームに埋め込むトークン。
+    /// 呼び出すたびにランダムなマスクとシークレットのXORを取るため、毎回異なる値になります
+    /// (Djangoの `_mask_cipher_secret()` に相当)。
+    pub fn token(&self) -> String {
+        let mut mask = [0u8; SECRET_LENGTH];
+        rand::thread_rng().fill(&mut mask[..]);
+        let mut masked = mask.to_vec();
+        masked.extend(self.secret.iter().zip(mask).map(|(s, m)| s ^ m));
+        URL_SAFE_NO_PAD.encode(masked)
+    }
+
+    /// 送信されたトークンを検証します。
+    /// マスク済みのトークンのほか、JavaScript が Cookie から読み取った値そのものも受け付けます。
+    pub fn verify(&self, submitted: &str, config: &CsrfConfig) -> bool {
+        if self.is_expired(config.max_age) {
             return false;
         }
-
-        // トークンをデコードしてタイムスタンプを確認
-        if let Ok(decoded) = URL_SAFE_NO_PAD.decode(&self.0) {
-            if decoded.len() >= 8 {
-                let timestamp_bytes: [u8; 8] = decoded[..8].try_into().unwrap_or([0; 8]);
-                let token_time = u64::from_be_bytes(timestamp_bytes);
-                let current_time = SystemTime::now()
-                    .duration_since(UNIX_EPOCH)
-                    .unwrap()
-                    .as_secs();
-                
-                // 有効期限をチェック
-                return current_time - token_time < CSRF_TOKEN_EXPIRY;
-            }
+        if constant_time_eq(submitted.as_bytes(), self.cookie_value(config).as_bytes()) {
+            return true;
         }
-        false
+
+        let decoded = match URL_SAFE_NO_PAD.decode(submitted) {
+            Ok(decoded) if decoded.len() == SECRET_LENGTH * 2 => decoded,
+            _ => return false,
+        };
+        let (mask, cipher) = decoded.split_at(SECRET_LENGTH);
+        let unmasked: Vec<u8> = cipher.iter().zip(mask).map(|(c, m)| c ^ m).collect();
+        constant_time_eq(&unmasked, &self.secret)
     }
 
-    /// トークン文字列を取得
-    pub fn token(&self) -> &str {
-        &self.0
+    /// シークレットを保存するCookie
+    pub fn cookie(&self, config: &CsrfConfig, secure: bool) -> Cookie<'static> {
+        Cookie::build((CSRF_COOKIE_NAME, self.cookie_value(config)))
+            .path("/")
+            .same_site(SameSite::Strict)
+            .http_only(false) // HTMX/JSからアクセス可能にする
+            .secure(secure || config.cookie_secure)
+            .max_age(rocket::time::Duration::seconds(config.max_age as i64))
+            .build()
     }
 }
 
+/// HTTPSでのリクエストか (Djangoの `request.is_secure()`)。
+/// リバースプロキシの `X-Forwarded-Proto` ヘッダーも考慮します。
+pub fn is_secure(request: &Request<'_>) -> bool {
+    request.rocket().config().tls_enabled()
+        || request.headers().get_one("X-Forwarded-Proto") == Some("https")
+}
+
+/// `Origin` / `Referer` ヘッダーを検証します (Djangoの `CsrfViewMiddleware._origin_verified()` /
+/// `_check_referer()` に相当)。
+///
+/// - `Origin` ヘッダーがあれば、リクエスト先と同じオリジンか信頼済みオリジンである必要があります
+/// - `Origin` がないHTTPSのリクエストは、`Referer` がHTTPSで同じホストか信頼済みオリジンである必要があります
+fn origin_verified(request: &Request<'_>, config: &CsrfConfig) -> bool {
+    let secure = is_secure(request);
+    let good_origin = request
+        .host()
+        .map(|host| format!("{}://{}", if secure { "https" } else { "http" }, host));
+
+    if let Some(origin) = request.headers().get_one("Origin") {
+        return good_origin.as_deref() == Some(origin) || config.is_trusted_origin(origin);
+    }
+    if !secure {
+        return true;
+    }
+
+    let referer = match request.headers().get_one("Referer").and_then(|r| Absolute::parse(r).ok()) {
+        Some(referer) if referer.scheme() == "https" => referer,
+        _ => return false,
+    };
+    let referer_origin = match referer.authority() {
+        Some(authority) => format!("https://{}", authority),
+        None => return false,
+    };
+    good_origin.as_deref() == Some(referer_origin.as_str()) || config.is_trusted_origin(&referer_origin)
+}
+
 /// リクエストからCSRFトークンを取得するガード。
-/// CookieからCSRFトークンを読み取り、なければ新規生成します。
+/// CookieからCSRFトークンを読み取り、なければ (または署名が不正・期限切れなら) 新規生成します。
 #[rocket::async_trait]
 impl<'r> FromRequest<'r> for CsrfToken {
     type Error = ();
 
     async fn from_request(request: &'r Request<'_>) -> Outcome<Self, Self::Error> {
+        let config = match request.rocket().state::<CsrfConfig>() {
+            Some(config) => config,
+            None => return Outcome::Error((Status::InternalServerError, ())),
+        };
         let cookies = request.cookies();
-        
+
         // 既存のトークンがあれば使用、なければ新規生成
-        let token = if let Some(cookie) = cookies.get("csrf_token") {
-            CsrfToken(cookie.value().to_string())
-        } else {
-            let new_token = CsrfToken::generate();
-            
-            // Cookieに保存（HttpOnly=false でJavaScript/HTMXからアクセス可能に）
-            let cookie = Cookie::build(("csrf_token", new_token.0.clone()))
-                .path("/")
-                .same_site(SameSite::Strict)
-                .http_only(false) // HTMX/JSからアクセス可能にする
-                .secure(false);   // 開発用。本番では true に
-            
-            cookies.add(cookie);
-            new_token
+        let existing = cookies
+            .get(CSRF_COOKIE_NAME)
+            .and_then(|cookie| CsrfToken::from_cookie_value(cookie.value(), config))
+            .filter(|token| !token.is_expired(config.max_age));
+        let token = match existing {
+            Some(token) => token,
+            None => {
+                let new_token = CsrfToken::generate();
+                cookies.add(new_token.cookie(config, is_secure(request)));
+                new_token
+            }
         };
-        
+
         Outcome::Success(token)
     }
 }
 
+/// フォームの本文から読み取ったトークン。
+/// ガードからはリクエストの本文を読めないため、`CsrfProtection` Fairing が本文を先読みして格納します。
+#[derive(Debug, Clone, Default)]
+pub struct SubmittedCsrfToken(pub Option<String>);
+
 /// POSTリクエストのCSRFトークンを検証するガード。
-/// DjangoのCsrfViewMiddlewareに相当します。
+/// `Origin` / `Referer` ヘッダーを確認したうえで、`X-CSRF-Token` ヘッダー、またはフォームの
+/// `csrf_token` フィールドのトークンを、Cookieのトークンと照合します。
+/// 安全でないメソッドのリクエストは `CsrfProtection` Fairing (Djangoの `CsrfViewMiddleware`) がこのガードで
+/// 一括して検証するため、通常はハンドラーで使う必要はありません。
 pub struct CsrfValidation;
 
 #[rocket::async_trait]
@@ -99,75 +271,106 @@ impl<'r> FromRequest<'r> for CsrfValidation {
     type Error = ();
 
     async fn from_request(request: &'r Request<'_>) -> Outcome<Self, Self::Error> {
-        let cookies = request.cookies();
-        
+        let config = match request.rocket().state::<CsrfConfig>() {
+            Some(config) => config,
+            None => return Outcome::Error((Status::InternalServerError, ())),
+        };
+        if !origin_verified(request, config) {
+            return Outcome::Error((Status::Forbidden, ()));
+        }
+
         // Cookieからトークンを取得
-        let cookie_token = match cookies.get("csrf_token") {
-            Some(c) => c.value().to_string(),
+        let cookie_token = match request
+            .cookies()
+            .get(CSRF_COOKIE_NAME)
+            .and_then(|c| CsrfToken::from_cookie_value(c.value(), config))
+        {
+            Some(token) => token,
             None => return Outcome::Error((Status::Forbidden, ())),
         };
-        
-        // ヘッダーからトークンを取得（HTMX用）
-        // X-CSRF-Token ヘッダーをチェック
-        let header_token = request
-            .headers()
-            .get_one("X-CSRF-Token")
-            .map(|s| s.to_string());
-        
-        // フォームからトークンを取得（通常のフォーム用）
-        // Content-Typeがform-urlencodedの場合のみ
-        // Note: Rocketではフォームデータをガード内で読み取るのは難しいため、
-        // HTMX経由のヘッダー方式を推奨
-        
-        let submitted_token = match header_token {
-            Some(t) => t,
-            None => {
-                // HTMXヘッダーがない場合は検証スキップ（フォームで別途検証）
-                // 本番環境ではより厳密にする必要あり
-                return Outcome::Success(CsrfValidation);
-            }
+
+        // ヘッダー (HTMX用) を優先し、なければフォームのフィールドを使う
+        let submitted = match request.headers().get_one(CSRF_HEADER_NAME) {
+            Some(token) => Some(token),
+            None => request.local_cache(SubmittedCsrfToken::default).0.as_deref(),
         };
-        
-        // トークンを検証
-        let csrf_token = CsrfToken(cookie_token);
-        if csrf_token.verify(&submitted_token) {
-            Outcome::Success(CsrfValidation)
-        } else {
-            Outcome::Error((Status::Forbidden, ()))
+
+        match submitted {
+            Some(token) if cookie_token.verify(token, config) => Outcome::Success(CsrfValidation),
+            _ => Outcome::Error((Status::Forbidden, ())),
         }
     }
 }
 
 /// テンプレートにCSRFトークンを渡すためのヘルパー
 pub fn csrf_context(token: &CsrfToken) -> String {
-    token.0.clone()
+    token.token()
 }
 
 #[cfg(test)]
 mod tests {
     use super::*;
 
+    fn config() -> CsrfConfig {
+        CsrfConfig::new(b"test-secret", vec!["https://*.example.com".into(), "https://partner.test".into()])
+    }
+
     #[test]
     fn test_csrf_token_generation() {
         let token1 = CsrfToken::generate();
         let token2 = CsrfToken::generate();
-        
+
         // 異なるトークンが生成される
-        assert_ne!(token1.0, token2.0);
-        
-        // トークンは空でない
-        assert!(!token1.0.is_empty());
+        assert_ne!(token1.secret, token2.secret);
+
+        // マスクはレスポンスごとに変わる
+        assert_ne!(token1.token(), token1.token());
     }
 
     #[test]
     fn test_csrf_token_verification() {
+        let config = config();
         let token = CsrfToken::generate();
-        let token_string = token.0.clone();
-        
-        // 正しいトークンは検証成功
-        assert!(token.verify(&token_string));
-        
-        // 不正なトークンは検証失敗
-        assert!(!token.verify("invalid_token"));
+
+        // マスク済みのトークンとCookieの値は検証成功
+        assert!(token.verify(&token.token(), &config));
+        assert!(token.verify(&token.cookie_value(&config), &config));
+
+        // 不正なトークン・別のトークンは検証失敗
+        assert!(!token.verify("invalid_token", &config));
+        assert!(!token.verify(&CsrfToken::generate().token(), &config));
+    }
+
+    #[test]
+    fn test_cookie_signature() {
+        let config = config();
+        let token = CsrfToken::generate();
+        let value = token.cookie_value(&config);
+        assert!(CsrfToken::from_cookie_value(&value, &config).is_some());
+
+        // 別の鍵で署名されたCookieや改ざんされたCookieは受け付けない
+        let other = CsrfConfig::new(b"other-secret", vec![]);
+        assert!(CsrfToken::from_cookie_value(&value, &other).is_none());
+        let (_, signature) = value.split_once('.').unwrap();
+        let forged = format!("{}.{}", URL_SAFE_NO_PAD.encode([0u8; SECRET_LENGTH]), signature);
+        assert!(CsrfToken::from_cookie_value(&forged, &config).is_none());
+    }
+
+    #[test]
+    fn test_expired_token() {
+        let mut token = CsrfToken::generate();
+        token.secret[..8].copy_from_slice(&(now() - DEFAULT_CSRF_COOKIE_AGE).to_be_bytes());
+        assert!(token.is_expired(DEFAULT_CSRF_COOKIE_AGE));
+        assert!(!token.verify(&token.token(), &config()));
+    }
+
+    #[test]
+    fn test_trusted_origins() {
+        let config = config();
+        assert!(config.is_trusted_origin("https://partner.test"));
+        assert!(config.is_trusted_origin("https://app.example.com"));
+        assert!(!config.is_trusted_origin("http://app.example.com"));
+        assert!(!config.is_trusted_origin("https://example.com.evil.test"));
+        assert!(!config.is_trusted_origin("https://evil.test"));
     }
 }
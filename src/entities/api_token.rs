@@ -0,0 +1,73 @@
+use sea_orm::entity::prelude::*;
+use serde::{Deserialize, Serialize};
+
+/// APIアクセス用の個人トークン。
+/// DRF の `authtoken.Token` に相当しますが、1ユーザーが複数のトークンを持て、名前・有効期限・権限の範囲を設定できます。
+/// トークンそのものは保存せず、ハッシュのみを保持します。
+#[derive(Clone, Debug, PartialEq, Eq, DeriveEntityModel, Serialize, Deserialize)]
+#[sea_orm(table_name = "api_tokens")]
+pub struct Model {
+    #[sea_orm(primary_key)]
+    pub id: i32,
+
+    /// 所有ユーザーのID (外部キー)
+    pub user_id: i32,
+
+    /// 用途が分かる名前 (例: "デプロイスクリプト")
+    pub name: String,
+
+    /// 一覧で見分けるためのトークンの先頭部分
+    pub token_prefix: String,
+
+    /// トークンのSHA-256ハッシュ
+    #[serde(skip_serializing)]
+    #[sea_orm(unique)]
+    pub token_hash: String,
+
+    /// 発行日時
+    pub created_at: DateTimeWithTimeZone,
+
+    /// 有効期限 (無期限の場合は `None`)
+    pub expires_at: Option<DateTimeWithTimeZone>,
+
+    /// 最終使用日時
+    pub last_used_at: Option<DateTimeWithTimeZone>,
+}
+
+#[derive(Copy, Clone, Debug, EnumIter, DeriveRelation)]
+pub enum Relation {
+    #[sea_orm(
+        belongs_to = "super::user::Entity",
+        from = "Column::UserId",
+        to = "super::user::Column::Id",
+        on_update = "NoAction",
+        on_delete = "Cascade"
+    )]
+    User,
+    #[sea_orm(has_many = "super::api_token_permission::Entity")]
+    ApiTokenPermissions,
+}
+
+impl Related<super::user::Entity> for Entity {
+    fn to() -> RelationDef {
+        Relation::User.def()
+    }
+}
+
+impl Related<super::permission::Entity> for Entity {
+    fn to() -> RelationDef {
+        super::api_token_permission::Relation::Permission.def()
+    }
+    fn via() -> Option<RelationDef> {
+        Some(super::api_token_permission::Relation::ApiToken.def().rev())
+    }
+}
+
+impl ActiveModelBehavior for ActiveModel {}
+
+impl Model {
+    /// 有効期限切れかどうか
+    pub fn is_expired(&self) -> bool {
+        self.expires_at.is_some_and(|expires| expires <= chrono::Utc::now())
+    }
+}
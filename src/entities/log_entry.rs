@@ -0,0 +1,154 @@
+use sea_orm::entity::prelude::*;
+use serde::{Deserialize, Serialize};
+use rocket::serde::json::serde_json;
+use crate::admin::AdminSite;
+
+/// 追加 (Djangoの `ADDITION`)
+pub const ADDITION: i16 = 1;
+/// 変更 (Djangoの `CHANGE`)
+pub const CHANGE: i16 = 2;
+/// 削除 (Djangoの `DELETION`)
+pub const DELETION: i16 = 3;
+
+/// 管理画面での操作の履歴。
+/// Djangoの `django.contrib.admin.models.LogEntry` に相当します。
+#[derive(Clone, Debug, PartialEq, Eq, DeriveEntityModel, Serialize, Deserialize)]
+#[sea_orm(table_name = "log_entries")]
+pub struct Model {
+    #[sea_orm(primary_key)]
+    pub id: i32,
+
+    /// 操作した日時
+    pub action_time: DateTimeWithTimeZone,
+
+    /// 操作したユーザー (削除済みの場合は `None`)
+    pub user_id: Option<i32>,
+
+    /// 対象のモデル (テーブル名。例: `user`)
+    pub content_type: String,
+
+    /// 対象のオブジェクトの主キー
+    pub object_id: String,
+
+    /// 操作した時点でのオブジェクトの表示名
+    pub object_repr: String,
+
+    /// `ADDITION` / `CHANGE` / `DELETION`
+    pub action_flag: i16,
+
+    /// 変更内容。`[{"changed": {"fields": ["email"]}}]` のようなJSON、または任意の文字列
+    pub change_message: String,
+}
+
+#[derive(Copy, Clone, Debug, EnumIter, DeriveRelation)]
+pub enum Relation {
+    #[sea_orm(
+        belongs_to = "super::user::Entity",
+        from = "Column::UserId",
+        to = "super::user::Column::Id",
+        on_update = "NoAction",
+        on_delete = "SetNull"
+    )]
+    User,
+}
+
+impl Related<super::user::Entity> for Entity {
+    fn to() -> RelationDef {
+        Relation::User.def()
+    }
+}
+
+impl ActiveModelBehavior for ActiveModel {}
+
+impl Model {
+    pub fn is_addition(&self) -> bool {
+        self.action_flag == ADDITION
+    }
+
+    pub fn is_change(&self) -> bool {
+        self.action_flag == CHANGE
+    }
+
+    pub fn is_deletion(&self) -> bool {
+        self.action_flag == DELETION
+    }
+
+    /// 変更内容を表示用の文に変換します (Djangoの `LogEntry.get_change_message()`)。
+    /// JSONでない場合はそのまま返します。
+    pub fn get_change_message(&self) -> String {
+        let Ok(serde_json::Value::Array(items)) = serde_json::from_str::<serde_json::Value>(&self.change_message) else {
+            return self.change_message.clone();
+        };
+
+        let messages: Vec<String> = items
+            .iter()
+            .filter_map(|item| {
+                if item.get("added").is_some() {
+                    Some("追加しました。".to_string())
+                } else if let Some(changed) = item.get("changed") {
+                    let fields: Vec<&str> = changed["fields"]
+                        .as_array()
+                        .map(|fields| fields.iter().filter_map(|f| f.as_str()).collect())
+                        .unwrap_or_default();
+                    (!fields.is_empty()).then(|| format!("{} を変更しました。", fields.join("、")))
+                } else if item.get("deleted").is_some() {
+                    Some("削除しました。".to_string())
+                } else {
+                    None
+                }
+            })
+            .collect();
+
+        if messages.is_empty() {
+            "変更はありません。".to_string()
+        } else {
+            messages.join(" ")
+        }
+    }
+
+    /// 管理画面の編集ページのURL。削除した場合や管理サイトに登録していないモデルでは `None`
+    /// (Djangoの `LogEntry.get_admin_url()`)
+    pub fn get_admin_url(&self, site: &AdminSite) -> Option<String> {
+        if self.is_deletion() {
+            return None;
+        }
+        site.admin_url(&self.content_type, &self.object_id)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn entry(action_flag: i16, change_message: &str) -> Model {
+        Model {
+            id: 1,
+            action_time: chrono::Utc::now().into(),
+            user_id: Some(1),
+            content_type: "user".into(),
+            object_id: "42".into(),
+            object_repr: "alice".into(),
+            action_flag,
+            change_message: change_message.into(),
+        }
+    }
+
+    #[test]
+    fn test_change_message_display() {
+        assert_eq!(entry(ADDITION, r#"[{"added": {}}]"#).get_change_message(), "追加しました。");
+        assert_eq!(
+            entry(CHANGE, r#"[{"changed": {"fields": ["email", "is_active"]}}]"#).get_change_message(),
+            "email、is_active を変更しました。"
+        );
+        assert_eq!(entry(CHANGE, r#"[{"changed": {"fields": []}}]"#).get_change_message(), "変更はありません。");
+        assert_eq!(entry(CHANGE, "2要素認証をリセットしました。").get_change_message(), "2要素認証をリセットしました。");
+    }
+
+    #[test]
+    fn test_admin_url() {
+        let site = AdminSite::new().register(crate::controllers::admin::UserAdmin);
+        assert_eq!(entry(CHANGE, "").get_admin_url(&site).as_deref(), Some("/admin/users/edit/42"));
+        assert_eq!(entry(DELETION, "").get_admin_url(&site), None);
+        assert_eq!(entry(CHANGE, "").get_admin_url(&AdminSite::new()), None);
+    }
+}
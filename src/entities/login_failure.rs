@@ -0,0 +1,31 @@
+use sea_orm::entity::prelude::*;
+use serde::{Deserialize, Serialize};
+
+/// ログイン失敗の集計。ユーザー名ごと・IPアドレスごとに1行を持ちます。
+/// django-axes の `AccessAttempt` に相当します。
+#[derive(Clone, Debug, PartialEq, Eq, DeriveEntityModel, Serialize, Deserialize)]
+#[sea_orm(table_name = "login_failures")]
+pub struct Model {
+    #[sea_orm(primary_key)]
+    pub id: i32,
+
+    /// 集計の単位 (`username` または `ip`)
+    pub scope: String,
+
+    /// ユーザー名またはIPアドレス
+    pub identifier: String,
+
+    /// 連続したログイン失敗の回数
+    pub failure_count: i32,
+
+    /// 最後に失敗した日時
+    pub last_failure_at: DateTimeWithTimeZone,
+
+    /// ロックの解除日時 (ロックされていない場合は `None`)
+    pub locked_until: Option<DateTimeWithTimeZone>,
+}
+
+#[derive(Copy, Clone, Debug, EnumIter, DeriveRelation)]
+pub enum Relation {}
+
+impl ActiveModelBehavior for ActiveModel {}
@@ -6,3 +6,10 @@ pub mod group_user;
 pub mod permission;
 pub mod group_permission;
 pub mod user_permission;
+pub mod session;
+pub mod login_failure;
+pub mod otp_recovery_code;
+pub mod api_token;
+pub mod api_token_permission;
+pub mod user_identity;
+pub mod log_entry;
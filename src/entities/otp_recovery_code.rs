@@ -0,0 +1,44 @@
+use sea_orm::entity::prelude::*;
+use serde::{Deserialize, Serialize};
+
+/// 2要素認証のリカバリーコード (1回限り使用可能)。
+/// django-otp の `StaticToken` に相当します。コードそのものは保存せず、ハッシュのみを保持します。
+#[derive(Clone, Debug, PartialEq, Eq, DeriveEntityModel, Serialize, Deserialize)]
+#[sea_orm(table_name = "otp_recovery_codes")]
+pub struct Model {
+    #[sea_orm(primary_key)]
+    pub id: i32,
+
+    /// 所有ユーザーのID (外部キー)
+    pub user_id: i32,
+
+    /// コードのSHA-256ハッシュ
+    #[serde(skip_serializing)]
+    pub code_hash: String,
+
+    /// 発行日時
+    pub created_at: DateTimeWithTimeZone,
+
+    /// 使用日時 (未使用の場合は `None`)
+    pub used_at: Option<DateTimeWithTimeZone>,
+}
+
+#[derive(Copy, Clone, Debug, EnumIter, DeriveRelation)]
+pub enum Relation {
+    #[sea_orm(
+        belongs_to = "super::user::Entity",
+        from = "Column::UserId",
+        to = "super::user::Column::Id",
+        on_update = "NoAction",
+        on_delete = "Cascade"
+    )]
+    User,
+}
+
+impl Related<super::user::Entity> for Entity {
+    fn to() -> RelationDef {
+        Relation::User.def()
+    }
+}
+
+impl ActiveModelBehavior for ActiveModel {}
@@ -17,6 +17,8 @@ pub enum Relation {
     GroupPermission,
     #[sea_orm(has_many = "super::user_permission::Entity")]
     UserPermission,
+    #[sea_orm(has_many = "super::api_token_permission::Entity")]
+    ApiTokenPermission,
 }
 
 impl Related<super::group_permission::Entity> for Entity {
@@ -31,4 +33,10 @@ impl Related<super::user_permission::Entity> for Entity {
     }
 }
 
+impl Related<super::api_token_permission::Entity> for Entity {
+    fn to() -> RelationDef {
+        Relation::ApiTokenPermission.def()
+    }
+}
+
 impl ActiveModelBehavior for ActiveModel {}
@@ -5,3 +5,10 @@ pub use super::group_user::Entity as GroupUser;
 pub use super::permission::Entity as Permission;
 pub use super::group_permission::Entity as GroupPermission;
 pub use super::user_permission::Entity as UserPermission;
+pub use super::session::Entity as Session;
+pub use super::login_failure::Entity as LoginFailure;
+pub use super::otp_recovery_code::Entity as OtpRecoveryCode;
+pub use super::api_token::Entity as ApiToken;
+pub use super::api_token_permission::Entity as ApiTokenPermission;
+pub use super::user_identity::Entity as UserIdentity;
+pub use super::log_entry::Entity as LogEntry;
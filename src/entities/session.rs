@@ -0,0 +1,63 @@
+use sea_orm::entity::prelude::*;
+use serde::{Deserialize, Serialize};
+
+/// ログインセッション。
+/// Djangoの `django.contrib.sessions.models.Session` に相当します。
+#[derive(Clone, Debug, PartialEq, Eq, DeriveEntityModel, Serialize, Deserialize)]
+#[sea_orm(table_name = "sessions")]
+pub struct Model {
+    /// セッションキー (Cookie `sessionid` に格納される値)
+    #[sea_orm(primary_key, auto_increment = false)]
+    #[serde(skip_serializing)]
+    pub session_key: String,
+
+    /// ログインユーザーのID (外部キー)
+    pub user_id: i32,
+
+    /// ログイン日時
+    pub created_at: DateTimeWithTimeZone,
+
+    /// 最終アクセス日時
+    pub last_activity: DateTimeWithTimeZone,
+
+    /// 有効期限 (これを過ぎたセッションは無効)
+    pub expire_date: DateTimeWithTimeZone,
+
+    /// ログイン時のクライアントIPアドレス
+    pub ip_address: Option<String>,
+
+    /// ログイン時のUser-Agent
+    pub user_agent: Option<String>,
+}
+
+#[derive(Copy, Clone, Debug, EnumIter, DeriveRelation)]
+pub enum Relation {
+    #[sea_orm(
+        belongs_to = "super::user::Entity",
+        from = "Column::UserId",
+        to = "super::user::Column::Id",
+        on_update = "NoAction",
+        on_delete = "Cascade"
+    )]
+    User,
+}
+
+impl Related<super::user::Entity> for Entity {
+    fn to() -> RelationDef {
+        Relation::User.def()
+    }
+}
+
+impl ActiveModelBehavior for ActiveModel {}
+
+impl Model {
+    /// 画面やURLで使用する、セッションキーを推測できない識別子。
+    /// セッションキーそのものを露出させないよう、SHA-256ハッシュの先頭を使用します。
+    pub fn public_id(&self) -> String {
+        use base64::{Engine as _, engine::general_purpose::URL_SAFE_NO_PAD};
+        use sha2::{Digest, Sha256};
+
+        let digest = Sha256::digest(self.session_key.as_bytes());
+        URL_SAFE_NO_PAD.encode(&digest[..16])
+    }
+}
@@ -13,6 +13,7 @@ pub struct Model {
     pub title: String,
     
     /// 詳細な説明 (任意)
+    #[sea_orm(column_type = "Text", nullable)]
     pub description: Option<String>,
     
     /// 完了状態
@@ -41,7 +42,9 @@ pub enum Relation {
     #[sea_orm(
         belongs_to = "super::user::Entity",
         from = "Column::UserId",
-        to = "super::user::Column::Id"
+        to = "super::user::Column::Id",
+        on_update = "NoAction",
+        on_delete = "Cascade"
     )]
     User,
     #[sea_orm(
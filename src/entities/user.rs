@@ -1,5 +1,5 @@
 use sea_orm::entity::prelude::*;
-use sea_orm::{JoinType, QuerySelect};
+use sea_orm::{ActiveValue, Condition, JoinType, QuerySelect, QueryTrait};
 use serde::{Deserialize, Serialize};
 
 // Djangoの `models.Model` に相当する構造体。
@@ -12,8 +12,19 @@ pub struct Model {
     #[sea_orm(unique)]
     pub username: String,
     pub password_hash: String,
+    /// メールアドレス (未設定の場合は空文字)
+    pub email: String,
     pub is_active: bool,
     pub is_admin: bool,
+    /// TOTPの共有シークレット (Base32)。2要素認証が無効の場合は `None`
+    #[serde(skip_serializing)]
+    pub totp_secret: Option<String>,
+    /// 最後に使用されたTOTPのカウンター値 (同じコードの再利用を防ぐ)
+    #[serde(skip_serializing)]
+    pub totp_last_counter: Option<i64>,
+    /// アカウントを有効化した日時。一度も有効化されていない場合は `None`
+    /// (Djangoの `date_joined` のように、有効化後は変更しない)
+    pub activated_at: Option<DateTimeWithTimeZone>,
 }
 
 // Djangoの `RelatedName` や `ForeignKey` などのリレーションを定義する場所。
@@ -24,6 +35,12 @@ pub enum Relation {
     GroupUsers,
     #[sea_orm(has_many = "super::user_permission::Entity")]
     UserPermissions,
+    #[sea_orm(has_many = "super::otp_recovery_code::Entity")]
+    OtpRecoveryCodes,
+    #[sea_orm(has_many = "super::api_token::Entity")]
+    ApiTokens,
+    #[sea_orm(has_many = "super::user_identity::Entity")]
+    UserIdentities,
 }
 
 impl Related<super::group::Entity> for Entity {
@@ -38,7 +55,26 @@ impl Related<super::group::Entity> for Entity {
 use super::{permission, user_permission, group_permission};
 
 // ActiveModelの振る舞い（保存前バリデーションなど）を定義。
-impl ActiveModelBehavior for ActiveModel {}
+#[async_trait::async_trait]
+impl ActiveModelBehavior for ActiveModel {
+    /// 有効なユーザーとして保存する際に、まだ記録がなければ `activated_at` を設定します。
+    /// 有効化リンク・管理画面・ユーザー作成のいずれで有効になった場合も記録されます
+    async fn before_save<C>(mut self, _db: &C, insert: bool) -> Result<Self, DbErr>
+    where
+        C: ConnectionTrait,
+    {
+        let activating = matches!(self.is_active, ActiveValue::Set(true));
+        let recorded = match &self.activated_at {
+            ActiveValue::Set(value) | ActiveValue::Unchanged(value) => value.is_some(),
+            // 部分的な更新では現在の値が分からないため変更しない
+            ActiveValue::NotSet => !insert,
+        };
+        if activating && !recorded {
+            self.activated_at = ActiveValue::Set(Some(chrono::Utc::now().into()));
+        }
+        Ok(self)
+    }
+}
 
 impl Model {
     /// ユーザーが指定された権限を持っているか確認する。
@@ -95,4 +131,45 @@ impl Model {
 
         Ok(group_has > 0)
     }
+
+    /// ユーザーが持つ全権限のコードネームを取得する。
+    ///
+    /// Djangoの `user.get_all_permissions()` に相当。
+    /// スーパーユーザー (`is_admin`) の場合は定義済みの全権限を返します。
+    pub async fn get_all_permissions(&self, db: &DatabaseConnection) -> Result<Vec<String>, DbErr> {
+        let mut query = permission::Entity::find()
+            .select_only()
+            .column(permission::Column::Codename)
+            .distinct();
+
+        if !self.is_admin {
+            let group_ids: Vec<i32> = super::group_user::Entity::find()
+                .filter(super::group_user::Column::UserId.eq(self.id))
+                .select_only()
+                .column(super::group_user::Column::GroupId)
+                .into_tuple()
+                .all(db)
+                .await?;
+
+            // 直接付与された権限 OR 所属グループの権限
+            let user_perm_ids = user_permission::Entity::find()
+                .filter(user_permission::Column::UserId.eq(self.id))
+                .select_only()
+                .column(user_permission::Column::PermissionId)
+                .into_query();
+            let group_perm_ids = group_permission::Entity::find()
+                .filter(group_permission::Column::GroupId.is_in(group_ids))
+                .select_only()
+                .column(group_permission::Column::PermissionId)
+                .into_query();
+
+            query = query.filter(
+                Condition::any()
+                    .add(permission::Column::Id.in_subquery(user_perm_ids))
+                    .add(permission::Column::Id.in_subquery(group_perm_ids)),
+            );
+        }
+
+        query.into_tuple().all(db).await
+    }
 }
@@ -0,0 +1,46 @@
+use sea_orm::entity::prelude::*;
+use serde::{Deserialize, Serialize};
+
+/// 外部IdP (OpenID Connect) のアカウントとローカルユーザーの紐付け。
+/// django-allauth の `SocialAccount` に相当します。
+#[derive(Clone, Debug, PartialEq, Eq, DeriveEntityModel, Serialize, Deserialize)]
+#[sea_orm(table_name = "user_identities")]
+pub struct Model {
+    #[sea_orm(primary_key)]
+    pub id: i32,
+
+    /// ローカルユーザーのID (外部キー)
+    pub user_id: i32,
+
+    /// プロバイダー名 (`/auth/oidc/<provider>` の `<provider>`)
+    pub provider: String,
+
+    /// IdP上のユーザー識別子 (IDトークンの `sub` クレーム)
+    pub subject: String,
+
+    /// 紐付けた日時
+    pub created_at: DateTimeWithTimeZone,
+
+    /// このIdPで最後にログインした日時
+    pub last_login_at: Option<DateTimeWithTimeZone>,
+}
+
+#[derive(Copy, Clone, Debug, EnumIter, DeriveRelation)]
+pub enum Relation {
+    #[sea_orm(
+        belongs_to = "super::user::Entity",
+        from = "Column::UserId",
+        to = "super::user::Column::Id",
+        on_update = "NoAction",
+        on_delete = "Cascade"
+    )]
+    User,
+}
+
+impl Related<super::user::Entity> for Entity {
+    fn to() -> RelationDef {
+        Relation::User.def()
+    }
+}
+
+impl ActiveModelBehavior for ActiveModel {}
@@ -1,7 +1,9 @@
 use rocket::{fairing::{Fairing, Info, Kind}, Data, Request};
 
 use rocket::serde::json::serde_json;
+use crate::admin::AdminSite;
 use crate::guards::auth::AuthenticatedUser;
+use crate::guards::permission::StaffUser;
 use crate::csrf::CsrfToken;
 
 /// コンテキストプロセッサとしてのFairing。
@@ -46,6 +48,19 @@ impl Fairing for ContextFairing {
              let token_str = csrf.token().to_string();
              request.local_cache(|| Some(CachedCsrf(token_str)));
         }
+
+        // 3. Admin Apps (Djangoの `AdminSite.each_context` の `available_apps`)
+        // 管理画面のメニューとダッシュボードに、権限のあるモデルだけを表示する
+        if request.uri().path().starts_with("/admin") {
+            if let (rocket::outcome::Outcome::Success(staff), Some(site)) =
+                (request.guard::<StaffUser>().await, request.rocket().state::<AdminSite>())
+            {
+                let apps = site.available_apps(|codename| staff.has_perm(codename));
+                if let Ok(apps_json) = serde_json::to_value(apps) {
+                    request.local_cache(|| Some(CachedAdminApps(apps_json)));
+                }
+            }
+        }
     }
 }
 
@@ -58,3 +73,6 @@ pub struct CachedIsAdmin(pub bool);
 
 #[derive(Clone)]
 pub struct CachedCsrf(pub String);
+
+#[derive(Clone)]
+pub struct CachedAdminApps(pub serde_json::Value);
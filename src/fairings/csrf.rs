@@ -0,0 +1,183 @@
+use rocket::fairing::{Fairing, Info, Kind};
+use rocket::http::uri::Origin;
+use rocket::http::{Method, Status};
+use rocket::outcome::Outcome;
+use rocket::serde::json::Json;
+use rocket::{Build, Data, Request, Response, Rocket, Route};
+use rocket_dyn_templates::context;
+use crate::controllers::catchers::{wants_json, ErrorBody, ErrorResponse};
+use crate::csrf::{is_secure, CsrfConfig, CsrfToken, CsrfValidation, SubmittedCsrfToken, CSRF_FIELD_NAME};
+use crate::guards::auth::bearer_token;
+use crate::sessions::SESSION_COOKIE_NAME;
+use crate::settings::Settings;
+use crate::views::app_template::AppTemplate;
+
+/// 検証に失敗したリクエストの振り替え先
+const FAILURE_PATH: &str = "/__csrf_failure";
+
+/// フォームの本文から参照できるバイト数 (Rocket の `Data::peek()` の上限)
+const PEEK_LIMIT: usize = 512;
+
+/// 安全でないメソッド (POST/PUT/PATCH/DELETE) のリクエストに対してCSRF検証を行うFairing。
+/// Djangoの `CsrfViewMiddleware` に相当します。
+///
+/// トークンは `X-CSRF-Token` ヘッダー (HTMX) か、フォームの `csrf_token` フィールドで送信します。
+/// フォームの本文は先頭の512バイトしか参照できないため、`csrf_token` はフォームの先頭に置いてください。
+/// 検証に失敗したリクエストはハンドラーに渡さず、`403 Forbidden` を返すルートへ振り替えます。
+///
+/// `Authorization: Bearer` ヘッダー付きのリクエスト (APIトークン認証) は、
+/// ブラウザーが自動で送信するものではないため検証しません (DRF の `TokenAuthentication` と同じ扱い)。
+///
+/// ログイン・ログアウトなどでセッションのCookieが変わったレスポンスでは、CSRFトークンも新しくします
+/// (Djangoの `rotate_token()` に相当)。
+pub struct CsrfProtection {
+    config: CsrfConfig,
+    exempt: Vec<String>,
+}
+
+impl CsrfProtection {
+    pub fn new(config: CsrfConfig) -> Self {
+        CsrfProtection { config, exempt: Vec::new() }
+    }
+
+    /// 検証を行わないパスを追加します (Djangoの `@csrf_exempt` に相当)。
+    /// `/` で終わるパスはその配下すべてに一致します (例: `/webhooks/`)。
+    pub fn exempt(mut self, path: impl Into<String>) -> Self {
+        self.exempt.push(path.into());
+        self
+    }
+
+    fn is_exempt(&self, request: &Request<'_>) -> bool {
+        let path = request.uri().path();
+        bearer_token(request).is_some()
+            || self.exempt.iter().any(|exempt| match exempt.strip_suffix('/') {
+                Some(prefix) => path == prefix || path.starts_with(exempt.as_str()),
+                None => path == exempt.as_str(),
+            })
+    }
+}
+
+/// `GET` / `HEAD` / `OPTIONS` / `TRACE` 以外のメソッドは検証の対象
+fn is_unsafe(method: Method) -> bool {
+    !matches!(method, Method::Get | Method::Head | Method::Options | Method::Trace)
+}
+
+/// フォームの本文 (`application/x-www-form-urlencoded`) から `csrf_token` を取り出す
+fn form_token(body: &[u8]) -> Option<String> {
+    String::from_utf8_lossy(body).split('&').find_map(|pair| {
+        let (name, value) = pair.split_once('=')?;
+        (name == CSRF_FIELD_NAME).then(|| {
+            rocket::http::RawStr::new(value)
+                .url_decode_lossy()
+                .into_owned()
+        })
+    })
+}
+
+#[rocket::async_trait]
+impl Fairing for CsrfProtection {
+    fn info(&self) -> Info {
+        Info {
+            name: "CSRF Protection",
+            kind: Kind::Ignite | Kind::Request | Kind::Response,
+        }
+    }
+
+    async fn on_ignite(&self, rocket: Rocket<Build>) -> rocket::fairing::Result {
+        let routes: Vec<Route> = [Method::Post, Method::Put, Method::Patch, Method::Delete]
+            .into_iter()
+            .map(|method| Route::new(method, FAILURE_PATH, csrf_failure_handler))
+            .collect();
+        Ok(rocket.manage(self.config.clone()).mount("/", routes))
+    }
+
+    async fn on_request(&self, request: &mut Request<'_>, data: &mut Data<'_>) {
+        if !is_unsafe(request.method()) || self.is_exempt(request) {
+            return;
+        }
+
+        if request.content_type().is_some_and(|ct| ct.is_form()) {
+            let token = form_token(data.peek(PEEK_LIMIT).await);
+            request.local_cache(|| SubmittedCsrfToken(token));
+        }
+
+        if !matches!(request.guard::<CsrfValidation>().await, Outcome::Success(_)) {
+            request.set_uri(Origin::parse(FAILURE_PATH).expect("valid failure path"));
+        }
+    }
+
+    async fn on_response<'r>(&self, request: &'r Request<'_>, response: &mut Response<'r>) {
+        let session_changed = response
+            .headers()
+            .get("Set-Cookie")
+            .any(|cookie| cookie.starts_with(&format!("{}=", SESSION_COOKIE_NAME)));
+        if session_changed {
+            let token = CsrfToken::generate();
+            response.adjoin_header(token.cookie(&self.config, is_secure(request)));
+        }
+    }
+}
+
+fn csrf_failure_handler<'r>(request: &'r Request<'_>, _data: Data<'r>) -> rocket::route::BoxFuture<'r> {
+    Box::pin(async move { rocket::route::Outcome::from(request, csrf_failure(request)) })
+}
+
+/// CSRF検証に失敗した場合のレスポンス (Djangoの `CSRF_FAILURE_VIEW` に相当)
+fn csrf_failure(request: &Request<'_>) -> ErrorResponse {
+    if wants_json(request) {
+        return ErrorResponse::Json((Status::Forbidden, Json(ErrorBody {
+            status: Status::Forbidden.code,
+            error: "csrf_failed",
+            message: "CSRF検証に失敗しました",
+        })));
+    }
+    ErrorResponse::Html((Status::Forbidden, AppTemplate::new("error", context! {
+        title: "CSRF検証に失敗しました",
+        status: 403,
+        message: "フォームの有効期限が切れているか、不正な送信元からのリクエストです。ページを再読み込みしてから、もう一度送信してください。",
+        login_url: request.rocket().state::<Settings>().map_or("/auth/login", |s| s.login_url.as_str()),
+    })))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_form_token() {
+        assert_eq!(form_token(b"csrf_token=abc-_1&name=x").as_deref(), Some("abc-_1"));
+        assert_eq!(form_token(b"name=a%26b&csrf_token=x%2Dy").as_deref(), Some("x-y"));
+        assert_eq!(form_token(b"name=csrf_token"), None);
+    }
+
+    /// `templates/` の `method="post"` のフォームは全て `csrf_token` が最初のフィールド
+    #[test]
+    fn test_templates_put_csrf_token_first() {
+        fn visit(dir: &std::path::Path, files: &mut Vec<std::path::PathBuf>) {
+            for entry in std::fs::read_dir(dir).unwrap().flatten() {
+                let path = entry.path();
+                if path.is_dir() {
+                    visit(&path, files);
+                } else if path.extension().is_some_and(|ext| ext == "tera") {
+                    files.push(path);
+                }
+            }
+        }
+        let mut files = Vec::new();
+        visit(std::path::Path::new("templates"), &mut files);
+        assert!(!files.is_empty());
+
+        for file in files {
+            let content = std::fs::read_to_string(&file).unwrap();
+            for form in content.split("<form").skip(1) {
+                let (tag, body) = form.split_once('>').unwrap();
+                if !tag.to_lowercase().contains("method=\"post\"") {
+                    continue;
+                }
+                let body = body.split("</form>").next().unwrap();
+                let first = body.split("name=\"").nth(1).and_then(|rest| rest.split('"').next());
+                assert_eq!(first, Some(CSRF_FIELD_NAME), "{}: csrf_token がフォームの先頭にありません", file.display());
+            }
+        }
+    }
+}
@@ -1 +1,3 @@
 pub mod context;
+pub mod csrf;
+pub mod security;
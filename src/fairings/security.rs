@@ -0,0 +1,152 @@
+use base64::{Engine as _, engine::general_purpose::URL_SAFE_NO_PAD};
+use rand::Rng;
+use rocket::fairing::{Fairing, Info, Kind};
+use rocket::http::Header;
+use rocket::shield::Shield;
+use rocket::{fairing, Build, Data, Request, Response, Rocket};
+use crate::csrf::is_secure;
+use crate::settings::Settings;
+
+/// 標準の Content-Security-Policy。
+/// インラインのスクリプトは `nonce="{{ csp_nonce }}"` を付けたものだけを実行し、
+/// インラインの `style` 属性はテンプレートで多用しているため許可します。
+pub const DEFAULT_CSP: &str = "default-src 'self'; \
+    script-src 'self' 'nonce-{nonce}'; \
+    style-src 'self' 'unsafe-inline'; \
+    img-src 'self' data:; \
+    object-src 'none'; \
+    base-uri 'self'; \
+    frame-ancestors 'none'";
+
+/// `Referrer-Policy` に指定できる値
+pub const REFERRER_POLICY_VALUES: [&str; 8] = [
+    "no-referrer",
+    "no-referrer-when-downgrade",
+    "origin",
+    "origin-when-cross-origin",
+    "same-origin",
+    "strict-origin",
+    "strict-origin-when-cross-origin",
+    "unsafe-url",
+];
+
+/// CSPのポリシー中でnonceに置き換える文字列
+const NONCE_PLACEHOLDER: &str = "{nonce}";
+
+/// リクエストごとのCSPのnonce。`AppTemplate` が `csp_nonce` としてテンプレートに渡します。
+#[derive(Clone)]
+pub struct CspNonce(pub String);
+
+/// セキュリティヘッダーの設定
+#[derive(Clone)]
+pub struct SecurityConfig {
+    /// `Strict-Transport-Security` の `max-age` (秒)。`0` の場合は付けない
+    pub hsts_seconds: u64,
+    pub hsts_include_subdomains: bool,
+    pub hsts_preload: bool,
+    pub content_type_nosniff: bool,
+    pub x_frame_options: String,
+    pub referrer_policy: String,
+    pub cross_origin_opener_policy: String,
+    pub csp: String,
+    pub csp_report_only: String,
+}
+
+impl SecurityConfig {
+    /// 設定から構築します。
+    ///
+    /// - `SECURE_HSTS_SECONDS` / `SECURE_HSTS_INCLUDE_SUBDOMAINS` / `SECURE_HSTS_PRELOAD`
+    /// - `SECURE_CONTENT_TYPE_NOSNIFF`
+    /// - `X_FRAME_OPTIONS`
+    /// - `SECURE_REFERRER_POLICY`
+    /// - `SECURE_CROSS_ORIGIN_OPENER_POLICY`
+    /// - `SECURE_CSP` / `SECURE_CSP_REPORT_ONLY`: `{nonce}` はリクエストごとのnonceに置き換えます
+    pub fn from_settings(settings: &Settings) -> Self {
+        SecurityConfig {
+            hsts_seconds: settings.secure_hsts_seconds,
+            hsts_include_subdomains: settings.secure_hsts_include_subdomains,
+            hsts_preload: settings.secure_hsts_preload,
+            content_type_nosniff: settings.secure_content_type_nosniff,
+            x_frame_options: settings.x_frame_options.clone(),
+            referrer_policy: settings.secure_referrer_policy.clone(),
+            cross_origin_opener_policy: settings.secure_cross_origin_opener_policy.clone(),
+            csp: settings.secure_csp.clone(),
+            csp_report_only: settings.secure_csp_report_only.clone(),
+        }
+    }
+
+    /// `Strict-Transport-Security` の値
+    fn hsts(&self) -> String {
+        let mut value = format!("max-age={}", self.hsts_seconds);
+        if self.hsts_include_subdomains {
+            value.push_str("; includeSubDomains");
+        }
+        if self.hsts_preload {
+            value.push_str("; preload");
+        }
+        value
+    }
+}
+
+/// レスポンスにセキュリティヘッダーを付けるFairing。
+/// Djangoの `SecurityMiddleware`・`XFrameOptionsMiddleware` と、Django 6.0 の `ContentSecurityPolicyMiddleware` に相当します。
+///
+/// - `Strict-Transport-Security` (HTTPSのリクエストのみ)
+/// - `X-Content-Type-Options` / `X-Frame-Options` / `Referrer-Policy` / `Cross-Origin-Opener-Policy`
+/// - `Content-Security-Policy` / `Content-Security-Policy-Report-Only`
+///
+/// CSPのnonceはリクエストごとに生成し、テンプレートでは `<script nonce="{{ csp_nonce }}">` のように使います。
+/// ハンドラーが既に同じヘッダーを設定している場合は上書きしません。
+/// 同じヘッダーを付ける Rocket 標準の `Shield` は、設定が二重にならないよう空にします。
+pub struct SecurityMiddleware {
+    config: SecurityConfig,
+}
+
+impl SecurityMiddleware {
+    pub fn new(config: SecurityConfig) -> Self {
+        SecurityMiddleware { config }
+    }
+}
+
+#[rocket::async_trait]
+impl Fairing for SecurityMiddleware {
+    fn info(&self) -> Info {
+        Info {
+            name: "Security Headers",
+            kind: Kind::Ignite | Kind::Request | Kind::Response,
+        }
+    }
+
+    async fn on_ignite(&self, rocket: Rocket<Build>) -> fairing::Result {
+        Ok(rocket.attach(Shield::new()))
+    }
+
+    async fn on_request(&self, request: &mut Request<'_>, _data: &mut Data<'_>) {
+        let nonce = URL_SAFE_NO_PAD.encode(rand::thread_rng().gen::<[u8; 16]>());
+        request.local_cache(|| Some(CspNonce(nonce)));
+    }
+
+    async fn on_response<'r>(&self, request: &'r Request<'_>, response: &mut Response<'r>) {
+        let config = &self.config;
+        let nonce = request.local_cache(|| None::<CspNonce>).as_ref().map(|n| n.0.as_str()).unwrap_or_default();
+
+        let mut headers = Vec::new();
+        if config.hsts_seconds > 0 && is_secure(request) {
+            headers.push(("Strict-Transport-Security", config.hsts()));
+        }
+        if config.content_type_nosniff {
+            headers.push(("X-Content-Type-Options", "nosniff".to_string()));
+        }
+        headers.push(("X-Frame-Options", config.x_frame_options.clone()));
+        headers.push(("Referrer-Policy", config.referrer_policy.clone()));
+        headers.push(("Cross-Origin-Opener-Policy", config.cross_origin_opener_policy.clone()));
+        headers.push(("Content-Security-Policy", config.csp.replace(NONCE_PLACEHOLDER, nonce)));
+        headers.push(("Content-Security-Policy-Report-Only", config.csp_report_only.replace(NONCE_PLACEHOLDER, nonce)));
+
+        for (name, value) in headers {
+            if !value.is_empty() && !response.headers().contains(name) {
+                response.set_header(Header::new(name, value));
+            }
+        }
+    }
+}
@@ -2,12 +2,17 @@ use rocket::request::{Outcome, Request, FromRequest};
 use rocket::http::Status;
 use rocket::State;
 use sea_orm::*;
-use crate::entities::{prelude::*, user};
+use crate::entities::{prelude::*, api_token, session, user};
+use crate::otp::TwoFactorConfig;
+use crate::services::api_tokens::ApiTokenService;
+use crate::sessions::SessionManager;
 
 /// 認証済みユーザーを表すリクエストガード。
 /// Djangoの `request.user` に相当し、ビューの引数に含めるだけで自動的に認証チェックが行われます。
 pub struct AuthenticatedUser {
     pub user: user::Model,
+    /// 現在のログインセッション (Django: `request.session`)
+    pub session: session::Model,
 }
 
 #[rocket::async_trait]
@@ -21,24 +26,33 @@ impl<'r> FromRequest<'r> for AuthenticatedUser {
             _ => return Outcome::Error((Status::InternalServerError, ())),
         };
 
-        // セッション代わりのクッキーを確認
-        let user_id = request
-            .cookies()
-            .get_private("user_id")
-            .and_then(|c| c.value().parse::<i32>().ok());
-
-        match user_id {
-            Some(id) => {
-                // DBからユーザーを取得
-                match User::find_by_id(id).one(db.inner()).await {
-                    Ok(Some(user)) if user.is_active => {
-                        Outcome::Success(AuthenticatedUser { user })
-                    }
-                    _ => Outcome::Error((Status::Unauthorized, ())),
-                }
+        let sessions = match request.guard::<&State<SessionManager>>().await {
+            Outcome::Success(sessions) => sessions,
+            _ => return Outcome::Error((Status::InternalServerError, ())),
+        };
+
+        // Cookieのセッションキーからサーバー側のセッションを取得
+        let session = match sessions.current(request.cookies()).await {
+            Ok(Some(session)) => session,
+            Ok(None) => return Outcome::Error((Status::Unauthorized, ())),
+            Err(_) => return Outcome::Error((Status::InternalServerError, ())),
+        };
+
+        // DBからユーザーを取得
+        let user = match User::find_by_id(session.user_id).one(db.inner()).await {
+            Ok(Some(user)) if user.is_active => user,
+            _ => return Outcome::Error((Status::Unauthorized, ())),
+        };
+
+        // 2要素認証が必須なのに未設定のユーザーは、設定を完了するまで認証済みとみなさない
+        // (ポリシーを後から有効にした場合、既存のセッションもここで弾かれる)
+        if let Some(config) = request.rocket().state::<TwoFactorConfig>() {
+            if config.needs_setup(&user) {
+                return Outcome::Error((Status::Unauthorized, ()));
             }
-            None => Outcome::Error((Status::Unauthorized, ())),
         }
+
+        Outcome::Success(AuthenticatedUser { user, session })
     }
 }
 
@@ -63,3 +77,97 @@ impl<'r> FromRequest<'r> for AdminUser {
         }
     }
 }
+
+/// `Authorization: Bearer <トークン>` で認証されたユーザーを表すリクエストガード。
+/// DRF の `TokenAuthentication` に相当します。
+///
+/// トークンで許可されるのは、トークンの範囲 (`scopes`) とユーザー自身の権限の両方に含まれる操作のみです。
+pub struct TokenAuthenticatedUser {
+    pub user: user::Model,
+    pub token: api_token::Model,
+    /// トークンの範囲 (権限のコードネーム)
+    pub scopes: Vec<String>,
+}
+
+impl TokenAuthenticatedUser {
+    /// トークンの範囲内で、ユーザーが権限を持っているか
+    pub async fn has_perm(&self, db: &DatabaseConnection, perm_codename: &str) -> Result<bool, DbErr> {
+        if !self.scopes.iter().any(|scope| scope == perm_codename) {
+            return Ok(false);
+        }
+        self.user.has_perm(db, perm_codename).await
+    }
+}
+
+/// `Authorization` ヘッダーから Bearer トークンを取り出す
+pub(crate) fn bearer_token<'r>(request: &'r Request<'_>) -> Option<&'r str> {
+    let value = request.headers().get_one("Authorization")?;
+    let (scheme, token) = value.split_once(' ')?;
+    scheme.eq_ignore_ascii_case("bearer").then(|| token.trim())
+}
+
+#[rocket::async_trait]
+impl<'r> FromRequest<'r> for TokenAuthenticatedUser {
+    type Error = ();
+
+    async fn from_request(request: &'r Request<'_>) -> Outcome<Self, Self::Error> {
+        let token = match bearer_token(request) {
+            Some(token) => token,
+            None => return Outcome::Error((Status::Unauthorized, ())),
+        };
+
+        let db = match request.guard::<&State<DatabaseConnection>>().await {
+            Outcome::Success(db) => db,
+            _ => return Outcome::Error((Status::InternalServerError, ())),
+        };
+
+        let (user, token) = match ApiTokenService::authenticate(db.inner(), token).await {
+            Ok(Some(found)) => found,
+            Ok(None) => return Outcome::Error((Status::Unauthorized, ())),
+            Err(_) => return Outcome::Error((Status::InternalServerError, ())),
+        };
+
+        match ApiTokenService::permissions(db.inner(), &token).await {
+            Ok(scopes) => Outcome::Success(TokenAuthenticatedUser { user, token, scopes }),
+            Err(_) => Outcome::Error((Status::InternalServerError, ())),
+        }
+    }
+}
+
+/// セッション (Cookie) と APIトークンのどちらでも認証できるガード。
+/// DRF の `authentication_classes = [SessionAuthentication, TokenAuthentication]` に相当します。
+///
+/// `Authorization` ヘッダーがある場合はトークンのみで認証し、セッションにはフォールバックしません。
+pub enum ApiUser {
+    Session(AuthenticatedUser),
+    Token(TokenAuthenticatedUser),
+}
+
+impl ApiUser {
+    pub fn user(&self) -> &user::Model {
+        match self {
+            ApiUser::Session(auth) => &auth.user,
+            ApiUser::Token(auth) => &auth.user,
+        }
+    }
+
+    /// 権限チェック。トークンの場合はトークンの範囲も考慮します。
+    pub async fn has_perm(&self, db: &DatabaseConnection, perm_codename: &str) -> Result<bool, DbErr> {
+        match self {
+            ApiUser::Session(auth) => auth.user.has_perm(db, perm_codename).await,
+            ApiUser::Token(auth) => auth.has_perm(db, perm_codename).await,
+        }
+    }
+}
+
+#[rocket::async_trait]
+impl<'r> FromRequest<'r> for ApiUser {
+    type Error = ();
+
+    async fn from_request(request: &'r Request<'_>) -> Outcome<Self, Self::Error> {
+        if request.headers().contains("Authorization") {
+            return TokenAuthenticatedUser::from_request(request).await.map(ApiUser::Token);
+        }
+        AuthenticatedUser::from_request(request).await.map(ApiUser::Session)
+    }
+}
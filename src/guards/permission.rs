@@ -0,0 +1,163 @@
+use std::marker::PhantomData;
+use rocket::request::{Outcome, Request, FromRequest};
+use rocket::http::Status;
+use rocket::State;
+use sea_orm::*;
+use crate::guards::auth::{ApiUser, AuthenticatedUser};
+
+/// 権限のコードネームを型として表すためのトレイト。
+/// `PermissionRequired<P>` の型引数として使用します。
+pub trait PermissionCodename {
+    /// `permissions.codename` に対応する文字列 (例: "auth.view_user")
+    const CODENAME: &'static str;
+}
+
+/// 権限マーカー型を定義するマクロ。
+/// マイグレーション (`m20260205_000001_seed_permissions`) で投入されるコードネームと一致させること。
+macro_rules! define_permissions {
+    ($($(#[$meta:meta])* $name:ident => $codename:expr),* $(,)?) => {
+        $(
+            $(#[$meta])*
+            pub struct $name;
+
+            impl PermissionCodename for $name {
+                const CODENAME: &'static str = $codename;
+            }
+        )*
+    };
+}
+
+define_permissions! {
+    /// ユーザーを閲覧
+    ViewUser => "auth.view_user",
+    /// ユーザーを追加
+    AddUser => "auth.add_user",
+    /// ユーザーを変更
+    ChangeUser => "auth.change_user",
+    /// ユーザーを削除
+    DeleteUser => "auth.delete_user",
+    /// グループを閲覧
+    ViewGroup => "auth.view_group",
+    /// グループを追加
+    AddGroup => "auth.add_group",
+    /// グループを変更
+    ChangeGroup => "auth.change_group",
+    /// グループを削除
+    DeleteGroup => "auth.delete_group",
+}
+
+/// 指定した権限を持つユーザーのみを許可するガード。
+/// Djangoの `PermissionRequiredMixin` / `@permission_required` に相当。
+///
+/// ```ignore
+/// #[get("/users")]
+/// pub async fn list_users(_perm: PermissionRequired<ViewUser>) -> ... { ... }
+/// ```
+///
+/// 未ログインの場合は 401、権限がない場合は 403 を返します。
+pub struct PermissionRequired<P: PermissionCodename> {
+    pub user: AuthenticatedUser,
+    _marker: PhantomData<fn() -> P>,
+}
+
+#[rocket::async_trait]
+impl<'r, P: PermissionCodename> FromRequest<'r> for PermissionRequired<P> {
+    type Error = ();
+
+    async fn from_request(request: &'r Request<'_>) -> Outcome<Self, Self::Error> {
+        let auth = match AuthenticatedUser::from_request(request).await {
+            Outcome::Success(auth) => auth,
+            _ => return Outcome::Error((Status::Unauthorized, ())),
+        };
+
+        let db = match request.guard::<&State<DatabaseConnection>>().await {
+            Outcome::Success(db) => db,
+            _ => return Outcome::Error((Status::InternalServerError, ())),
+        };
+
+        match auth.user.has_perm(db.inner(), P::CODENAME).await {
+            Ok(true) => Outcome::Success(PermissionRequired {
+                user: auth,
+                _marker: PhantomData,
+            }),
+            Ok(false) => Outcome::Error((Status::Forbidden, ())),
+            Err(_) => Outcome::Error((Status::InternalServerError, ())),
+        }
+    }
+}
+
+/// `PermissionRequired` のAPI版。セッションとAPIトークンのどちらでも認証でき、
+/// トークンの場合はトークンの範囲に権限が含まれている必要があります。
+pub struct ApiPermissionRequired<P: PermissionCodename> {
+    pub user: ApiUser,
+    _marker: PhantomData<fn() -> P>,
+}
+
+#[rocket::async_trait]
+impl<'r, P: PermissionCodename> FromRequest<'r> for ApiPermissionRequired<P> {
+    type Error = ();
+
+    async fn from_request(request: &'r Request<'_>) -> Outcome<Self, Self::Error> {
+        let auth = match ApiUser::from_request(request).await {
+            Outcome::Success(auth) => auth,
+            _ => return Outcome::Error((Status::Unauthorized, ())),
+        };
+
+        let db = match request.guard::<&State<DatabaseConnection>>().await {
+            Outcome::Success(db) => db,
+            _ => return Outcome::Error((Status::InternalServerError, ())),
+        };
+
+        match auth.has_perm(db.inner(), P::CODENAME).await {
+            Ok(true) => Outcome::Success(ApiPermissionRequired {
+                user: auth,
+                _marker: PhantomData,
+            }),
+            Ok(false) => Outcome::Error((Status::Forbidden, ())),
+            Err(_) => Outcome::Error((Status::InternalServerError, ())),
+        }
+    }
+}
+
+/// 管理サイトにアクセスできるユーザーを許可するガード。
+/// Djangoの `is_staff` チェック (`admin_view`) に相当。
+///
+/// スーパーユーザー (`is_admin`) か、何らかの権限を持つユーザーを許可します。
+/// 個々の画面で必要な権限は `PermissionRequired` で別途チェックしてください。
+pub struct StaffUser {
+    pub user: AuthenticatedUser,
+    /// ユーザーが持つ全権限のコードネーム (テンプレートの表示切り替え用)
+    pub perms: Vec<String>,
+}
+
+impl StaffUser {
+    /// 権限を持っているか (スーパーユーザーは全て許可)
+    pub fn has_perm(&self, codename: &str) -> bool {
+        self.user.user.is_admin || self.perms.iter().any(|p| p == codename)
+    }
+}
+
+#[rocket::async_trait]
+impl<'r> FromRequest<'r> for StaffUser {
+    type Error = ();
+
+    async fn from_request(request: &'r Request<'_>) -> Outcome<Self, Self::Error> {
+        let auth = match AuthenticatedUser::from_request(request).await {
+            Outcome::Success(auth) => auth,
+            _ => return Outcome::Error((Status::Unauthorized, ())),
+        };
+
+        let db = match request.guard::<&State<DatabaseConnection>>().await {
+            Outcome::Success(db) => db,
+            _ => return Outcome::Error((Status::InternalServerError, ())),
+        };
+
+        match auth.user.get_all_permissions(db.inner()).await {
+            Ok(perms) if auth.user.is_admin || !perms.is_empty() => {
+                Outcome::Success(StaffUser { user: auth, perms })
+            }
+            Ok(_) => Outcome::Error((Status::Forbidden, ())),
+            Err(_) => Outcome::Error((Status::InternalServerError, ())),
+        }
+    }
+}
@@ -0,0 +1,368 @@
+//! パスワードハッシュのレジストリ。
+//! Djangoの `django.contrib.auth.hashers` と `PASSWORD_HASHERS` に相当します。
+//!
+//! 保存されたハッシュは先頭のアルゴリズム名 (`<algorithm>$...`) で判別し、対応する `PasswordHasher` で検証します。
+//! 本キットが作成するハッシュは Argon2id の PHC 文字列 (`$argon2id$v=19$...`) です。
+//! Djangoから移行した `pbkdf2_sha256` / `bcrypt_sha256` / `argon2` のハッシュもそのまま検証でき、
+//! 既定以外の形式や弱いパラメータのハッシュは、ログイン成功時に既定のハッシュで保存し直されます。
+
+use anyhow::{anyhow, Result};
+use argon2::password_hash::{
+    rand_core::OsRng, PasswordHash, PasswordHasher as _, PasswordVerifier as _, SaltString,
+};
+use argon2::{Algorithm, Argon2, Params, Version};
+use base64::Engine as _;
+use lazy_static::lazy_static;
+use pbkdf2::pbkdf2_hmac;
+use rand::distributions::Alphanumeric;
+use rand::Rng;
+use sha2::{Digest, Sha256};
+use crate::auth_utils::constant_time_eq;
+
+lazy_static! {
+    static ref DEFAULT_HASHERS: PasswordHashers = PasswordHashers::default();
+}
+
+/// 既定のレジストリ (`auth_utils::hash_password` / `verify_password` が使用)
+pub fn default_hashers() -> &'static PasswordHashers {
+    &DEFAULT_HASHERS
+}
+
+/// パスワードハッシュの方式。
+/// Djangoの `BasePasswordHasher` (`encode()` / `verify()` / `must_update()`) に相当します。
+pub trait PasswordHasher: Send + Sync {
+    /// ハッシュの先頭に付くアルゴリズム名
+    fn algorithm(&self) -> &'static str;
+
+    /// パスワードをハッシュ化する
+    fn encode(&self, password: &str) -> Result<String>;
+
+    /// パスワードがハッシュと一致するか
+    fn verify(&self, password: &str, encoded: &str) -> bool;
+
+    /// 同じ方式のまま、現在の設定で保存し直すべきか (反復回数やコストが古い場合など)
+    fn must_update(&self, _encoded: &str) -> bool {
+        false
+    }
+}
+
+/// Argon2id (本キットの既定)。
+/// 本キットの形式 (`$argon2id$...`) と Djangoの `Argon2PasswordHasher` の形式 (`argon2$argon2id$...`) の両方を検証します。
+#[derive(Default)]
+pub struct Argon2Hasher {
+    pub params: Params,
+}
+
+impl Argon2Hasher {
+    fn argon2(&self) -> Argon2<'static> {
+        Argon2::new(Algorithm::Argon2id, Version::V0x13, self.params.clone())
+    }
+
+    /// Django形式の先頭の `argon2` を取り除いた PHC 文字列
+    fn phc(encoded: &str) -> &str {
+        encoded.strip_prefix("argon2").unwrap_or(encoded)
+    }
+}
+
+impl PasswordHasher for Argon2Hasher {
+    fn algorithm(&self) -> &'static str {
+        "argon2"
+    }
+
+    fn encode(&self, password: &str) -> Result<String> {
+        let salt = SaltString::generate(&mut OsRng);
+        Ok(self
+            .argon2()
+            .hash_password(password.as_bytes(), &salt)
+            .map_err(|e| anyhow!("Failed to hash password: {}", e))?
+            .to_string())
+    }
+
+    fn verify(&self, password: &str, encoded: &str) -> bool {
+        // アルゴリズム・パラメータはハッシュに含まれるものが使われる
+        match PasswordHash::new(Self::phc(encoded)) {
+            Ok(hash) => Argon2::default().verify_password(password.as_bytes(), &hash).is_ok(),
+            Err(_) => false,
+        }
+    }
+
+    fn must_update(&self, encoded: &str) -> bool {
+        if !encoded.starts_with('$') {
+            // Django形式は本キットの形式に揃える
+            return true;
+        }
+        let hash = match PasswordHash::new(encoded) {
+            Ok(hash) => hash,
+            Err(_) => return true,
+        };
+        let params = match Params::try_from(&hash) {
+            Ok(params) => params,
+            Err(_) => return true,
+        };
+        hash.algorithm != Algorithm::Argon2id.ident()
+            || hash.version != Some(Version::V0x13.into())
+            || params.m_cost() != self.params.m_cost()
+            || params.t_cost() != self.params.t_cost()
+            || params.p_cost() != self.params.p_cost()
+    }
+}
+
+/// Djangoの既定の `PBKDF2PasswordHasher` (`pbkdf2_sha256$<反復回数>$<ソルト>$<ハッシュ>`)。
+pub struct Pbkdf2Sha256Hasher {
+    pub iterations: u32,
+}
+
+impl Default for Pbkdf2Sha256Hasher {
+    fn default() -> Self {
+        // Django 5.2 の既定値
+        Pbkdf2Sha256Hasher { iterations: 1_000_000 }
+    }
+}
+
+impl Pbkdf2Sha256Hasher {
+    fn derive(password: &str, salt: &str, iterations: u32) -> [u8; 32] {
+        let mut output = [0u8; 32];
+        pbkdf2_hmac::<Sha256>(password.as_bytes(), salt.as_bytes(), iterations, &mut output);
+        output
+    }
+}
+
+impl PasswordHasher for Pbkdf2Sha256Hasher {
+    fn algorithm(&self) -> &'static str {
+        "pbkdf2_sha256"
+    }
+
+    fn encode(&self, password: &str) -> Result<String> {
+        let salt: String = rand::thread_rng()
+            .sample_iter(&Alphanumeric)
+            .take(22)
+            .map(char::from)
+            .collect();
+        let hash = Self::derive(password, &salt, self.iterations);
+        Ok(format!(
+            "pbkdf2_sha256${}${}${}",
+            self.iterations,
+            salt,
+            base64::engine::general_purpose::STANDARD.encode(hash)
+        ))
+    }
+
+    fn verify(&self, password: &str, encoded: &str) -> bool {
+        let mut parts = encoded.splitn(4, '$');
+        let (Some("pbkdf2_sha256"), Some(iterations), Some(salt), Some(hash)) =
+            (parts.next(), parts.next(), parts.next(), parts.next())
+        else {
+            return false;
+        };
+        let (Ok(iterations), Ok(expected)) = (
+            iterations.parse::<u32>(),
+            base64::engine::general_purpose::STANDARD.decode(hash),
+        ) else {
+            return false;
+        };
+        if iterations == 0 {
+            return false;
+        }
+        constant_time_eq(&Self::derive(password, salt, iterations), &expected)
+    }
+
+    fn must_update(&self, encoded: &str) -> bool {
+        encoded
+            .split('$')
+            .nth(1)
+            .and_then(|iterations| iterations.parse::<u32>().ok())
+            .is_none_or(|iterations| iterations != self.iterations)
+    }
+}
+
+/// Djangoの `BCryptSHA256PasswordHasher` (`bcrypt_sha256$$2b$<コスト>$...`)。
+/// bcrypt の72バイト制限を避けるため、パスワードの SHA-256 (16進数) を bcrypt でハッシュ化します。
+pub struct BcryptSha256Hasher {
+    pub cost: u32,
+}
+
+impl Default for BcryptSha256Hasher {
+    fn default() -> Self {
+        BcryptSha256Hasher { cost: 12 }
+    }
+}
+
+impl BcryptSha256Hasher {
+    fn prehash(password: &str) -> String {
+        Sha256::digest(password.as_bytes()).iter().map(|b| format!("{:02x}", b)).collect()
+    }
+}
+
+impl PasswordHasher for BcryptSha256Hasher {
+    fn algorithm(&self) -> &'static str {
+        "bcrypt_sha256"
+    }
+
+    fn encode(&self, password: &str) -> Result<String> {
+        let hash = bcrypt::hash(Self::prehash(password), self.cost)
+            .map_err(|e| anyhow!("Failed to hash password: {}", e))?;
+        Ok(format!("bcrypt_sha256${}", hash))
+    }
+
+    fn verify(&self, password: &str, encoded: &str) -> bool {
+        match encoded.strip_prefix("bcrypt_sha256$") {
+            Some(hash) => bcrypt::verify(Self::prehash(password), hash).unwrap_or(false),
+            None => false,
+        }
+    }
+
+    fn must_update(&self, encoded: &str) -> bool {
+        encoded
+            .split('$')
+            .nth(3)
+            .and_then(|cost| cost.parse::<u32>().ok())
+            .is_none_or(|cost| cost != self.cost)
+    }
+}
+
+/// パスワード検証の結果 (Djangoの `check_password()` の戻り値と `setter` の呼び出し有無)
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct PasswordCheck {
+    /// パスワードが一致したか
+    pub valid: bool,
+    /// 既定のハッシュで保存し直すべきか (`valid` の場合のみ `true` になる)
+    pub must_update: bool,
+}
+
+/// パスワードハッシュのレジストリ。先頭のハッシュが新しいパスワードの保存に使われます。
+/// Djangoの `PASSWORD_HASHERS` 設定に相当します。
+pub struct PasswordHashers {
+    hashers: Vec<Box<dyn PasswordHasher>>,
+}
+
+impl Default for PasswordHashers {
+    /// Argon2id を既定とし、Djangoから移行したハッシュの検証に PBKDF2 / bcrypt を加えた構成
+    fn default() -> Self {
+        PasswordHashers::new(vec![
+            Box::new(Argon2Hasher::default()),
+            Box::new(Pbkdf2Sha256Hasher::default()),
+            Box::new(BcryptSha256Hasher::default()),
+        ])
+    }
+}
+
+impl PasswordHashers {
+    /// 先頭を既定とするレジストリを作成します (空の場合はパニックします)
+    pub fn new(hashers: Vec<Box<dyn PasswordHasher>>) -> Self {
+        assert!(!hashers.is_empty(), "PasswordHashers には少なくとも1つのハッシュが必要です");
+        PasswordHashers { hashers }
+    }
+
+    /// ハッシュを末尾に追加する (既定は変わらない)
+    pub fn with(mut self, hasher: impl PasswordHasher + 'static) -> Self {
+        self.hashers.push(Box::new(hasher));
+        self
+    }
+
+    /// 新しいパスワードの保存に使うハッシュ (Django: `get_hasher('default')`)
+    pub fn preferred(&self) -> &dyn PasswordHasher {
+        self.hashers[0].as_ref()
+    }
+
+    /// 保存形式からハッシュを判別する (Django: `identify_hasher()`)
+    pub fn identify(&self, encoded: &str) -> Option<&dyn PasswordHasher> {
+        let algorithm = if encoded.starts_with("$argon2") {
+            "argon2"
+        } else {
+            encoded.split('$').next()?
+        };
+        self.hashers
+            .iter()
+            .find(|hasher| hasher.algorithm() == algorithm)
+            .map(|hasher| hasher.as_ref())
+    }
+
+    /// 既定のハッシュでパスワードをハッシュ化する (Django: `make_password()`)
+    pub fn make_password(&self, password: &str) -> Result<String> {
+        self.preferred().encode(password)
+    }
+
+    /// パスワードを検証し、保存し直す必要があるかも返す (Django: `check_password()`)
+    pub fn check_password(&self, password: &str, encoded: &str) -> PasswordCheck {
+        let hasher = match self.identify(encoded) {
+            Some(hasher) => hasher,
+            None => return PasswordCheck { valid: false, must_update: false },
+        };
+        if !hasher.verify(password, encoded) {
+            return PasswordCheck { valid: false, must_update: false };
+        }
+
+        let preferred = self.preferred();
+        let must_update = hasher.algorithm() != preferred.algorithm() || preferred.must_update(encoded);
+        PasswordCheck { valid: true, must_update }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn weak_argon2() -> Argon2Hasher {
+        Argon2Hasher { params: Params::new(1024, 1, 1, None).unwrap() }
+    }
+
+    #[test]
+    fn test_default_hash_does_not_need_update() {
+        let hashers = PasswordHashers::default();
+        let encoded = hashers.make_password("lètmein").unwrap();
+
+        assert!(encoded.starts_with("$argon2id$v=19$"));
+        assert_eq!(hashers.check_password("lètmein", &encoded), PasswordCheck { valid: true, must_update: false });
+        assert!(!hashers.check_password("letmein", &encoded).valid);
+    }
+
+    #[test]
+    fn test_django_pbkdf2_sha256() {
+        // Django: make_password("lètmein", "seasalt", "pbkdf2_sha256") (反復回数のみ変更)
+        let encoded = "pbkdf2_sha256$10000$seasalt$CWWFdHOWwPnki7HvkcqN9iA2T3KLW1cf2uZ5kvArtVY=";
+        let hashers = PasswordHashers::default();
+
+        assert_eq!(hashers.check_password("lètmein", encoded), PasswordCheck { valid: true, must_update: true });
+        assert!(!hashers.check_password("letmein", encoded).valid);
+        assert!(!hashers.check_password("lètmein", "pbkdf2_sha256$0$seasalt$").valid);
+
+        let hasher = Pbkdf2Sha256Hasher { iterations: 10000 };
+        assert!(!hasher.must_update(encoded));
+        assert!(hasher.verify("lètmein", &hasher.encode("lètmein").unwrap()));
+    }
+
+    #[test]
+    fn test_django_bcrypt_sha256() {
+        let hasher = BcryptSha256Hasher { cost: 4 };
+        let encoded = hasher.encode("lètmein").unwrap();
+        assert!(encoded.starts_with("bcrypt_sha256$$2b$04$"));
+
+        let hashers = PasswordHashers::default();
+        assert_eq!(hashers.check_password("lètmein", &encoded), PasswordCheck { valid: true, must_update: true });
+        assert!(!hashers.check_password("letmein", &encoded).valid);
+        assert!(BcryptSha256Hasher::default().must_update(&encoded));
+    }
+
+    #[test]
+    fn test_django_argon2_format_and_weak_params_are_upgraded() {
+        let hashers = PasswordHashers::default();
+
+        // Djangoの Argon2PasswordHasher は先頭に "argon2" を付けて保存する
+        let weak = weak_argon2().encode("lètmein").unwrap();
+        let django_format = format!("argon2{}", weak);
+        assert_eq!(hashers.check_password("lètmein", &django_format), PasswordCheck { valid: true, must_update: true });
+        assert!(!hashers.check_password("letmein", &django_format).valid);
+
+        // 本キットの形式でもパラメータが既定より弱ければ更新する
+        assert_eq!(hashers.check_password("lètmein", &weak), PasswordCheck { valid: true, must_update: true });
+        assert!(!weak_argon2().must_update(&weak));
+    }
+
+    #[test]
+    fn test_unknown_or_unusable_hash_is_rejected() {
+        let hashers = PasswordHashers::default();
+        assert!(!hashers.check_password("", "").valid);
+        assert!(!hashers.check_password("lètmein", "!unusable").valid);
+        assert!(!hashers.check_password("lètmein", "md5$seasalt$0123456789abcdef").valid);
+    }
+}
@@ -11,36 +11,119 @@ pub mod db;
 pub mod entities;
 pub mod guards;
 pub mod auth_utils;
+pub mod hashers;
 pub mod errors;
 pub mod services;
 pub mod views;
 use crate::views::app_template::AppTemplate;
 pub mod fairings;
-pub mod macros;
 pub mod csrf;
 pub mod validation;
+pub mod password_validation;
+pub mod sessions;
+pub mod mail;
+pub mod tokens;
+pub mod management;
+pub mod otp;
+pub mod oidc;
+pub mod settings;
+pub mod checks;
+pub mod admin;
 
 /// Rocketインスタンスを構築する関数。
 /// テスト時にも利用できるように分離しています。
 pub async fn build_rocket() -> rocket::Rocket<Build> {
-    // .envファイルを読み込む (環境変数の読み込み)
-    dotenvy::dotenv().ok();
+    // 0. 設定の読み込みと検証 (Djangoの settings.py)。.env・Rocket.toml・環境変数から読み込む
+    let settings = settings::Settings::load().unwrap_or_else(|errors| panic!("Invalid settings:\n{}", errors));
 
     // 1. データベース接続
-    let db = db::set_up_db().await.expect("Failed to connect to DB");
+    let db = db::set_up_db(&settings.database_url).await.expect("Failed to connect to DB");
 
     // 2. マイグレーションの実行
     Migrator::up(&db, None).await.expect("Failed to run migrations");
 
-    // 3. Rocketインスタンスの構築
+    // 3. システムチェック (Djangoの `manage.py check`)。release プロファイルではデプロイ用のチェックも実行する
+    let deploy = rocket::Config::figment().profile() == rocket::Config::RELEASE_PROFILE;
+    let report = checks::CheckRegistry::default()
+        .run(&checks::CheckContext::new(&settings, &db), deploy)
+        .await;
+    if !report.messages.is_empty() {
+        eprintln!("{}", report);
+    }
+    if report.fails_at(checks::Level::Error) {
+        panic!("System check failed");
+    }
+
+    // 4. 管理画面に登録するモデル (Djangoの `admin.site.register`)。各モデルの権限もここで作成する
+    let admin_site = admin::AdminSite::new()
+        .register(controllers::admin::UserAdmin)
+        .register(controllers::admin_groups::GroupAdmin)
+        .register(controllers::todo::TodoAdmin);
+    admin_site.create_permissions(&db).await.expect("Failed to create admin permissions");
+
+    // 5. セッションストアの構築 (Djangoの SESSION_ENGINE)
+    let sessions = sessions::SessionManager::from_settings(&settings, db.clone());
+
+    // 6. メール送信バックエンドの構築 (Djangoの EMAIL_BACKEND)
+    let mailer = mail::Mailer::from_settings(&settings).expect("Failed to configure email backend");
+
+    // 7. パスワードリセットトークン (Rocketの secret_key で署名する)
+    let password_reset_tokens = tokens::PasswordResetTokenGenerator::from_settings(&settings);
+
+    // 8. ログイン試行制限 (django-axes 相当)
+    let login_throttle = services::login_throttle::LoginThrottle::from_settings(&settings);
+
+    // 9. 2要素認証の設定 (django-otp 相当)
+    let two_factor = otp::TwoFactorConfig::from_settings(&settings);
+
+    // 10. パスワードの強度チェック (Djangoの AUTH_PASSWORD_VALIDATORS)
+    let password_validators = password_validation::PasswordValidators::from_settings(&settings)
+        .expect("Failed to configure password validators");
+
+    // 11. ユーザー登録 (django-registration 相当)。有効化リンクも secret_key で署名する
+    let signup = services::registration::SignupConfig::from_settings(&settings);
+    let activation_tokens = tokens::AccountActivationTokenGenerator::from_settings(&settings, signup.activation_timeout());
+
+    // 12. 外部IdPでのログイン (mozilla-django-oidc 相当)
+    let oidc_providers = oidc::OidcProviders::from_settings(&settings).expect("Failed to configure OIDC providers");
+
+    // 13. CSRF検証 (Djangoの CsrfViewMiddleware)。Cookieは secret_key で署名する
+    //     検証しないパスは `.exempt("/webhooks/")` のように追加する
+    let csrf_protection = fairings::csrf::CsrfProtection::new(csrf::CsrfConfig::from_settings(&settings));
+
+    // 14. セキュリティヘッダー (Djangoの SecurityMiddleware)。CSPのnonceはリクエストごとに生成する
+    let security_middleware = fairings::security::SecurityMiddleware::new(fairings::security::SecurityConfig::from_settings(&settings));
+
+    // 15. Rocketインスタンスの構築
     rocket::build()
+        .manage(settings)
         .manage(db)
+        .manage(sessions)
+        .manage(mailer)
+        .manage(password_reset_tokens)
+        .manage(login_throttle)
+        .manage(two_factor)
+        .manage(password_validators)
+        .manage(signup)
+        .manage(activation_tokens)
+        .manage(oidc_providers)
+        .manage(admin_site)
         .attach(Template::fairing())
+        .attach(csrf_protection)
+        .attach(security_middleware)
         .attach(fairings::context::ContextFairing)
-        .mount("/", routes![index, setup_admin])
+        .register("/", controllers::catchers::catchers())
+        .mount("/", routes![index])
         .mount("/auth", routes![controllers::auth::login, controllers::auth::logout, controllers::auth::login_form])
+        .mount("/auth", controllers::two_factor::login_routes())
+        .mount("/auth", controllers::password_reset::routes())
+        .mount("/auth", controllers::registration::routes())
+        .mount("/auth", controllers::oidc::routes())
         .mount("/admin", controllers::admin::routes())
-        .mount("/admin", controllers::admin_groups::routes())
+        .mount("/admin", controllers::admin_site::routes())
+        .mount("/account", controllers::account::routes())
+        .mount("/account", controllers::two_factor::account_routes())
+        .mount("/api", controllers::api::routes())
         .mount("/todo", routes![
             controllers::todo::list_todos,
             controllers::todo::create_todo_form,
@@ -59,42 +142,3 @@ fn index() -> AppTemplate {
         title: "Welcome",
     })
 }
-
-#[get("/setup_admin")]
-async fn setup_admin(db: &rocket::State<sea_orm::DatabaseConnection>) -> String {
-    use sea_orm::*;
-    use crate::entities::user;
-    use crate::auth_utils::hash_password;
-    
-    let username = "admin";
-    let password = "password";
-    
-    // Check if exists
-    let existing = user::Entity::find().filter(user::Column::Username.eq(username)).one(db.inner()).await;
-    
-    let hash = hash_password(password).unwrap();
-    
-    if let Ok(Some(u)) = existing {
-        let mut active: user::ActiveModel = u.into();
-        active.password_hash = Set(hash);
-        match active.update(db.inner()).await {
-             Ok(_) => "Admin password updated".to_string(),
-             Err(e) => format!("Error updating: {}", e),
-        }
-    } else {
-        let user = user::ActiveModel {
-            username: Set(username.to_owned()),
-            password_hash: Set(hash),
-            is_admin: Set(true),
-            is_active: Set(true),
-            ..Default::default()
-        };
-        
-        match user.insert(db.inner()).await {
-            Ok(_) => "Admin created".to_string(),
-            Err(e) => format!("Error creating: {}", e),
-        }
-    }
-}
-
-
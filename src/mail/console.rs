@@ -0,0 +1,17 @@
+use super::{EmailBackend, EmailMessage};
+use crate::errors::AppError;
+
+/// メールを標準出力に書き出すバックエンド。開発用。
+/// Djangoの `django.core.mail.backends.console.EmailBackend` に相当します。
+pub struct ConsoleEmailBackend;
+
+#[rocket::async_trait]
+impl EmailBackend for ConsoleEmailBackend {
+    async fn send_messages(&self, messages: &[EmailMessage]) -> Result<usize, AppError> {
+        for message in messages {
+            println!("{}", message.to_message_string());
+            println!("{}", "-".repeat(79));
+        }
+        Ok(messages.len())
+    }
+}
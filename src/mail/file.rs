@@ -0,0 +1,69 @@
+use std::path::PathBuf;
+use chrono::Utc;
+use rocket::tokio::fs;
+use super::{EmailBackend, EmailMessage};
+use crate::errors::AppError;
+
+/// メールをディレクトリ内のファイルに書き出すバックエンド。
+/// Djangoの `django.core.mail.backends.filebased.EmailBackend` に相当し、
+/// 送信処理ごとに `<日時>-<UUID>.log` を作成します。テストや開発で送信内容を確認するのに使います。
+pub struct FileEmailBackend {
+    file_path: PathBuf,
+}
+
+impl FileEmailBackend {
+    pub fn new(file_path: impl Into<PathBuf>) -> Self {
+        FileEmailBackend {
+            file_path: file_path.into(),
+        }
+    }
+}
+
+#[rocket::async_trait]
+impl EmailBackend for FileEmailBackend {
+    async fn send_messages(&self, messages: &[EmailMessage]) -> Result<usize, AppError> {
+        if messages.is_empty() {
+            return Ok(0);
+        }
+
+        fs::create_dir_all(&self.file_path)
+            .await
+            .map_err(|e| AppError::Internal(format!("Failed to create email directory: {}", e)))?;
+
+        let mut content = String::new();
+        for message in messages {
+            content.push_str(&message.to_message_string());
+            content.push_str(&"-".repeat(79));
+            content.push('\n');
+        }
+
+        let file_name = format!("{}-{}.log", Utc::now().format("%Y%m%d-%H%M%S"), uuid::Uuid::new_v4());
+        fs::write(self.file_path.join(file_name), content)
+            .await
+            .map_err(|e| AppError::Internal(format!("Failed to write email: {}", e)))?;
+
+        Ok(messages.len())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[rocket::async_test]
+    async fn test_writes_messages_to_file() {
+        let dir = std::env::temp_dir().join(format!("mail-test-{}", uuid::Uuid::new_v4()));
+        let backend = FileEmailBackend::new(&dir);
+        let message = EmailMessage::new("件名\nBcc: evil@example.com", "本文", "from@example.com", vec!["to@example.com".into()]);
+
+        assert_eq!(backend.send_messages(&[message]).await.unwrap(), 1);
+
+        let mut entries = std::fs::read_dir(&dir).unwrap();
+        let content = std::fs::read_to_string(entries.next().unwrap().unwrap().path()).unwrap();
+        assert!(content.contains("Subject: 件名 Bcc: evil@example.com\n"));
+        assert!(content.contains("To: to@example.com"));
+        assert!(content.contains("本文"));
+
+        std::fs::remove_dir_all(&dir).unwrap();
+    }
+}
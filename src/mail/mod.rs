@@ -0,0 +1,104 @@
+use chrono::Utc;
+use crate::errors::AppError;
+use crate::settings::Settings;
+
+pub mod console;
+pub mod file;
+pub mod smtp;
+
+pub use console::ConsoleEmailBackend;
+pub use file::FileEmailBackend;
+pub use smtp::{SmtpEmailBackend, SmtpSettings};
+
+/// 送信元アドレスのデフォルト値 (Django: DEFAULT_FROM_EMAIL)
+pub(crate) const DEFAULT_FROM_EMAIL: &str = "webmaster@localhost";
+
+/// 送信するメール1通分のデータ。
+/// Djangoの `django.core.mail.EmailMessage` に相当します (テキストメールのみ)。
+#[derive(Debug, Clone)]
+pub struct EmailMessage {
+    pub subject: String,
+    pub body: String,
+    pub from_email: String,
+    pub to: Vec<String>,
+}
+
+impl EmailMessage {
+    pub fn new(subject: impl Into<String>, body: impl Into<String>, from_email: impl Into<String>, to: Vec<String>) -> Self {
+        EmailMessage {
+            // 件名に改行が含まれるとヘッダーインジェクションになるため除去する
+            subject: subject.into().lines().collect::<Vec<_>>().join(" "),
+            body: body.into(),
+            from_email: from_email.into(),
+            to,
+        }
+    }
+
+    /// コンソール/ファイル出力用のメッセージ文字列を生成します。
+    /// Djangoの `EmailMessage.message().as_bytes()` に相当。
+    pub fn to_message_string(&self) -> String {
+        format!(
+            "Content-Type: text/plain; charset=\"utf-8\"\nSubject: {}\nFrom: {}\nTo: {}\nDate: {}\n\n{}\n",
+            self.subject,
+            self.from_email,
+            self.to.join(", "),
+            Utc::now().to_rfc2822(),
+            self.body,
+        )
+    }
+}
+
+/// メール送信バックエンドを抽象化するトレイト。
+/// Djangoの `EMAIL_BACKEND` (`django.core.mail.backends.*`) に相当します。
+#[rocket::async_trait]
+pub trait EmailBackend: Send + Sync {
+    /// メールを送信し、送信に成功した件数を返す
+    async fn send_messages(&self, messages: &[EmailMessage]) -> Result<usize, AppError>;
+}
+
+/// メール送信の窓口。
+/// Rocketの managed state として登録し、`send_mail` でメールを送信します。
+pub struct Mailer {
+    backend: Box<dyn EmailBackend>,
+    default_from_email: String,
+}
+
+impl Mailer {
+    pub fn new(backend: Box<dyn EmailBackend>, default_from_email: impl Into<String>) -> Self {
+        Mailer {
+            backend,
+            default_from_email: default_from_email.into(),
+        }
+    }
+
+    /// 設定からバックエンドを選択して構築します。
+    ///
+    /// - `EMAIL_BACKEND`: `console` (デフォルト)、`file` または `smtp`
+    /// - `EMAIL_FILE_PATH`: `file` バックエンドの出力先ディレクトリ
+    /// - `EMAIL_HOST` / `EMAIL_PORT` / `EMAIL_HOST_USER` / `EMAIL_HOST_PASSWORD` / `EMAIL_USE_TLS` / `EMAIL_USE_SSL`: `smtp` バックエンドの接続設定
+    /// - `DEFAULT_FROM_EMAIL`: 送信元アドレス
+    pub fn from_settings(settings: &Settings) -> Result<Self, AppError> {
+        let backend: Box<dyn EmailBackend> = match settings.email_backend.as_str() {
+            "file" => {
+                let path = settings.email_file_path.clone()
+                    .ok_or_else(|| AppError::Internal("EMAIL_BACKEND が `file` の場合は EMAIL_FILE_PATH を設定してください".into()))?;
+                Box::new(FileEmailBackend::new(path))
+            }
+            "smtp" => Box::new(SmtpEmailBackend::new(&SmtpSettings::from_settings(settings))?),
+            _ => Box::new(ConsoleEmailBackend),
+        };
+
+        Ok(Mailer::new(backend, settings.default_from_email.clone()))
+    }
+
+    pub fn backend(&self) -> &dyn EmailBackend {
+        self.backend.as_ref()
+    }
+
+    /// 1通のメールを送信します。
+    /// Djangoの `send_mail(subject, message, from_email, recipient_list)` に相当。
+    pub async fn send_mail(&self, subject: &str, body: &str, to: Vec<String>) -> Result<usize, AppError> {
+        let message = EmailMessage::new(subject, body, self.default_from_email.clone(), to);
+        self.backend.send_messages(&[message]).await
+    }
+}
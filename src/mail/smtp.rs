@@ -0,0 +1,96 @@
+use lettre::message::{header::ContentType, Mailbox};
+use lettre::transport::smtp::authentication::Credentials;
+use lettre::{AsyncSmtpTransport, AsyncTransport, Message, Tokio1Executor};
+use super::{EmailBackend, EmailMessage};
+use crate::errors::AppError;
+use crate::settings::Settings;
+
+/// SMTPの接続設定。
+/// Djangoの `EMAIL_HOST` / `EMAIL_PORT` / `EMAIL_HOST_USER` / `EMAIL_HOST_PASSWORD` / `EMAIL_USE_TLS` / `EMAIL_USE_SSL` に相当します。
+#[derive(Debug, Clone)]
+pub struct SmtpSettings {
+    pub host: String,
+    pub port: u16,
+    pub username: Option<String>,
+    pub password: Option<String>,
+    /// STARTTLSを使用する
+    pub use_tls: bool,
+    /// 接続時からTLSを使用する (SMTPS)
+    pub use_ssl: bool,
+}
+
+impl SmtpSettings {
+    pub fn from_settings(settings: &Settings) -> Self {
+        SmtpSettings {
+            host: settings.email_host.clone(),
+            port: settings.email_port,
+            username: settings.email_host_user.clone(),
+            password: settings.email_host_password.clone(),
+            use_tls: settings.email_use_tls,
+            use_ssl: settings.email_use_ssl,
+        }
+    }
+}
+
+/// SMTPサーバー経由でメールを送信するバックエンド。
+/// Djangoの `django.core.mail.backends.smtp.EmailBackend` に相当します。
+pub struct SmtpEmailBackend {
+    transport: AsyncSmtpTransport<Tokio1Executor>,
+}
+
+impl SmtpEmailBackend {
+    pub fn new(settings: &SmtpSettings) -> Result<Self, AppError> {
+        let builder = if settings.use_ssl {
+            AsyncSmtpTransport::<Tokio1Executor>::relay(&settings.host)
+        } else if settings.use_tls {
+            AsyncSmtpTransport::<Tokio1Executor>::starttls_relay(&settings.host)
+        } else {
+            Ok(AsyncSmtpTransport::<Tokio1Executor>::builder_dangerous(&settings.host))
+        }
+        .map_err(|e| AppError::Internal(format!("Invalid SMTP settings: {}", e)))?;
+
+        let mut builder = builder.port(settings.port);
+        if let (Some(username), Some(password)) = (&settings.username, &settings.password) {
+            builder = builder.credentials(Credentials::new(username.clone(), password.clone()));
+        }
+
+        Ok(SmtpEmailBackend {
+            transport: builder.build(),
+        })
+    }
+
+    fn build_message(message: &EmailMessage) -> Result<Message, AppError> {
+        let parse = |addr: &str| {
+            addr.parse::<Mailbox>()
+                .map_err(|e| AppError::BadRequest(format!("Invalid email address '{}': {}", addr, e)))
+        };
+
+        let mut builder = Message::builder()
+            .from(parse(&message.from_email)?)
+            .subject(message.subject.clone())
+            .header(ContentType::TEXT_PLAIN);
+        for to in &message.to {
+            builder = builder.to(parse(to)?);
+        }
+
+        builder
+            .body(message.body.clone())
+            .map_err(|e| AppError::Internal(format!("Failed to build email: {}", e)))
+    }
+}
+
+#[rocket::async_trait]
+impl EmailBackend for SmtpEmailBackend {
+    async fn send_messages(&self, messages: &[EmailMessage]) -> Result<usize, AppError> {
+        let mut sent = 0;
+        for message in messages {
+            let email = Self::build_message(message)?;
+            self.transport
+                .send(email)
+                .await
+                .map_err(|e| AppError::Internal(format!("Failed to send email: {}", e)))?;
+            sent += 1;
+        }
+        Ok(sent)
+    }
+}
@@ -0,0 +1,64 @@
+//! 管理コマンド (`src/bin/manage.rs`) の処理本体。
+//! Djangoの `django.contrib.auth.management.commands` に相当します。
+//! 入力の受け付け (プロンプト) はバイナリ側で行い、ここではDB操作のみを扱います。
+
+use sea_orm::DatabaseConnection;
+use crate::entities::user;
+use crate::errors::AppError;
+use crate::password_validation::PasswordValidators;
+use crate::services::user_service::UserService;
+use crate::sessions::SessionManager;
+use crate::settings::Settings;
+use crate::validation::UserFormValidation;
+
+/// スーパーユーザーを作成します。
+/// Djangoの `manage.py createsuperuser` に相当。
+pub async fn create_superuser(
+    db: &DatabaseConnection,
+    settings: &Settings,
+    username: &str,
+    email: &str,
+    password: &str,
+) -> Result<user::Model, AppError> {
+    UserFormValidation::new(username, Some(password))
+        .with_email(email)
+        .validate_fields(&password_validators(settings)?)
+        .map_err(|errors| AppError::BadRequest(errors.to_string()))?;
+
+    if UserService::find_by_username(db, username).await?.is_some() {
+        return Err(AppError::BadRequest("このユーザー名は既に使用されています".into()));
+    }
+
+    UserService::create(db, username, email, password, true).await
+}
+
+/// 指定したユーザーのパスワードを変更し、既存のセッションを無効化します。
+/// Djangoの `manage.py changepassword <username>` に相当。
+pub async fn change_password(
+    db: &DatabaseConnection,
+    settings: &Settings,
+    username: &str,
+    password: &str,
+) -> Result<user::Model, AppError> {
+    let user = UserService::find_by_username(db, username)
+        .await?
+        .ok_or_else(|| AppError::BadRequest(format!("ユーザー '{}' は存在しません", username)))?;
+
+    UserFormValidation::new(username, Some(password))
+        .with_email(&user.email)
+        .validate_fields(&password_validators(settings)?)
+        .map_err(|errors| AppError::BadRequest(errors.to_string()))?;
+
+    let user = UserService::set_password(db, user, password).await?;
+
+    SessionManager::from_settings(settings, db.clone())
+        .invalidate_user(user.id, None)
+        .await?;
+
+    Ok(user)
+}
+
+/// 設定 (`AUTH_PASSWORD_VALIDATORS` 等) に従ってパスワードバリデーターを構築します。
+fn password_validators(settings: &Settings) -> Result<PasswordValidators, AppError> {
+    PasswordValidators::from_settings(settings).map_err(AppError::Internal)
+}
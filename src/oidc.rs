@@ -0,0 +1,426 @@
+//! OpenID Connect (認可コードフロー + PKCE) のクライアント。
+//! mozilla-django-oidc / django-allauth の `openid_connect` プロバイダーに相当します。
+//!
+//! - ディスカバリー: `{issuer}/.well-known/openid-configuration` からエンドポイントを取得します
+//! - PKCE: RFC 7636 の `S256` で認可コードの横取りを防ぎます
+//! - IDトークン: IdPの JWKS で署名を検証し、`iss` / `aud` / `exp` / `nonce` を確認します
+
+use base64::{Engine as _, engine::general_purpose::URL_SAFE_NO_PAD};
+use jsonwebtoken::jwk::JwkSet;
+use jsonwebtoken::{Algorithm, DecodingKey, Validation};
+use rand::Rng;
+use rocket::serde::json::serde_json::Value;
+use serde::{Deserialize, Serialize};
+use sha2::{Digest, Sha256};
+use std::collections::HashMap;
+use std::time::{Duration, Instant};
+use tokio::sync::RwLock;
+use crate::errors::AppError;
+use crate::settings::Settings;
+
+/// ディスカバリー情報・JWKS をキャッシュする時間
+const METADATA_CACHE_TTL: Duration = Duration::from_secs(60 * 60);
+
+/// IdPへのリクエストのタイムアウト
+const HTTP_TIMEOUT: Duration = Duration::from_secs(10);
+
+/// IDトークンの署名に使用できるアルゴリズム (共通鍵方式の HS* は受け付けない)
+const ALLOWED_ALGORITHMS: &[Algorithm] = &[
+    Algorithm::RS256,
+    Algorithm::RS384,
+    Algorithm::RS512,
+    Algorithm::PS256,
+    Algorithm::PS384,
+    Algorithm::PS512,
+    Algorithm::ES256,
+    Algorithm::ES384,
+    Algorithm::EdDSA,
+];
+
+fn truthy(value: &str) -> bool {
+    matches!(value.to_lowercase().as_str(), "1" | "true" | "yes")
+}
+
+/// 1つのIdPの設定
+#[derive(Debug, Clone)]
+pub struct OidcProviderConfig {
+    /// URLに使う名前 (`/auth/oidc/<name>`)
+    pub name: String,
+    /// ログイン画面のボタンに表示する名前
+    pub display_name: String,
+    /// IdPの Issuer URL (ディスカバリーの起点)
+    pub issuer: String,
+    pub client_id: String,
+    /// クライアントシークレット (パブリッククライアントの場合は `None`)
+    pub client_secret: Option<String>,
+    /// 要求するスコープ
+    pub scopes: Vec<String>,
+    /// コールバックURL。未設定の場合はリクエストのホストから組み立てます
+    pub redirect_uri: Option<String>,
+    /// 未登録のユーザーを自動で作成するか
+    pub auto_create: bool,
+    /// 所属グループを表すクレーム名 (未設定の場合はグループを同期しない)
+    pub groups_claim: Option<String>,
+    /// IdPが所属を管理するグループ名 (`prefix*` で前方一致)。
+    /// 該当するグループはクレームに含まれなければ外されます。未設定の場合はクレームのグループへの追加のみ行います
+    pub managed_groups: Vec<String>,
+}
+
+impl OidcProviderConfig {
+    /// `OIDC_<NAME>_*` 環境変数から1つのIdPの設定を読み込みます。
+    fn from_env(name: &str) -> Result<Self, AppError> {
+        let prefix = format!("OIDC_{}_", name.to_uppercase());
+        let var = |key: &str| std::env::var(format!("{}{}", prefix, key)).ok().filter(|v| !v.is_empty());
+        let required = |key: &str| {
+            var(key).ok_or_else(|| AppError::Internal(format!("{}{} が設定されていません", prefix, key)))
+        };
+
+        Ok(OidcProviderConfig {
+            name: name.to_string(),
+            display_name: var("DISPLAY_NAME").unwrap_or_else(|| name.to_string()),
+            issuer: required("ISSUER")?.trim_end_matches('/').to_string(),
+            client_id: required("CLIENT_ID")?,
+            client_secret: var("CLIENT_SECRET"),
+            scopes: var("SCOPES")
+                .unwrap_or_else(|| "openid email profile".to_string())
+                .split_whitespace()
+                .map(str::to_string)
+                .collect(),
+            redirect_uri: var("REDIRECT_URI"),
+            auto_create: var("AUTO_CREATE").map(|v| truthy(&v)).unwrap_or(true),
+            groups_claim: var("GROUPS_CLAIM"),
+            managed_groups: var("MANAGED_GROUPS")
+                .map(|v| v.split(',').map(str::trim).filter(|g| !g.is_empty()).map(str::to_string).collect())
+                .unwrap_or_default(),
+        })
+    }
+
+    /// グループの所属をIdPが管理するかどうか
+    pub fn manages_group(&self, name: &str) -> bool {
+        self.managed_groups.iter().any(|pattern| match pattern.strip_suffix('*') {
+            Some(prefix) => name.starts_with(prefix),
+            None => name == pattern,
+        })
+    }
+}
+
+/// ディスカバリーで取得するIdPのメタデータ (必要な項目のみ)
+#[derive(Debug, Clone, Deserialize)]
+pub struct ProviderMetadata {
+    pub issuer: String,
+    pub authorization_endpoint: String,
+    pub token_endpoint: String,
+    pub jwks_uri: String,
+}
+
+/// トークンエンドポイントのレスポンス
+#[derive(Debug, Deserialize)]
+struct TokenResponse {
+    id_token: String,
+}
+
+/// IDトークンのクレーム
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct IdTokenClaims {
+    pub sub: String,
+    pub nonce: Option<String>,
+    pub email: Option<String>,
+    #[serde(default)]
+    pub email_verified: bool,
+    pub preferred_username: Option<String>,
+    pub name: Option<String>,
+    /// 上記以外のクレーム (グループなど)
+    #[serde(flatten)]
+    pub extra: HashMap<String, Value>,
+}
+
+impl IdTokenClaims {
+    /// 指定したクレームを文字列のリストとして取得します (文字列1つの場合も受け付ける)
+    pub fn string_list(&self, claim: &str) -> Vec<String> {
+        match self.extra.get(claim) {
+            Some(Value::Array(values)) => values.iter().filter_map(|v| v.as_str().map(str::to_string)).collect(),
+            Some(Value::String(value)) => vec![value.clone()],
+            _ => Vec::new(),
+        }
+    }
+}
+
+/// 認可リクエストごとに生成する値。コールバックまでCookieに保持します。
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct AuthorizationRequest {
+    pub provider: String,
+    /// CSRF対策の `state`
+    pub state: String,
+    /// リプレイ対策の `nonce` (IDトークンに含まれる)
+    pub nonce: String,
+    /// PKCE の `code_verifier`
+    pub code_verifier: String,
+    /// ログイン後のリダイレクト先
+    pub next: Option<String>,
+}
+
+fn random_string() -> String {
+    let bytes: [u8; 32] = rand::thread_rng().gen();
+    URL_SAFE_NO_PAD.encode(bytes)
+}
+
+/// PKCE の `code_challenge` (S256)
+pub fn code_challenge(code_verifier: &str) -> String {
+    URL_SAFE_NO_PAD.encode(Sha256::digest(code_verifier.as_bytes()))
+}
+
+impl AuthorizationRequest {
+    pub fn new(provider: &str, next: Option<String>) -> Self {
+        AuthorizationRequest {
+            provider: provider.to_string(),
+            state: random_string(),
+            nonce: random_string(),
+            code_verifier: random_string(),
+            next,
+        }
+    }
+}
+
+/// IdPのクライアント。ディスカバリー情報と JWKS をキャッシュします。
+pub struct OidcProvider {
+    pub config: OidcProviderConfig,
+    http: reqwest::Client,
+    metadata: RwLock<Option<(Instant, ProviderMetadata)>>,
+    jwks: RwLock<Option<(Instant, JwkSet)>>,
+}
+
+impl OidcProvider {
+    pub fn new(config: OidcProviderConfig, http: reqwest::Client) -> Self {
+        OidcProvider {
+            config,
+            http,
+            metadata: RwLock::new(None),
+            jwks: RwLock::new(None),
+        }
+    }
+
+    async fn get_json<T: serde::de::DeserializeOwned>(&self, url: &str) -> Result<T, AppError> {
+        let response = self
+            .http
+            .get(url)
+            .send()
+            .await
+            .and_then(|r| r.error_for_status())
+            .map_err(|e| AppError::Internal(format!("IdPへのリクエストに失敗しました: {}", e)))?;
+        response
+            .json()
+            .await
+            .map_err(|e| AppError::Internal(format!("IdPのレスポンスが不正です: {}", e)))
+    }
+
+    /// ディスカバリー情報を取得します (キャッシュ付き)
+    pub async fn metadata(&self) -> Result<ProviderMetadata, AppError> {
+        if let Some((fetched_at, metadata)) = self.metadata.read().await.as_ref() {
+            if fetched_at.elapsed() < METADATA_CACHE_TTL {
+                return Ok(metadata.clone());
+            }
+        }
+
+        let url = format!("{}/.well-known/openid-configuration", self.config.issuer);
+        let metadata: ProviderMetadata = self.get_json(&url).await?;
+        // OpenID Connect Discovery 1.0 §4.3: issuer は設定値と一致しなければならない
+        if metadata.issuer.trim_end_matches('/') != self.config.issuer {
+            return Err(AppError::Internal(format!("issuer が一致しません: {}", metadata.issuer)));
+        }
+        *self.metadata.write().await = Some((Instant::now(), metadata.clone()));
+        Ok(metadata)
+    }
+
+    /// 署名検証用の鍵を取得します。`kid` が見つからない場合は鍵の更新とみなして取得し直します。
+    async fn decoding_key(&self, kid: Option<&str>) -> Result<DecodingKey, AppError> {
+        let find = |jwks: &JwkSet| match kid {
+            Some(kid) => jwks.find(kid).cloned(),
+            None if jwks.keys.len() == 1 => jwks.keys.first().cloned(),
+            None => None,
+        };
+
+        let cached = match self.jwks.read().await.as_ref() {
+            Some((fetched_at, jwks)) if fetched_at.elapsed() < METADATA_CACHE_TTL => find(jwks),
+            _ => None,
+        };
+        let jwk = match cached {
+            Some(jwk) => jwk,
+            None => {
+                let metadata = self.metadata().await?;
+                let jwks: JwkSet = self.get_json(&metadata.jwks_uri).await?;
+                let jwk = find(&jwks);
+                *self.jwks.write().await = Some((Instant::now(), jwks));
+                jwk.ok_or_else(|| AppError::Internal("IDトークンの署名鍵が見つかりません".into()))?
+            }
+        };
+        DecodingKey::from_jwk(&jwk).map_err(|e| AppError::Internal(format!("署名鍵が不正です: {}", e)))
+    }
+
+    /// IdPの認可エンドポイントへのURLを組み立てます
+    pub async fn authorization_url(&self, request: &AuthorizationRequest, redirect_uri: &str) -> Result<String, AppError> {
+        let metadata = self.metadata().await?;
+        let mut url = reqwest::Url::parse(&metadata.authorization_endpoint)
+            .map_err(|e| AppError::Internal(format!("authorization_endpoint が不正です: {}", e)))?;
+        url.query_pairs_mut()
+            .append_pair("response_type", "code")
+            .append_pair("client_id", &self.config.client_id)
+            .append_pair("redirect_uri", redirect_uri)
+            .append_pair("scope", &self.config.scopes.join(" "))
+            .append_pair("state", &request.state)
+            .append_pair("nonce", &request.nonce)
+            .append_pair("code_challenge", &code_challenge(&request.code_verifier))
+            .append_pair("code_challenge_method", "S256");
+        Ok(url.into())
+    }
+
+    /// 認可コードをトークンと交換し、検証済みのIDトークンのクレームを返します
+    pub async fn exchange_code(
+        &self,
+        code: &str,
+        request: &AuthorizationRequest,
+        redirect_uri: &str,
+    ) -> Result<IdTokenClaims, AppError> {
+        let metadata = self.metadata().await?;
+        let mut params = vec![
+            ("grant_type", "authorization_code"),
+            ("code", code),
+            ("redirect_uri", redirect_uri),
+            ("client_id", self.config.client_id.as_str()),
+            ("code_verifier", request.code_verifier.as_str()),
+        ];
+        if let Some(secret) = &self.config.client_secret {
+            params.push(("client_secret", secret));
+        }
+
+        let response = self
+            .http
+            .post(&metadata.token_endpoint)
+            .form(&params)
+            .send()
+            .await
+            .and_then(|r| r.error_for_status())
+            .map_err(|e| AppError::Internal(format!("トークンの取得に失敗しました: {}", e)))?;
+        let tokens: TokenResponse = response
+            .json()
+            .await
+            .map_err(|e| AppError::Internal(format!("トークンレスポンスが不正です: {}", e)))?;
+
+        self.verify_id_token(&tokens.id_token, &metadata.issuer, &request.nonce).await
+    }
+
+    /// IDトークンの署名とクレームを検証します
+    pub async fn verify_id_token(&self, id_token: &str, issuer: &str, nonce: &str) -> Result<IdTokenClaims, AppError> {
+        let header = jsonwebtoken::decode_header(id_token).map_err(|_| AppError::Unauthorized)?;
+        if !ALLOWED_ALGORITHMS.contains(&header.alg) {
+            return Err(AppError::Unauthorized);
+        }
+        let key = self.decoding_key(header.kid.as_deref()).await?;
+
+        let mut validation = Validation::new(header.alg);
+        validation.set_issuer(&[issuer]);
+        validation.set_audience(&[&self.config.client_id]);
+        validation.set_required_spec_claims(&["exp", "iss", "aud", "sub"]);
+
+        let claims = jsonwebtoken::decode::<IdTokenClaims>(id_token, &key, &validation)
+            .map_err(|_| AppError::Unauthorized)?
+            .claims;
+        if claims.nonce.as_deref() != Some(nonce) {
+            return Err(AppError::Unauthorized);
+        }
+        Ok(claims)
+    }
+}
+
+/// 有効なIdPの一覧。Rocketの managed state として登録します。
+#[derive(Default)]
+pub struct OidcProviders {
+    providers: Vec<OidcProvider>,
+}
+
+impl OidcProviders {
+    /// 設定と環境変数から読み込みます。
+    ///
+    /// - `OIDC_PROVIDERS`: 有効にするIdPの名前 (カンマ区切り。未設定の場合はOIDCログインを無効)
+    /// - `OIDC_<NAME>_ISSUER` / `OIDC_<NAME>_CLIENT_ID`: 必須
+    /// - `OIDC_<NAME>_CLIENT_SECRET` / `_DISPLAY_NAME` / `_SCOPES` / `_REDIRECT_URI` / `_AUTO_CREATE` / `_GROUPS_CLAIM`: 任意
+    pub fn from_settings(settings: &Settings) -> Result<Self, AppError> {
+        let configs = settings
+            .oidc_providers
+            .iter()
+            .map(|name| OidcProviderConfig::from_env(name))
+            .collect::<Result<Vec<_>, _>>()?;
+        Self::new(configs)
+    }
+
+    pub fn new(configs: Vec<OidcProviderConfig>) -> Result<Self, AppError> {
+        let http = reqwest::Client::builder()
+            .timeout(HTTP_TIMEOUT)
+            .redirect(reqwest::redirect::Policy::none())
+            .build()
+            .map_err(|e| AppError::Internal(e.to_string()))?;
+        Ok(OidcProviders {
+            providers: configs.into_iter().map(|config| OidcProvider::new(config, http.clone())).collect(),
+        })
+    }
+
+    pub fn get(&self, name: &str) -> Option<&OidcProvider> {
+        self.providers.iter().find(|p| p.config.name == name)
+    }
+
+    pub fn iter(&self) -> impl Iterator<Item = &OidcProvider> {
+        self.providers.iter()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.providers.is_empty()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_code_challenge_rfc7636_vector() {
+        // RFC 7636 Appendix B
+        assert_eq!(
+            code_challenge("dBjftJeZ4CVP-mB92K27uhbUJU1p1r_wW1gFWFOEjXk"),
+            "E9Melhoa2OwvFrEMTJguCHaoeK1t8URWbuGJSstw-cM"
+        );
+    }
+
+    #[test]
+    fn test_groups_claim_accepts_list_or_string() {
+        use rocket::serde::json::serde_json;
+
+        let claims: IdTokenClaims = serde_json::from_value(serde_json::json!({
+            "sub": "1",
+            "groups": ["staff", "dev"],
+            "role": "admin",
+        }))
+        .unwrap();
+        assert_eq!(claims.string_list("groups"), vec!["staff", "dev"]);
+        assert_eq!(claims.string_list("role"), vec!["admin"]);
+        assert!(claims.string_list("missing").is_empty());
+    }
+
+    #[test]
+    fn test_managed_groups_match_name_or_prefix() {
+        let config = OidcProviderConfig {
+            name: "company".to_string(),
+            display_name: "company".to_string(),
+            issuer: "https://idp.example.com".to_string(),
+            client_id: "starter".to_string(),
+            client_secret: None,
+            scopes: Vec::new(),
+            redirect_uri: None,
+            auto_create: true,
+            groups_claim: Some("groups".to_string()),
+            managed_groups: vec!["staff".to_string(), "idp-*".to_string()],
+        };
+        assert!(config.manages_group("staff"));
+        assert!(config.manages_group("idp-dev"));
+        assert!(!config.manages_group("staff-local"));
+        assert!(!config.manages_group("local"));
+    }
+}
@@ -0,0 +1,331 @@
+//! 2要素認証 (TOTP) のアルゴリズムと設定。
+//! django-otp の `django_otp.oath` / `django_otp.plugins.otp_totp` に相当します。
+//!
+//! - TOTP: RFC 6238 (HMAC-SHA1, 30秒, 6桁)。認証アプリ (Google Authenticator 等) と互換性があります
+//! - リカバリーコード: 端末を紛失した場合に1回だけ使えるコード (django-otp の `StaticToken`)
+
+use hmac::{Hmac, Mac};
+use rand::Rng;
+use sha1::Sha1;
+use sha2::{Digest, Sha256};
+use crate::auth_utils::constant_time_eq;
+use crate::entities::user;
+use crate::settings::Settings;
+
+type HmacSha1 = Hmac<Sha1>;
+
+/// TOTPの時間ステップ (秒)
+pub const TOTP_STEP: u64 = 30;
+
+/// TOTPの桁数
+pub const TOTP_DIGITS: u32 = 6;
+
+/// 時計のずれを許容するステップ数 (前後1ステップ = ±30秒)
+const TOTP_TOLERANCE: u64 = 1;
+
+/// 生成するシークレットのバイト数 (RFC 4226 推奨の160ビット)
+const SECRET_LENGTH: usize = 20;
+
+/// 一度に発行するリカバリーコードの数
+pub const RECOVERY_CODE_COUNT: usize = 10;
+
+const BASE32_ALPHABET: &[u8; 32] = b"ABCDEFGHIJKLMNOPQRSTUVWXYZ234567";
+
+/// リカバリーコードに使う文字 (読み間違えやすい 0/O, 1/I/L を除く)
+const RECOVERY_CODE_ALPHABET: &[u8] = b"23456789ABCDEFGHJKMNPQRSTUVWXYZ";
+
+/// 2要素認証の設定。Rocketの managed state として登録します。
+#[derive(Debug, Clone)]
+pub struct TwoFactorConfig {
+    /// 管理者 (`is_admin`) に2要素認証を必須とするか
+    pub required_for_admins: bool,
+    /// 認証アプリに表示される発行者名
+    pub issuer: String,
+}
+
+impl Default for TwoFactorConfig {
+    fn default() -> Self {
+        TwoFactorConfig {
+            required_for_admins: false,
+            issuer: "Rust Django Starter".to_string(),
+        }
+    }
+}
+
+impl TwoFactorConfig {
+    /// 設定から読み込みます。
+    ///
+    /// - `OTP_REQUIRED_FOR_ADMINS`: `true` の場合、管理者は2要素認証を設定するまでログインを完了できません
+    /// - `OTP_ISSUER`: 認証アプリに表示される発行者名
+    pub fn from_settings(settings: &Settings) -> Self {
+        TwoFactorConfig {
+            required_for_admins: settings.otp_required_for_admins,
+            issuer: settings.otp_issuer.clone(),
+        }
+    }
+
+    /// ユーザーに2要素認証が必須かどうか
+    pub fn is_required_for(&self, user: &user::Model) -> bool {
+        self.required_for_admins && user.is_admin
+    }
+
+    /// 2要素認証が必須なのに未設定の (設定を完了するまでログインさせない) ユーザーかどうか
+    pub fn needs_setup(&self, user: &user::Model) -> bool {
+        self.is_required_for(user) && user.totp_secret.is_none()
+    }
+}
+
+/// TOTPの生成・検証を行います。
+pub struct Totp {
+    secret: Vec<u8>,
+}
+
+impl Totp {
+    pub fn new(secret: &[u8]) -> Self {
+        Totp { secret: secret.to_vec() }
+    }
+
+    /// Base32でエンコードされたシークレットから構築します (不正な文字列の場合は `None`)。
+    pub fn from_base32(secret: &str) -> Option<Self> {
+        base32_decode(secret).filter(|s| !s.is_empty()).map(|s| Totp::new(&s))
+    }
+
+    /// 新しいシークレットをランダムに生成し、Base32で返します。
+    pub fn generate_secret() -> String {
+        let bytes: [u8; SECRET_LENGTH] = rand::thread_rng().gen();
+        base32_encode(&bytes)
+    }
+
+    /// UNIX時刻に対応するカウンター値
+    pub fn counter_at(unix_time: u64) -> u64 {
+        unix_time / TOTP_STEP
+    }
+
+    /// カウンター値に対するコードを生成します (RFC 4226 HOTP)。
+    pub fn code_at(&self, counter: u64) -> String {
+        let mut mac = HmacSha1::new_from_slice(&self.secret).expect("HMAC can take key of any size");
+        mac.update(&counter.to_be_bytes());
+        let digest = mac.finalize().into_bytes();
+
+        // 動的切り捨て (Dynamic Truncation)
+        let offset = (digest[digest.len() - 1] & 0x0f) as usize;
+        let binary = u32::from_be_bytes([
+            digest[offset] & 0x7f,
+            digest[offset + 1],
+            digest[offset + 2],
+            digest[offset + 3],
+        ]);
+        format!("{:0width$}", binary % 10u32.pow(TOTP_DIGITS), width = TOTP_DIGITS as usize)
+    }
+
+    /// 現在時刻のコードを生成します。
+    pub fn current_code(&self) -> String {
+        self.code_at(Self::counter_at(unix_now()))
+    }
+
+    /// コードを検証し、一致したカウンター値を返します。
+    ///
+    /// 前後 `TOTP_TOLERANCE` ステップのずれを許容します。
+    /// `last_counter` 以前のカウンターは使用済みとして拒否します (リプレイ攻撃対策)。
+    pub fn verify(&self, code: &str, unix_time: u64, last_counter: Option<i64>) -> Option<i64> {
+        let code: String = code.chars().filter(|c| !c.is_whitespace()).collect();
+        if code.len() != TOTP_DIGITS as usize || !code.chars().all(|c| c.is_ascii_digit()) {
+            return None;
+        }
+
+        let current = Self::counter_at(unix_time);
+        let start = current.saturating_sub(TOTP_TOLERANCE);
+        (start..=current + TOTP_TOLERANCE)
+            .filter(|counter| last_counter.is_none_or(|last| *counter as i64 > last))
+            .find(|counter| constant_time_eq(self.code_at(*counter).as_bytes(), code.as_bytes()))
+            .map(|counter| counter as i64)
+    }
+
+    /// 認証アプリに登録するための `otpauth://` URIを生成します。
+    /// (Google Authenticator の Key Uri Format)
+    pub fn provisioning_uri(secret: &str, issuer: &str, account: &str) -> String {
+        format!(
+            "otpauth://totp/{}:{}?secret={}&issuer={}&algorithm=SHA1&digits={}&period={}",
+            percent_encode(issuer),
+            percent_encode(account),
+            secret,
+            percent_encode(issuer),
+            TOTP_DIGITS,
+            TOTP_STEP,
+        )
+    }
+}
+
+/// リカバリーコードを生成します (`XXXXX-XXXXX` 形式)。
+pub fn generate_recovery_codes() -> Vec<String> {
+    let mut rng = rand::thread_rng();
+    (0..RECOVERY_CODE_COUNT)
+        .map(|_| {
+            let chars: String = (0..10)
+                .map(|_| RECOVERY_CODE_ALPHABET[rng.gen_range(0..RECOVERY_CODE_ALPHABET.len())] as char)
+                .collect();
+            format!("{}-{}", &chars[..5], &chars[5..])
+        })
+        .collect()
+}
+
+/// リカバリーコードを保存用のハッシュに変換します。
+/// 入力の揺れ (ハイフン・空白・小文字) を吸収してからハッシュ化します。
+pub fn hash_recovery_code(code: &str) -> String {
+    let normalized: String = code
+        .chars()
+        .filter(|c| c.is_ascii_alphanumeric())
+        .map(|c| c.to_ascii_uppercase())
+        .collect();
+    hex_encode(&Sha256::digest(normalized.as_bytes()))
+}
+
+/// 現在のUNIX時刻 (秒)
+pub fn unix_now() -> u64 {
+    chrono::Utc::now().timestamp().max(0) as u64
+}
+
+fn hex_encode(bytes: &[u8]) -> String {
+    bytes.iter().map(|b| format!("{:02x}", b)).collect()
+}
+
+/// RFC 3986 の非予約文字以外をパーセントエンコードします。
+fn percent_encode(value: &str) -> String {
+    value
+        .bytes()
+        .map(|b| match b {
+            b'A'..=b'Z' | b'a'..=b'z' | b'0'..=b'9' | b'-' | b'.' | b'_' | b'~' => (b as char).to_string(),
+            _ => format!("%{:02X}", b),
+        })
+        .collect()
+}
+
+/// Base32 (RFC 4648, パディングなし) エンコード
+pub fn base32_encode(data: &[u8]) -> String {
+    let mut output = String::with_capacity(data.len().div_ceil(5) * 8);
+    let mut buffer: u32 = 0;
+    let mut bits = 0;
+
+    for &byte in data {
+        buffer = (buffer << 8) | byte as u32;
+        bits += 8;
+        while bits >= 5 {
+            bits -= 5;
+            output.push(BASE32_ALPHABET[((buffer >> bits) & 0x1f) as usize] as char);
+        }
+    }
+    if bits > 0 {
+        output.push(BASE32_ALPHABET[((buffer << (5 - bits)) & 0x1f) as usize] as char);
+    }
+    output
+}
+
+/// Base32 デコード (大文字小文字・空白・パディングを許容)
+pub fn base32_decode(input: &str) -> Option<Vec<u8>> {
+    let mut output = Vec::with_capacity(input.len() * 5 / 8);
+    let mut buffer: u32 = 0;
+    let mut bits = 0;
+
+    for c in input.chars().filter(|c| !c.is_whitespace() && *c != '=') {
+        let value = BASE32_ALPHABET.iter().position(|&a| a as char == c.to_ascii_uppercase())? as u32;
+        buffer = (buffer << 5) | value;
+        bits += 5;
+        if bits >= 8 {
+            bits -= 8;
+            output.push((buffer >> bits) as u8);
+        }
+    }
+    Some(output)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// RFC 6238 Appendix B のテストベクター (SHA1, 下6桁)
+    #[test]
+    fn test_rfc6238_vectors() {
+        let totp = Totp::new(b"12345678901234567890");
+        let cases = [
+            (59, "287082"),
+            (1111111109, "081804"),
+            (1111111111, "050471"),
+            (1234567890, "005924"),
+            (2000000000, "279037"),
+        ];
+        for (time, expected) in cases {
+            assert_eq!(totp.code_at(Totp::counter_at(time)), expected, "time={}", time);
+        }
+    }
+
+    #[test]
+    fn test_base32_roundtrip() {
+        assert_eq!(base32_encode(b"12345678901234567890"), "GEZDGNBVGY3TQOJQGEZDGNBVGY3TQOJQ");
+        assert_eq!(base32_decode("gezd gnbv gy3t qojq gezd gnbv gy3t qojq").unwrap(), b"12345678901234567890");
+        assert!(base32_decode("not-base32!").is_none());
+
+        let secret = Totp::generate_secret();
+        assert_eq!(base32_decode(&secret).unwrap().len(), SECRET_LENGTH);
+    }
+
+    #[test]
+    fn test_verify_window_and_replay() {
+        let totp = Totp::new(b"12345678901234567890");
+        let now = 1111111111;
+        let counter = Totp::counter_at(now) as i64;
+
+        // 前後1ステップは許容する
+        let previous = totp.code_at(counter as u64 - 1);
+        assert_eq!(totp.verify(&previous, now, None), Some(counter - 1));
+        let far = totp.code_at(counter as u64 - 3);
+        assert_eq!(totp.verify(&far, now, None), None);
+
+        // 使用済みのカウンターは拒否する
+        let code = totp.code_at(counter as u64);
+        assert_eq!(totp.verify(&code, now, Some(counter)), None);
+        assert_eq!(totp.verify(&code, now, Some(counter - 1)), Some(counter));
+
+        assert_eq!(totp.verify("abcdef", now, None), None);
+    }
+
+    #[test]
+    fn test_recovery_code_hash_is_normalized() {
+        let codes = generate_recovery_codes();
+        assert_eq!(codes.len(), RECOVERY_CODE_COUNT);
+        let code = &codes[0];
+        assert_eq!(hash_recovery_code(code), hash_recovery_code(&code.replace('-', " ").to_lowercase()));
+        assert_ne!(hash_recovery_code(code), hash_recovery_code(&codes[1]));
+    }
+
+    #[test]
+    fn test_provisioning_uri() {
+        let uri = Totp::provisioning_uri("ABC", "My App", "alice@example.com");
+        assert_eq!(
+            uri,
+            "otpauth://totp/My%20App:alice%40example.com?secret=ABC&issuer=My%20App&algorithm=SHA1&digits=6&period=30"
+        );
+    }
+
+    #[test]
+    fn test_config_requires_setup_for_admins() {
+        let config = TwoFactorConfig { required_for_admins: true, ..Default::default() };
+        let mut user = user::Model {
+            id: 1,
+            username: "root".into(),
+            password_hash: String::new(),
+            email: String::new(),
+            is_active: true,
+            is_admin: true,
+            totp_secret: None,
+            totp_last_counter: None,
+            activated_at: None,
+        };
+        assert!(config.needs_setup(&user));
+        user.totp_secret = Some("ABC".into());
+        assert!(!config.needs_setup(&user));
+        user.is_admin = false;
+        user.totp_secret = None;
+        assert!(!config.needs_setup(&user));
+        assert!(!TwoFactorConfig::default().needs_setup(&user));
+    }
+}
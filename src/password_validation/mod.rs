@@ -0,0 +1,437 @@
+//! パスワードの強度チェック。
+//! Djangoの `django.contrib.auth.password_validation` と `AUTH_PASSWORD_VALIDATORS` に相当します。
+//!
+//! `PasswordValidator` を実装した検証ルールを `PasswordValidators` に並べて使います。
+//! 標準のルールは設定 (`Settings::auth_password_validators`) で選択でき、独自のルールはトレイトを実装して `with` で追加します。
+//!
+//! ```ignore
+//! struct NoSpaces;
+//!
+//! impl PasswordValidator for NoSpaces {
+//!     fn validate(&self, password: &str, _user: Option<&UserAttributes>) -> Result<(), String> {
+//!         if password.contains(' ') { Err("空白は使用できません".into()) } else { Ok(()) }
+//!     }
+//!     fn help_text(&self) -> String {
+//!         "空白を含むパスワードにはできません。".into()
+//!     }
+//! }
+//!
+//! let validators = PasswordValidators::from_settings(&settings)?.with(NoSpaces);
+//! ```
+
+use std::collections::HashSet;
+use std::path::Path;
+use lazy_static::lazy_static;
+use crate::settings::Settings;
+
+/// 同梱のよく使われるパスワード一覧 (1行に1つ、小文字で比較)
+const COMMON_PASSWORDS: &str = include_str!("common-passwords.txt");
+
+lazy_static! {
+    static ref DEFAULT_COMMON_PASSWORDS: HashSet<String> = parse_password_list(COMMON_PASSWORDS);
+}
+
+/// 類似度チェックに使うユーザーの属性。
+/// 新規作成時など、保存前のユーザーも扱えるよう `user::Model` ではなく値で受け取ります。
+#[derive(Debug, Clone, Copy, Default)]
+pub struct UserAttributes<'a> {
+    pub username: &'a str,
+    pub email: &'a str,
+}
+
+impl<'a> UserAttributes<'a> {
+    pub fn new(username: &'a str, email: &'a str) -> Self {
+        UserAttributes { username, email }
+    }
+}
+
+/// パスワードの検証ルール。
+/// Djangoの `AUTH_PASSWORD_VALIDATORS` に登録するクラス (`validate()` / `get_help_text()`) に相当します。
+pub trait PasswordValidator: Send + Sync {
+    /// パスワードを検証し、不適切な場合はエラーメッセージを返す
+    fn validate(&self, password: &str, user: Option<&UserAttributes>) -> Result<(), String>;
+
+    /// フォームに表示する説明文
+    fn help_text(&self) -> String;
+}
+
+/// 最小文字数のチェック (Django: `MinimumLengthValidator`)
+pub struct MinimumLengthValidator {
+    pub min_length: usize,
+}
+
+impl Default for MinimumLengthValidator {
+    fn default() -> Self {
+        MinimumLengthValidator { min_length: 8 }
+    }
+}
+
+impl PasswordValidator for MinimumLengthValidator {
+    fn validate(&self, password: &str, _user: Option<&UserAttributes>) -> Result<(), String> {
+        if password.chars().count() < self.min_length {
+            return Err(format!("このパスワードは短すぎます。最低 {} 文字以上必要です。", self.min_length));
+        }
+        Ok(())
+    }
+
+    fn help_text(&self) -> String {
+        format!("パスワードは最低 {} 文字以上必要です。", self.min_length)
+    }
+}
+
+/// ユーザー名・メールアドレスとの類似度のチェック (Django: `UserAttributeSimilarityValidator`)
+pub struct UserAttributeSimilarityValidator {
+    /// この類似度 (0.0〜1.0) 以上のパスワードを拒否する。Djangoと同じく 0.1 未満は指定できません
+    pub max_similarity: f64,
+}
+
+impl Default for UserAttributeSimilarityValidator {
+    fn default() -> Self {
+        UserAttributeSimilarityValidator { max_similarity: 0.7 }
+    }
+}
+
+impl UserAttributeSimilarityValidator {
+    pub fn new(max_similarity: f64) -> Self {
+        UserAttributeSimilarityValidator { max_similarity: max_similarity.max(0.1) }
+    }
+
+    /// パスワードが属性値に比べて十分に長い場合は類似度を計算しない (Django: `exceeds_maximum_length_ratio`)
+    fn exceeds_maximum_length_ratio(&self, password_len: usize, value_len: usize) -> bool {
+        let length_bound_similarity = self.max_similarity / 2.0 * password_len as f64;
+        password_len >= 10 * value_len && (value_len as f64) < length_bound_similarity
+    }
+}
+
+impl PasswordValidator for UserAttributeSimilarityValidator {
+    fn validate(&self, password: &str, user: Option<&UserAttributes>) -> Result<(), String> {
+        let user = match user {
+            Some(user) => user,
+            None => return Ok(()),
+        };
+        let password = password.to_lowercase();
+
+        for (verbose_name, value) in [("ユーザー名", user.username), ("メールアドレス", user.email)] {
+            if value.is_empty() {
+                continue;
+            }
+            let value = value.to_lowercase();
+            // "alice.smith@example.com" なら "alice", "smith", "example", "com" と全体をそれぞれ比較する
+            let parts = value
+                .split(|c: char| !(c.is_alphanumeric() || c == '_'))
+                .filter(|part| !part.is_empty())
+                .chain(std::iter::once(value.as_str()));
+
+            for part in parts {
+                if self.exceeds_maximum_length_ratio(password.chars().count(), part.chars().count()) {
+                    continue;
+                }
+                // Djangoと同じく、上限値で足切りしてから実際の類似度を計算する
+                if quick_ratio(&password, part) >= self.max_similarity && ratio(&password, part) >= self.max_similarity {
+                    return Err(format!("このパスワードは {} と似すぎています。", verbose_name));
+                }
+            }
+        }
+        Ok(())
+    }
+
+    fn help_text(&self) -> String {
+        "あなたの他の個人情報と似ているパスワードにはできません。".to_string()
+    }
+}
+
+/// よく使われるパスワードのチェック (Django: `CommonPasswordValidator`)
+pub struct CommonPasswordValidator {
+    passwords: Option<HashSet<String>>,
+}
+
+impl Default for CommonPasswordValidator {
+    /// 同梱の一覧を使用します
+    fn default() -> Self {
+        CommonPasswordValidator { passwords: None }
+    }
+}
+
+impl CommonPasswordValidator {
+    /// 独自の一覧ファイル (1行に1つ) を使用します
+    pub fn from_file(path: impl AsRef<Path>) -> std::io::Result<Self> {
+        let content = std::fs::read_to_string(path)?;
+        Ok(CommonPasswordValidator { passwords: Some(parse_password_list(&content)) })
+    }
+
+    fn passwords(&self) -> &HashSet<String> {
+        self.passwords.as_ref().unwrap_or(&DEFAULT_COMMON_PASSWORDS)
+    }
+}
+
+impl PasswordValidator for CommonPasswordValidator {
+    fn validate(&self, password: &str, _user: Option<&UserAttributes>) -> Result<(), String> {
+        if self.passwords().contains(password.trim().to_lowercase().as_str()) {
+            return Err("このパスワードは一般的すぎます。".to_string());
+        }
+        Ok(())
+    }
+
+    fn help_text(&self) -> String {
+        "よく使われるパスワードにはできません。".to_string()
+    }
+}
+
+/// 数字のみのパスワードのチェック (Django: `NumericPasswordValidator`)
+pub struct NumericPasswordValidator;
+
+impl PasswordValidator for NumericPasswordValidator {
+    fn validate(&self, password: &str, _user: Option<&UserAttributes>) -> Result<(), String> {
+        if !password.is_empty() && password.chars().all(|c| c.is_numeric()) {
+            return Err("このパスワードは数字しか使われていません。".to_string());
+        }
+        Ok(())
+    }
+
+    fn help_text(&self) -> String {
+        "数字だけのパスワードにはできません。".to_string()
+    }
+}
+
+/// 検証ルールの一覧。Rocketの managed state として登録します。
+/// Djangoの `get_default_password_validators()` の戻り値に相当します。
+pub struct PasswordValidators {
+    validators: Vec<Box<dyn PasswordValidator>>,
+}
+
+impl Default for PasswordValidators {
+    /// Djangoの `startproject` が生成する `AUTH_PASSWORD_VALIDATORS` と同じ4つのルール
+    fn default() -> Self {
+        PasswordValidators::new(Vec::new())
+            .with(UserAttributeSimilarityValidator::default())
+            .with(MinimumLengthValidator::default())
+            .with(CommonPasswordValidator::default())
+            .with(NumericPasswordValidator)
+    }
+}
+
+impl PasswordValidators {
+    pub fn new(validators: Vec<Box<dyn PasswordValidator>>) -> Self {
+        PasswordValidators { validators }
+    }
+
+    /// ルールを追加します
+    pub fn with(mut self, validator: impl PasswordValidator + 'static) -> Self {
+        self.validators.push(Box::new(validator));
+        self
+    }
+
+    /// 設定から構築します。
+    ///
+    /// - `AUTH_PASSWORD_VALIDATORS`: 使用するルールをカンマ区切りで指定
+    ///   (`user_attribute_similarity`, `minimum_length`, `common`, `numeric`)。未設定の場合は全て、空文字の場合はなし
+    /// - `PASSWORD_MIN_LENGTH`: 最小文字数 (デフォルト: 8)
+    /// - `PASSWORD_MAX_SIMILARITY`: ユーザー属性との類似度の上限 (デフォルト: 0.7)
+    /// - `PASSWORD_COMMON_LIST`: よく使われるパスワード一覧のファイルパス (デフォルト: 同梱の一覧)
+    pub fn from_settings(settings: &Settings) -> Result<Self, String> {
+        let mut validators = PasswordValidators::new(Vec::new());
+        for name in &settings.auth_password_validators {
+            validators = match name.as_str() {
+                "user_attribute_similarity" => {
+                    validators.with(UserAttributeSimilarityValidator::new(settings.password_max_similarity))
+                }
+                "minimum_length" => validators.with(MinimumLengthValidator { min_length: settings.password_min_length }),
+                "common" => match &settings.password_common_list {
+                    Some(path) => validators.with(
+                        CommonPasswordValidator::from_file(path)
+                            .map_err(|e| format!("PASSWORD_COMMON_LIST '{}' を読み込めません: {}", path, e))?,
+                    ),
+                    None => validators.with(CommonPasswordValidator::default()),
+                },
+                "numeric" => validators.with(NumericPasswordValidator),
+                other => return Err(format!("不明なパスワードバリデーターです: {}", other)),
+            };
+        }
+        Ok(validators)
+    }
+
+    /// 全てのルールで検証し、エラーメッセージをまとめて返します。
+    /// Djangoの `validate_password(password, user)` に相当。
+    pub fn validate(&self, password: &str, user: Option<&UserAttributes>) -> Result<(), Vec<String>> {
+        let errors: Vec<String> = self
+            .validators
+            .iter()
+            .filter_map(|v| v.validate(password, user).err())
+            .collect();
+        if errors.is_empty() { Ok(()) } else { Err(errors) }
+    }
+
+    /// 各ルールの説明文 (Django: `password_validators_help_texts()`)
+    pub fn help_texts(&self) -> Vec<String> {
+        self.validators.iter().map(|v| v.help_text()).collect()
+    }
+}
+
+fn parse_password_list(content: &str) -> HashSet<String> {
+    content
+        .lines()
+        .map(|line| line.trim().to_lowercase())
+        .filter(|line| !line.is_empty())
+        .collect()
+}
+
+/// 文字の出現回数だけで求める類似度の上限値。
+/// Pythonの `difflib.SequenceMatcher.quick_ratio()` と同じ計算です。
+fn quick_ratio(a: &str, b: &str) -> f64 {
+    let total = a.chars().count() + b.chars().count();
+    if total == 0 {
+        return 1.0;
+    }
+
+    let mut available = std::collections::HashMap::new();
+    for c in b.chars() {
+        *available.entry(c).or_insert(0usize) += 1;
+    }
+    let mut matches = 0;
+    for c in a.chars() {
+        if let Some(count) = available.get_mut(&c) {
+            if *count > 0 {
+                *count -= 1;
+                matches += 1;
+            }
+        }
+    }
+    2.0 * matches as f64 / total as f64
+}
+
+/// 一致する部分文字列の長さの合計から求める類似度。
+/// Pythonの `difflib.SequenceMatcher.ratio()` と同じ計算です (自動のjunk判定が働かない200文字未満の場合)。
+fn ratio(a: &str, b: &str) -> f64 {
+    let a: Vec<char> = a.chars().collect();
+    let b: Vec<char> = b.chars().collect();
+    let total = a.len() + b.len();
+    if total == 0 {
+        return 1.0;
+    }
+
+    // `get_matching_blocks()` と同様に、最長の一致の前後を再帰的に探す
+    let mut matches = 0;
+    let mut queue = vec![(0, a.len(), 0, b.len())];
+    while let Some((alo, ahi, blo, bhi)) = queue.pop() {
+        let (i, j, size) = find_longest_match(&a, &b, alo, ahi, blo, bhi);
+        if size == 0 {
+            continue;
+        }
+        matches += size;
+        if alo < i && blo < j {
+            queue.push((alo, i, blo, j));
+        }
+        if i + size < ahi && j + size < bhi {
+            queue.push((i + size, ahi, j + size, bhi));
+        }
+    }
+    2.0 * matches as f64 / total as f64
+}
+
+/// `a[alo..ahi]` と `b[blo..bhi]` の最長の共通部分を `(aの開始位置, bの開始位置, 長さ)` で返します。
+/// 同じ長さの場合は `a`、次に `b` で先に現れるものを返します (`SequenceMatcher.find_longest_match()`)。
+fn find_longest_match(a: &[char], b: &[char], alo: usize, ahi: usize, blo: usize, bhi: usize) -> (usize, usize, usize) {
+    let (mut best_i, mut best_j, mut best_size) = (alo, blo, 0);
+    // lengths[j + 1] は a[i] と b[j] で終わる共通部分の長さ
+    let mut lengths = vec![0usize; b.len() + 1];
+    for (i, ca) in a.iter().enumerate().take(ahi).skip(alo) {
+        let mut next = vec![0usize; b.len() + 1];
+        for (j, cb) in b.iter().enumerate().take(bhi).skip(blo) {
+            if ca == cb {
+                let size = lengths[j] + 1;
+                next[j + 1] = size;
+                if size > best_size {
+                    (best_i, best_j, best_size) = (i + 1 - size, j + 1 - size, size);
+                }
+            }
+        }
+        lengths = next;
+    }
+    (best_i, best_j, best_size)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_minimum_length() {
+        let v = MinimumLengthValidator { min_length: 8 };
+        assert!(v.validate("1234567", None).is_err());
+        assert!(v.validate("12345678", None).is_ok());
+        // 文字数はバイト数ではなく文字で数える
+        assert!(v.validate("パスワード", None).is_err());
+    }
+
+    #[test]
+    fn test_user_attribute_similarity() {
+        let v = UserAttributeSimilarityValidator::default();
+        let user = UserAttributes::new("tanaka_taro", "taro.tanaka@example.com");
+        assert!(v.validate("tanaka_taro1", Some(&user)).is_err());
+        assert!(v.validate("TaroTanaka", Some(&user)).is_err());
+        assert!(v.validate("example", Some(&user)).is_err());
+        assert!(v.validate("correct-horse-battery", Some(&user)).is_ok());
+        assert!(v.validate("tanaka_taro1", None).is_ok());
+    }
+
+    #[test]
+    fn test_similarity_uses_matching_blocks() {
+        // 文字の構成は同じでも並びが異なれば似ていない (quick_ratio は約0.77、ratio は約0.15)
+        assert!(quick_ratio("nimda123", "admin") >= 0.7);
+        assert!((ratio("nimda123", "admin") - 2.0 / 13.0).abs() < 1e-9);
+        assert!((ratio("tarotanaka", "tanaka") - 0.75).abs() < 1e-9);
+        assert_eq!(ratio("", ""), 1.0);
+
+        let v = UserAttributeSimilarityValidator::default();
+        let user = UserAttributes::new("admin", "");
+        assert!(v.validate("nimda123", Some(&user)).is_ok());
+        assert!(v.validate("admin123", Some(&user)).is_err());
+    }
+
+    #[test]
+    fn test_common_password() {
+        let v = CommonPasswordValidator::default();
+        assert!(v.validate("password", None).is_err());
+        assert!(v.validate("Password123", None).is_err());
+        assert!(v.validate("correct-horse-battery", None).is_ok());
+    }
+
+    #[test]
+    fn test_common_password_from_file() {
+        let path = std::env::temp_dir().join(format!("common-passwords-{}.txt", uuid::Uuid::new_v4()));
+        std::fs::write(&path, "Rustacean2024\n").unwrap();
+        let v = CommonPasswordValidator::from_file(&path).unwrap();
+        std::fs::remove_file(&path).unwrap();
+        assert!(v.validate("rustacean2024", None).is_err());
+        assert!(v.validate("password", None).is_ok());
+    }
+
+    #[test]
+    fn test_numeric_password() {
+        assert!(NumericPasswordValidator.validate("20240101", None).is_err());
+        assert!(NumericPasswordValidator.validate("2024x0101", None).is_ok());
+    }
+
+    #[test]
+    fn test_validators_collect_all_errors_and_custom_rules() {
+        struct NoSpaces;
+        impl PasswordValidator for NoSpaces {
+            fn validate(&self, password: &str, _user: Option<&UserAttributes>) -> Result<(), String> {
+                if password.contains(' ') { Err("空白は使用できません".into()) } else { Ok(()) }
+            }
+            fn help_text(&self) -> String {
+                "空白を含むパスワードにはできません。".into()
+            }
+        }
+
+        let validators = PasswordValidators::default().with(NoSpaces);
+        assert_eq!(validators.help_texts().len(), 5);
+
+        // 短い・一般的・数字のみ の3つのエラーがまとめて返る
+        let errors = validators.validate("123456", None).unwrap_err();
+        assert_eq!(errors.len(), 3);
+
+        assert_eq!(validators.validate("a b c d e f g h", None).unwrap_err(), vec!["空白は使用できません"]);
+        assert!(validators.validate("correct-horse-battery", None).is_ok());
+    }
+}
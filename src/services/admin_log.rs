@@ -0,0 +1,183 @@
+use chrono::Utc;
+use rocket::serde::json::serde_json;
+use sea_orm::*;
+use serde::Serialize;
+use crate::admin::AdminSite;
+use crate::entities::{prelude::*, log_entry};
+
+/// `object_repr` の最大長 (Djangoの `LogEntry.object_repr` の `max_length`)
+const OBJECT_REPR_MAX_LENGTH: usize = 200;
+
+/// オブジェクトの表示名に使うフィールド (Djangoの `__str__` の代わり)
+const REPR_FIELDS: [&str; 3] = ["username", "name", "title"];
+
+/// 画面表示用の操作履歴
+#[derive(Serialize)]
+pub struct LogEntryInfo {
+    pub action_time: String,
+    /// 操作したユーザー名 (削除済みの場合は `None`)
+    pub username: Option<String>,
+    pub content_type: String,
+    pub object_id: String,
+    pub object_repr: String,
+    pub is_addition: bool,
+    pub is_change: bool,
+    pub is_deletion: bool,
+    pub change_message: String,
+    pub admin_url: Option<String>,
+}
+
+impl LogEntryInfo {
+    fn new(entry: log_entry::Model, user: Option<crate::entities::user::Model>, site: &AdminSite) -> Self {
+        LogEntryInfo {
+            action_time: entry.action_time.format("%Y-%m-%d %H:%M").to_string(),
+            username: user.map(|u| u.username),
+            is_addition: entry.is_addition(),
+            is_change: entry.is_change(),
+            is_deletion: entry.is_deletion(),
+            change_message: entry.get_change_message(),
+            admin_url: entry.get_admin_url(site),
+            content_type: entry.content_type,
+            object_id: entry.object_id,
+            object_repr: entry.object_repr,
+        }
+    }
+}
+
+/// 管理画面の操作履歴を記録・取得するサービス。
+/// Djangoの `LogEntry.objects.log_action()` と、管理画面の「最近のアクション」・「履歴」に相当します。
+pub struct AdminLogService;
+
+impl AdminLogService {
+    /// 操作を記録します。
+    pub async fn log_action<C>(
+        db: &C,
+        user_id: i32,
+        content_type: &str,
+        object_id: &str,
+        object_repr: &str,
+        action_flag: i16,
+        change_message: String,
+    ) -> Result<log_entry::Model, DbErr>
+    where
+        C: ConnectionTrait,
+    {
+        log_entry::ActiveModel {
+            action_time: Set(Utc::now().into()),
+            user_id: Set(Some(user_id)),
+            content_type: Set(content_type.to_string()),
+            object_id: Set(object_id.to_string()),
+            object_repr: Set(object_repr.chars().take(OBJECT_REPR_MAX_LENGTH).collect()),
+            action_flag: Set(action_flag),
+            change_message: Set(change_message),
+            ..Default::default()
+        }
+        .insert(db)
+        .await
+    }
+
+    /// 追加を記録します。`object` はモデルをJSONにしたもの
+    pub async fn log_addition<C: ConnectionTrait>(
+        db: &C,
+        user_id: i32,
+        content_type: &str,
+        object: &serde_json::Value,
+    ) -> Result<log_entry::Model, DbErr> {
+        let message = serde_json::json!([{ "added": {} }]).to_string();
+        Self::log_action(db, user_id, content_type, &object_id(object), &object_repr(object), log_entry::ADDITION, message).await
+    }
+
+    /// 変更を記録します。`fields` は変更したフィールド名
+    pub async fn log_change<C: ConnectionTrait>(
+        db: &C,
+        user_id: i32,
+        content_type: &str,
+        object: &serde_json::Value,
+        fields: &[String],
+    ) -> Result<log_entry::Model, DbErr> {
+        let message = serde_json::json!([{ "changed": { "fields": fields } }]).to_string();
+        Self::log_action(db, user_id, content_type, &object_id(object), &object_repr(object), log_entry::CHANGE, message).await
+    }
+
+    /// 削除を記録します。`object` は削除前のモデルをJSONにしたもの
+    pub async fn log_deletion<C: ConnectionTrait>(
+        db: &C,
+        user_id: i32,
+        content_type: &str,
+        object: &serde_json::Value,
+    ) -> Result<log_entry::Model, DbErr> {
+        let message = serde_json::json!([{ "deleted": {} }]).to_string();
+        Self::log_action(db, user_id, content_type, &object_id(object), &object_repr(object), log_entry::DELETION, message).await
+    }
+
+    /// ユーザーの最近の操作 (ダッシュボードの「最近のアクション」)
+    pub async fn recent_for_user(db: &DatabaseConnection, site: &AdminSite, user_id: i32, limit: u64) -> Result<Vec<LogEntryInfo>, DbErr> {
+        let entries = LogEntry::find()
+            .filter(log_entry::Column::UserId.eq(user_id))
+            .order_by_desc(log_entry::Column::ActionTime)
+            .order_by_desc(log_entry::Column::Id)
+            .limit(limit)
+            .find_also_related(User)
+            .all(db)
+            .await?;
+        Ok(entries.into_iter().map(|(entry, user)| LogEntryInfo::new(entry, user, site)).collect())
+    }
+
+    /// オブジェクトの操作履歴 (古い順。Djangoの `history_view`)
+    pub async fn for_object(db: &DatabaseConnection, site: &AdminSite, content_type: &str, object_id: &str) -> Result<Vec<LogEntryInfo>, DbErr> {
+        let entries = LogEntry::find()
+            .filter(log_entry::Column::ContentType.eq(content_type))
+            .filter(log_entry::Column::ObjectId.eq(object_id))
+            .order_by_asc(log_entry::Column::ActionTime)
+            .order_by_asc(log_entry::Column::Id)
+            .find_also_related(User)
+            .all(db)
+            .await?;
+        Ok(entries.into_iter().map(|(entry, user)| LogEntryInfo::new(entry, user, site)).collect())
+    }
+}
+
+/// モデルのJSONから主キーを取り出す
+pub fn object_id(object: &serde_json::Value) -> String {
+    match &object["id"] {
+        serde_json::Value::String(id) => id.clone(),
+        serde_json::Value::Null => String::new(),
+        id => id.to_string(),
+    }
+}
+
+/// モデルのJSONから表示名を作る (`username` / `name` / `title`、なければ `#<id>`)
+pub fn object_repr(object: &serde_json::Value) -> String {
+    REPR_FIELDS
+        .iter()
+        .find_map(|field| object[*field].as_str().map(str::to_string))
+        .unwrap_or_else(|| format!("#{}", object_id(object)))
+}
+
+/// 変更前後のモデルのJSONを比較し、値が変わったフィールド名を返す
+/// (Djangoの `ModelForm.changed_data`)
+pub fn changed_fields(before: &serde_json::Value, after: &serde_json::Value) -> Vec<String> {
+    let (Some(before), Some(after)) = (before.as_object(), after.as_object()) else {
+        return Vec::new();
+    };
+    after
+        .iter()
+        .filter(|(key, value)| before.get(*key) != Some(value))
+        .map(|(key, _)| key.clone())
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_object_repr_and_changed_fields() {
+        let before = serde_json::json!({ "id": 3, "username": "alice", "email": "a@example.com", "is_active": true });
+        let after = serde_json::json!({ "id": 3, "username": "alice", "email": "b@example.com", "is_active": false });
+        assert_eq!(object_id(&after), "3");
+        assert_eq!(object_repr(&after), "alice");
+        assert_eq!(object_repr(&serde_json::json!({ "id": 7 })), "#7");
+        assert_eq!(changed_fields(&before, &after), vec!["email", "is_active"]);
+    }
+}
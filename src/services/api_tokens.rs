@@ -0,0 +1,165 @@
+use base64::{Engine as _, engine::general_purpose::URL_SAFE_NO_PAD};
+use chrono::{Duration, Utc};
+use rand::Rng;
+use sea_orm::*;
+use sea_orm::prelude::DateTimeWithTimeZone;
+use sea_orm::sea_query::Expr;
+use sha2::{Digest, Sha256};
+use crate::entities::{prelude::*, api_token, api_token_permission, permission, user};
+use crate::errors::AppError;
+
+/// トークンの先頭に付ける文字列 (漏洩時に検出しやすくするため)
+pub const TOKEN_PREFIX: &str = "rds_";
+
+/// 最終使用日時を更新する間隔 (秒)。リクエストごとに書き込みが発生しないよう間引きます。
+const LAST_USED_UPDATE_INTERVAL: i64 = 60;
+
+/// 一覧表示用のトークン情報 (範囲の権限を含む)
+#[derive(Debug, serde::Serialize)]
+pub struct ApiTokenInfo {
+    #[serde(flatten)]
+    pub token: api_token::Model,
+    pub permissions: Vec<String>,
+    pub is_expired: bool,
+}
+
+/// APIトークンの発行・検証を行うサービス。
+/// DRF の `TokenAuthentication` に相当します。
+pub struct ApiTokenService;
+
+impl ApiTokenService {
+    /// 新しいトークン文字列を生成する
+    fn generate() -> String {
+        let random_bytes: [u8; 32] = rand::thread_rng().gen();
+        format!("{}{}", TOKEN_PREFIX, URL_SAFE_NO_PAD.encode(random_bytes))
+    }
+
+    /// 保存用のハッシュ (トークンは十分なエントロピーを持つため、低速なハッシュは不要)
+    pub fn hash_token(token: &str) -> String {
+        format!("{:x}", Sha256::digest(token.as_bytes()))
+    }
+
+    /// トークンを発行します。戻り値の文字列はこの時点でしか取得できません。
+    /// 範囲に指定できるのはユーザー自身が持つ権限のみです。
+    pub async fn create(
+        db: &DatabaseConnection,
+        user: &user::Model,
+        name: &str,
+        expires_at: Option<DateTimeWithTimeZone>,
+        codenames: &[String],
+    ) -> Result<(api_token::Model, String), AppError> {
+        let name = name.trim();
+        if name.is_empty() || name.chars().count() > 100 {
+            return Err(AppError::BadRequest("名前は1〜100文字で入力してください".into()));
+        }
+
+        let user_perms = user.get_all_permissions(db).await?;
+        if let Some(codename) = codenames.iter().find(|c| !user_perms.contains(c)) {
+            return Err(AppError::BadRequest(format!("持っていない権限は指定できません: {}", codename)));
+        }
+        let permissions = Permission::find()
+            .filter(permission::Column::Codename.is_in(codenames.iter().cloned()))
+            .all(db)
+            .await?;
+
+        let plaintext = Self::generate();
+        let txn = db.begin().await?;
+        let token = api_token::ActiveModel {
+            user_id: Set(user.id),
+            name: Set(name.to_owned()),
+            token_prefix: Set(plaintext.chars().take(TOKEN_PREFIX.len() + 6).collect()),
+            token_hash: Set(Self::hash_token(&plaintext)),
+            created_at: Set(Utc::now().into()),
+            expires_at: Set(expires_at),
+            ..Default::default()
+        }
+        .insert(&txn)
+        .await?;
+        if !permissions.is_empty() {
+            let relations = permissions.iter().map(|p| api_token_permission::ActiveModel {
+                api_token_id: Set(token.id),
+                permission_id: Set(p.id),
+            });
+            ApiTokenPermission::insert_many(relations).exec(&txn).await?;
+        }
+        txn.commit().await?;
+
+        Ok((token, plaintext))
+    }
+
+    /// トークンの範囲 (権限のコードネーム)
+    pub async fn permissions(db: &DatabaseConnection, token: &api_token::Model) -> Result<Vec<String>, AppError> {
+        Ok(token
+            .find_related(Permission)
+            .all(db)
+            .await?
+            .into_iter()
+            .map(|p| p.codename)
+            .collect())
+    }
+
+    /// ユーザーのトークン一覧を新しい順に取得します
+    pub async fn list_for_user(db: &DatabaseConnection, user_id: i32) -> Result<Vec<ApiTokenInfo>, AppError> {
+        let tokens = ApiToken::find()
+            .filter(api_token::Column::UserId.eq(user_id))
+            .order_by_desc(api_token::Column::CreatedAt)
+            .all(db)
+            .await?;
+
+        let mut items = Vec::with_capacity(tokens.len());
+        for token in tokens {
+            items.push(ApiTokenInfo {
+                permissions: Self::permissions(db, &token).await?,
+                is_expired: token.is_expired(),
+                token,
+            });
+        }
+        Ok(items)
+    }
+
+    /// ユーザーのトークンを削除します。該当するトークンがなければ `false` を返します。
+    pub async fn revoke(db: &DatabaseConnection, user_id: i32, token_id: i32) -> Result<bool, AppError> {
+        let result = ApiToken::delete_many()
+            .filter(api_token::Column::Id.eq(token_id))
+            .filter(api_token::Column::UserId.eq(user_id))
+            .exec(db)
+            .await?;
+        Ok(result.rows_affected > 0)
+    }
+
+    /// トークン文字列から、有効なトークンとその所有ユーザーを取得します。
+    /// 期限切れのトークンや無効なユーザーの場合は `None` を返します。
+    pub async fn authenticate(
+        db: &DatabaseConnection,
+        token: &str,
+    ) -> Result<Option<(user::Model, api_token::Model)>, AppError> {
+        if !token.starts_with(TOKEN_PREFIX) {
+            return Ok(None);
+        }
+
+        let found = ApiToken::find()
+            .filter(api_token::Column::TokenHash.eq(Self::hash_token(token)))
+            .find_also_related(User)
+            .one(db)
+            .await?;
+        let (token, user) = match found {
+            Some((token, Some(user))) if !token.is_expired() && user.is_active => (token, user),
+            _ => return Ok(None),
+        };
+
+        let now = Utc::now();
+        let stale = token
+            .last_used_at
+            .is_none_or(|used| now - used.with_timezone(&Utc) >= Duration::seconds(LAST_USED_UPDATE_INTERVAL));
+        if stale {
+            let now: DateTimeWithTimeZone = now.into();
+            ApiToken::update_many()
+                .col_expr(api_token::Column::LastUsedAt, Expr::value(now))
+                .filter(api_token::Column::Id.eq(token.id))
+                .exec(db)
+                .await?;
+        }
+
+        Ok(Some((user, token)))
+    }
+}
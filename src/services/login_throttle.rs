@@ -0,0 +1,288 @@
+use chrono::{Duration, Utc};
+use sea_orm::*;
+use sea_orm::prelude::DateTimeWithTimeZone;
+use sea_orm::sea_query::{Expr, OnConflict};
+use rocket::serde::json::serde_json;
+use crate::entities::{prelude::*, login_failure, log_entry, user};
+use crate::errors::AppError;
+use crate::services::admin_log::{self, AdminLogService};
+use crate::settings::Settings;
+
+/// ユーザー名ごとの集計を表すスコープ
+pub const SCOPE_USERNAME: &str = "username";
+/// IPアドレスごとの集計を表すスコープ
+pub const SCOPE_IP: &str = "ip";
+
+/// 操作履歴に記録するロックのフィールド名
+pub const LOCKOUT_FIELD: &str = "locked_until";
+
+/// ログイン試行制限の設定。
+/// django-axes の `AXES_FAILURE_LIMIT` / `AXES_COOLOFF_TIME` に相当します。
+#[derive(Debug, Clone)]
+pub struct ThrottleConfig {
+    /// ユーザー名ごとの、ロックされるまでの失敗回数
+    pub failure_limit: i32,
+    /// IPアドレスごとの、ロックされるまでの失敗回数
+    pub ip_failure_limit: i32,
+    /// 失敗回数をリセットするまでの期間 (最後の失敗またはロック解除からの経過時間)
+    pub failure_window: Duration,
+    /// 最初のロック期間。以降は失敗するたびに2倍になります
+    pub lockout_duration: Duration,
+    /// ロック期間の上限
+    pub max_lockout_duration: Duration,
+}
+
+impl Default for ThrottleConfig {
+    fn default() -> Self {
+        ThrottleConfig {
+            failure_limit: 5,
+            ip_failure_limit: 20,
+            failure_window: Duration::minutes(15),
+            lockout_duration: Duration::minutes(1),
+            max_lockout_duration: Duration::hours(24),
+        }
+    }
+}
+
+impl ThrottleConfig {
+    /// 設定から読み込みます。
+    ///
+    /// - `LOGIN_FAILURE_LIMIT` / `LOGIN_IP_FAILURE_LIMIT`: ロックされるまでの失敗回数
+    /// - `LOGIN_FAILURE_WINDOW`: 失敗回数をリセットするまでの期間 (秒)
+    /// - `LOGIN_LOCKOUT_DURATION` / `LOGIN_LOCKOUT_MAX_DURATION`: ロック期間の初期値と上限 (秒)
+    pub fn from_settings(settings: &Settings) -> Self {
+        ThrottleConfig {
+            failure_limit: settings.login_failure_limit,
+            ip_failure_limit: settings.login_ip_failure_limit,
+            failure_window: Duration::seconds(settings.login_failure_window),
+            lockout_duration: Duration::seconds(settings.login_lockout_duration),
+            max_lockout_duration: Duration::seconds(settings.login_lockout_max_duration),
+        }
+    }
+
+    /// 失敗回数に応じたロック期間を返します。
+    /// `limit` 回目で `lockout_duration`、以降は1回ごとに2倍 (上限 `max_lockout_duration`)。
+    pub fn lockout_for(&self, failure_count: i32, limit: i32) -> Option<Duration> {
+        if limit <= 0 || failure_count < limit {
+            return None;
+        }
+        let exponent = (failure_count - limit).min(30) as u32;
+        let seconds = self.lockout_duration.num_seconds().saturating_mul(1i64 << exponent);
+        Some(Duration::seconds(seconds).min(self.max_lockout_duration))
+    }
+}
+
+/// ログイン失敗の記録とロック判定を行うサービス。
+/// Rocketの managed state として登録し、ログイン処理と管理画面から利用します。
+/// django-axes の `AxesHandler` に相当します。
+pub struct LoginThrottle {
+    config: ThrottleConfig,
+}
+
+impl LoginThrottle {
+    pub fn new(config: ThrottleConfig) -> Self {
+        LoginThrottle { config }
+    }
+
+    pub fn from_settings(settings: &Settings) -> Self {
+        LoginThrottle::new(ThrottleConfig::from_settings(settings))
+    }
+
+    pub fn config(&self) -> &ThrottleConfig {
+        &self.config
+    }
+
+    /// ユーザー名またはIPアドレスがロック中であれば、解除日時を返します。
+    /// ロック中はパスワードの検証 (Argon2) 自体を行わないよう、認証の前に呼び出します。
+    pub async fn check(
+        &self,
+        db: &DatabaseConnection,
+        username: &str,
+        ip_address: Option<&str>,
+    ) -> Result<Option<DateTimeWithTimeZone>, AppError> {
+        let now = Utc::now();
+        let mut locked_until = None;
+
+        for (scope, identifier) in Self::keys(username, ip_address) {
+            if let Some(record) = Self::find(db, scope, identifier).await? {
+                if let Some(until) = record.locked_until.filter(|until| *until > now) {
+                    locked_until = locked_until.max(Some(until));
+                }
+            }
+        }
+
+        Ok(locked_until)
+    }
+
+    /// ログイン失敗を記録します。
+    /// この失敗でロックされた場合は解除日時を返します。
+    pub async fn record_failure(
+        &self,
+        db: &DatabaseConnection,
+        username: &str,
+        ip_address: Option<&str>,
+    ) -> Result<Option<DateTimeWithTimeZone>, AppError> {
+        let now = Utc::now();
+        let mut locked_until = None;
+
+        for (scope, identifier) in Self::keys(username, ip_address) {
+            let limit = if scope == SCOPE_IP { self.config.ip_failure_limit } else { self.config.failure_limit };
+
+            // 同時に失敗した場合も取りこぼさないよう、回数の加算と期限切れの判定は1つの文で行う
+            let record = login_failure::ActiveModel {
+                scope: Set(scope.to_owned()),
+                identifier: Set(identifier.to_owned()),
+                failure_count: Set(1),
+                last_failure_at: Set(now.into()),
+                locked_until: Set(None),
+                ..Default::default()
+            };
+            let expired = self.expired_condition(now);
+            let record = LoginFailure::insert(record)
+                .on_conflict(
+                    OnConflict::columns([login_failure::Column::Scope, login_failure::Column::Identifier])
+                        .value(
+                            login_failure::Column::FailureCount,
+                            Expr::case(expired.clone(), 1)
+                                .finally(Expr::col((LoginFailure, login_failure::Column::FailureCount)).add(1)),
+                        )
+                        .value(
+                            login_failure::Column::LockedUntil,
+                            Expr::case(expired, Expr::value(Option::<DateTimeWithTimeZone>::None))
+                                .finally(Expr::col((LoginFailure, login_failure::Column::LockedUntil))),
+                        )
+                        .update_column(login_failure::Column::LastFailureAt)
+                        .to_owned(),
+                )
+                .exec_with_returning(db)
+                .await?;
+
+            let until: Option<DateTimeWithTimeZone> = self
+                .config
+                .lockout_for(record.failure_count, limit)
+                .map(|duration| (now + duration).into());
+            if let Some(until) = until {
+                // 並行する失敗がより長いロックを設定済みであれば短縮しない
+                LoginFailure::update_many()
+                    .col_expr(login_failure::Column::LockedUntil, Expr::value(until))
+                    .filter(login_failure::Column::Id.eq(record.id))
+                    .filter(
+                        Condition::any()
+                            .add(login_failure::Column::LockedUntil.is_null())
+                            .add(login_failure::Column::LockedUntil.lt(until)),
+                    )
+                    .exec(db)
+                    .await?;
+            }
+
+            if scope == SCOPE_USERNAME {
+                if let Some(until) = until {
+                    Self::log_lockout(db, username, until).await?;
+                }
+            }
+            locked_until = locked_until.max(until);
+        }
+
+        Ok(locked_until)
+    }
+
+    /// ユーザー名のロックを操作履歴に記録します。操作したユーザーはロックされたユーザー自身とします
+    async fn log_lockout(db: &DatabaseConnection, username: &str, until: DateTimeWithTimeZone) -> Result<(), AppError> {
+        let Some(user) = User::find().filter(user::Column::Username.eq(username)).one(db).await? else {
+            return Ok(());
+        };
+        let object = serde_json::to_value(&user).unwrap_or_default();
+        AdminLogService::log_action(
+            db,
+            user.id,
+            "user",
+            &admin_log::object_id(&object),
+            &admin_log::object_repr(&object),
+            log_entry::CHANGE,
+            format!("ログインの失敗が続いたため {} までロックしました。", until.format("%Y-%m-%d %H:%M")),
+        )
+        .await?;
+        Ok(())
+    }
+
+    /// ユーザー名の失敗記録を消去し、ロックを解除します。
+    /// ログイン成功時と、管理画面からのロック解除で使用します。
+    pub async fn reset(&self, db: &DatabaseConnection, username: &str) -> Result<(), AppError> {
+        LoginFailure::delete_many()
+            .filter(login_failure::Column::Scope.eq(SCOPE_USERNAME))
+            .filter(login_failure::Column::Identifier.eq(username))
+            .exec(db)
+            .await?;
+        Ok(())
+    }
+
+    /// ユーザー名の失敗記録を取得します (期限切れの記録は `None`)。
+    pub async fn status(&self, db: &DatabaseConnection, username: &str) -> Result<Option<login_failure::Model>, AppError> {
+        Ok(Self::find(db, SCOPE_USERNAME, username)
+            .await?
+            .filter(|record| !self.is_expired(record)))
+    }
+
+    fn keys<'a>(username: &'a str, ip_address: Option<&'a str>) -> Vec<(&'static str, &'a str)> {
+        let mut keys = vec![(SCOPE_USERNAME, username)];
+        if let Some(ip) = ip_address {
+            keys.push((SCOPE_IP, ip));
+        }
+        keys
+    }
+
+    async fn find(db: &DatabaseConnection, scope: &str, identifier: &str) -> Result<Option<login_failure::Model>, AppError> {
+        Ok(LoginFailure::find()
+            .filter(login_failure::Column::Scope.eq(scope))
+            .filter(login_failure::Column::Identifier.eq(identifier))
+            .one(db)
+            .await?)
+    }
+
+    /// `is_expired` と同じ判定を、既存の記録に対するSQLの条件として返します
+    fn expired_condition(&self, now: chrono::DateTime<Utc>) -> Condition {
+        let cutoff: DateTimeWithTimeZone = (now - self.config.failure_window).into();
+        Condition::all()
+            .add(Expr::col((LoginFailure, login_failure::Column::LastFailureAt)).lt(cutoff))
+            .add(
+                Condition::any()
+                    .add(Expr::col((LoginFailure, login_failure::Column::LockedUntil)).is_null())
+                    .add(Expr::col((LoginFailure, login_failure::Column::LockedUntil)).lt(cutoff)),
+            )
+    }
+
+    /// 最後の失敗 (ロック中だった場合はロック解除) から `failure_window` が経過した記録は無効とする
+    fn is_expired(&self, record: &login_failure::Model) -> bool {
+        let reference = record.locked_until.map_or(record.last_failure_at, |until| until.max(record.last_failure_at));
+        Utc::now() - reference.with_timezone(&Utc) > self.config.failure_window
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_lockout_starts_at_limit() {
+        let config = ThrottleConfig::default();
+
+        assert_eq!(config.lockout_for(4, 5), None);
+        assert_eq!(config.lockout_for(5, 5), Some(Duration::minutes(1)));
+    }
+
+    #[test]
+    fn test_lockout_backoff_is_exponential_and_capped() {
+        let config = ThrottleConfig::default();
+
+        assert_eq!(config.lockout_for(6, 5), Some(Duration::minutes(2)));
+        assert_eq!(config.lockout_for(8, 5), Some(Duration::minutes(8)));
+        assert_eq!(config.lockout_for(100, 5), Some(Duration::hours(24)));
+    }
+
+    #[test]
+    fn test_zero_limit_disables_lockout() {
+        let config = ThrottleConfig::default();
+
+        assert_eq!(config.lockout_for(100, 0), None);
+    }
+}
@@ -1 +1,7 @@
 pub mod user_service;
+pub mod login_throttle;
+pub mod two_factor;
+pub mod registration;
+pub mod api_tokens;
+pub mod oidc;
+pub mod admin_log;
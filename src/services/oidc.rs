@@ -0,0 +1,184 @@
+use chrono::Utc;
+use sea_orm::*;
+use sea_orm::prelude::DateTimeWithTimeZone;
+use crate::auth_utils::make_unusable_password;
+use crate::entities::{prelude::*, group, group_user, user, user_identity};
+use crate::errors::AppError;
+use crate::oidc::{IdTokenClaims, OidcProviderConfig};
+use crate::validation::clean_email;
+
+/// ユーザー名の最大長 (Djangoの `AbstractUser.username` と同じ)
+const USERNAME_MAX_LENGTH: usize = 150;
+
+/// IDトークンのクレームとローカルユーザーを対応付けるサービス。
+/// mozilla-django-oidc の `OIDCAuthenticationBackend` に相当します。
+pub struct OidcUserService;
+
+impl OidcUserService {
+    /// クレームに対応するユーザーを取得します。
+    ///
+    /// 1. `(provider, sub)` で紐付け済みのユーザー
+    /// 2. IdPが検証済みとしたメールアドレスを持つ既存ユーザー (紐付けを作成)
+    /// 3. `auto_create` が有効なら新しいユーザーを作成 (パスワードは使用不可)
+    ///
+    /// `groups_claim` が設定されている場合、クレームのグループに追加し、
+    /// `managed_groups` に該当するグループはクレームに含まれなければ外します。
+    pub async fn authenticate(
+        db: &DatabaseConnection,
+        config: &OidcProviderConfig,
+        claims: &IdTokenClaims,
+    ) -> Result<user::Model, AppError> {
+        let now: DateTimeWithTimeZone = Utc::now().into();
+        let txn = db.begin().await?;
+
+        let identity = UserIdentity::find()
+            .filter(user_identity::Column::Provider.eq(&config.name))
+            .filter(user_identity::Column::Subject.eq(&claims.sub))
+            .one(&txn)
+            .await?;
+
+        let user = match identity {
+            Some(identity) => {
+                let user_id = identity.user_id;
+                let mut active: user_identity::ActiveModel = identity.into();
+                active.last_login_at = Set(Some(now));
+                active.update(&txn).await?;
+                User::find_by_id(user_id).one(&txn).await?.ok_or(AppError::NotFound)?
+            }
+            None => {
+                let user = match Self::find_by_verified_email(&txn, claims).await? {
+                    Some(user) => user,
+                    None if config.auto_create => Self::create_user(&txn, claims).await?,
+                    None => return Err(AppError::Forbidden),
+                };
+                user_identity::ActiveModel {
+                    user_id: Set(user.id),
+                    provider: Set(config.name.clone()),
+                    subject: Set(claims.sub.clone()),
+                    created_at: Set(now),
+                    last_login_at: Set(Some(now)),
+                    ..Default::default()
+                }
+                .insert(&txn)
+                .await?;
+                user
+            }
+        };
+
+        if let Some(claim) = &config.groups_claim {
+            Self::sync_groups(&txn, config, &user, &claims.string_list(claim)).await?;
+        }
+        txn.commit().await?;
+
+        Ok(user)
+    }
+
+    /// IdPがメールアドレスを検証済みの場合のみ、同じメールアドレスの既存ユーザーを返します
+    async fn find_by_verified_email(
+        db: &DatabaseTransaction,
+        claims: &IdTokenClaims,
+    ) -> Result<Option<user::Model>, AppError> {
+        let email = match claims.email.as_deref().map(clean_email) {
+            Some(Ok(email)) if claims.email_verified && !email.is_empty() => email,
+            _ => return Ok(None),
+        };
+        let mut users = User::find()
+            .filter(user::Column::Email.eq(email))
+            .limit(2)
+            .all(db)
+            .await?;
+        // 同じメールアドレスのユーザーが複数いる場合は、どれに紐付けるべきか判断できない
+        Ok(if users.len() == 1 { users.pop() } else { None })
+    }
+
+    /// クレームから使用可能なユーザー名を決めます (重複する場合は連番を付ける)
+    async fn unique_username(db: &DatabaseTransaction, claims: &IdTokenClaims) -> Result<String, AppError> {
+        let base: String = claims
+            .preferred_username
+            .as_deref()
+            .or_else(|| claims.email.as_deref().and_then(|email| email.split('@').next()))
+            .unwrap_or(&claims.sub)
+            .chars()
+            .filter(|c| c.is_alphanumeric() || "@.+-_".contains(*c))
+            .take(USERNAME_MAX_LENGTH - 4)
+            .collect();
+        let base = if base.is_empty() { "user".to_string() } else { base };
+
+        let taken: Vec<String> = User::find()
+            .select_only()
+            .column(user::Column::Username)
+            .filter(user::Column::Username.starts_with(&base))
+            .into_tuple()
+            .all(db)
+            .await?;
+        if !taken.contains(&base) {
+            return Ok(base);
+        }
+        (2..1000)
+            .map(|n| format!("{}{}", base, n))
+            .find(|candidate| !taken.contains(candidate))
+            .ok_or_else(|| AppError::Internal("ユーザー名を決定できません".into()))
+    }
+
+    async fn create_user(db: &DatabaseTransaction, claims: &IdTokenClaims) -> Result<user::Model, AppError> {
+        let email = claims
+            .email
+            .as_deref()
+            .and_then(|email| clean_email(email).ok())
+            .unwrap_or_default();
+        Ok(user::ActiveModel {
+            username: Set(Self::unique_username(db, claims).await?),
+            email: Set(email),
+            password_hash: Set(make_unusable_password()),
+            is_active: Set(true),
+            is_admin: Set(false),
+            ..Default::default()
+        }
+        .insert(db)
+        .await?)
+    }
+
+    /// 所属グループをクレームの内容に合わせます。ローカルに存在しないグループ名は無視します。
+    /// 管理画面などで追加した、IdPが管理しないグループの所属はそのまま残します。
+    async fn sync_groups(
+        db: &DatabaseTransaction,
+        config: &OidcProviderConfig,
+        user: &user::Model,
+        names: &[String],
+    ) -> Result<(), AppError> {
+        let current = user.find_related(Group).all(db).await?;
+
+        let removed: Vec<i32> = current
+            .iter()
+            .filter(|g| config.manages_group(&g.name) && !names.contains(&g.name))
+            .map(|g| g.id)
+            .collect();
+        if !removed.is_empty() {
+            GroupUser::delete_many()
+                .filter(group_user::Column::UserId.eq(user.id))
+                .filter(group_user::Column::GroupId.is_in(removed))
+                .exec(db)
+                .await?;
+        }
+
+        // `managed_groups` を設定している場合、それ以外のグループにはIdPから追加しない
+        let added: Vec<group::Model> = Group::find()
+            .filter(group::Column::Name.is_in(names.iter().cloned()))
+            .all(db)
+            .await?
+            .into_iter()
+            .filter(|g| config.managed_groups.is_empty() || config.manages_group(&g.name))
+            .filter(|g| !current.iter().any(|c| c.id == g.id))
+            .collect();
+        if added.is_empty() {
+            return Ok(());
+        }
+        let relations = added.iter().map(|g| group_user::ActiveModel {
+            group_id: Set(g.id),
+            user_id: Set(user.id),
+            ..Default::default()
+        });
+        GroupUser::insert_many(relations).exec(db).await?;
+        Ok(())
+    }
+}
@@ -0,0 +1,137 @@
+use sea_orm::*;
+use crate::auth_utils::hash_password;
+use crate::entities::{prelude::*, group, group_user, user};
+use crate::errors::AppError;
+use crate::settings::Settings;
+
+/// 有効化リンクの有効期間のデフォルト値 (日)。django-registration の `ACCOUNT_ACTIVATION_DAYS` と同じ7日間。
+pub(crate) const DEFAULT_ACCOUNT_ACTIVATION_DAYS: i64 = 7;
+
+/// ユーザー登録後に呼び出されるフック。
+/// django-registration の `user_registered` シグナルに相当します。
+/// 登録時のトランザクション内で呼ばれるため、エラーを返すとユーザーの作成も取り消されます。
+#[rocket::async_trait]
+pub trait SignupHook: Send + Sync {
+    async fn user_registered(&self, db: &DatabaseTransaction, user: &user::Model) -> Result<(), AppError>;
+}
+
+/// 新規ユーザーを指定したグループに所属させるフック
+pub struct DefaultGroups {
+    pub names: Vec<String>,
+}
+
+#[rocket::async_trait]
+impl SignupHook for DefaultGroups {
+    async fn user_registered(&self, db: &DatabaseTransaction, user: &user::Model) -> Result<(), AppError> {
+        if self.names.is_empty() {
+            return Ok(());
+        }
+
+        let groups = Group::find()
+            .filter(group::Column::Name.is_in(self.names.iter().cloned()))
+            .all(db)
+            .await?;
+        if let Some(missing) = self.names.iter().find(|name| !groups.iter().any(|g| &g.name == *name)) {
+            return Err(AppError::Internal(format!("デフォルトのグループが見つかりません: {}", missing)));
+        }
+
+        let relations = groups.iter().map(|g| group_user::ActiveModel {
+            group_id: Set(g.id),
+            user_id: Set(user.id),
+            ..Default::default()
+        });
+        GroupUser::insert_many(relations).exec(db).await?;
+        Ok(())
+    }
+}
+
+/// ユーザー登録 (サインアップ) の設定。
+/// django-registration の `REGISTRATION_OPEN` / `ACCOUNT_ACTIVATION_DAYS` に相当します。
+pub struct SignupConfig {
+    /// `/auth/signup` を公開するか
+    pub enabled: bool,
+    /// 有効化リンクの有効期間 (日)
+    pub activation_days: i64,
+    hooks: Vec<Box<dyn SignupHook>>,
+}
+
+impl Default for SignupConfig {
+    fn default() -> Self {
+        SignupConfig {
+            enabled: false,
+            activation_days: DEFAULT_ACCOUNT_ACTIVATION_DAYS,
+            hooks: Vec::new(),
+        }
+    }
+}
+
+impl SignupConfig {
+    /// 設定から読み込みます。
+    ///
+    /// - `SIGNUP_ENABLED`: `true` の場合、ユーザー登録を受け付けます (デフォルト: `false`)
+    /// - `ACCOUNT_ACTIVATION_DAYS`: 有効化リンクの有効期間 (日)
+    /// - `SIGNUP_DEFAULT_GROUPS`: 新規ユーザーを所属させるグループ名 (カンマ区切り)
+    pub fn from_settings(settings: &Settings) -> Self {
+        let config = SignupConfig {
+            enabled: settings.signup_enabled,
+            activation_days: settings.account_activation_days,
+            hooks: Vec::new(),
+        };
+        config.with_hook(DefaultGroups { names: settings.signup_default_groups.clone() })
+    }
+
+    /// 登録後のフックを追加する
+    pub fn with_hook(mut self, hook: impl SignupHook + 'static) -> Self {
+        self.hooks.push(Box::new(hook));
+        self
+    }
+
+    /// 有効化リンクの有効期間 (秒)
+    pub fn activation_timeout(&self) -> i64 {
+        self.activation_days * 60 * 60 * 24
+    }
+}
+
+/// ユーザー登録と有効化を行うサービス。
+/// django-registration の `RegistrationView.register()` / `ActivationView.activate()` に相当します。
+pub struct RegistrationService;
+
+impl RegistrationService {
+    /// 無効状態 (`is_active = false`) のユーザーを作成し、登録後のフックを実行します。
+    /// 入力値は呼び出し側で検証済みであることを前提とします。
+    pub async fn register(
+        db: &DatabaseConnection,
+        config: &SignupConfig,
+        username: &str,
+        email: &str,
+        password: &str,
+    ) -> Result<user::Model, AppError> {
+        let password_hash = hash_password(password)?;
+
+        let txn = db.begin().await?;
+        let user = user::ActiveModel {
+            username: Set(username.to_owned()),
+            email: Set(email.to_owned()),
+            password_hash: Set(password_hash),
+            is_active: Set(false),
+            is_admin: Set(false),
+            ..Default::default()
+        }
+        .insert(&txn)
+        .await?;
+
+        for hook in &config.hooks {
+            hook.user_registered(&txn, &user).await?;
+        }
+        txn.commit().await?;
+
+        Ok(user)
+    }
+
+    /// ユーザーを有効にします
+    pub async fn activate(db: &DatabaseConnection, user: user::Model) -> Result<user::Model, AppError> {
+        let mut active: user::ActiveModel = user.into();
+        active.is_active = Set(true);
+        Ok(active.update(db).await?)
+    }
+}
@@ -0,0 +1,123 @@
+use chrono::Utc;
+use sea_orm::*;
+use sea_orm::prelude::DateTimeWithTimeZone;
+use sea_orm::sea_query::Expr;
+use crate::entities::{prelude::*, otp_recovery_code, user};
+use crate::errors::AppError;
+use crate::otp::{generate_recovery_codes, hash_recovery_code, unix_now, Totp};
+
+/// 2要素認証の登録・検証を行うサービス。
+/// django-otp の `TOTPDevice` / `StaticDevice` の `verify_token()` に相当します。
+pub struct TwoFactorService;
+
+impl TwoFactorService {
+    /// ユーザーが2要素認証を有効にしているか
+    pub fn is_enabled(user: &user::Model) -> bool {
+        user.totp_secret.is_some()
+    }
+
+    /// 確認コードを検証してからシークレットを保存し、2要素認証を有効にします。
+    /// 成功した場合は新しいリカバリーコード (平文) を返します。
+    pub async fn enable(
+        db: &DatabaseConnection,
+        user: user::Model,
+        secret: &str,
+        code: &str,
+    ) -> Result<Vec<String>, AppError> {
+        let totp = Totp::from_base32(secret)
+            .ok_or_else(|| AppError::BadRequest("シークレットが不正です".into()))?;
+        let counter = totp
+            .verify(code, unix_now(), None)
+            .ok_or_else(|| AppError::BadRequest("確認コードが正しくありません".into()))?;
+
+        let user_id = user.id;
+        let mut active: user::ActiveModel = user.into();
+        active.totp_secret = Set(Some(secret.to_owned()));
+        active.totp_last_counter = Set(Some(counter));
+        active.update(db).await?;
+
+        Self::regenerate_recovery_codes(db, user_id).await
+    }
+
+    /// 2要素認証を無効にし、リカバリーコードも削除します (管理画面からのリセットにも使用)。
+    pub async fn disable(db: &DatabaseConnection, user: user::Model) -> Result<user::Model, AppError> {
+        OtpRecoveryCode::delete_many()
+            .filter(otp_recovery_code::Column::UserId.eq(user.id))
+            .exec(db)
+            .await?;
+
+        let mut active: user::ActiveModel = user.into();
+        active.totp_secret = Set(None);
+        active.totp_last_counter = Set(None);
+        Ok(active.update(db).await?)
+    }
+
+    /// TOTPコードまたはリカバリーコードを検証します。
+    /// 使用したコードは再利用できないよう記録します。
+    pub async fn verify(db: &DatabaseConnection, user: &user::Model, code: &str) -> Result<bool, AppError> {
+        let totp = match user.totp_secret.as_deref().and_then(Totp::from_base32) {
+            Some(totp) => totp,
+            None => return Ok(false),
+        };
+
+        if let Some(counter) = totp.verify(code, unix_now(), user.totp_last_counter) {
+            // 同じコードの同時使用を防ぐため、前回より新しいカウンターの場合のみ更新する
+            let result = User::update_many()
+                .col_expr(user::Column::TotpLastCounter, Expr::value(counter))
+                .filter(user::Column::Id.eq(user.id))
+                .filter(
+                    Condition::any()
+                        .add(user::Column::TotpLastCounter.is_null())
+                        .add(user::Column::TotpLastCounter.lt(counter)),
+                )
+                .exec(db)
+                .await?;
+            return Ok(result.rows_affected == 1);
+        }
+
+        Self::use_recovery_code(db, user.id, code).await
+    }
+
+    /// 未使用のリカバリーコードであれば使用済みにして `true` を返します。
+    pub async fn use_recovery_code(db: &DatabaseConnection, user_id: i32, code: &str) -> Result<bool, AppError> {
+        let now: DateTimeWithTimeZone = Utc::now().into();
+        let result = OtpRecoveryCode::update_many()
+            .col_expr(otp_recovery_code::Column::UsedAt, Expr::value(now))
+            .filter(otp_recovery_code::Column::UserId.eq(user_id))
+            .filter(otp_recovery_code::Column::CodeHash.eq(hash_recovery_code(code)))
+            .filter(otp_recovery_code::Column::UsedAt.is_null())
+            .exec(db)
+            .await?;
+        Ok(result.rows_affected > 0)
+    }
+
+    /// リカバリーコードを再発行します。既存のコードは全て無効になります。
+    pub async fn regenerate_recovery_codes(db: &DatabaseConnection, user_id: i32) -> Result<Vec<String>, AppError> {
+        let codes = generate_recovery_codes();
+
+        let txn = db.begin().await?;
+        OtpRecoveryCode::delete_many()
+            .filter(otp_recovery_code::Column::UserId.eq(user_id))
+            .exec(&txn)
+            .await?;
+        let records = codes.iter().map(|code| otp_recovery_code::ActiveModel {
+            user_id: Set(user_id),
+            code_hash: Set(hash_recovery_code(code)),
+            created_at: Set(Utc::now().into()),
+            ..Default::default()
+        });
+        OtpRecoveryCode::insert_many(records).exec(&txn).await?;
+        txn.commit().await?;
+
+        Ok(codes)
+    }
+
+    /// 未使用のリカバリーコードの数
+    pub async fn remaining_recovery_codes(db: &DatabaseConnection, user_id: i32) -> Result<u64, AppError> {
+        Ok(OtpRecoveryCode::find()
+            .filter(otp_recovery_code::Column::UserId.eq(user_id))
+            .filter(otp_recovery_code::Column::UsedAt.is_null())
+            .count(db)
+            .await?)
+    }
+}
@@ -1,6 +1,7 @@
 use sea_orm::*;
 use crate::entities::{prelude::*, user};
-use crate::auth_utils::{hash_password, verify_password};
+use sea_orm::sea_query::Expr;
+use crate::auth_utils::{check_password, hash_password};
 use crate::errors::AppError;
 
 /// ユーザー関連のビジネスロジックを集約するサービス。
@@ -37,6 +38,7 @@ impl UserService {
     pub async fn create(
         db: &DatabaseConnection,
         username: &str,
+        email: &str,
         password: &str,
         is_admin: bool,
     ) -> Result<user::Model, AppError> {
@@ -44,6 +46,7 @@ impl UserService {
         
         let new_user = user::ActiveModel {
             username: Set(username.to_owned()),
+            email: Set(email.to_owned()),
             password_hash: Set(password_hash),
             is_active: Set(true),
             is_admin: Set(is_admin),
@@ -53,6 +56,37 @@ impl UserService {
         new_user.insert(db).await.map_err(AppError::Database)
     }
 
+    /// パスワードを変更する (Django: user.set_password() + user.save())
+    pub async fn set_password(
+        db: &DatabaseConnection,
+        user: user::Model,
+        password: &str,
+    ) -> Result<user::Model, AppError> {
+        let mut active: user::ActiveModel = user.into();
+        active.password_hash = Set(hash_password(password)?);
+        active.update(db).await.map_err(AppError::Database)
+    }
+
+    /// パスワードを検証し、古い方式のハッシュであれば既定の方式で保存し直す
+    /// (Django: user.check_password() の setter によるハッシュの更新)
+    pub async fn check_password(
+        db: &DatabaseConnection,
+        user: &user::Model,
+        password: &str,
+    ) -> Result<bool, AppError> {
+        let check = check_password(password, &user.password_hash);
+        if check.must_update {
+            // 同時に変更されたパスワードを上書きしないよう、検証したハッシュのままの場合のみ更新する
+            User::update_many()
+                .col_expr(user::Column::PasswordHash, Expr::value(hash_password(password)?))
+                .filter(user::Column::Id.eq(user.id))
+                .filter(user::Column::PasswordHash.eq(user.password_hash.as_str()))
+                .exec(db)
+                .await?;
+        }
+        Ok(check.valid)
+    }
+
     /// 認証処理 (Django: authenticate())
     pub async fn authenticate(
         db: &DatabaseConnection,
@@ -63,7 +97,7 @@ impl UserService {
             .await?
             .ok_or(AppError::Unauthorized)?;
 
-        if !verify_password(password, &user.password_hash) {
+        if !Self::check_password(db, &user, password).await? {
             return Err(AppError::Unauthorized);
         }
 
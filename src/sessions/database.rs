@@ -0,0 +1,69 @@
+use sea_orm::*;
+use sea_orm::prelude::{DateTimeWithTimeZone, Expr};
+use chrono::Utc;
+use crate::entities::{prelude::*, session};
+use crate::errors::AppError;
+use super::SessionStore;
+
+/// データベースにセッションを保存するバックエンド。
+/// Djangoの `django.contrib.sessions.backends.db` に相当します。
+pub struct DatabaseSessionStore {
+    db: DatabaseConnection,
+}
+
+impl DatabaseSessionStore {
+    pub fn new(db: DatabaseConnection) -> Self {
+        DatabaseSessionStore { db }
+    }
+}
+
+#[rocket::async_trait]
+impl SessionStore for DatabaseSessionStore {
+    async fn create(&self, session: session::Model) -> Result<(), AppError> {
+        let active_model: session::ActiveModel = session.into();
+        Session::insert(active_model).exec(&self.db).await?;
+        Ok(())
+    }
+
+    async fn load(&self, session_key: &str) -> Result<Option<session::Model>, AppError> {
+        Ok(Session::find_by_id(session_key.to_owned()).one(&self.db).await?)
+    }
+
+    async fn touch(&self, session_key: &str, now: DateTimeWithTimeZone) -> Result<(), AppError> {
+        Session::update_many()
+            .col_expr(session::Column::LastActivity, Expr::value(now))
+            .filter(session::Column::SessionKey.eq(session_key))
+            .exec(&self.db)
+            .await?;
+        Ok(())
+    }
+
+    async fn delete(&self, session_key: &str) -> Result<(), AppError> {
+        Session::delete_by_id(session_key.to_owned()).exec(&self.db).await?;
+        Ok(())
+    }
+
+    async fn list_for_user(&self, user_id: i32) -> Result<Vec<session::Model>, AppError> {
+        Ok(Session::find()
+            .filter(session::Column::UserId.eq(user_id))
+            .all(&self.db)
+            .await?)
+    }
+
+    async fn delete_for_user(&self, user_id: i32, except: Option<&str>) -> Result<u64, AppError> {
+        let mut query = Session::delete_many().filter(session::Column::UserId.eq(user_id));
+        if let Some(key) = except {
+            query = query.filter(session::Column::SessionKey.ne(key));
+        }
+        Ok(query.exec(&self.db).await?.rows_affected)
+    }
+
+    async fn clear_expired(&self) -> Result<u64, AppError> {
+        let now: DateTimeWithTimeZone = Utc::now().into();
+        let result = Session::delete_many()
+            .filter(session::Column::ExpireDate.lte(now))
+            .exec(&self.db)
+            .await?;
+        Ok(result.rows_affected)
+    }
+}
@@ -0,0 +1,133 @@
+use std::collections::HashMap;
+use std::sync::Mutex;
+use sea_orm::prelude::DateTimeWithTimeZone;
+use chrono::Utc;
+use crate::entities::session;
+use crate::errors::AppError;
+use super::SessionStore;
+
+/// プロセス内のメモリにセッションを保存するバックエンド。
+/// Djangoの `django.contrib.sessions.backends.cache` (LocMemCache) に相当します。
+///
+/// サーバーを再起動すると全セッションが失われ、複数プロセス間でも共有されません。
+/// 開発やテスト用途を想定しています。
+#[derive(Default)]
+pub struct InMemorySessionStore {
+    sessions: Mutex<HashMap<String, session::Model>>,
+}
+
+impl InMemorySessionStore {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    fn lock(&self) -> Result<std::sync::MutexGuard<'_, HashMap<String, session::Model>>, AppError> {
+        self.sessions
+            .lock()
+            .map_err(|_| AppError::Internal("Session store lock poisoned".into()))
+    }
+}
+
+#[rocket::async_trait]
+impl SessionStore for InMemorySessionStore {
+    async fn create(&self, session: session::Model) -> Result<(), AppError> {
+        self.lock()?.insert(session.session_key.clone(), session);
+        Ok(())
+    }
+
+    async fn load(&self, session_key: &str) -> Result<Option<session::Model>, AppError> {
+        Ok(self.lock()?.get(session_key).cloned())
+    }
+
+    async fn touch(&self, session_key: &str, now: DateTimeWithTimeZone) -> Result<(), AppError> {
+        if let Some(session) = self.lock()?.get_mut(session_key) {
+            session.last_activity = now;
+        }
+        Ok(())
+    }
+
+    async fn delete(&self, session_key: &str) -> Result<(), AppError> {
+        self.lock()?.remove(session_key);
+        Ok(())
+    }
+
+    async fn list_for_user(&self, user_id: i32) -> Result<Vec<session::Model>, AppError> {
+        Ok(self
+            .lock()?
+            .values()
+            .filter(|s| s.user_id == user_id)
+            .cloned()
+            .collect())
+    }
+
+    async fn delete_for_user(&self, user_id: i32, except: Option<&str>) -> Result<u64, AppError> {
+        let mut sessions = self.lock()?;
+        let before = sessions.len();
+        sessions.retain(|key, s| s.user_id != user_id || Some(key.as_str()) == except);
+        Ok((before - sessions.len()) as u64)
+    }
+
+    async fn clear_expired(&self) -> Result<u64, AppError> {
+        let now = Utc::now();
+        let mut sessions = self.lock()?;
+        let before = sessions.len();
+        sessions.retain(|_, s| s.expire_date > now);
+        Ok((before - sessions.len()) as u64)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use chrono::Duration;
+
+    fn make_session(key: &str, user_id: i32, expires_in: Duration) -> session::Model {
+        let now = Utc::now();
+        session::Model {
+            session_key: key.to_string(),
+            user_id,
+            created_at: now.into(),
+            last_activity: now.into(),
+            expire_date: (now + expires_in).into(),
+            ip_address: None,
+            user_agent: None,
+        }
+    }
+
+    #[rocket::async_test]
+    async fn test_create_load_delete() {
+        let store = InMemorySessionStore::new();
+        store.create(make_session("key1", 1, Duration::hours(1))).await.unwrap();
+
+        assert_eq!(store.load("key1").await.unwrap().unwrap().user_id, 1);
+
+        store.delete("key1").await.unwrap();
+        assert!(store.load("key1").await.unwrap().is_none());
+    }
+
+    #[rocket::async_test]
+    async fn test_delete_for_user_keeps_current_session() {
+        let store = InMemorySessionStore::new();
+        store.create(make_session("a", 1, Duration::hours(1))).await.unwrap();
+        store.create(make_session("b", 1, Duration::hours(1))).await.unwrap();
+        store.create(make_session("c", 2, Duration::hours(1))).await.unwrap();
+
+        let deleted = store.delete_for_user(1, Some("a")).await.unwrap();
+
+        assert_eq!(deleted, 1);
+        assert!(store.load("a").await.unwrap().is_some());
+        assert!(store.load("b").await.unwrap().is_none());
+        assert!(store.load("c").await.unwrap().is_some());
+    }
+
+    #[rocket::async_test]
+    async fn test_clear_expired() {
+        let store = InMemorySessionStore::new();
+        store.create(make_session("live", 1, Duration::hours(1))).await.unwrap();
+        store.create(make_session("dead", 1, Duration::hours(-1))).await.unwrap();
+
+        assert_eq!(store.clear_expired().await.unwrap(), 1);
+        assert!(store.load("live").await.unwrap().is_some());
+        assert!(store.load("dead").await.unwrap().is_none());
+    }
+}
@@ -0,0 +1,268 @@
+use base64::{Engine as _, engine::general_purpose::URL_SAFE_NO_PAD};
+use chrono::{Duration, Utc};
+use rand::Rng;
+use rocket::http::{Cookie, CookieJar, SameSite};
+use rocket::request::{FromRequest, Outcome, Request};
+use sea_orm::DatabaseConnection;
+use sea_orm::prelude::DateTimeWithTimeZone;
+use serde::Serialize;
+use crate::entities::session;
+use crate::errors::AppError;
+use crate::settings::Settings;
+
+pub mod database;
+pub mod memory;
+
+pub use database::DatabaseSessionStore;
+pub use memory::InMemorySessionStore;
+
+/// セッションキーを格納するCookie名 (Django: SESSION_COOKIE_NAME)
+pub const SESSION_COOKIE_NAME: &str = "sessionid";
+
+/// セッションの有効期間のデフォルト値 (秒)。Djangoと同じ2週間。
+pub(crate) const DEFAULT_SESSION_COOKIE_AGE: i64 = 60 * 60 * 24 * 14;
+
+/// 最終アクセス日時を更新する間隔 (秒)。
+/// リクエストごとに書き込みが発生しないよう間引きます。
+const LAST_ACTIVITY_UPDATE_INTERVAL: i64 = 60;
+
+/// セッションの保存先を抽象化するトレイト。
+/// Djangoの `SESSION_ENGINE` (`django.contrib.sessions.backends.*`) に相当します。
+#[rocket::async_trait]
+pub trait SessionStore: Send + Sync {
+    /// セッションを保存する
+    async fn create(&self, session: session::Model) -> Result<(), AppError>;
+
+    /// セッションキーからセッションを取得する (期限切れでも返す)
+    async fn load(&self, session_key: &str) -> Result<Option<session::Model>, AppError>;
+
+    /// 最終アクセス日時を更新する
+    async fn touch(&self, session_key: &str, now: DateTimeWithTimeZone) -> Result<(), AppError>;
+
+    /// セッションを削除する
+    async fn delete(&self, session_key: &str) -> Result<(), AppError>;
+
+    /// 指定ユーザーのセッション一覧を取得する (期限切れも含む)
+    async fn list_for_user(&self, user_id: i32) -> Result<Vec<session::Model>, AppError>;
+
+    /// 指定ユーザーのセッションを削除し、削除件数を返す。
+    /// `except` を指定した場合はそのセッションを残します。
+    async fn delete_for_user(&self, user_id: i32, except: Option<&str>) -> Result<u64, AppError>;
+
+    /// 期限切れセッションを削除する (Django: `manage.py clearsessions`)
+    async fn clear_expired(&self) -> Result<u64, AppError>;
+}
+
+/// 画面表示用のセッション情報。
+/// セッションキーの代わりに `public_id` を `id` として公開します。
+#[derive(Debug, Serialize)]
+pub struct SessionInfo {
+    pub id: String,
+    pub created_at: DateTimeWithTimeZone,
+    pub last_activity: DateTimeWithTimeZone,
+    pub expire_date: DateTimeWithTimeZone,
+    pub ip_address: Option<String>,
+    pub user_agent: Option<String>,
+    /// リクエスト中のセッションかどうか
+    pub is_current: bool,
+}
+
+impl SessionInfo {
+    pub fn new(session: &session::Model, current_key: Option<&str>) -> Self {
+        SessionInfo {
+            id: session.public_id(),
+            created_at: session.created_at,
+            last_activity: session.last_activity,
+            expire_date: session.expire_date,
+            ip_address: session.ip_address.clone(),
+            user_agent: session.user_agent.clone(),
+            is_current: current_key == Some(session.session_key.as_str()),
+        }
+    }
+}
+
+/// ログイン時に記録するクライアント情報。
+pub struct ClientInfo {
+    pub ip_address: Option<String>,
+    pub user_agent: Option<String>,
+}
+
+#[rocket::async_trait]
+impl<'r> FromRequest<'r> for ClientInfo {
+    type Error = std::convert::Infallible;
+
+    async fn from_request(request: &'r Request<'_>) -> Outcome<Self, Self::Error> {
+        Outcome::Success(ClientInfo {
+            ip_address: request.client_ip().map(|ip| ip.to_string()),
+            user_agent: request.headers().get_one("User-Agent").map(|s| s.to_string()),
+        })
+    }
+}
+
+/// セッションの発行・検証・破棄を行うマネージャー。
+/// Rocketの managed state として登録し、`AuthenticatedUser` ガードやログイン処理から利用します。
+/// Djangoの `django.contrib.auth.login()` / `logout()` と `SessionMiddleware` の役割を担います。
+pub struct SessionManager {
+    store: Box<dyn SessionStore>,
+    cookie_age: Duration,
+    cookie_secure: bool,
+}
+
+impl SessionManager {
+    pub fn new(store: Box<dyn SessionStore>, cookie_age: Duration) -> Self {
+        SessionManager { store, cookie_age, cookie_secure: false }
+    }
+
+    /// 設定からバックエンドを選択して構築します。
+    ///
+    /// - `SESSION_BACKEND`: `db` (デフォルト) または `memory`
+    /// - `SESSION_COOKIE_AGE`: セッションの有効期間 (秒)
+    /// - `SESSION_COOKIE_SECURE`: `true` の場合、CookieをHTTPSでのみ送信します
+    pub fn from_settings(settings: &Settings, db: DatabaseConnection) -> Self {
+        let store: Box<dyn SessionStore> = match settings.session_backend.as_str() {
+            "memory" => Box::new(InMemorySessionStore::new()),
+            _ => Box::new(DatabaseSessionStore::new(db)),
+        };
+
+        SessionManager::new(store, Duration::seconds(settings.session_cookie_age))
+            .with_secure_cookie(settings.session_cookie_secure)
+    }
+
+    /// Cookieに `Secure` 属性を付けるか (Django: `SESSION_COOKIE_SECURE`)
+    pub fn with_secure_cookie(mut self, secure: bool) -> Self {
+        self.cookie_secure = secure;
+        self
+    }
+
+    pub fn store(&self) -> &dyn SessionStore {
+        self.store.as_ref()
+    }
+
+    /// 新しいセッションキーを生成する
+    fn generate_key() -> String {
+        let random_bytes: [u8; 32] = rand::thread_rng().gen();
+        URL_SAFE_NO_PAD.encode(random_bytes)
+    }
+
+    /// ログイン処理。セッションを作成してCookieにセッションキーを保存します。
+    /// Djangoの `login(request, user)` に相当。
+    pub async fn login(
+        &self,
+        cookies: &CookieJar<'_>,
+        user_id: i32,
+        client: &ClientInfo,
+    ) -> Result<session::Model, AppError> {
+        // 既存のセッションがあれば破棄 (セッション固定攻撃対策)
+        self.logout(cookies).await?;
+        self.store.clear_expired().await?;
+
+        let now = Utc::now();
+        let session = session::Model {
+            session_key: Self::generate_key(),
+            user_id,
+            created_at: now.into(),
+            last_activity: now.into(),
+            expire_date: (now + self.cookie_age).into(),
+            ip_address: client.ip_address.clone(),
+            user_agent: client.user_agent.clone(),
+        };
+        self.store.create(session.clone()).await?;
+
+        let cookie = Cookie::build((SESSION_COOKIE_NAME, session.session_key.clone()))
+            .path("/")
+            .same_site(SameSite::Lax)
+            .http_only(true)
+            .secure(self.cookie_secure)
+            .max_age(rocket::time::Duration::seconds(self.cookie_age.num_seconds()));
+        cookies.add_private(cookie);
+
+        Ok(session)
+    }
+
+    /// ログアウト処理。サーバー側のセッションを削除し、Cookieも破棄します。
+    /// Djangoの `logout(request)` に相当。
+    pub async fn logout(&self, cookies: &CookieJar<'_>) -> Result<(), AppError> {
+        if let Some(cookie) = cookies.get_private(SESSION_COOKIE_NAME) {
+            self.store.delete(cookie.value()).await?;
+            cookies.remove_private(Cookie::build(SESSION_COOKIE_NAME).path("/"));
+        }
+        Ok(())
+    }
+
+    /// Cookieのセッションキーから有効なセッションを取得します。
+    /// 期限切れのセッションは削除され `None` を返します。
+    pub async fn current(&self, cookies: &CookieJar<'_>) -> Result<Option<session::Model>, AppError> {
+        let session_key = match cookies.get_private(SESSION_COOKIE_NAME) {
+            Some(cookie) => cookie.value().to_string(),
+            None => return Ok(None),
+        };
+
+        let session = match self.store.load(&session_key).await? {
+            Some(s) => s,
+            None => return Ok(None),
+        };
+
+        let now = Utc::now();
+        if session.expire_date <= now {
+            self.store.delete(&session_key).await?;
+            return Ok(None);
+        }
+
+        if (now - session.last_activity.with_timezone(&Utc)).num_seconds() >= LAST_ACTIVITY_UPDATE_INTERVAL {
+            self.store.touch(&session_key, now.into()).await?;
+        }
+
+        Ok(Some(session))
+    }
+
+    /// ユーザーの有効なセッション一覧を、最終アクセスの新しい順に取得します。
+    pub async fn list_for_user(&self, user_id: i32) -> Result<Vec<session::Model>, AppError> {
+        let now = Utc::now();
+        let mut sessions: Vec<session::Model> = self
+            .store
+            .list_for_user(user_id)
+            .await?
+            .into_iter()
+            .filter(|s| s.expire_date > now)
+            .collect();
+        sessions.sort_by_key(|s| std::cmp::Reverse(s.last_activity));
+        Ok(sessions)
+    }
+
+    /// `public_id` で指定したユーザーのセッションを1件無効化します。
+    /// 該当するセッションがなければ `false` を返します。
+    pub async fn revoke(&self, user_id: i32, public_id: &str) -> Result<bool, AppError> {
+        let target = self
+            .store
+            .list_for_user(user_id)
+            .await?
+            .into_iter()
+            .find(|s| s.public_id() == public_id);
+
+        match target {
+            Some(session) => {
+                self.store.delete(&session.session_key).await?;
+                Ok(true)
+            }
+            None => Ok(false),
+        }
+    }
+
+    /// ユーザーの全セッションを無効化します (パスワード変更時など)。
+    /// `keep` に現在のセッションキーを渡すと、そのセッションのみ残します。
+    pub async fn invalidate_user(&self, user_id: i32, keep: Option<&str>) -> Result<u64, AppError> {
+        self.store.delete_for_user(user_id, keep).await
+    }
+
+    /// パスワード変更後、他の端末のセッションを全て無効化し、現在の端末には新しいセッションキーを発行します。
+    /// Djangoの `update_session_auth_hash(request, user)` に相当。
+    pub async fn update_session_auth_hash(
+        &self,
+        cookies: &CookieJar<'_>,
+        user_id: i32,
+        client: &ClientInfo,
+    ) -> Result<session::Model, AppError> {
+        self.invalidate_user(user_id, None).await?;
+        self.login(cookies, user_id, client).await
+    }
+}
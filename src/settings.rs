@@ -0,0 +1,490 @@
+//! アプリケーションの設定。Djangoの `settings.py` に相当します。
+//!
+//! 設定は次の順に読み込み、後のものほど優先されます。
+//!
+//! 1. 各項目のデフォルト値 (`Settings::default()`)
+//! 2. `Rocket.toml` の `[default]` テーブル
+//! 3. `Rocket.toml` のプロファイル別テーブル (`[debug]` / `[release]`。`ROCKET_PROFILE` で選択)
+//! 4. 環境変数 (`.env` ファイルを含む)。項目名を大文字にしたもの (例: `login_redirect_url` → `LOGIN_REDIRECT_URL`)
+//!
+//! 起動時に `Settings::load()` で読み込んで検証し、Rocketの managed state として登録します。
+//! ガードやビューからは `&State<Settings>` で、テンプレートからは `settings.<項目名>` で参照できます
+//! (パスワードなどの秘密情報はテンプレートに渡しません)。
+
+use rocket::figment::providers::Env;
+use rocket::figment::Figment;
+use serde::de::{self, Deserializer, Unexpected};
+use serde::{Deserialize, Serialize};
+use std::fmt;
+use crate::csrf::DEFAULT_CSRF_COOKIE_AGE;
+use crate::fairings::security::{DEFAULT_CSP, REFERRER_POLICY_VALUES};
+use crate::mail::DEFAULT_FROM_EMAIL;
+use crate::services::registration::DEFAULT_ACCOUNT_ACTIVATION_DAYS;
+use crate::sessions::DEFAULT_SESSION_COOKIE_AGE;
+use crate::tokens::{secret_from_figment, DEFAULT_PASSWORD_RESET_TIMEOUT};
+use crate::validation::is_safe_redirect_url;
+
+/// `AUTH_PASSWORD_VALIDATORS` に指定できるルール名
+pub const PASSWORD_VALIDATOR_NAMES: [&str; 4] = ["user_attribute_similarity", "minimum_length", "common", "numeric"];
+
+/// アプリケーションの設定。
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(default)]
+pub struct Settings {
+    /// 接続先のデータベース (Django: `DATABASES`)
+    #[serde(skip_serializing)]
+    pub database_url: String,
+    /// 署名に使う鍵 (Rocket.toml の `secret_key` / `ROCKET_SECRET_KEY`)。未設定の場合は起動ごとにランダムな鍵
+    #[serde(skip)]
+    secret_key: Vec<u8>,
+
+    // --- サイト ---------------------------------------------------------------
+    /// メール内のリンクやOIDCのコールバックURLに使うサイトのURL (例: `https://example.com`)。
+    /// 設定した場合はリクエストの `Host` / `X-Forwarded-Proto` ヘッダーを使いません
+    #[serde(deserialize_with = "optional_string")]
+    pub site_url: Option<String>,
+    /// `SITE_URL` が未設定の場合に、リンクの組み立てに使ってよいホスト名 (Django: `ALLOWED_HOSTS`)。
+    /// `.example.com` はサブドメインを含み、`*` は全てのホストを許可します
+    #[serde(deserialize_with = "string_list")]
+    pub allowed_hosts: Vec<String>,
+
+    // --- 認証 -------------------------------------------------------------
+    /// 未ログイン時のリダイレクト先 (Django: `LOGIN_URL`)
+    pub login_url: String,
+    /// `next` がない場合のログイン後のリダイレクト先 (Django: `LOGIN_REDIRECT_URL`)
+    pub login_redirect_url: String,
+    /// パスワードリセットトークンの有効期間 (秒)
+    pub password_reset_timeout: i64,
+    /// 使用するパスワードの強度チェック (`PASSWORD_VALIDATOR_NAMES`。カンマ区切り、空文字の場合はなし)
+    #[serde(deserialize_with = "string_list")]
+    pub auth_password_validators: Vec<String>,
+    /// パスワードの最小文字数
+    pub password_min_length: usize,
+    /// ユーザー属性との類似度の上限
+    pub password_max_similarity: f64,
+    /// よく使われるパスワード一覧のファイルパス (未設定の場合は同梱の一覧)
+    pub password_common_list: Option<String>,
+
+    // --- ログイン試行制限 (django-axes) ------------------------------------
+    pub login_failure_limit: i32,
+    pub login_ip_failure_limit: i32,
+    /// 失敗回数をリセットするまでの期間 (秒)
+    pub login_failure_window: i64,
+    /// ロック期間の初期値と上限 (秒)
+    pub login_lockout_duration: i64,
+    pub login_lockout_max_duration: i64,
+
+    // --- 2要素認証 (django-otp) --------------------------------------------
+    #[serde(deserialize_with = "flag")]
+    pub otp_required_for_admins: bool,
+    pub otp_issuer: String,
+
+    // --- ユーザー登録 (django-registration) --------------------------------
+    #[serde(deserialize_with = "flag")]
+    pub signup_enabled: bool,
+    pub account_activation_days: i64,
+    #[serde(deserialize_with = "string_list")]
+    pub signup_default_groups: Vec<String>,
+
+    // --- セッション ----------------------------------------------------------
+    /// `db` または `memory` (Django: `SESSION_ENGINE`)
+    pub session_backend: String,
+    /// セッションの有効期間 (秒)
+    pub session_cookie_age: i64,
+    /// セッションのCookieをHTTPSでのみ送信する
+    #[serde(deserialize_with = "flag")]
+    pub session_cookie_secure: bool,
+
+    // --- CSRF ---------------------------------------------------------------
+    /// CSRFトークンの有効期間 (秒)
+    pub csrf_cookie_age: u64,
+    /// CSRFトークンのCookieを常にHTTPSでのみ送信する (未設定の場合はHTTPSのリクエストでのみ付与)
+    #[serde(deserialize_with = "flag")]
+    pub csrf_cookie_secure: bool,
+    /// 別オリジンからの送信を許可するオリジン (例: `https://*.example.com`)
+    #[serde(deserialize_with = "string_list")]
+    pub csrf_trusted_origins: Vec<String>,
+
+    // --- セキュリティヘッダー (Djangoの SecurityMiddleware) -------------------------
+    /// HTTPSのレスポンスに付ける `Strict-Transport-Security` の `max-age` (秒)。`0` の場合は付けない
+    pub secure_hsts_seconds: u64,
+    #[serde(deserialize_with = "flag")]
+    pub secure_hsts_include_subdomains: bool,
+    #[serde(deserialize_with = "flag")]
+    pub secure_hsts_preload: bool,
+    /// `X-Content-Type-Options: nosniff` を付ける
+    #[serde(deserialize_with = "flag")]
+    pub secure_content_type_nosniff: bool,
+    /// `DENY` または `SAMEORIGIN` (空の場合は付けない)
+    pub x_frame_options: String,
+    /// `Referrer-Policy` (空の場合は付けない)
+    pub secure_referrer_policy: String,
+    /// `Cross-Origin-Opener-Policy` (空の場合は付けない)
+    pub secure_cross_origin_opener_policy: String,
+    /// `Content-Security-Policy`。`{nonce}` はリクエストごとのnonceに置き換える (空の場合は付けない)
+    pub secure_csp: String,
+    /// `Content-Security-Policy-Report-Only` (空の場合は付けない)
+    pub secure_csp_report_only: String,
+
+    // --- メール ---------------------------------------------------------------
+    /// `console`、`file` または `smtp`
+    pub email_backend: String,
+    /// `file` バックエンドの出力先ディレクトリ
+    pub email_file_path: Option<String>,
+    pub default_from_email: String,
+    pub email_host: String,
+    pub email_port: u16,
+    #[serde(skip_serializing, deserialize_with = "optional_string")]
+    pub email_host_user: Option<String>,
+    #[serde(skip_serializing, deserialize_with = "optional_string")]
+    pub email_host_password: Option<String>,
+    #[serde(deserialize_with = "flag")]
+    pub email_use_tls: bool,
+    #[serde(deserialize_with = "flag")]
+    pub email_use_ssl: bool,
+
+    // --- 外部IdP (OIDC) -------------------------------------------------------
+    /// 有効にするIdPの名前。IdPごとの設定は `OIDC_<NAME>_*` 環境変数で指定します
+    #[serde(deserialize_with = "string_list")]
+    pub oidc_providers: Vec<String>,
+
+    // --- 管理画面 -------------------------------------------------------------
+    /// 一覧画面の1ページあたりの件数 (Django: `ModelAdmin.list_per_page`)
+    pub list_per_page: u64,
+
+    // --- システムチェック -------------------------------------------------------
+    /// 表示しないチェックのID (例: `security.W008`。Django: `SILENCED_SYSTEM_CHECKS`)
+    #[serde(deserialize_with = "string_list")]
+    pub silenced_system_checks: Vec<String>,
+}
+
+impl Default for Settings {
+    fn default() -> Self {
+        Settings {
+            database_url: String::new(),
+            secret_key: Vec::new(),
+            site_url: None,
+            allowed_hosts: vec!["localhost".to_string(), "127.0.0.1".to_string(), "[::1]".to_string()],
+            login_url: "/auth/login".to_string(),
+            login_redirect_url: "/".to_string(),
+            password_reset_timeout: DEFAULT_PASSWORD_RESET_TIMEOUT,
+            auth_password_validators: PASSWORD_VALIDATOR_NAMES.iter().map(|name| name.to_string()).collect(),
+            password_min_length: 8,
+            password_max_similarity: 0.7,
+            password_common_list: None,
+            login_failure_limit: 5,
+            login_ip_failure_limit: 20,
+            login_failure_window: 60 * 15,
+            login_lockout_duration: 60,
+            login_lockout_max_duration: 60 * 60 * 24,
+            otp_required_for_admins: false,
+            otp_issuer: "Rust Django Starter".to_string(),
+            signup_enabled: false,
+            account_activation_days: DEFAULT_ACCOUNT_ACTIVATION_DAYS,
+            signup_default_groups: Vec::new(),
+            session_backend: "db".to_string(),
+            session_cookie_age: DEFAULT_SESSION_COOKIE_AGE,
+            session_cookie_secure: false,
+            csrf_cookie_age: DEFAULT_CSRF_COOKIE_AGE,
+            csrf_cookie_secure: false,
+            csrf_trusted_origins: Vec::new(),
+            secure_hsts_seconds: 0,
+            secure_hsts_include_subdomains: false,
+            secure_hsts_preload: false,
+            secure_content_type_nosniff: true,
+            x_frame_options: "DENY".to_string(),
+            secure_referrer_policy: "same-origin".to_string(),
+            secure_cross_origin_opener_policy: "same-origin".to_string(),
+            secure_csp: DEFAULT_CSP.to_string(),
+            secure_csp_report_only: String::new(),
+            email_backend: "console".to_string(),
+            email_file_path: None,
+            default_from_email: DEFAULT_FROM_EMAIL.to_string(),
+            email_host: "localhost".to_string(),
+            email_port: 25,
+            email_host_user: None,
+            email_host_password: None,
+            email_use_tls: false,
+            email_use_ssl: false,
+            oidc_providers: Vec::new(),
+            list_per_page: 10,
+            silenced_system_checks: Vec::new(),
+        }
+    }
+}
+
+impl Settings {
+    /// Rocket.toml (プロファイル別) と環境変数を合わせた設定ソース。
+    /// 環境変数はプロファイルに関係なく最優先です。
+    pub fn figment() -> Figment {
+        rocket::Config::figment().merge(Env::raw().ignore(&["SECRET_KEY"]).global())
+    }
+
+    /// `.env` を含む環境変数と Rocket.toml から設定を読み込み、検証します。
+    pub fn load() -> Result<Self, String> {
+        dotenvy::dotenv().ok();
+        Settings::from_figment(&Settings::figment())
+    }
+
+    /// 設定ソースから読み込み、検証します。誤りがあれば全ての項目のエラーをまとめて返します。
+    pub fn from_figment(figment: &Figment) -> Result<Self, String> {
+        let mut settings: Settings = figment.extract().map_err(|e| format!("設定を読み込めません: {}", e))?;
+        settings.secret_key = secret_from_figment(figment);
+        settings.validate().map_err(|errors| errors.join("\n"))?;
+        Ok(settings)
+    }
+
+    /// 署名に使う鍵
+    pub fn secret_key(&self) -> &[u8] {
+        &self.secret_key
+    }
+
+    /// 値の組み合わせや範囲を検証します (Djangoの `manage.py check` の設定チェックに相当)。
+    pub fn validate(&self) -> Result<(), Vec<String>> {
+        let mut errors = Vec::new();
+        let mut check = |ok: bool, message: String| {
+            if !ok {
+                errors.push(message);
+            }
+        };
+
+        check(!self.database_url.trim().is_empty(), "DATABASE_URL が設定されていません".into());
+        if let Some(site_url) = &self.site_url {
+            check(
+                split_site_url(site_url).is_some(),
+                format!("SITE_URL はスキームとホスト名で指定してください (例: https://example.com): {}", site_url),
+            );
+        }
+        check(is_safe_redirect_url(&self.login_url), format!("LOGIN_URL は `/` から始まるパスで指定してください: {}", self.login_url));
+        check(
+            is_safe_redirect_url(&self.login_redirect_url),
+            format!("LOGIN_REDIRECT_URL は `/` から始まるパスで指定してください: {}", self.login_redirect_url),
+        );
+        for name in &self.auth_password_validators {
+            check(PASSWORD_VALIDATOR_NAMES.contains(&name.as_str()), format!("不明なパスワードバリデーターです: {}", name));
+        }
+        check(
+            (0.1..=1.0).contains(&self.password_max_similarity),
+            format!("PASSWORD_MAX_SIMILARITY は 0.1 以上 1.0 以下で指定してください: {}", self.password_max_similarity),
+        );
+        for (name, value) in [
+            ("PASSWORD_RESET_TIMEOUT", self.password_reset_timeout),
+            ("LOGIN_FAILURE_WINDOW", self.login_failure_window),
+            ("LOGIN_LOCKOUT_DURATION", self.login_lockout_duration),
+            ("LOGIN_LOCKOUT_MAX_DURATION", self.login_lockout_max_duration),
+            ("ACCOUNT_ACTIVATION_DAYS", self.account_activation_days),
+            ("SESSION_COOKIE_AGE", self.session_cookie_age),
+            ("CSRF_COOKIE_AGE", self.csrf_cookie_age as i64),
+            ("LIST_PER_PAGE", self.list_per_page as i64),
+        ] {
+            check(value > 0, format!("{} は1以上で指定してください: {}", name, value));
+        }
+        check(
+            matches!(self.session_backend.as_str(), "db" | "memory"),
+            format!("SESSION_BACKEND は `db` または `memory` で指定してください: {}", self.session_backend),
+        );
+        for origin in &self.csrf_trusted_origins {
+            check(
+                origin.starts_with("http://") || origin.starts_with("https://"),
+                format!("CSRF_TRUSTED_ORIGINS にはスキームを含めてください (例: https://example.com): {}", origin),
+            );
+        }
+        check(
+            matches!(self.x_frame_options.as_str(), "DENY" | "SAMEORIGIN" | ""),
+            format!("X_FRAME_OPTIONS は `DENY` または `SAMEORIGIN` で指定してください: {}", self.x_frame_options),
+        );
+        for policy in self.secure_referrer_policy.split(',').map(str::trim).filter(|p| !p.is_empty()) {
+            check(REFERRER_POLICY_VALUES.contains(&policy), format!("SECURE_REFERRER_POLICY に不明な値があります: {}", policy));
+        }
+        check(
+            matches!(self.secure_cross_origin_opener_policy.as_str(), "same-origin" | "same-origin-allow-popups" | "unsafe-none" | ""),
+            format!(
+                "SECURE_CROSS_ORIGIN_OPENER_POLICY は `same-origin`、`same-origin-allow-popups` または `unsafe-none` で指定してください: {}",
+                self.secure_cross_origin_opener_policy
+            ),
+        );
+        check(
+            matches!(self.email_backend.as_str(), "console" | "file" | "smtp"),
+            format!("EMAIL_BACKEND は `console`、`file` または `smtp` で指定してください: {}", self.email_backend),
+        );
+        check(
+            self.email_backend != "file" || self.email_file_path.is_some(),
+            "EMAIL_BACKEND が `file` の場合は EMAIL_FILE_PATH を設定してください".into(),
+        );
+        check(
+            !(self.email_use_tls && self.email_use_ssl),
+            "EMAIL_USE_TLS と EMAIL_USE_SSL は同時に有効にできません".into(),
+        );
+
+        if errors.is_empty() { Ok(()) } else { Err(errors) }
+    }
+}
+
+/// `SITE_URL` をプロトコルとドメイン (ポートを含む) に分けます。パスを含む場合は `None`
+pub fn split_site_url(site_url: &str) -> Option<(&'static str, &str)> {
+    let (protocol, rest) = if let Some(rest) = site_url.strip_prefix("https://") {
+        ("https", rest)
+    } else {
+        ("http", site_url.strip_prefix("http://")?)
+    };
+    let domain = rest.strip_suffix('/').unwrap_or(rest);
+    if domain.is_empty() || domain.contains(['/', '?', '#', '@']) {
+        return None;
+    }
+    Some((protocol, domain))
+}
+
+/// `true` / `false` のほか、環境変数で使われる `1` / `0`、`yes` / `no` も受け付ける
+fn flag<'de, D: Deserializer<'de>>(deserializer: D) -> Result<bool, D::Error> {
+    struct Visitor;
+
+    impl de::Visitor<'_> for Visitor {
+        type Value = bool;
+
+        fn expecting(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+            f.write_str("a boolean")
+        }
+
+        fn visit_bool<E: de::Error>(self, value: bool) -> Result<bool, E> {
+            Ok(value)
+        }
+
+        fn visit_i64<E: de::Error>(self, value: i64) -> Result<bool, E> {
+            Ok(value != 0)
+        }
+
+        fn visit_u64<E: de::Error>(self, value: u64) -> Result<bool, E> {
+            Ok(value != 0)
+        }
+
+        fn visit_str<E: de::Error>(self, value: &str) -> Result<bool, E> {
+            match value.to_lowercase().as_str() {
+                "1" | "true" | "yes" | "on" => Ok(true),
+                "0" | "false" | "no" | "off" | "" => Ok(false),
+                _ => Err(E::invalid_value(Unexpected::Str(value), &self)),
+            }
+        }
+    }
+
+    deserializer.deserialize_any(Visitor)
+}
+
+/// カンマ区切りの文字列 (環境変数) と配列 (Rocket.toml) のどちらも受け付ける
+fn string_list<'de, D: Deserializer<'de>>(deserializer: D) -> Result<Vec<String>, D::Error> {
+    #[derive(Deserialize)]
+    #[serde(untagged)]
+    enum List {
+        Comma(String),
+        Items(Vec<String>),
+    }
+
+    Ok(match List::deserialize(deserializer)? {
+        List::Comma(value) => value
+            .split(',')
+            .map(str::trim)
+            .filter(|item| !item.is_empty())
+            .map(str::to_string)
+            .collect(),
+        List::Items(items) => items,
+    })
+}
+
+/// 数字だけのパスワードなど、環境変数から数値として読み込まれた値も文字列として受け付ける。
+/// 空文字は未設定として扱います。
+fn optional_string<'de, D: Deserializer<'de>>(deserializer: D) -> Result<Option<String>, D::Error> {
+    #[derive(Deserialize)]
+    #[serde(untagged)]
+    enum Text {
+        String(String),
+        Integer(i64),
+        Float(f64),
+    }
+
+    Ok(Option::<Text>::deserialize(deserializer)?
+        .map(|text| match text {
+            Text::String(value) => value,
+            Text::Integer(value) => value.to_string(),
+            Text::Float(value) => value.to_string(),
+        })
+        .filter(|value| !value.is_empty()))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use rocket::figment::providers::{Format, Serialized, Toml};
+    use rocket::figment::Profile;
+
+    const TOML: &str = r#"
+        [default]
+        database_url = "postgres://localhost/app"
+        login_redirect_url = "/todo"
+        csrf_trusted_origins = ["https://*.example.com"]
+
+        [release]
+        session_cookie_secure = true
+        list_per_page = 50
+    "#;
+
+    fn figment(profile: &str) -> Figment {
+        Figment::new().merge(Toml::string(TOML).nested()).select(Profile::new(profile))
+    }
+
+    #[test]
+    fn test_defaults_and_profiles() {
+        let settings = Settings::from_figment(&figment("debug")).unwrap();
+        assert_eq!(settings.login_redirect_url, "/todo");
+        assert_eq!(settings.csrf_trusted_origins, vec!["https://*.example.com"]);
+        assert_eq!(settings.list_per_page, 10);
+        assert!(!settings.session_cookie_secure);
+        assert_eq!(settings.auth_password_validators.len(), 4);
+
+        let settings = Settings::from_figment(&figment("release")).unwrap();
+        assert!(settings.session_cookie_secure);
+        assert_eq!(settings.list_per_page, 50);
+    }
+
+    #[test]
+    fn test_environment_style_values() {
+        // 環境変数は文字列か、数値として解釈できる場合は数値として渡される
+        let figment = figment("debug").merge(Serialized::globals(rocket::serde::json::json!({
+            "signup_enabled": "yes",
+            "signup_default_groups": "editors, viewers",
+            "auth_password_validators": "",
+            "email_host_password": 12345,
+            "email_host_user": "",
+        })));
+        let settings = Settings::from_figment(&figment).unwrap();
+        assert!(settings.signup_enabled);
+        assert_eq!(settings.signup_default_groups, vec!["editors", "viewers"]);
+        assert!(settings.auth_password_validators.is_empty());
+        assert_eq!(settings.email_host_password.as_deref(), Some("12345"));
+        assert!(settings.email_host_user.is_none());
+    }
+
+    #[test]
+    fn test_validation_reports_every_error() {
+        let figment = figment("debug").merge(Serialized::globals(rocket::serde::json::json!({
+            "database_url": "",
+            "login_redirect_url": "https://evil.test/",
+            "list_per_page": 0,
+            "session_backend": "redis",
+            "csrf_trusted_origins": "example.com",
+            "site_url": "https://example.com/app",
+            "email_backend": "file",
+        })));
+        let errors = Settings::from_figment(&figment).unwrap_err();
+        for name in ["DATABASE_URL", "LOGIN_REDIRECT_URL", "LIST_PER_PAGE", "SESSION_BACKEND", "CSRF_TRUSTED_ORIGINS", "SITE_URL", "EMAIL_FILE_PATH"] {
+            assert!(errors.contains(name), "{} not reported in:\n{}", name, errors);
+        }
+    }
+
+    #[test]
+    fn test_split_site_url() {
+        assert_eq!(split_site_url("https://example.com"), Some(("https", "example.com")));
+        assert_eq!(split_site_url("http://localhost:8000/"), Some(("http", "localhost:8000")));
+        assert_eq!(split_site_url("example.com"), None);
+        assert_eq!(split_site_url("https://example.com/app"), None);
+        assert_eq!(split_site_url("https://"), None);
+    }
+}
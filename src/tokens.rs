@@ -0,0 +1,257 @@
+use base64::{Engine as _, engine::general_purpose::URL_SAFE_NO_PAD};
+use chrono::Utc;
+use hmac::{Hmac, Mac};
+use rand::Rng;
+use rocket::figment::Figment;
+use sha2::{Digest, Sha256};
+use crate::entities::user;
+use crate::settings::Settings;
+
+type HmacSha256 = Hmac<Sha256>;
+
+/// パスワードリセットトークンの有効期間のデフォルト値 (秒)。Djangoと同じ3日間。
+pub(crate) const DEFAULT_PASSWORD_RESET_TIMEOUT: i64 = 60 * 60 * 24 * 3;
+
+/// HMACの鍵を用途ごとに分けるためのソルト (Django: `key_salt`)
+const KEY_SALT: &str = "rust_django_starter.tokens.PasswordResetTokenGenerator";
+
+/// アカウント有効化トークン用のソルト
+const ACTIVATION_KEY_SALT: &str = "rust_django_starter.tokens.AccountActivationTokenGenerator";
+
+/// パスワードリセット用トークンの生成・検証を行います。
+/// Djangoの `django.contrib.auth.tokens.PasswordResetTokenGenerator` に相当します。
+///
+/// トークンは `<タイムスタンプ(36進数)>-<HMAC>` の形式で、HMACには
+/// ユーザーID・現在の `password_hash`・タイムスタンプを含めます。
+/// パスワードが変更されるとハッシュが変わるため、使用済みのトークンは自動的に無効になります。
+pub struct PasswordResetTokenGenerator {
+    secret: Vec<u8>,
+    timeout: i64,
+    key_salt: &'static str,
+    /// `is_active` と `activated_at` をHMACに含める (有効化後にトークンを無効にするため)
+    include_activation: bool,
+}
+
+/// Rocketの `secret_key` を取得します。未設定の場合は起動ごとにランダムな鍵を使用します。
+pub(crate) fn secret_from_figment(figment: &Figment) -> Vec<u8> {
+    match figment.extract_inner::<String>("secret_key") {
+        Ok(key) => key.into_bytes(),
+        Err(_) => rand::thread_rng().gen::<[u8; 32]>().to_vec(),
+    }
+}
+
+impl PasswordResetTokenGenerator {
+    pub fn new(secret: &[u8], timeout: i64) -> Self {
+        PasswordResetTokenGenerator {
+            secret: secret.to_vec(),
+            timeout,
+            key_salt: KEY_SALT,
+            include_activation: false,
+        }
+    }
+
+    /// 設定から構築します。
+    ///
+    /// - `secret_key`: Rocket.toml / `ROCKET_SECRET_KEY`。未設定の場合は起動ごとにランダムな鍵を使用します
+    /// - `PASSWORD_RESET_TIMEOUT`: トークンの有効期間 (秒)
+    pub fn from_settings(settings: &Settings) -> Self {
+        PasswordResetTokenGenerator::new(settings.secret_key(), settings.password_reset_timeout)
+    }
+
+    /// ユーザーのリセットトークンを生成します。
+    pub fn make_token(&self, user: &user::Model) -> String {
+        self.make_token_with_timestamp(user, Utc::now().timestamp())
+    }
+
+    /// トークンがユーザーに対して有効か検証します。
+    pub fn check_token(&self, user: &user::Model, token: &str) -> bool {
+        let (ts_b36, signature) = match token.split_once('-') {
+            Some(parts) => parts,
+            None => return false,
+        };
+        let timestamp = match i64::from_str_radix(ts_b36, 36) {
+            Ok(ts) => ts,
+            Err(_) => return false,
+        };
+        let signature = match URL_SAFE_NO_PAD.decode(signature) {
+            Ok(sig) => sig,
+            Err(_) => return false,
+        };
+
+        // 定数時間で署名を比較する
+        if self.mac(user, timestamp).verify_slice(&signature).is_err() {
+            return false;
+        }
+
+        let age = Utc::now().timestamp() - timestamp;
+        (0..=self.timeout).contains(&age)
+    }
+
+    fn make_token_with_timestamp(&self, user: &user::Model, timestamp: i64) -> String {
+        let signature = self.mac(user, timestamp).finalize().into_bytes();
+        format!("{}-{}", to_base36(timestamp), URL_SAFE_NO_PAD.encode(signature))
+    }
+
+    /// Djangoの `salted_hmac` と同様に、ソルトと秘密鍵から派生した鍵でHMACを計算する
+    fn mac(&self, user: &user::Model, timestamp: i64) -> HmacSha256 {
+        let key = Sha256::new()
+            .chain_update(self.key_salt.as_bytes())
+            .chain_update(&self.secret)
+            .finalize();
+        let mut mac = HmacSha256::new_from_slice(&key).expect("HMAC can take a key of any size");
+        mac.update(format!("{}{}{}", user.id, user.password_hash, timestamp).as_bytes());
+        if self.include_activation {
+            mac.update(if user.is_active { b"1" } else { b"0" });
+            if let Some(activated_at) = user.activated_at {
+                mac.update(activated_at.timestamp().to_string().as_bytes());
+            }
+        }
+        mac
+    }
+}
+
+/// アカウント有効化用トークンの生成・検証を行います。
+/// django-registration の `HMACActivationView` で使われる署名付きの有効化キーに相当します。
+///
+/// 形式はパスワードリセットと同じですが、別のソルトで署名し、`is_active` と `activated_at` もHMACに含めるため、
+/// 有効化後 (管理者が再び無効化した場合も含む) やパスワードリセットのトークンとしては使えません。
+pub struct AccountActivationTokenGenerator(PasswordResetTokenGenerator);
+
+impl AccountActivationTokenGenerator {
+    pub fn new(secret: &[u8], timeout: i64) -> Self {
+        AccountActivationTokenGenerator(PasswordResetTokenGenerator {
+            secret: secret.to_vec(),
+            timeout,
+            key_salt: ACTIVATION_KEY_SALT,
+            include_activation: true,
+        })
+    }
+
+    /// 設定の `secret_key` で署名します。`timeout` はリンクの有効期間 (秒) です。
+    pub fn from_settings(settings: &Settings, timeout: i64) -> Self {
+        AccountActivationTokenGenerator::new(settings.secret_key(), timeout)
+    }
+
+    /// ユーザーの有効化トークンを生成します。
+    pub fn make_token(&self, user: &user::Model) -> String {
+        self.0.make_token(user)
+    }
+
+    /// トークンがユーザーに対して有効か検証します。
+    pub fn check_token(&self, user: &user::Model, token: &str) -> bool {
+        self.0.check_token(user, token)
+    }
+}
+
+/// ユーザーIDをURLに埋め込む形式にエンコードします。
+/// Djangoの `urlsafe_base64_encode(force_bytes(user.pk))` に相当。
+pub fn encode_uid(id: i32) -> String {
+    URL_SAFE_NO_PAD.encode(id.to_string())
+}
+
+/// `encode_uid` でエンコードしたユーザーIDをデコードします。
+pub fn decode_uid(uidb64: &str) -> Option<i32> {
+    let bytes = URL_SAFE_NO_PAD.decode(uidb64).ok()?;
+    String::from_utf8(bytes).ok()?.parse().ok()
+}
+
+fn to_base36(mut n: i64) -> String {
+    const DIGITS: &[u8] = b"0123456789abcdefghijklmnopqrstuvwxyz";
+    if n <= 0 {
+        return "0".to_string();
+    }
+    let mut buf = Vec::new();
+    while n > 0 {
+        buf.push(DIGITS[(n % 36) as usize]);
+        n /= 36;
+    }
+    buf.reverse();
+    String::from_utf8(buf).unwrap_or_default()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn test_user(password_hash: &str) -> user::Model {
+        user::Model {
+            id: 42,
+            username: "alice".to_string(),
+            password_hash: password_hash.to_string(),
+            email: "alice@example.com".to_string(),
+            is_active: true,
+            is_admin: false,
+            totp_secret: None,
+            totp_last_counter: None,
+            activated_at: None,
+        }
+    }
+
+    #[test]
+    fn test_make_and_check_token() {
+        let generator = PasswordResetTokenGenerator::new(b"secret", 3600);
+        let user = test_user("hash1");
+        let token = generator.make_token(&user);
+
+        assert!(generator.check_token(&user, &token));
+        assert!(!generator.check_token(&user, "invalid"));
+        assert!(!generator.check_token(&user, &format!("{}x", token)));
+    }
+
+    #[test]
+    fn test_token_invalid_after_password_change() {
+        let generator = PasswordResetTokenGenerator::new(b"secret", 3600);
+        let token = generator.make_token(&test_user("hash1"));
+
+        assert!(!generator.check_token(&test_user("hash2"), &token));
+    }
+
+    #[test]
+    fn test_token_invalid_with_other_secret() {
+        let user = test_user("hash1");
+        let token = PasswordResetTokenGenerator::new(b"secret", 3600).make_token(&user);
+
+        assert!(!PasswordResetTokenGenerator::new(b"other", 3600).check_token(&user, &token));
+    }
+
+    #[test]
+    fn test_token_expires() {
+        let generator = PasswordResetTokenGenerator::new(b"secret", 3600);
+        let user = test_user("hash1");
+        let token = generator.make_token_with_timestamp(&user, Utc::now().timestamp() - 3601);
+
+        assert!(!generator.check_token(&user, &token));
+    }
+
+    #[test]
+    fn test_activation_token_is_single_use_and_separate_from_reset() {
+        let generator = AccountActivationTokenGenerator::new(b"secret", 3600);
+        let mut user = test_user("hash1");
+        user.is_active = false;
+        let token = generator.make_token(&user);
+
+        assert!(generator.check_token(&user, &token));
+        assert!(!PasswordResetTokenGenerator::new(b"secret", 3600).check_token(&user, &token));
+
+        user.is_active = true;
+        assert!(!generator.check_token(&user, &token));
+    }
+
+    #[test]
+    fn test_activation_token_invalid_after_deactivation() {
+        let generator = AccountActivationTokenGenerator::new(b"secret", 3600);
+        let mut user = test_user("hash1");
+        user.is_active = false;
+        let token = generator.make_token(&user);
+
+        // 有効化後に管理者が無効化しても、元のトークンでは再び有効化できない
+        user.activated_at = Some(Utc::now().into());
+        assert!(!generator.check_token(&user, &token));
+    }
+
+    #[test]
+    fn test_uid_roundtrip() {
+        assert_eq!(decode_uid(&encode_uid(123)), Some(123));
+        assert_eq!(decode_uid("!!!"), None);
+    }
+}
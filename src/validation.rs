@@ -1,6 +1,9 @@
-use validator::{Validate, ValidationError};
+use std::collections::BTreeMap;
+use validator::{Validate, ValidateEmail, ValidationError};
 use lazy_static::lazy_static;
 use regex::Regex;
+use serde::Serialize;
+use crate::password_validation::{PasswordValidators, UserAttributes};
 
 lazy_static! {
     /// Djangoのusernameバリデーション正規表現
@@ -8,8 +11,106 @@ lazy_static! {
     static ref USERNAME_REGEX: Regex = Regex::new(r"^[\w.@+-]+$").unwrap();
 }
 
+/// フィールドごとのエラーメッセージ。
+/// Djangoの `form.errors` に相当し、テンプレートでは `field_errors.<フィールド名>` で参照します。
+/// フィールドに属さないエラーは `__all__` に入れます (Django: `non_field_errors`)。
+#[derive(Debug, Default, Clone, Serialize)]
+#[serde(transparent)]
+pub struct FieldErrors(BTreeMap<String, Vec<String>>);
+
+impl FieldErrors {
+    /// フィールドに属さないエラーのキー
+    pub const NON_FIELD: &'static str = "__all__";
+
+    pub fn new() -> Self {
+        FieldErrors::default()
+    }
+
+    pub fn add(&mut self, field: &str, message: impl Into<String>) {
+        self.0.entry(field.to_string()).or_default().push(message.into());
+    }
+
+    pub fn extend(&mut self, field: &str, messages: impl IntoIterator<Item = String>) {
+        for message in messages {
+            self.add(field, message);
+        }
+    }
+
+    pub fn get(&self, field: &str) -> &[String] {
+        self.0.get(field).map(Vec::as_slice).unwrap_or_default()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.0.is_empty()
+    }
+
+    /// 別のエラーをまとめる
+    pub fn merge(&mut self, other: FieldErrors) {
+        for (field, messages) in other.0 {
+            self.extend(&field, messages);
+        }
+    }
+
+    /// エラーがなければ `Ok(())` を返します
+    pub fn into_result(self) -> Result<(), FieldErrors> {
+        if self.is_empty() { Ok(()) } else { Err(self) }
+    }
+
+    /// 全てのメッセージをフィールド順に並べて返します (CLIやFlashメッセージ用)
+    pub fn messages(&self) -> Vec<String> {
+        self.0.values().flatten().cloned().collect()
+    }
+}
+
+impl std::fmt::Display for FieldErrors {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "{}", self.messages().join("\n"))
+    }
+}
+
+/// メールアドレスの正規化と検証。
+/// Djangoの `EmailField.clean()` + `BaseUserManager.normalize_email()` に相当 (空欄は許可)。
+pub fn clean_email(email: &str) -> Result<String, String> {
+    let email = email.trim();
+    if email.is_empty() {
+        return Ok(String::new());
+    }
+    if !email.validate_email() {
+        return Err("有効なメールアドレスを入力してください".into());
+    }
+    // ドメイン部分のみ小文字化する
+    match email.rsplit_once('@') {
+        Some((local, domain)) => Ok(format!("{}@{}", local, domain.to_lowercase())),
+        None => Ok(email.to_string()),
+    }
+}
+
+/// ログイン後のリダイレクト先として安全なURLか (同一サイト内の相対パスのみ許可)。
+/// Djangoの `url_has_allowed_host_and_scheme()` に相当し、オープンリダイレクトを防ぎます。
+pub fn is_safe_redirect_url(url: &str) -> bool {
+    url.starts_with('/')
+        && !url.starts_with("//")
+        && !url.chars().any(|c| c.is_control() || c == '\\')
+}
+
+/// `Host` ヘッダーのホスト名 (ポートを除く) が許可されているか。
+/// Djangoの `validate_host()` と同じく、`.example.com` は `example.com` とそのサブドメインに、`*` は全てに一致します
+pub fn is_allowed_host(host: &str, allowed_hosts: &[String]) -> bool {
+    let host = host.trim_end_matches('.').to_lowercase();
+    !host.is_empty()
+        && allowed_hosts.iter().any(|pattern| {
+            let pattern = pattern.to_lowercase();
+            pattern == "*"
+                || match pattern.strip_prefix('.') {
+                    Some(domain) => host == domain || host.ends_with(&pattern),
+                    None => host == pattern,
+                }
+        })
+}
+
 /// ユーザー作成/編集フォームのバリデーション。
 /// Djangoの `forms.ModelForm` + `clean_*` メソッドに相当。
+/// パスワードの強度は `PasswordValidators` (Django: `AUTH_PASSWORD_VALIDATORS`) で検証します。
 #[derive(Debug, Validate)]
 pub struct UserFormValidation {
     #[validate(
@@ -18,8 +119,11 @@ pub struct UserFormValidation {
     )]
     pub username: String,
 
-    #[validate(length(min = 8, message = "パスワードは8文字以上で入力してください"))]
+    /// 編集時にパスワードを変更しない場合は `None`
     pub password: Option<String>,
+
+    /// ユーザー属性との類似度チェックに使うメールアドレス
+    pub email: String,
 }
 
 /// ユーザー名の文字種バリデーション
@@ -37,26 +141,40 @@ impl UserFormValidation {
         Self {
             username: username.to_string(),
             password: password.map(|p| p.to_string()),
+            email: String::new(),
         }
     }
 
-    /// バリデーションを実行し、エラーメッセージを返す
-    pub fn validate_form(&self) -> Result<(), Vec<String>> {
-        match self.validate() {
-            Ok(_) => Ok(()),
-            Err(errors) => {
-                let mut messages = Vec::new();
-                for (field, field_errors) in errors.field_errors() {
-                    for error in field_errors {
-                        let msg = error.message.as_ref()
-                            .map(|m| m.to_string())
-                            .unwrap_or_else(|| format!("{} が不正です", field));
-                        messages.push(msg);
-                    }
+    /// 類似度チェック用のメールアドレスを設定する
+    pub fn with_email(mut self, email: &str) -> Self {
+        self.email = email.to_string();
+        self
+    }
+
+    /// 指定したパスワードバリデーターで検証し、フィールドごとのエラーを返す。
+    /// Djangoの `form.is_valid()` + `form.errors` に相当。
+    pub fn validate_fields(&self, validators: &PasswordValidators) -> Result<(), FieldErrors> {
+        let mut errors = FieldErrors::new();
+
+        if let Err(validation_errors) = self.validate() {
+            for (field, field_errors) in validation_errors.field_errors() {
+                for error in field_errors {
+                    let msg = error.message.as_ref()
+                        .map(|m| m.to_string())
+                        .unwrap_or_else(|| format!("{} が不正です", field));
+                    errors.add(field, msg);
                 }
-                Err(messages)
             }
         }
+
+        if let Some(password) = &self.password {
+            let user = UserAttributes::new(&self.username, &self.email);
+            if let Err(messages) = validators.validate(password, Some(&user)) {
+                errors.extend("password", messages);
+            }
+        }
+
+        errors.into_result()
     }
 }
 
@@ -64,41 +182,81 @@ impl UserFormValidation {
 mod tests {
     use super::*;
 
+    #[test]
+    fn test_allowed_hosts() {
+        let allowed = vec!["example.com".to_string(), ".example.org".to_string()];
+        assert!(is_allowed_host("example.com", &allowed));
+        assert!(is_allowed_host("EXAMPLE.com.", &allowed));
+        assert!(is_allowed_host("example.org", &allowed));
+        assert!(is_allowed_host("www.example.org", &allowed));
+        assert!(!is_allowed_host("www.example.com", &allowed));
+        assert!(!is_allowed_host("evil-example.org", &allowed));
+        assert!(!is_allowed_host("", &allowed));
+        assert!(is_allowed_host("anything.test", &["*".to_string()]));
+    }
+
     #[test]
     fn test_valid_username() {
-        let form = UserFormValidation::new("valid_user123", Some("password123"));
-        assert!(form.validate_form().is_ok());
+        let form = UserFormValidation::new("valid_user123", Some("correct-horse-battery"));
+        assert!(form.validate_fields(&PasswordValidators::default()).is_ok());
     }
 
     #[test]
     fn test_username_with_special_chars() {
-        let form = UserFormValidation::new("user@example.com", Some("password123"));
-        assert!(form.validate_form().is_ok());
+        let form = UserFormValidation::new("user@example.com", Some("correct-horse-battery"));
+        assert!(form.validate_fields(&PasswordValidators::default()).is_ok());
     }
 
     #[test]
     fn test_empty_username() {
-        let form = UserFormValidation::new("", Some("password123"));
-        assert!(form.validate_form().is_err());
+        let form = UserFormValidation::new("", Some("correct-horse-battery"));
+        assert!(form.validate_fields(&PasswordValidators::default()).is_err());
     }
 
     #[test]
     fn test_username_too_long() {
         let long_username = "a".repeat(151);
-        let form = UserFormValidation::new(&long_username, Some("password123"));
-        assert!(form.validate_form().is_err());
+        let form = UserFormValidation::new(&long_username, Some("correct-horse-battery"));
+        assert!(form.validate_fields(&PasswordValidators::default()).is_err());
     }
 
     #[test]
     fn test_password_too_short() {
         let form = UserFormValidation::new("validuser", Some("short"));
-        assert!(form.validate_form().is_err());
+        assert!(form.validate_fields(&PasswordValidators::default()).is_err());
+    }
+
+    #[test]
+    fn test_password_errors_are_reported_per_field() {
+        let form = UserFormValidation::new("bad user", Some("12345"));
+        let errors = form.validate_fields(&PasswordValidators::default()).unwrap_err();
+        assert_eq!(errors.get("username").len(), 1);
+        // 短い・一般的・数字のみ
+        assert_eq!(errors.get("password").len(), 3);
+    }
+
+    #[test]
+    fn test_password_similar_to_username() {
+        let form = UserFormValidation::new("hanako", Some("hanako2024")).with_email("hanako@example.com");
+        let errors = form.validate_fields(&PasswordValidators::default()).unwrap_err();
+        assert!(errors.get("password")[0].contains("ユーザー名"));
     }
 
     #[test]
     fn test_password_optional_for_edit() {
         // 編集時はパスワード省略可能
         let form = UserFormValidation::new("validuser", None);
-        assert!(form.validate_form().is_ok());
+        assert!(form.validate_fields(&PasswordValidators::default()).is_ok());
+    }
+
+    #[test]
+    fn test_safe_redirect_url() {
+        assert!(is_safe_redirect_url("/todo"));
+        assert!(is_safe_redirect_url("/admin/users?page=2"));
+        assert!(!is_safe_redirect_url("https://evil.example.com/"));
+        assert!(!is_safe_redirect_url("//evil.example.com/"));
+        assert!(!is_safe_redirect_url("/\\evil.example.com/"));
+        assert!(!is_safe_redirect_url("/\tevil"));
+        assert!(!is_safe_redirect_url(""));
     }
 }
@@ -3,7 +3,9 @@ use rocket::request::Request;
 use rocket::response::{Responder, Result};
 use rocket::serde::json::serde_json;
 use rocket_dyn_templates::Template;
-use crate::fairings::context::{CachedUser, CachedIsAdmin, CachedCsrf};
+use crate::fairings::context::{CachedUser, CachedIsAdmin, CachedCsrf, CachedAdminApps};
+use crate::fairings::security::CspNonce;
+use crate::settings::Settings;
 
 /// アプリケーション標準のテンプレートレスポンダー。
 /// Djangoの `context_processors` のように、共通のコンテキスト（ユーザー情報、CSRFトークンなど）を自動注入します。
@@ -45,9 +47,24 @@ impl<'r> Responder<'r, 'static> for AppTemplate {
              global_context.insert("csrf_token".into(), serde_json::Value::String(cached_csrf.0.clone()));
         }
 
+        // Admin Apps (ContextFairingでキャッシュ済み。管理画面のみ)
+        if let Some(apps) = request.local_cache(|| None::<CachedAdminApps>) {
+             global_context.insert("available_apps".into(), apps.0.clone());
+        }
+
+        // CSP nonce (SecurityMiddlewareが生成。インラインのスクリプトに `nonce="{{ csp_nonce }}"` として付ける)
+        if let Some(nonce) = request.local_cache(|| None::<CspNonce>) {
+             global_context.insert("csp_nonce".into(), serde_json::Value::String(nonce.0.clone()));
+        }
+
+        // Settings Context (秘密情報を除いた設定。`settings.login_url` のように参照する)
+        if let Some(settings) = request.rocket().state::<Settings>() {
+             global_context.insert("settings".into(), serde_json::to_value(settings).unwrap_or_default());
+        }
+
         // Active Nav Logic (Simple URI check)
         // Manual override possible
-        let uri = request.uri().path();
+        let _uri = request.uri().path();
         // default active_nav based on path?
         // e.g. /admin/users -> "users"
         // But manual override is common.
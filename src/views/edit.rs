@@ -4,6 +4,13 @@ use rocket_dyn_templates::context;
 use crate::views::app_template::AppTemplate;
 use rocket::serde::json::serde_json;
 use serde::Serialize;
+use crate::admin::deletion::{self, DeletedObjects};
+use crate::entities::user;
+use crate::services::admin_log::{self, AdminLogService};
+use crate::validation::FieldErrors;
+
+/// `clean` でエラーになった場合にフォーム上部に表示するメッセージ
+const INVALID_FORM_MESSAGE: &str = "下記のエラーを修正してください。";
 
 /// 新規作成ビューのためのトレイト。
 /// Djangoの `CreateView` に相当します。
@@ -11,13 +18,18 @@ use serde::Serialize;
 pub trait CreateView<A>
 where
     A: ActiveModelTrait + Send,
-    <A::Entity as EntityTrait>::Model: IntoActiveModel<A> + Sync,
+    <A::Entity as EntityTrait>::Model: IntoActiveModel<A> + Sync + Serialize,
 {
     /// 使用するテンプレート名
     fn template_name(&self) -> &'static str {
         "admin/form" // デフォルト
     }
 
+    /// 操作履歴に記録するモデル名 (デフォルトはテーブル名)
+    fn content_type(&self) -> String {
+        <A::Entity as Default>::default().table_name().to_string()
+    }
+
     /// 成功時のリダイレクト先URL
     fn success_url(&self) -> String;
 
@@ -31,6 +43,12 @@ where
         serde_json::json!({})
     }
 
+    /// 保存前の入力検証。フィールドごとのエラーを返すとフォームを再表示します。
+    /// Djangoの `form.is_valid()` (`clean()` / `clean_<field>()`) に相当。
+    async fn clean(&self, _db: &DatabaseConnection, _data: &serde_json::Value) -> Result<(), FieldErrors> {
+        Ok(())
+    }
+
     /// 保存処理の実装。
     /// フォームデータを受け取り、ActiveModelを構築して保存するロジックは実装者が記述する。
     /// Djangoの `form_valid` に相当。
@@ -38,7 +56,7 @@ where
 
     /// GETリクエスト: フォーム表示
     async fn get(&self, db: &DatabaseConnection, extra_context: serde_json::Value) -> AppTemplate {
-        let mut context = context! {
+        let context = context! {
             // 初期データなどをここに埋め込む
         };
         
@@ -69,22 +87,33 @@ where
     }
 
     /// POSTリクエスト: 保存処理
-    /// form_data は フォームから受け取ったデータを JSON Value などに変換したもの、あるいは構造体ラッパー
+    /// form_data は フォームから受け取ったデータを JSON Value などに変換したもの、あるいは構造体ラッパー。
+    /// 保存に成功すると `actor` の操作として履歴 (`LogEntry`) に記録します。
     async fn post(
         &self,
         db: &DatabaseConnection,
+        actor: &user::Model,
         form_data: &serde_json::Value,
         extra_context: serde_json::Value,
     ) -> Result<Flash<Redirect>, AppTemplate> {
-        match self.save(db, form_data).await {
-            Ok(_) => {
-                Ok(Flash::success(Redirect::to(self.success_url()), "作成しました"))
+        let result = match self.clean(db, form_data).await {
+            Ok(()) => self.save(db, form_data).await.map_err(|e| (e.to_string(), FieldErrors::new())),
+            Err(field_errors) => Err((INVALID_FORM_MESSAGE.to_string(), field_errors)),
+        };
+
+        match result {
+            Ok(object) => {
+                let object = serde_json::to_value(&object).unwrap_or_default();
+                match AdminLogService::log_addition(db, actor.id, &self.content_type(), &object).await {
+                    Ok(_) => Ok(Flash::success(Redirect::to(self.success_url()), "作成しました")),
+                    Err(e) => Ok(Flash::warning(Redirect::to(self.success_url()), format!("作成しましたが、履歴の記録に失敗しました: {}", e))),
+                }
             }
-            Err(e) => {
+            Err((error, field_errors)) => {
                 // エラー時はフォームを再表示
-                // ここでエラーメッセージをコンテキストに含めたい
                 let mut context_value = serde_json::json!({
-                    "error": e.to_string(),
+                    "error": error,
+                    "field_errors": field_errors,
                     "form": form_data, // 入力値を戻す
                 });
 
@@ -119,12 +148,23 @@ where
         "admin/form"
     }
 
+    /// 操作履歴に記録するモデル名 (デフォルトはテーブル名)
+    fn content_type(&self) -> String {
+        <A::Entity as Default>::default().table_name().to_string()
+    }
+
     fn success_url(&self) -> String;
 
     async fn get_context_data(&self, _db: &DatabaseConnection) -> serde_json::Value {
         serde_json::json!({})
     }
 
+    /// 履歴に記録する変更したフィールド名。`before` / `after` は保存前後のモデルをJSONにしたもの
+    /// (Djangoの `construct_change_message()`)。モデル以外の値 (多対多など) も記録する場合は上書きします。
+    fn changed_fields(&self, before: &serde_json::Value, after: &serde_json::Value, _form_data: &serde_json::Value) -> Vec<String> {
+        admin_log::changed_fields(before, after)
+    }
+
     /// IDからモデルを取得する
     async fn get_object(&self, _db: &DatabaseConnection, _id: i32) -> Result<Option<<A::Entity as EntityTrait>::Model>, DbErr> {
          // デフォルト実装は難しい（Entityを知る必要があるため）。
@@ -132,6 +172,11 @@ where
          Err(DbErr::Custom("Not implemented".to_owned()))
     }
 
+    /// 保存前の入力検証 (`CreateView::clean` と同じ)
+    async fn clean(&self, _db: &DatabaseConnection, _id: i32, _data: &serde_json::Value) -> Result<(), FieldErrors> {
+        Ok(())
+    }
+
     /// 保存処理
     async fn save(&self, db: &DatabaseConnection, id: i32, data: &serde_json::Value) -> Result<<A::Entity as EntityTrait>::Model, DbErr>;
 
@@ -143,9 +188,13 @@ where
         object: Option<<A::Entity as EntityTrait>::Model>,
         extra_context: serde_json::Value
     ) -> Result<AppTemplate, Flash<Redirect>> {
+        // 呼び出し側でオブジェクトを渡さなかった場合は `get_object` で取得する
         let model = match object {
             Some(m) => m,
-            None => return Err(Flash::error(Redirect::to(self.success_url()), "Object not found")),
+            None => match self.get_object(db, id).await {
+                Ok(Some(m)) => m,
+                _ => return Err(Flash::error(Redirect::to(self.success_url()), "Object not found")),
+            },
         };
 
         // モデルをJSONに変換してフォーム初期値とする
@@ -173,19 +222,37 @@ where
         Ok(AppTemplate::new(self.template_name(), context_value))
     }
 
-    /// POST: 更新実行
+    /// POST: 更新実行。保存に成功すると変更したフィールドを `actor` の操作として履歴に記録します。
     async fn post(
         &self,
         db: &DatabaseConnection,
+        actor: &user::Model,
         id: i32,
         form_data: &serde_json::Value,
         extra_context: serde_json::Value,
     ) -> Result<Flash<Redirect>, AppTemplate> {
-        match self.save(db, id, form_data).await {
-            Ok(_) => Ok(Flash::success(Redirect::to(self.success_url()), "更新しました")),
-            Err(e) => {
+        let before = match self.get_object(db, id).await {
+            Ok(Some(object)) => serde_json::to_value(&object).unwrap_or_default(),
+            _ => serde_json::Value::Null,
+        };
+        let result = match self.clean(db, id, form_data).await {
+            Ok(()) => self.save(db, id, form_data).await.map_err(|e| (e.to_string(), FieldErrors::new())),
+            Err(field_errors) => Err((INVALID_FORM_MESSAGE.to_string(), field_errors)),
+        };
+
+        match result {
+            Ok(object) => {
+                let after = serde_json::to_value(&object).unwrap_or_default();
+                let fields = self.changed_fields(&before, &after, form_data);
+                match AdminLogService::log_change(db, actor.id, &self.content_type(), &after, &fields).await {
+                    Ok(_) => Ok(Flash::success(Redirect::to(self.success_url()), "更新しました")),
+                    Err(e) => Ok(Flash::warning(Redirect::to(self.success_url()), format!("更新しましたが、履歴の記録に失敗しました: {}", e))),
+                }
+            }
+            Err((error, field_errors)) => {
                  let mut context_value = serde_json::json!({
-                    "error": e.to_string(),
+                    "error": error,
+                    "field_errors": field_errors,
                     "form": form_data,
                     "is_edit": true,
                     "id": id,
@@ -216,20 +283,94 @@ where
 pub trait DeleteView<E>
 where
     E: EntityTrait + Send,
-    E::Model: IntoActiveModel<E::ActiveModel> + Sync,
+    E::Model: IntoActiveModel<E::ActiveModel> + Sync + Serialize,
     <<E as EntityTrait>::PrimaryKey as PrimaryKeyTrait>::ValueType: From<i32>,
 {
     fn success_url(&self) -> String;
 
+    /// 確認画面のテンプレート名 (Djangoの `delete_confirmation`)
+    fn template_name(&self) -> &'static str {
+        "admin/delete_confirmation"
+    }
+
+    /// 操作履歴に記録するモデル名 (デフォルトはテーブル名)
+    fn content_type(&self) -> String {
+        E::default().table_name().to_string()
+    }
+
+    /// 確認画面の追加コンテキストデータ
+    async fn get_context_data(&self, _db: &DatabaseConnection) -> serde_json::Value {
+        serde_json::json!({})
+    }
+
+    /// 一緒に削除されるオブジェクトの表示名やリンクを設定します
+    fn describe(&self, _deleted: &mut DeletedObjects) {}
+
+    /// 削除すると一緒に削除・変更されるオブジェクト (Djangoの `get_deleted_objects`)
+    async fn deleted_objects(&self, db: &DatabaseConnection, object: &E::Model) -> Result<DeletedObjects, DbErr> {
+        let object = serde_json::to_value(object).unwrap_or_default();
+        let mut deleted = deletion::collect(db, E::default().table_name(), &[object]).await?;
+        self.describe(&mut deleted);
+        Ok(deleted)
+    }
+
+    /// GET: 確認画面。一緒に削除されるオブジェクトと、削除を妨げるオブジェクトを表示します
+    async fn get(&self, db: &DatabaseConnection, id: i32, extra_context: serde_json::Value) -> Result<AppTemplate, Flash<Redirect>> {
+        let not_found = || Flash::error(Redirect::to(self.success_url()), "削除するオブジェクトが見つかりません");
+        let object = E::find_by_id(id).one(db).await.ok().flatten().ok_or_else(not_found)?;
+        let deleted = self
+            .deleted_objects(db, &object)
+            .await
+            .map_err(|e| Flash::error(Redirect::to(self.success_url()), format!("削除するオブジェクトの取得に失敗しました: {}", e)))?;
+
+        let mut context_value = serde_json::json!({
+            "id": id,
+            "object_repr": admin_log::object_repr(&serde_json::to_value(&object).unwrap_or_default()),
+            "deleted_objects": deleted.objects,
+            "model_count": deleted.model_count(),
+            "protected": deleted.protected,
+        });
+        if let serde_json::Value::Object(ref mut map) = context_value {
+            if let serde_json::Value::Object(extra) = self.get_context_data(db).await {
+                map.extend(extra);
+            }
+            if let serde_json::Value::Object(dynamic_extra) = extra_context {
+                map.extend(dynamic_extra);
+            }
+        }
+        Ok(AppTemplate::new(self.template_name(), context_value))
+    }
+
     /// 削除実行
     async fn delete(&self, db: &DatabaseConnection, id: i32) -> Result<DeleteResult, DbErr> {
         E::delete_by_id(id).exec(db).await
     }
 
-    /// POST: 削除処理 (通常は確認画面なしでPOSTで削除、あるいは確認画面を挟むが今回は簡易版)
-    async fn post(&self, db: &DatabaseConnection, id: i32) -> Result<Flash<Redirect>, Flash<Redirect>> {
+    /// POST: 確認画面からの削除処理。
+    /// 削除を妨げるオブジェクト (`on_delete` が `Cascade` / `SetNull` でない参照) がある場合は削除しません。
+    /// 削除したオブジェクトは `actor` の操作として履歴に記録します。
+    async fn post(&self, db: &DatabaseConnection, actor: &user::Model, id: i32) -> Result<Flash<Redirect>, Flash<Redirect>> {
+        // 履歴に表示名を残すため、削除前のオブジェクトを取得しておく
+        let object = E::find_by_id(id).one(db).await.ok().flatten();
+        if let Some(object) = &object {
+            match self.deleted_objects(db, object).await {
+                Ok(deleted) if deleted.is_protected() => {
+                    return Err(Flash::error(Redirect::to(self.success_url()), deletion::PROTECTED_MESSAGE));
+                }
+                Ok(_) => {}
+                Err(e) => return Err(Flash::error(Redirect::to(self.success_url()), format!("削除失敗: {}", e))),
+            }
+        }
         match self.delete(db, id).await {
-            Ok(_) => Ok(Flash::success(Redirect::to(self.success_url()), "削除しました")),
+            Ok(result) => {
+                if let (Some(object), true) = (object, result.rows_affected > 0) {
+                    let object = serde_json::to_value(&object).unwrap_or_default();
+                    if let Err(e) = AdminLogService::log_deletion(db, actor.id, &self.content_type(), &object).await {
+                        return Ok(Flash::warning(Redirect::to(self.success_url()), format!("削除しましたが、履歴の記録に失敗しました: {}", e)));
+                    }
+                }
+                Ok(Flash::success(Redirect::to(self.success_url()), "削除しました"))
+            }
             Err(e) => Err(Flash::error(Redirect::to(self.success_url()), format!("削除失敗: {}", e))),
         }
     }
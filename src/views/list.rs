@@ -49,6 +49,12 @@ where
         query
     }
 
+    /// 一覧に表示する各行を作る hooks。
+    /// デフォルトはモデルをそのままJSONにします (関連するデータを追加する場合に上書き)
+    async fn get_items(&self, _db: &DatabaseConnection, items: Vec<E::Model>) -> Vec<serde_json::Value> {
+        items.iter().map(|item| serde_json::to_value(item).unwrap_or_default()).collect()
+    }
+
     /// フィルタパラメータを適用する hooks
     /// params: URLクエリパラメータ (key=value)
     fn apply_filters(&self, query: Select<E>, _params: &std::collections::HashMap<String, String>) -> Select<E> {
@@ -120,6 +126,7 @@ where
         let paginator = query.paginate(db, per_page);
         let num_pages = paginator.num_pages().await.unwrap_or(0);
         let items = paginator.fetch_page((page - 1) as u64).await.unwrap_or_default();
+        let items = self.get_items(db, items).await;
 
         // フィルタ定義と現在の選択状態を構築
         let defined_filters = self.get_filters();
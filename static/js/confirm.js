@@ -0,0 +1,9 @@
+// `data-confirm` 属性を持つフォーム・送信ボタンで、送信前に確認ダイアログを表示する。
+// Content-Security-Policy によりインラインのイベントハンドラー (onclick / onsubmit) は実行されないため、ここで処理する。
+document.addEventListener('submit', function (event) {
+    const submitter = event.submitter;
+    const message = (submitter && submitter.dataset.confirm) || event.target.dataset.confirm;
+    if (message && !window.confirm(message)) {
+        event.preventDefault();
+    }
+});
@@ -0,0 +1,126 @@
+use rocket::http::{ContentType, Status};
+use rocket::local::blocking::Client;
+use rust_django_starter::entities::{group, group_user, user};
+use rust_django_starter::services::user_service::UserService;
+use sea_orm::{ActiveModelTrait, ColumnTrait, DatabaseConnection, EntityTrait, QueryFilter, Set};
+
+mod common;
+
+fn login(client: &Client, username: &str, password: &str) -> Status {
+    client.post("/auth/login")
+        .body(common::with_login_csrf(client, format!("username={}&password={}", username, password)))
+        .header(ContentType::Form)
+        .dispatch()
+        .status()
+}
+
+fn csrf_token(client: &Client) -> String {
+    client.cookies().get("csrf_token").unwrap().value().to_string()
+}
+
+fn post_form(client: &Client, uri: &str, body: String) -> (Status, Option<String>, String) {
+    let response = client.post(uri.to_string())
+        .body(body)
+        .header(ContentType::Form)
+        .dispatch();
+    let status = response.status();
+    let location = response.headers().get_one("Location").map(str::to_string);
+    (status, location, response.into_string().unwrap_or_default())
+}
+
+/// パスワードを "password" に戻したユーザーを用意する (前回のテスト実行で変更されている場合がある)
+fn fresh_user(client: &Client, username: &str) -> user::Model {
+    let db = client.rocket().state::<DatabaseConnection>().unwrap();
+    let user = common::create_test_user_with_perms(client, username, &[]);
+    common::block_on(UserService::set_password(db, user, "password")).unwrap()
+}
+
+#[test]
+fn test_profile_shows_groups_and_updates_username() {
+    let client = common::setup();
+    let db = client.rocket().state::<DatabaseConnection>().unwrap();
+    let user = fresh_user(&client, "profile_user");
+    common::create_test_user_with_perms(&client, "profile_taken", &[]);
+
+    let group_name = format!("profile_group_{}", uuid::Uuid::new_v4().simple());
+    common::block_on(async {
+        let group = group::ActiveModel { name: Set(group_name.clone()), ..Default::default() }
+            .insert(db)
+            .await
+            .unwrap();
+        group_user::ActiveModel { group_id: Set(group.id), user_id: Set(user.id), ..Default::default() }
+            .insert(db)
+            .await
+            .unwrap();
+    });
+
+    assert_eq!(client.get("/account").dispatch().status(), Status::SeeOther);
+    assert_eq!(login(&client, "profile_user", "password"), Status::SeeOther);
+
+    let body = client.get("/account").dispatch().into_string().unwrap();
+    assert!(body.contains("profile_user"));
+    assert!(body.contains(&group_name));
+
+    // 既に使われているユーザー名・不正なメールアドレスはフィールドごとのエラーになる
+    let (status, _, body) = post_form(&client, "/account",
+        format!("username=profile_taken&email=invalid&csrf_token={}", csrf_token(&client)));
+    assert_eq!(status, Status::Ok);
+    assert!(body.contains("このユーザー名は既に使用されています"));
+    assert!(body.contains("有効なメールアドレスを入力してください"));
+
+    let (status, location, _) = post_form(&client, "/account",
+        format!("username=profile_user&email=Profile%40EXAMPLE.com&csrf_token={}", csrf_token(&client)));
+    assert_eq!((status, location.as_deref()), (Status::SeeOther, Some("/account")));
+    let reloaded = common::block_on(UserService::find_by_id(db, user.id)).unwrap().unwrap();
+    assert_eq!(reloaded.email, "Profile@example.com");
+
+    // 後片付け (グループは所属ごと削除される)
+    common::block_on(group::Entity::delete_many().filter(group::Column::Name.eq(group_name)).exec(db)).unwrap();
+}
+
+#[test]
+fn test_password_change_requires_old_password_and_rotates_session() {
+    let client = common::setup();
+    fresh_user(&client, "password_change_user");
+
+    assert_eq!(login(&client, "password_change_user", "password"), Status::SeeOther);
+    let old_session = client.cookies().get_private("sessionid").unwrap().value().to_string();
+
+    // 別の端末のセッション
+    let other = common::setup();
+    assert_eq!(login(&other, "password_change_user", "password"), Status::SeeOther);
+
+    let body = client.get("/account/password").dispatch().into_string().unwrap();
+    assert!(body.contains("パスワードは最低 8 文字以上必要です。"));
+
+    let (status, _, body) = post_form(&client, "/account/password",
+        format!("old_password=wrong&new_password1=Tr0ubador-Horse&new_password2=Tr0ubador-Horse&csrf_token={}", csrf_token(&client)));
+    assert_eq!(status, Status::Ok);
+    assert!(body.contains("現在のパスワードが間違っています"));
+
+    let (status, _, body) = post_form(&client, "/account/password",
+        format!("old_password=password&new_password1=12345678&new_password2=12345678&csrf_token={}", csrf_token(&client)));
+    assert_eq!(status, Status::Ok);
+    assert!(body.contains("このパスワードは数字しか使われていません。"));
+
+    let (status, location, _) = post_form(&client, "/account/password",
+        format!("old_password=password&new_password1=Tr0ubador-Horse&new_password2=Tr0ubador-Horse&csrf_token={}", csrf_token(&client)));
+    assert_eq!((status, location.as_deref()), (Status::SeeOther, Some("/account/password/done")));
+
+    // 現在の端末はログインしたままセッションキーが変わり、他の端末はログアウトされる
+    let new_session = client.cookies().get_private("sessionid").unwrap().value().to_string();
+    assert_ne!(old_session, new_session);
+    assert_eq!(client.get("/account/password/done").dispatch().status(), Status::Ok);
+    assert_eq!(other.get("/account").dispatch().status(), Status::SeeOther);
+
+    client.post("/auth/logout").header(common::csrf_header(&client)).dispatch();
+    assert_eq!(login(&client, "password_change_user", "password"), Status::Ok);
+    assert_eq!(login(&client, "password_change_user", "Tr0ubador-Horse"), Status::SeeOther);
+}
+
+#[test]
+fn test_signup_is_disabled_by_default() {
+    let client = common::setup();
+    assert_eq!(client.get("/auth/signup").dispatch().status(), Status::NotFound);
+    assert!(!client.get("/auth/login").dispatch().into_string().unwrap().contains("アカウントを作成"));
+}
@@ -0,0 +1,149 @@
+use rocket::http::{ContentType, Status};
+use rocket::local::blocking::{Client, LocalResponse};
+use rust_django_starter::entities::{group, group_user, prelude::*, user};
+use sea_orm::{ActiveModelTrait, ColumnTrait, DatabaseConnection, EntityTrait, QueryFilter, Set};
+
+mod common;
+
+fn login(client: &Client, username: &str) -> Status {
+    client.post("/auth/login")
+        .body(common::with_login_csrf(client, format!("username={}&password=password", username)))
+        .header(ContentType::Form)
+        .dispatch()
+        .status()
+}
+
+fn action<'c>(client: &'c Client, uri: &str, body: &str) -> LocalResponse<'c> {
+    client.post(uri.to_string())
+        .body(format!("csrf_token={}&{}", common::login_csrf_token(client), body))
+        .header(ContentType::Form)
+        .dispatch()
+}
+
+fn fresh_user(client: &Client, prefix: &str) -> user::Model {
+    let name = format!("{}_{}", prefix, uuid::Uuid::new_v4().simple());
+    common::create_test_user_with_perms(client, &name, &[])
+}
+
+fn reload(db: &DatabaseConnection, id: i32) -> user::Model {
+    common::block_on(User::find_by_id(id).one(db)).unwrap().unwrap()
+}
+
+fn admin_client() -> Client {
+    let client = common::setup();
+    common::create_test_admin(&client);
+    assert_eq!(login(&client, "admin"), Status::SeeOther);
+    client
+}
+
+#[test]
+fn test_deactivate_and_activate_users() {
+    let client = admin_client();
+    let db = client.rocket().state::<DatabaseConnection>().unwrap();
+    let target = fresh_user(&client, "action_active");
+
+    let response = action(&client, "/admin/users/action", &format!("action=deactivate_users&selected_ids={}", target.id));
+    assert_eq!(response.status(), Status::SeeOther);
+    assert!(!reload(db, target.id).is_active);
+
+    // 無効になったユーザーはログインできない
+    let other = common::setup();
+    assert_ne!(login(&other, &target.username), Status::SeeOther);
+
+    action(&client, "/admin/users/action", &format!("action=activate_users&selected_ids={}", target.id));
+    assert!(reload(db, target.id).is_active);
+}
+
+#[test]
+fn test_add_and_remove_group_with_confirmation() {
+    let client = admin_client();
+    let db = client.rocket().state::<DatabaseConnection>().unwrap();
+    let target = fresh_user(&client, "action_group");
+    let name = format!("action_group_{}", uuid::Uuid::new_v4().simple());
+    let created = common::block_on(group::ActiveModel { name: Set(name.clone()), ..Default::default() }.insert(db)).unwrap();
+    let is_member = || {
+        common::block_on(GroupUser::find()
+            .filter(group_user::Column::GroupId.eq(created.id))
+            .filter(group_user::Column::UserId.eq(target.id))
+            .one(db))
+            .unwrap()
+            .is_some()
+    };
+
+    // 確認画面でグループを選択する
+    let form = format!("action=add_to_group&selected_ids={}", target.id);
+    let response = action(&client, "/admin/users/action", &form);
+    assert_eq!(response.status(), Status::Ok);
+    let body = response.into_string().unwrap();
+    assert!(body.contains(&target.username));
+    assert!(body.contains(&format!("<option value=\"{}\">{}</option>", created.id, name)));
+    assert!(body.contains("name=\"post\" value=\"yes\""));
+    assert!(!is_member());
+
+    // グループを選択しない場合は実行しない
+    let response = action(&client, "/admin/users/action", &format!("{}&post=yes&group=", form));
+    assert_eq!(response.status(), Status::SeeOther);
+    assert!(!is_member());
+
+    action(&client, "/admin/users/action", &format!("{}&post=yes&group={}", form, created.id));
+    assert!(is_member());
+
+    let form = format!("action=remove_from_group&selected_ids={}&post=yes&group={}", target.id, created.id);
+    action(&client, "/admin/users/action", &form);
+    assert!(!is_member());
+}
+
+#[test]
+fn test_export_csv() {
+    let client = admin_client();
+    let target = fresh_user(&client, "action_csv");
+
+    let response = action(&client, "/admin/users/action", &format!("action=export_csv&selected_ids={}", target.id));
+    assert_eq!(response.status(), Status::Ok);
+    assert_eq!(response.content_type(), Some(ContentType::CSV));
+    assert_eq!(response.headers().get_one("Content-Disposition"), Some("attachment; filename=\"user.csv\""));
+    let body = response.into_string().unwrap();
+    assert!(body.starts_with("id,username,email,is_active,is_admin\r\n"));
+    assert!(body.contains(&format!("{},{},", target.id, target.username)));
+    assert!(!body.contains(&target.password_hash));
+}
+
+#[test]
+fn test_reset_passwords_logs_out_users() {
+    let client = admin_client();
+    let db = client.rocket().state::<DatabaseConnection>().unwrap();
+    let target = fresh_user(&client, "action_password");
+
+    let user_client = common::setup();
+    assert_eq!(login(&user_client, &target.username), Status::SeeOther);
+    assert_eq!(user_client.get("/account/security").dispatch().status(), Status::Ok);
+
+    let form = format!("action=reset_passwords&selected_ids={}", target.id);
+    assert_eq!(action(&client, "/admin/users/action", &form).status(), Status::Ok);
+    assert_eq!(reload(db, target.id).password_hash, target.password_hash);
+
+    assert_eq!(action(&client, "/admin/users/action", &format!("{}&post=yes", form)).status(), Status::SeeOther);
+    assert_ne!(reload(db, target.id).password_hash, target.password_hash);
+    assert_ne!(user_client.get("/account/security").dispatch().status(), Status::Ok);
+    assert_ne!(login(&common::setup(), &target.username), Status::SeeOther);
+}
+
+#[test]
+fn test_actions_require_permission() {
+    let client = common::setup();
+    let name = format!("action_viewer_{}", uuid::Uuid::new_v4().simple());
+    common::create_test_user_with_perms(&client, &name, &["auth.view_user"]);
+    assert_eq!(login(&client, &name), Status::SeeOther);
+    let db = client.rocket().state::<DatabaseConnection>().unwrap();
+    let target = fresh_user(&client, "action_protected");
+
+    // 閲覧権限だけでは変更の操作は表示されず、実行もできない
+    let body = client.get("/admin/users").dispatch().into_string().unwrap();
+    assert!(body.contains("value=\"export_csv\""));
+    assert!(!body.contains("value=\"deactivate_users\""));
+    assert!(!body.contains("value=\"delete_selected\""));
+
+    let response = action(&client, "/admin/users/action", &format!("action=deactivate_users&selected_ids={}", target.id));
+    assert_eq!(response.status(), Status::SeeOther);
+    assert!(reload(db, target.id).is_active);
+}
@@ -0,0 +1,115 @@
+use rocket::http::{ContentType, Status};
+use rocket::local::blocking::Client;
+use rust_django_starter::entities::{group, permission, prelude::*, todo};
+use sea_orm::{ColumnTrait, DatabaseConnection, EntityTrait, QueryFilter};
+
+mod common;
+
+fn login(client: &Client, username: &str) {
+    let response = client.post("/auth/login")
+        .body(common::with_login_csrf(client, format!("username={}&password=password", username)))
+        .header(ContentType::Form)
+        .dispatch();
+    assert_eq!(response.status(), Status::SeeOther);
+}
+
+fn post(client: &Client, uri: &str, body: &str) -> Status {
+    client.post(uri.to_string())
+        .body(format!("csrf_token={}&{}", common::login_csrf_token(client), body))
+        .header(ContentType::Form)
+        .dispatch()
+        .status()
+}
+
+fn find_todo(db: &DatabaseConnection, title: &str) -> Option<todo::Model> {
+    common::block_on(Todo::find().filter(todo::Column::Title.eq(title)).one(db)).unwrap()
+}
+
+fn create_todo(client: &Client, title: &str, user_id: i32, group_id: Option<i32>) -> todo::Model {
+    let group_id = group_id.map(|id| id.to_string()).unwrap_or_default();
+    let form = format!("title={}&user_id={}&group_id={}&priority=1", title, user_id, group_id);
+    assert_eq!(post(client, "/admin/todos/create", &form), Status::SeeOther);
+    find_todo(client.rocket().state::<DatabaseConnection>().unwrap(), title).unwrap()
+}
+
+#[test]
+fn test_delete_confirmation_lists_cascaded_objects() {
+    let client = common::setup();
+    let admin = common::create_test_admin(&client);
+    login(&client, "admin");
+    let db = client.rocket().state::<DatabaseConnection>().unwrap();
+
+    let name = format!("delete_group_{}", uuid::Uuid::new_v4().simple());
+    let view_todo = common::block_on(Permission::find().filter(permission::Column::Codename.eq("todo.view_todo")).one(db))
+        .unwrap()
+        .unwrap();
+    let form = format!("name={}&permissions={}", name, view_todo.id);
+    assert_eq!(post(&client, "/admin/groups/create", &form), Status::SeeOther);
+    let created = common::block_on(Group::find().filter(group::Column::Name.eq(&name)).one(db)).unwrap().unwrap();
+    let title = format!("group_todo_{}", uuid::Uuid::new_v4().simple());
+    let todo = create_todo(&client, &title, admin.id, Some(created.id));
+
+    // 確認画面には一緒に削除されるオブジェクトが表示され、まだ削除されない
+    let body = client.get(format!("/admin/groups/delete/{}", created.id)).dispatch().into_string().unwrap();
+    assert!(body.contains(&format!("グループ \"{}\" を削除しますか？", name)));
+    assert!(body.contains(&format!("TODO: <a href=\"&#x2F;admin&#x2F;todos&#x2F;edit&#x2F;{}\">{}</a>", todo.id, title)));
+    assert!(body.contains("group_permissions"));
+    assert!(body.contains("TODO: 1"));
+    assert!(find_todo(db, &title).is_some());
+
+    assert_eq!(post(&client, &format!("/admin/groups/delete/{}", created.id), ""), Status::SeeOther);
+    assert!(common::block_on(Group::find_by_id(created.id).one(db)).unwrap().is_none());
+    assert!(find_todo(db, &title).is_none());
+}
+
+#[test]
+fn test_delete_selected_requires_confirmation() {
+    let client = common::setup();
+    let admin = common::create_test_admin(&client);
+    login(&client, "admin");
+    let db = client.rocket().state::<DatabaseConnection>().unwrap();
+
+    let titles: Vec<String> = (0..2).map(|_| format!("bulk_todo_{}", uuid::Uuid::new_v4().simple())).collect();
+    let todos: Vec<todo::Model> = titles.iter().map(|title| create_todo(&client, title, admin.id, None)).collect();
+    let selected: Vec<String> = todos.iter().map(|t| format!("selected_ids={}", t.id)).collect();
+    let form = format!("action=delete_selected&{}", selected.join("&"));
+
+    let response = client.post("/admin/todos/action")
+        .body(format!("csrf_token={}&{}", common::login_csrf_token(&client), form))
+        .header(ContentType::Form)
+        .dispatch();
+    assert_eq!(response.status(), Status::Ok);
+    let body = response.into_string().unwrap();
+    assert!(body.contains("TODO: 2"));
+    assert!(body.contains("name=\"post\" value=\"yes\""));
+    for todo in &todos {
+        assert!(body.contains(&format!("name=\"selected_ids\" value=\"{}\"", todo.id)));
+    }
+    assert!(titles.iter().all(|title| find_todo(db, title).is_some()));
+
+    assert_eq!(post(&client, "/admin/todos/action", &format!("{}&post=yes", form)), Status::SeeOther);
+    assert!(titles.iter().all(|title| find_todo(db, title).is_none()));
+}
+
+#[test]
+fn test_delete_user_lists_related_objects() {
+    let client = common::setup();
+    common::create_test_admin(&client);
+    let name = format!("delete_user_{}", uuid::Uuid::new_v4().simple());
+    let target = common::create_test_user_with_perms(&client, &name, &["todo.view_todo"]);
+    login(&client, "admin");
+    let db = client.rocket().state::<DatabaseConnection>().unwrap();
+    let title = format!("user_todo_{}", uuid::Uuid::new_v4().simple());
+    create_todo(&client, &title, target.id, None);
+
+    // ユーザーのTODOと個別の権限も削除される
+    let body = client.get(format!("/admin/users/delete/{}", target.id)).dispatch().into_string().unwrap();
+    assert!(body.contains(&title));
+    assert!(body.contains("user_permissions"));
+
+    assert_eq!(post(&client, &format!("/admin/users/delete/{}", target.id), ""), Status::SeeOther);
+    assert!(find_todo(db, &title).is_none());
+
+    let response = client.get(format!("/admin/users/delete/{}", target.id)).dispatch();
+    assert_eq!(response.status(), Status::SeeOther);
+}
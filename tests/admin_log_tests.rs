@@ -0,0 +1,95 @@
+use rocket::http::{ContentType, Status};
+use rocket::local::blocking::Client;
+use rust_django_starter::entities::{log_entry, prelude::*};
+use rust_django_starter::services::user_service::UserService;
+use sea_orm::{ColumnTrait, DatabaseConnection, EntityTrait, QueryFilter, QueryOrder};
+
+mod common;
+
+const USER_PERMS: [&str; 4] = ["auth.view_user", "auth.add_user", "auth.change_user", "auth.delete_user"];
+
+fn login(client: &Client, username: &str) {
+    let response = client.post("/auth/login")
+        .body(common::with_login_csrf(client, format!("username={}&password=password", username)))
+        .header(ContentType::Form)
+        .dispatch();
+    assert_eq!(response.status(), Status::SeeOther);
+}
+
+fn post(client: &Client, uri: &str, body: &str) -> Status {
+    client.post(uri.to_string())
+        .body(format!("csrf_token={}&{}", common::login_csrf_token(client), body))
+        .header(ContentType::Form)
+        .dispatch()
+        .status()
+}
+
+fn entries_for(db: &DatabaseConnection, object_id: i32) -> Vec<log_entry::Model> {
+    common::block_on(
+        LogEntry::find()
+            .filter(log_entry::Column::ContentType.eq("user"))
+            .filter(log_entry::Column::ObjectId.eq(object_id.to_string()))
+            .order_by_asc(log_entry::Column::Id)
+            .all(db),
+    )
+    .unwrap()
+}
+
+#[test]
+fn test_create_update_delete_are_logged_and_shown() {
+    let client = common::setup();
+    let actor = common::create_test_user_with_perms(&client, "log_actor", &USER_PERMS);
+    login(&client, "log_actor");
+    let db = client.rocket().state::<DatabaseConnection>().unwrap();
+    let username = format!("logged_{}", uuid::Uuid::new_v4().simple());
+
+    let form = format!("username={}&password=Str0ng-pass-phrase&is_active=true", username);
+    assert_eq!(post(&client, "/admin/users/create", &form), Status::SeeOther);
+    let target = common::block_on(UserService::find_by_username(db, &username)).unwrap().unwrap();
+
+    let form = format!("username={}&password=&email=logged%40example.com&is_active=true", username);
+    assert_eq!(post(&client, &format!("/admin/users/edit/{}", target.id), &form), Status::SeeOther);
+
+    // 履歴ページと「最近のアクション」に表示される
+    let body = client.get(format!("/admin/users/history/{}", target.id)).dispatch().into_string().unwrap();
+    assert!(body.contains("追加しました。"));
+    assert!(body.contains("email を変更しました。"));
+    assert!(body.contains("log_actor"));
+    let body = client.get("/admin").dispatch().into_string().unwrap();
+    assert!(body.contains(&format!("<a href=\"&#x2F;admin&#x2F;users&#x2F;edit&#x2F;{}\">{}</a>", target.id, username)));
+
+    assert_eq!(post(&client, &format!("/admin/users/delete/{}", target.id), ""), Status::SeeOther);
+
+    let entries = entries_for(db, target.id);
+    let flags: Vec<i16> = entries.iter().map(|e| e.action_flag).collect();
+    assert_eq!(flags, vec![log_entry::ADDITION, log_entry::CHANGE, log_entry::DELETION]);
+    assert!(entries.iter().all(|e| e.user_id == Some(actor.id) && e.object_repr == username));
+}
+
+#[test]
+fn test_bulk_delete_logs_each_user() {
+    let client = common::setup();
+    common::create_test_user_with_perms(&client, "log_bulk_actor", &USER_PERMS);
+    login(&client, "log_bulk_actor");
+    let db = client.rocket().state::<DatabaseConnection>().unwrap();
+
+    let targets: Vec<_> = (0..2)
+        .map(|_| {
+            let name = format!("bulk_{}", uuid::Uuid::new_v4().simple());
+            common::create_test_user_with_perms(&client, &name, &[])
+        })
+        .collect();
+    let selected: Vec<String> = targets.iter().map(|u| format!("selected_ids={}", u.id)).collect();
+    let body = format!("action=delete_selected&{}", selected.join("&"));
+    // 確認画面を挟んでから削除する
+    assert_eq!(post(&client, "/admin/users/action", &body), Status::Ok);
+    assert!(entries_for(db, targets[0].id).is_empty());
+    assert_eq!(post(&client, "/admin/users/action", &format!("{}&post=yes", body)), Status::SeeOther);
+
+    for target in &targets {
+        let entries = entries_for(db, target.id);
+        assert_eq!(entries.len(), 1);
+        assert!(entries[0].is_deletion());
+        assert_eq!(entries[0].object_repr, target.username);
+    }
+}
@@ -0,0 +1,166 @@
+use rocket::http::{ContentType, Status};
+use rocket::local::blocking::Client;
+use rust_django_starter::entities::{group, group_permission, permission, prelude::*, todo};
+use sea_orm::{ColumnTrait, DatabaseConnection, EntityTrait, QueryFilter};
+
+mod common;
+
+fn login(client: &Client, username: &str) {
+    let response = client.post("/auth/login")
+        .body(common::with_login_csrf(client, format!("username={}&password=password", username)))
+        .header(ContentType::Form)
+        .dispatch();
+    assert_eq!(response.status(), Status::SeeOther);
+}
+
+fn post(client: &Client, uri: &str, body: &str) -> Status {
+    client.post(uri.to_string())
+        .body(format!("csrf_token={}&{}", common::login_csrf_token(client), body))
+        .header(ContentType::Form)
+        .dispatch()
+        .status()
+}
+
+fn find_todo(db: &DatabaseConnection, title: &str) -> Option<todo::Model> {
+    common::block_on(Todo::find().filter(todo::Column::Title.eq(title)).one(db)).unwrap()
+}
+
+#[test]
+fn test_registered_model_crud_search_and_filter() {
+    let client = common::setup();
+    let admin = common::create_test_admin(&client);
+    login(&client, "admin");
+    let db = client.rocket().state::<DatabaseConnection>().unwrap();
+    let title = format!("admin_todo_{}", uuid::Uuid::new_v4().simple());
+
+    // 追加画面は fieldsets の見出しと読み取り専用のフィールドを表示する
+    let body = client.get("/admin/todos/create").dispatch().into_string().unwrap();
+    assert!(body.contains("TODOを追加"));
+    assert!(body.contains("状態"));
+    assert!(!body.contains("name=\"created_at\""));
+    // カラムの型とリレーションから入力欄が決まる
+    assert!(body.contains("<textarea name=\"description\""));
+    assert!(body.contains("<select name=\"user_id\""));
+    assert!(body.contains(&format!("<option value=\"{}\" >admin</option>", admin.id)));
+
+    // 参照先にない外部キーは選択肢のエラーになる
+    let form = format!("title={}&user_id=2147483647&priority=1", title);
+    assert_eq!(post(&client, "/admin/todos/create", &form), Status::Ok);
+    assert!(find_todo(db, &title).is_none());
+
+    // 型の合わない値はフィールドのエラーになる
+    let form = format!("title={}&user_id={}&priority=high", title, admin.id);
+    assert_eq!(post(&client, "/admin/todos/create", &form), Status::Ok);
+    assert!(find_todo(db, &title).is_none());
+
+    let form = format!("title={}&description=&user_id={}&group_id=&priority=3", title, admin.id);
+    assert_eq!(post(&client, "/admin/todos/create", &form), Status::SeeOther);
+    let created = find_todo(db, &title).unwrap();
+    assert_eq!(created.priority, 3);
+    assert_eq!(created.description, None);
+    assert!(!created.completed);
+
+    let form = format!("title={}&user_id={}&priority=1&completed=true", title, admin.id);
+    assert_eq!(post(&client, &format!("/admin/todos/edit/{}", created.id), &form), Status::SeeOther);
+    let updated = find_todo(db, &title).unwrap();
+    assert!(updated.completed);
+    assert_eq!(updated.priority, 1);
+    assert_eq!(updated.created_at, created.created_at);
+
+    // 検索と絞り込み (検索語は入力欄にも表示されるため、選択用のチェックボックスで確認する)
+    let row = format!("name=\"selected_ids\" value=\"{}\"", created.id);
+    let body = client.get(format!("/admin/todos?q={}", title)).dispatch().into_string().unwrap();
+    assert!(body.contains(&row));
+    let body = client.get(format!("/admin/todos?q={}&completed=false", title)).dispatch().into_string().unwrap();
+    assert!(!body.contains(&row));
+    let body = client.get(format!("/admin/todos?q={}&completed=true", title)).dispatch().into_string().unwrap();
+    assert!(body.contains(&row));
+
+    let body = client.get(format!("/admin/todos/history/{}", created.id)).dispatch().into_string().unwrap();
+    assert!(body.contains("追加しました。"));
+
+    assert_eq!(post(&client, &format!("/admin/todos/delete/{}", created.id), ""), Status::SeeOther);
+    assert!(find_todo(db, &title).is_none());
+}
+
+#[test]
+fn test_registered_model_requires_permissions() {
+    let client = common::setup();
+    common::create_test_user_with_perms(&client, "todo_viewer", &["todo.view_todo"]);
+    login(&client, "todo_viewer");
+
+    assert_eq!(client.get("/admin/todos").dispatch().status(), Status::Ok);
+    assert_eq!(client.get("/admin/todos/create").dispatch().status(), Status::Forbidden);
+    assert_eq!(post(&client, "/admin/todos/create", "title=x"), Status::Forbidden);
+    assert_eq!(client.get("/admin/unknowns").dispatch().status(), Status::NotFound);
+
+    // ダッシュボードとメニューには閲覧できるモデルだけ表示される
+    let body = client.get("/admin").dispatch().into_string().unwrap();
+    assert!(body.contains("/admin/todos"));
+    assert!(!body.contains("/admin/todos/create"));
+    assert!(!body.contains("/admin/users"));
+}
+
+fn permission_id(db: &DatabaseConnection, codename: &str) -> i32 {
+    common::block_on(Permission::find().filter(permission::Column::Codename.eq(codename)).one(db))
+        .unwrap()
+        .unwrap()
+        .id
+}
+
+fn group_permission_ids(db: &DatabaseConnection, group_id: i32) -> Vec<i32> {
+    let mut ids: Vec<i32> = common::block_on(
+        GroupPermission::find().filter(group_permission::Column::GroupId.eq(group_id)).all(db),
+    )
+    .unwrap()
+    .into_iter()
+    .map(|gp| gp.permission_id)
+    .collect();
+    ids.sort();
+    ids
+}
+
+#[test]
+fn test_many_to_many_and_unique_fields() {
+    let client = common::setup();
+    common::create_test_admin(&client);
+    login(&client, "admin");
+    let db = client.rocket().state::<DatabaseConnection>().unwrap();
+    let name = format!("m2m_group_{}", uuid::Uuid::new_v4().simple());
+    let view_todo = permission_id(db, "todo.view_todo");
+    let change_todo = permission_id(db, "todo.change_todo");
+
+    // 多対多は複数選択で送信し、中間テーブルに保存される
+    let form = format!("name={}&permissions={}&permissions={}", name, view_todo, change_todo);
+    assert_eq!(post(&client, "/admin/groups/create", &form), Status::SeeOther);
+    let created = common::block_on(Group::find().filter(group::Column::Name.eq(&name)).one(db)).unwrap().unwrap();
+    let mut expected = vec![view_todo, change_todo];
+    expected.sort();
+    assert_eq!(group_permission_ids(db, created.id), expected);
+
+    let body = client.get(format!("/admin/groups/edit/{}", created.id)).dispatch().into_string().unwrap();
+    assert!(body.contains(&format!("<option value=\"{}\" selected>", view_todo)));
+
+    // 選択を置き換え、変更が履歴に残る
+    let form = format!("name={}&permissions={}", name, view_todo);
+    assert_eq!(post(&client, &format!("/admin/groups/edit/{}", created.id), &form), Status::SeeOther);
+    assert_eq!(group_permission_ids(db, created.id), vec![view_todo]);
+    let body = client.get(format!("/admin/groups/history/{}", created.id)).dispatch().into_string().unwrap();
+    assert!(body.contains("permissions を変更しました。"));
+
+    // 存在しない権限は選択肢のエラー
+    let form = format!("name={}&permissions=2147483647", name);
+    assert_eq!(post(&client, &format!("/admin/groups/edit/{}", created.id), &form), Status::Ok);
+    assert_eq!(group_permission_ids(db, created.id), vec![view_todo]);
+
+    // unique のカラムは重複を確認する (自分自身は除く)
+    let response = client.post("/admin/groups/create")
+        .body(format!("csrf_token={}&name={}", common::login_csrf_token(&client), name))
+        .header(ContentType::Form)
+        .dispatch();
+    assert_eq!(response.status(), Status::Ok);
+    assert!(response.into_string().unwrap().contains("この 名前 を持った グループ が既に存在します。"));
+    let form = format!("name={}", name);
+    assert_eq!(post(&client, &format!("/admin/groups/edit/{}", created.id), &form), Status::SeeOther);
+    assert!(group_permission_ids(db, created.id).is_empty());
+}
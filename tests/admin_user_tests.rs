@@ -1,4 +1,6 @@
 use rocket::http::Status;
+use rust_django_starter::services::user_service::UserService;
+use sea_orm::DatabaseConnection;
 
 mod common;
 
@@ -9,8 +11,9 @@ fn test_admin_redirect_when_not_logged_in() {
     // 未ログインでアクセス
     let response = client.get("/admin/users").dispatch();
     
-    // 現状の実装では 401 が返る (リダイレクトではない)
-    assert_eq!(response.status(), Status::Unauthorized);
+    // ログイン画面へリダイレクトされ、元のURLが next に付く
+    assert_eq!(response.status(), Status::SeeOther);
+    assert_eq!(response.headers().get_one("Location"), Some("/auth/login?next=%2Fadmin%2Fusers"));
 }
 
 // 共通のcommonモジュールが使えるはず
@@ -23,7 +26,7 @@ fn test_admin_list_authorized() {
     
     // Login
     let response = client.post("/auth/login")
-        .body("username=admin&password=password")
+        .body(common::with_login_csrf(&client, "username=admin&password=password"))
         .header(rocket::http::ContentType::Form)
         .dispatch();
         
@@ -32,7 +35,7 @@ fn test_admin_list_authorized() {
     // Let's check where it redirects.
     
     // Session cookie should be set
-    let cookie = response.cookies().get("user_id");
+    let cookie = response.cookies().get("sessionid");
     assert!(cookie.is_some());
     
     // Now access admin
@@ -41,3 +44,52 @@ fn test_admin_list_authorized() {
     let body = response.into_string().unwrap();
     assert!(body.contains("ユーザー管理")); // Title check
 }
+
+#[test]
+fn test_create_user_rejects_weak_password_with_field_errors() {
+    let client = common::setup();
+    common::create_test_admin(&client);
+    client.post("/auth/login")
+        .body(common::with_login_csrf(&client, "username=admin&password=password"))
+        .header(rocket::http::ContentType::Form)
+        .dispatch();
+
+    let body = client.get("/admin/users/create").dispatch().into_string().unwrap();
+    assert!(body.contains("パスワードは最低 8 文字以上必要です。"));
+
+    let csrf_token = client.cookies().get("csrf_token").unwrap().value().to_string();
+    let response = client.post("/admin/users/create")
+        .body(format!("username=weak_pw_user&password=12345&is_active=true&csrf_token={}", csrf_token))
+        .header(rocket::http::ContentType::Form)
+        .dispatch();
+    assert_eq!(response.status(), Status::Ok);
+    let body = response.into_string().unwrap();
+    assert!(body.contains("このパスワードは短すぎます。最低 8 文字以上必要です。"));
+    assert!(body.contains("このパスワードは一般的すぎます。"));
+    assert!(body.contains("このパスワードは数字しか使われていません。"));
+
+    // ユーザーは作成されていない
+    let db = client.rocket().state::<DatabaseConnection>().unwrap();
+    assert!(common::block_on(UserService::find_by_username(db, "weak_pw_user")).unwrap().is_none());
+}
+
+#[test]
+fn test_generic_user_form_excludes_secrets() {
+    let client = common::setup();
+    let admin = common::create_test_admin(&client);
+    let db = client.rocket().state::<DatabaseConnection>().unwrap();
+    let site = client.rocket().state::<rust_django_starter::admin::AdminSite>().unwrap();
+
+    // 専用のルートがなくても、汎用のフォームでパスワードハッシュや2要素認証の秘密鍵を扱わない
+    let users = site.get("users").unwrap();
+    let page = common::block_on(users.change_view(db, admin.id, rocket::serde::json::serde_json::json!({}))).ok().unwrap();
+    let fields: Vec<&str> = page.context["fieldsets"]
+        .as_array()
+        .unwrap()
+        .iter()
+        .flat_map(|fieldset| fieldset["fields"].as_array().unwrap())
+        .map(|field| field["name"].as_str().unwrap())
+        .collect();
+    assert_eq!(fields, vec!["username", "email", "is_active", "is_admin"]);
+    assert!(!page.context.to_string().contains(&admin.password_hash));
+}
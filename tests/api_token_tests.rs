@@ -0,0 +1,126 @@
+use chrono::{Duration, Utc};
+use rocket::http::{ContentType, Header, Status};
+use rocket::local::blocking::Client;
+use rust_django_starter::entities::{api_token, prelude::*};
+use rust_django_starter::services::api_tokens::ApiTokenService;
+use sea_orm::{DatabaseConnection, EntityTrait};
+
+mod common;
+
+fn bearer(token: &str) -> Header<'static> {
+    Header::new("Authorization", format!("Bearer {}", token))
+}
+
+fn get_with_token(client: &Client, uri: &str, token: &str) -> (Status, String) {
+    let response = client.get(uri.to_string()).header(bearer(token)).dispatch();
+    let status = response.status();
+    (status, response.into_string().unwrap_or_default())
+}
+
+/// 発行直後の画面からトークン文字列を取り出す
+fn extract_token(body: &str) -> String {
+    let start = body.find("id=\"new-token\">").expect("token is shown once") + "id=\"new-token\">".len();
+    let end = body[start..].find('<').unwrap();
+    body[start..start + end].to_string()
+}
+
+#[test]
+fn test_create_token_from_account_page_and_use_bearer() {
+    let client = common::setup();
+    common::create_test_user_with_perms(&client, "api_token_ui_user", &["auth.view_user"]);
+
+    client.post("/auth/login")
+        .body(common::with_login_csrf(&client, "username=api_token_ui_user&password=password"))
+        .header(ContentType::Form)
+        .dispatch();
+    let page = client.get("/account/tokens").dispatch();
+    assert_eq!(page.status(), Status::Ok);
+    assert!(page.into_string().unwrap().contains("auth.view_user"));
+
+    let csrf = client.cookies().get("csrf_token").unwrap().value().to_string();
+    let response = client.post("/account/tokens")
+        .body(format!("name=CI&expires_days=30&permissions=auth.view_user&csrf_token={}", csrf))
+        .header(ContentType::Form)
+        .dispatch();
+    assert_eq!(response.status(), Status::Ok);
+    let token = extract_token(&response.into_string().unwrap());
+    assert!(token.starts_with("rds_"));
+
+    // 平文は保存されず、ハッシュのみが保存される
+    let db = client.rocket().state::<DatabaseConnection>().unwrap();
+    let stored = common::block_on(ApiToken::find().all(db)).unwrap();
+    assert!(stored.iter().all(|t| t.token_hash != token));
+    assert!(stored.iter().any(|t| t.token_hash == ApiTokenService::hash_token(&token)));
+
+    // セッションなしのクライアントからトークンで認証する
+    let api_client = common::setup();
+    let (status, body) = get_with_token(&api_client, "/api/me", &token);
+    assert_eq!(status, Status::Ok);
+    assert!(body.contains("\"username\":\"api_token_ui_user\""));
+    assert!(body.contains("\"auth\":\"token\""));
+    assert!(!body.contains("password_hash"));
+
+    let (status, body) = get_with_token(&api_client, "/api/users", &token);
+    assert_eq!(status, Status::Ok);
+    assert!(!body.contains("password_hash"));
+
+    // 最終使用日時が記録される
+    let used = common::block_on(ApiToken::find().all(db)).unwrap();
+    let used = used.iter().find(|t| t.token_hash == ApiTokenService::hash_token(&token)).unwrap();
+    assert!(used.last_used_at.is_some());
+
+    // セッション認証でも同じAPIを利用できる
+    let body = client.get("/api/me").dispatch().into_string().unwrap();
+    assert!(body.contains("\"auth\":\"session\""));
+}
+
+#[test]
+fn test_token_scope_limits_permissions() {
+    let client = common::setup();
+    let db = client.rocket().state::<DatabaseConnection>().unwrap();
+    let user = common::create_test_user_with_perms(&client, "api_token_scope_user", &["auth.view_user", "auth.view_group"]);
+
+    let (_, unscoped) = common::block_on(
+        ApiTokenService::create(db, &user, "groups only", None, &["auth.view_group".to_string()])
+    ).unwrap();
+    let (_, scoped) = common::block_on(
+        ApiTokenService::create(db, &user, "users", None, &["auth.view_user".to_string()])
+    ).unwrap();
+
+    // ユーザー自身が権限を持っていても、トークンの範囲外なら拒否される
+    assert_eq!(get_with_token(&client, "/api/users", &unscoped).0, Status::Forbidden);
+    assert_eq!(get_with_token(&client, "/api/users", &scoped).0, Status::Ok);
+
+    // ユーザーが持っていない権限は範囲に指定できない
+    let result = common::block_on(
+        ApiTokenService::create(db, &user, "too much", None, &["auth.delete_user".to_string()])
+    );
+    assert!(result.is_err());
+}
+
+#[test]
+fn test_revoked_expired_and_invalid_tokens_are_rejected() {
+    let client = common::setup();
+    let db = client.rocket().state::<DatabaseConnection>().unwrap();
+    let user = common::create_test_user_with_perms(&client, "api_token_revoke_user", &[]);
+
+    assert_eq!(client.get("/api/me").dispatch().status(), Status::Unauthorized);
+    assert_eq!(get_with_token(&client, "/api/me", "rds_invalid").0, Status::Unauthorized);
+    assert_eq!(get_with_token(&client, "/api/me", "not-a-token").0, Status::Unauthorized);
+
+    let expired_at = (Utc::now() - Duration::days(1)).into();
+    let (_, expired) = common::block_on(
+        ApiTokenService::create(db, &user, "expired", Some(expired_at), &[])
+    ).unwrap();
+    assert_eq!(get_with_token(&client, "/api/me", &expired).0, Status::Unauthorized);
+
+    let (token, plaintext): (api_token::Model, String) = common::block_on(
+        ApiTokenService::create(db, &user, "revoke me", None, &[])
+    ).unwrap();
+    assert_eq!(get_with_token(&client, "/api/me", &plaintext).0, Status::Ok);
+
+    // 他のユーザーのトークンは削除できない
+    assert!(!common::block_on(ApiTokenService::revoke(db, user.id + 100_000, token.id)).unwrap());
+    assert!(common::block_on(ApiTokenService::revoke(db, user.id, token.id)).unwrap());
+    assert_eq!(get_with_token(&client, "/api/me", &plaintext).0, Status::Unauthorized);
+}
@@ -0,0 +1,58 @@
+use rust_django_starter::checks::{CheckContext, CheckRegistry, Level};
+use rust_django_starter::entities::user;
+use rust_django_starter::settings::Settings;
+use sea_orm::{ActiveModelTrait, DatabaseConnection, EntityTrait, IntoActiveModel, Set};
+
+mod common;
+
+/// 以前のマイグレーションが作成していた初期管理者 (`admin` / `admin`) のパスワードハッシュ
+const SEEDED_ADMIN_PASSWORD_HASH: &str =
+    "$argon2id$v=19$m=19456,t=2,p=1$gbLN0HdzIAg3N/2UmMqJYQ$5xWILs4rN6xIJJE9uPQSAggsNMlPCFlRnS3iqv63Juk";
+
+#[test]
+fn test_standard_checks_pass_after_migrations() {
+    let client = common::setup();
+    let db = client.rocket().state::<DatabaseConnection>().unwrap();
+    let settings = client.rocket().state::<Settings>().unwrap();
+
+    let report = common::block_on(CheckRegistry::default().run(&CheckContext::new(settings, db), false));
+    assert!(report.messages.is_empty(), "{}", report);
+}
+
+#[test]
+fn test_deploy_checks_flag_committed_secret_and_seeded_admin() {
+    let client = common::setup();
+    let db = client.rocket().state::<DatabaseConnection>().unwrap();
+    let settings = client.rocket().state::<Settings>().unwrap();
+    let user = common::create_test_user_with_perms(&client, "check_seeded_admin", &[]);
+    let mut seeded = user.clone().into_active_model();
+    seeded.password_hash = Set(SEEDED_ADMIN_PASSWORD_HASH.to_string());
+    common::block_on(seeded.update(db)).unwrap();
+
+    let report = common::block_on(CheckRegistry::default().run(&CheckContext::new(settings, db), true));
+    common::block_on(user::Entity::delete_by_id(user.id).exec(db)).unwrap();
+
+    // テストでは Rocket.toml にコミットされた secret_key を使っている
+    assert!(report.messages.iter().any(|m| m.id == "security.E001"));
+    assert!(report
+        .messages
+        .iter()
+        .any(|m| m.id == "auth.E001" && m.msg.contains("check_seeded_admin")));
+    assert!(report.fails_at(Level::Error));
+    assert!(report.to_string().contains("HINT: `manage changepassword check_seeded_admin`"));
+}
+
+#[test]
+fn test_deploy_checks_flag_admin_created_by_setup_admin() {
+    let client = common::setup();
+    let db = client.rocket().state::<DatabaseConnection>().unwrap();
+    let settings = client.rocket().state::<Settings>().unwrap();
+    // 以前の `/setup_admin` が作成していた `admin` / `password` の管理者
+    common::create_test_admin(&client);
+
+    let report = common::block_on(CheckRegistry::default().run(&CheckContext::new(settings, db), true));
+    assert!(report
+        .messages
+        .iter()
+        .any(|m| m.id == "auth.E001" && m.msg.contains("'admin'")));
+}
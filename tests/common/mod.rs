@@ -1,5 +1,9 @@
+#![allow(dead_code)]
+
 use rocket::local::blocking::Client;
-use std::sync::Once;
+use rocket::tokio::runtime::Runtime;
+use std::future::Future;
+use std::sync::{Once, OnceLock};
 use migration::{Migrator, MigratorTrait};
 use rust_django_starter::build_rocket;
 use sea_orm::{DatabaseConnection, DbErr};
@@ -7,6 +11,24 @@ use sea_orm::{DatabaseConnection, DbErr};
 // Ensure environment setup runs only once
 static INIT: Once = Once::new();
 
+/// DB接続プールを作成したランタイム。
+/// 作成元のランタイムが破棄されるとプール内の接続が使えなくなるため、
+/// テストプロセスの間ずっと生かしておく。
+fn runtime() -> &'static Runtime {
+    static RUNTIME: OnceLock<Runtime> = OnceLock::new();
+    RUNTIME.get_or_init(|| {
+        rocket::tokio::runtime::Builder::new_multi_thread()
+            .enable_all()
+            .build()
+            .expect("failed to build test runtime")
+    })
+}
+
+/// テスト用ランタイム上で非同期処理を実行する。
+pub fn block_on<F: Future>(future: F) -> F::Output {
+    runtime().block_on(future)
+}
+
 pub fn setup() -> Client {
     INIT.call_once(|| {
         dotenvy::dotenv().ok();
@@ -22,11 +44,12 @@ pub fn setup() -> Client {
         // To mitigate, we will print a warning.
     });
 
-    let rocket = rocket::async_test(async {
-        build_rocket().await
-    });
+    let rocket = block_on(build_rocket());
     
-    Client::tracked(rocket).expect("valid rocket instance")
+    let client = Client::tracked(rocket).expect("valid rocket instance");
+    // リクエストは作成時点のCookieを使うため、ログインフォームに必要なCSRFトークンを先に発行しておく
+    login_csrf_token(&client);
+    client
 }
 
 use rust_django_starter::entities::{prelude::*, user};
@@ -41,41 +64,106 @@ pub async fn reset_db(db: &DatabaseConnection) -> Result<(), DbErr> {
 pub fn create_test_admin(client: &Client) -> user::Model {
     let db = client.rocket().state::<DatabaseConnection>().unwrap();
 
-    // Use tokio runtime to block on async DB operations
-    rocket::tokio::runtime::Builder::new_current_thread()
-        .enable_all()
-        .build()
-        .unwrap()
-        .block_on(async {
-            // Check if exists
-            if let Some(user) = user::Entity::find()
+    block_on(async {
+        let password_hash = hash_password("password").unwrap();
+
+        // Check if exists (マイグレーションで作成された admin はパスワードが異なるため揃える)
+        if let Some(user) = user::Entity::find()
+            .filter(user::Column::Username.eq("admin"))
+            .one(db)
+            .await
+            .unwrap()
+        {
+            let mut active: user::ActiveModel = user.into();
+            active.password_hash = Set(password_hash);
+            active.is_active = Set(true);
+            active.is_admin = Set(true);
+            return active.update(db).await.unwrap();
+        }
+        
+        let active_user = user::ActiveModel {
+            username: Set("admin".to_owned()),
+            password_hash: Set(password_hash),
+            is_active: Set(true),
+            is_admin: Set(true),
+            ..Default::default()
+        };
+        
+        // Insert or fetch if failed (race condition)
+        match active_user.insert(db).await {
+            Ok(u) => u,
+            Err(_) => user::Entity::find()
                 .filter(user::Column::Username.eq("admin"))
                 .one(db)
                 .await
                 .unwrap()
-            {
-                return user;
-            }
+                .unwrap()
+        }
+    })
+}
+
+/// 管理者ではないユーザーを作成し、指定した権限を直接付与する。
+/// 既に存在する場合はそのユーザーを返す。
+pub fn create_test_user_with_perms(client: &Client, username: &str, codenames: &[&str]) -> user::Model {
+    use rust_django_starter::entities::{permission, user_permission};
+
+    let db = client.rocket().state::<DatabaseConnection>().unwrap();
 
-            let password_hash = hash_password("password").unwrap();
-            
-            let active_user = user::ActiveModel {
-                username: Set("admin".to_owned()),
-                password_hash: Set(password_hash),
+    block_on(async {
+        let user = match user::Entity::find()
+            .filter(user::Column::Username.eq(username))
+            .one(db)
+            .await
+            .unwrap()
+        {
+            Some(user) => user,
+            None => user::ActiveModel {
+                username: Set(username.to_owned()),
+                password_hash: Set(hash_password("password").unwrap()),
                 is_active: Set(true),
-                is_admin: Set(true),
+                is_admin: Set(false),
                 ..Default::default()
-            };
-            
-            // Insert or fetch if failed (race condition)
-            match active_user.insert(db).await {
-                Ok(u) => u,
-                Err(_) => user::Entity::find()
-                    .filter(user::Column::Username.eq("admin"))
-                    .one(db)
-                    .await
-                    .unwrap()
-                    .unwrap()
             }
-        })
+            .insert(db)
+            .await
+            .unwrap(),
+        };
+
+        for codename in codenames {
+            let perm = Permission::find()
+                .filter(permission::Column::Codename.eq(*codename))
+                .one(db)
+                .await
+                .unwrap()
+                .expect("permission should be seeded by migrations");
+
+            let _ = user_permission::ActiveModel {
+                user_id: Set(user.id),
+                permission_id: Set(perm.id),
+            }
+            .insert(db)
+            .await;
+        }
+
+        user
+    })
+}
+
+/// ログイン画面を開いてCSRFトークンを取得する (Cookieがなければ発行される)
+pub fn login_csrf_token(client: &Client) -> String {
+    if let Some(cookie) = client.cookies().get("csrf_token") {
+        return cookie.value().to_string();
+    }
+    client.get("/auth/login").dispatch();
+    client.cookies().get("csrf_token").expect("csrf cookie").value().to_string()
+}
+
+/// ログインフォームの送信内容にCSRFトークンを付ける
+pub fn with_login_csrf(client: &Client, body: impl AsRef<str>) -> String {
+    format!("{}&csrf_token={}", body.as_ref(), login_csrf_token(client))
+}
+
+/// HTMX と同じく `X-CSRF-Token` ヘッダーでCSRFトークンを送る
+pub fn csrf_header(client: &Client) -> rocket::http::Header<'static> {
+    rocket::http::Header::new("X-CSRF-Token", login_csrf_token(client))
 }
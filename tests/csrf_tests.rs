@@ -0,0 +1,171 @@
+use chrono::Utc;
+use rocket::http::uri::Host;
+use rocket::http::{ContentType, Header, Status};
+use rocket::local::blocking::Client;
+use rust_django_starter::entities::todo;
+use rust_django_starter::csrf::{CsrfConfig, CsrfToken};
+use rust_django_starter::fairings::csrf::CsrfProtection;
+use rocket_dyn_templates::Template;
+use sea_orm::{ActiveModelTrait, DatabaseConnection, Set};
+
+mod common;
+
+fn login(client: &Client, username: &str) {
+    let response = client.post("/auth/login")
+        .body(common::with_login_csrf(client, format!("username={}&password=password", username)))
+        .header(ContentType::Form)
+        .dispatch();
+    assert_eq!(response.status(), Status::SeeOther);
+}
+
+#[test]
+fn test_htmx_post_requires_csrf_header() {
+    let client = common::setup();
+    let user = common::create_test_user_with_perms(&client, "csrf_todo_user", &[]);
+    login(&client, "csrf_todo_user");
+
+    let db = client.rocket().state::<DatabaseConnection>().unwrap();
+    let now = Utc::now();
+    let item = common::block_on(todo::ActiveModel {
+        title: Set("CSRF".to_string()),
+        completed: Set(false),
+        priority: Set(0),
+        user_id: Set(user.id),
+        created_at: Set(now.into()),
+        updated_at: Set(now.into()),
+        ..Default::default()
+    }.insert(db)).unwrap();
+
+    let uri = format!("/todo/toggle/{}", item.id);
+    let response = client.post(uri.as_str()).header(Header::new("HX-Request", "true")).dispatch();
+    assert_eq!(response.status(), Status::Forbidden);
+    assert_eq!(response.content_type(), Some(ContentType::JSON));
+
+    let response = client.post(uri.as_str()).header(common::csrf_header(&client)).dispatch();
+    assert_eq!(response.status(), Status::Ok);
+}
+
+#[test]
+fn test_logout_requires_csrf_token() {
+    let client = common::setup();
+    common::create_test_user_with_perms(&client, "csrf_logout_user", &[]);
+    login(&client, "csrf_logout_user");
+
+    // トークンのないログアウトは拒否され、ログイン状態のまま
+    assert_eq!(client.post("/auth/logout").dispatch().status(), Status::Forbidden);
+    assert_eq!(client.get("/account").dispatch().status(), Status::Ok);
+
+    let response = client.post("/auth/logout")
+        .header(ContentType::Form)
+        .body(format!("csrf_token={}", common::login_csrf_token(&client)))
+        .dispatch();
+    assert_eq!(response.status(), Status::SeeOther);
+    assert_eq!(client.get("/account").dispatch().status(), Status::SeeOther);
+}
+
+#[rocket::post("/hooks/ping")]
+fn hook() -> &'static str {
+    "pong"
+}
+
+#[rocket::post("/form")]
+fn protected() -> &'static str {
+    "ok"
+}
+
+#[rocket::get("/form")]
+fn form(_csrf: CsrfToken) -> &'static str {
+    "form"
+}
+
+#[test]
+fn test_exempt_paths_and_bearer_requests_skip_validation() {
+    let rocket = rocket::build()
+        .attach(Template::fairing())
+        .attach(CsrfProtection::new(CsrfConfig::new(b"secret", vec![])).exempt("/hooks/"))
+        .mount("/", rocket::routes![hook, protected]);
+    let client = Client::tracked(rocket).unwrap();
+
+    assert_eq!(client.post("/hooks/ping").dispatch().into_string().as_deref(), Some("pong"));
+    assert_eq!(client.post("/form").dispatch().status(), Status::Forbidden);
+
+    let response = client.post("/form").header(Header::new("Authorization", "Bearer rds_token")).dispatch();
+    assert_eq!(response.into_string().as_deref(), Some("ok"));
+}
+
+#[test]
+fn test_csrf_token_rotates_on_login() {
+    let client = common::setup();
+    common::create_test_user_with_perms(&client, "csrf_rotate_user", &[]);
+    let before = common::login_csrf_token(&client);
+    login(&client, "csrf_rotate_user");
+    assert_ne!(common::login_csrf_token(&client), before);
+
+    // フォームに埋め込まれるトークンはレスポンスごとにマスクされる
+    let page = |client: &Client| {
+        let body = client.get("/account").dispatch().into_string().unwrap();
+        let start = body.find("name=\"csrf_token\" value=\"").unwrap() + 25;
+        body[start..start + body[start..].find('"').unwrap()].to_string()
+    };
+    let (first, second) = (page(&client), page(&client));
+    assert_ne!(first, second);
+
+    let response = client.post("/account/security/revoke_others")
+        .header(ContentType::Form)
+        .body(format!("csrf_token={}", first))
+        .dispatch();
+    assert_eq!(response.status(), Status::SeeOther);
+}
+
+#[test]
+fn test_origin_and_referer_checks() {
+    let config = CsrfConfig::new(b"secret", vec!["https://*.trusted.test".into()]);
+    let rocket = rocket::build()
+        .attach(Template::fairing())
+        .attach(CsrfProtection::new(config))
+        .mount("/", rocket::routes![form, protected]);
+    let client = Client::tracked(rocket).unwrap();
+    client.get("/form").dispatch();
+    let token = client.cookies().get("csrf_token").unwrap().value().to_string();
+
+    let post = |headers: &[(&'static str, &'static str)]| {
+        let mut request = client.post("/form").header(Header::new("X-CSRF-Token", token.clone()));
+        request.set_host(Host::parse("app.test").unwrap());
+        for (name, value) in headers {
+            request = request.header(Header::new(*name, *value));
+        }
+        request.dispatch().status()
+    };
+
+    assert_eq!(post(&[]), Status::Ok);
+    assert_eq!(post(&[("Origin", "http://app.test")]), Status::Ok);
+    assert_eq!(post(&[("Origin", "https://evil.test")]), Status::Forbidden);
+    assert_eq!(post(&[("Origin", "null")]), Status::Forbidden);
+
+    // HTTPSでは Origin がなければ Referer が必須
+    let https = ("X-Forwarded-Proto", "https");
+    assert_eq!(post(&[https]), Status::Forbidden);
+    assert_eq!(post(&[https, ("Referer", "http://app.test/form")]), Status::Forbidden);
+    assert_eq!(post(&[https, ("Referer", "https://evil.test/")]), Status::Forbidden);
+    assert_eq!(post(&[https, ("Referer", "https://app.test/form")]), Status::Ok);
+    assert_eq!(post(&[https, ("Referer", "https://www.trusted.test/")]), Status::Ok);
+    assert_eq!(post(&[https, ("Origin", "https://www.trusted.test")]), Status::Ok);
+}
+
+#[test]
+fn test_form_token_must_be_within_peek_limit() {
+    let rocket = rocket::build()
+        .attach(Template::fairing())
+        .attach(CsrfProtection::new(CsrfConfig::new(b"secret", vec![])))
+        .mount("/", rocket::routes![form, protected]);
+    let client = Client::tracked(rocket).unwrap();
+    client.get("/form").dispatch();
+    let token = client.cookies().get("csrf_token").unwrap().value().to_string();
+    let post = |body: String| client.post("/form").header(ContentType::Form).body(body).dispatch().status();
+    let long = "a".repeat(4096);
+
+    // `csrf_token` が先頭にあれば、本文が長くても検証できる
+    assert_eq!(post(format!("csrf_token={}&description={}", token, long)), Status::Ok);
+    // 本文の先頭512バイトより後ろにあるトークンは読めないため拒否される (テンプレートでは先頭に置く)
+    assert_eq!(post(format!("description={}&csrf_token={}", long, token)), Status::Forbidden);
+}
@@ -0,0 +1,63 @@
+use rocket::http::{ContentType, Status};
+use rust_django_starter::controllers::auth::AuthenticationForm;
+use rust_django_starter::entities::user;
+use sea_orm::{ActiveModelTrait, DatabaseConnection, Set};
+
+mod common;
+
+#[test]
+fn test_wrong_password_rerenders_form_with_username_and_error() {
+    let client = common::setup();
+    common::create_test_user_with_perms(&client, "login_form_user", &[]);
+
+    let response = client.post("/auth/login")
+        .body(common::with_login_csrf(&client, "username=login_form_user&password=wrong&next=%2Ftodo"))
+        .header(ContentType::Form)
+        .dispatch();
+    assert_eq!(response.status(), Status::Ok);
+    assert!(response.cookies().get_private("sessionid").is_none());
+    let body = response.into_string().unwrap();
+    assert!(body.contains(AuthenticationForm::INVALID_LOGIN));
+    assert!(body.contains("value=\"login_form_user\""));
+    assert!(body.contains("name=\"next\""));
+
+    // 再表示したフォームからそのままログインできる
+    let response = client.post("/auth/login")
+        .body(common::with_login_csrf(&client, "username=login_form_user&password=password&next=%2Ftodo"))
+        .header(ContentType::Form)
+        .dispatch();
+    assert_eq!(response.status(), Status::SeeOther);
+    assert_eq!(response.headers().get_one("Location"), Some("/todo"));
+}
+
+#[test]
+fn test_inactive_user_sees_inactive_error() {
+    let client = common::setup();
+    let user = common::create_test_user_with_perms(&client, "login_form_inactive", &[]);
+    let db = client.rocket().state::<DatabaseConnection>().unwrap();
+    let mut active: user::ActiveModel = user.into();
+    active.is_active = Set(false);
+    common::block_on(active.update(db)).unwrap();
+
+    let response = client.post("/auth/login")
+        .body(common::with_login_csrf(&client, "username=login_form_inactive&password=password"))
+        .header(ContentType::Form)
+        .dispatch();
+    assert_eq!(response.status(), Status::Ok);
+    assert!(response.into_string().unwrap().contains(AuthenticationForm::INACTIVE));
+}
+
+#[test]
+fn test_login_without_csrf_token_is_rejected() {
+    let client = common::setup();
+    common::create_test_user_with_perms(&client, "login_form_csrf", &[]);
+    client.get("/auth/login").dispatch();
+
+    let response = client.post("/auth/login")
+        .body("username=login_form_csrf&password=password&csrf_token=invalid")
+        .header(ContentType::Form)
+        .dispatch();
+    assert_eq!(response.status(), Status::Forbidden);
+    assert!(response.cookies().get_private("sessionid").is_none());
+    assert!(response.into_string().unwrap().contains("CSRF検証に失敗しました"));
+}
@@ -0,0 +1,78 @@
+use rocket::http::{Accept, ContentType, Header, Status};
+use rocket::local::blocking::Client;
+
+mod common;
+
+fn login(client: &Client, username: &str, next: &str) -> Option<String> {
+    let response = client.post("/auth/login")
+        .body(common::with_login_csrf(client, format!("username={}&password=password&next={}", username, next)))
+        .header(ContentType::Form)
+        .dispatch();
+    assert_eq!(response.status(), Status::SeeOther);
+    response.headers().get_one("Location").map(str::to_string)
+}
+
+#[test]
+fn test_anonymous_html_request_redirects_to_login_with_next() {
+    let client = common::setup();
+    common::create_test_user_with_perms(&client, "next_redirect_user", &[]);
+
+    let response = client.get("/todo").dispatch();
+    assert_eq!(response.status(), Status::SeeOther);
+    assert_eq!(response.headers().get_one("Location"), Some("/auth/login?next=%2Ftodo"));
+
+    // ログイン画面は next をフォームに引き継ぐ
+    let body = client.get("/auth/login?next=%2Ftodo").dispatch().into_string().unwrap();
+    assert!(body.contains("name=\"next\""));
+
+    assert_eq!(login(&client, "next_redirect_user", "%2Ftodo").as_deref(), Some("/todo"));
+    assert_eq!(client.get("/todo").dispatch().status(), Status::Ok);
+}
+
+#[test]
+fn test_next_rejects_external_urls() {
+    let client = common::setup();
+    common::create_test_user_with_perms(&client, "next_open_redirect_user", &[]);
+
+    for next in ["https%3A%2F%2Fevil.example.com%2F", "%2F%2Fevil.example.com%2F", "%2F%5Cevil.example.com"] {
+        let location = login(&client, "next_open_redirect_user", next).unwrap();
+        assert!(!location.contains("evil.example.com"), "redirected to {}", location);
+    }
+
+    // 外部URLはログイン画面のフォームにも埋め込まない
+    let body = client.get("/auth/login?next=%2F%2Fevil.example.com%2F").dispatch().into_string().unwrap();
+    assert!(!body.contains("evil.example.com"));
+}
+
+#[test]
+fn test_api_and_htmx_requests_get_json_errors() {
+    let client = common::setup();
+
+    let response = client.get("/api/me").dispatch();
+    assert_eq!(response.status(), Status::Unauthorized);
+    assert_eq!(response.content_type(), Some(ContentType::JSON));
+    assert!(response.into_string().unwrap().contains("\"error\":\"unauthorized\""));
+
+    let response = client.get("/todo").header(Header::new("HX-Request", "true")).dispatch();
+    assert_eq!(response.status(), Status::Unauthorized);
+    assert_eq!(response.content_type(), Some(ContentType::JSON));
+
+    let response = client.get("/account").header(Accept::JSON).dispatch();
+    assert_eq!(response.status(), Status::Unauthorized);
+    assert_eq!(response.content_type(), Some(ContentType::JSON));
+}
+
+#[test]
+fn test_forbidden_shows_error_page_for_logged_in_user() {
+    let client = common::setup();
+    common::create_test_user_with_perms(&client, "next_forbidden_user", &[]);
+    login(&client, "next_forbidden_user", "");
+
+    let response = client.get("/admin/users").dispatch();
+    assert_eq!(response.status(), Status::Forbidden);
+    assert!(response.into_string().unwrap().contains("アクセス権がありません"));
+
+    let response = client.get("/admin/users").header(Header::new("HX-Request", "true")).dispatch();
+    assert_eq!(response.status(), Status::Forbidden);
+    assert!(response.into_string().unwrap().contains("\"error\":\"forbidden\""));
+}
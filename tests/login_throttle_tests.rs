@@ -0,0 +1,136 @@
+use rocket::http::{ContentType, Status};
+use rocket::local::blocking::Client;
+use rust_django_starter::entities::{log_entry, prelude::*};
+use rust_django_starter::services::login_throttle::LoginThrottle;
+use sea_orm::{ColumnTrait, DatabaseConnection, EntityTrait, QueryFilter, QueryOrder};
+
+mod common;
+
+fn login(client: &Client, username: &str, password: &str) -> Status {
+    client.post("/auth/login")
+        .body(common::with_login_csrf(client, format!("username={}&password={}", username, password)))
+        .header(ContentType::Form)
+        .dispatch()
+        .status()
+}
+
+/// 前回のテスト実行で残った失敗記録を消去する
+fn reset_failures(client: &Client, username: &str) {
+    let db = client.rocket().state::<DatabaseConnection>().unwrap();
+    let throttle = client.rocket().state::<LoginThrottle>().unwrap();
+    common::block_on(throttle.reset(db, username)).unwrap();
+}
+
+#[test]
+fn test_lockout_after_repeated_failures_and_admin_unlock() {
+    let client = common::setup();
+    let user = common::create_test_user_with_perms(&client, "throttle_locked", &[]);
+    reset_failures(&client, "throttle_locked");
+    let limit = client.rocket().state::<LoginThrottle>().unwrap().config().failure_limit;
+
+    for _ in 0..limit {
+        assert_eq!(login(&client, "throttle_locked", "wrong"), Status::Ok);
+    }
+
+    // ロック中は正しいパスワードでもログインできない
+    assert_eq!(login(&client, "throttle_locked", "password"), Status::TooManyRequests);
+
+    // 管理者は編集画面でロック状態を確認して解除できる
+    let admin = common::setup();
+    common::create_test_admin(&admin);
+    assert_eq!(login(&admin, "admin", "password"), Status::SeeOther);
+    let body = admin.get(format!("/admin/users/edit/{}", user.id)).dispatch().into_string().unwrap();
+    assert!(body.contains("ロック中"));
+
+    let csrf_token = admin.cookies().get("csrf_token").unwrap().value().to_string();
+    let response = admin.post(format!("/admin/users/unlock/{}", user.id))
+        .body(format!("csrf_token={}", csrf_token))
+        .header(ContentType::Form)
+        .dispatch();
+    assert_eq!(response.status(), Status::SeeOther);
+
+    assert_eq!(login(&client, "throttle_locked", "password"), Status::SeeOther);
+}
+
+#[test]
+fn test_lockout_and_unlock_are_logged() {
+    let client = common::setup();
+    let name = format!("throttle_logged_{}", uuid::Uuid::new_v4().simple());
+    let user = common::create_test_user_with_perms(&client, &name, &[]);
+    let limit = client.rocket().state::<LoginThrottle>().unwrap().config().failure_limit;
+    for _ in 0..limit {
+        login(&client, &name, "wrong");
+    }
+
+    let admin = common::setup();
+    let admin_user = common::create_test_admin(&admin);
+    assert_eq!(login(&admin, "admin", "password"), Status::SeeOther);
+    let response = admin.post(format!("/admin/users/unlock/{}", user.id))
+        .body(format!("csrf_token={}", common::login_csrf_token(&admin)))
+        .header(ContentType::Form)
+        .dispatch();
+    assert_eq!(response.status(), Status::SeeOther);
+
+    let db = client.rocket().state::<DatabaseConnection>().unwrap();
+    let entries = common::block_on(
+        LogEntry::find()
+            .filter(log_entry::Column::ContentType.eq("user"))
+            .filter(log_entry::Column::ObjectId.eq(user.id.to_string()))
+            .order_by_asc(log_entry::Column::Id)
+            .all(db),
+    )
+    .unwrap();
+    assert_eq!(entries.len(), 2);
+    // ロックはロックされたユーザー自身、解除は操作した管理者の操作として記録される
+    assert_eq!(entries[0].user_id, Some(user.id));
+    assert!(entries[0].get_change_message().contains("ロックしました"));
+    assert_eq!(entries[1].user_id, Some(admin_user.id));
+    assert_eq!(entries[1].get_change_message(), "locked_until を変更しました。");
+}
+
+#[test]
+fn test_successful_login_resets_failure_count() {
+    let client = common::setup();
+    common::create_test_user_with_perms(&client, "throttle_reset", &[]);
+    reset_failures(&client, "throttle_reset");
+    let limit = client.rocket().state::<LoginThrottle>().unwrap().config().failure_limit;
+
+    for _ in 0..limit - 1 {
+        assert_eq!(login(&client, "throttle_reset", "wrong"), Status::Ok);
+    }
+    assert_eq!(login(&client, "throttle_reset", "password"), Status::SeeOther);
+
+    // 成功で回数がリセットされるため、もう一度失敗してもロックされない
+    assert_eq!(login(&client, "throttle_reset", "wrong"), Status::Ok);
+    assert_eq!(login(&client, "throttle_reset", "password"), Status::SeeOther);
+}
+
+#[test]
+fn test_unknown_username_is_also_throttled() {
+    let client = common::setup();
+    reset_failures(&client, "throttle_nobody");
+    let limit = client.rocket().state::<LoginThrottle>().unwrap().config().failure_limit;
+
+    for _ in 0..limit {
+        assert_eq!(login(&client, "throttle_nobody", "wrong"), Status::Ok);
+    }
+    assert_eq!(login(&client, "throttle_nobody", "wrong"), Status::TooManyRequests);
+}
+
+#[test]
+fn test_concurrent_failures_are_all_counted() {
+    let client = common::setup();
+    let username = format!("throttle_{}", uuid::Uuid::new_v4().simple());
+    let db = client.rocket().state::<DatabaseConnection>().unwrap();
+    let throttle = client.rocket().state::<LoginThrottle>().unwrap();
+    let attempts = 10;
+
+    let failures = (0..attempts).map(|_| throttle.record_failure(db, &username, None));
+    for result in common::block_on(rocket::futures::future::join_all(failures)) {
+        result.unwrap();
+    }
+
+    let record = common::block_on(throttle.status(db, &username)).unwrap().unwrap();
+    assert_eq!(record.failure_count, attempts);
+    assert!(record.locked_until.is_some());
+}
@@ -0,0 +1,52 @@
+use rocket::http::{ContentType, Status};
+use rust_django_starter::management;
+use rust_django_starter::services::user_service::UserService;
+use rust_django_starter::settings::Settings;
+use sea_orm::DatabaseConnection;
+
+mod common;
+
+#[test]
+fn test_setup_admin_route_is_removed() {
+    let client = common::setup();
+    let response = client.get("/setup_admin").dispatch();
+    assert_eq!(response.status(), Status::NotFound);
+}
+
+#[test]
+fn test_createsuperuser_and_changepassword() {
+    let client = common::setup();
+    let db = client.rocket().state::<DatabaseConnection>().unwrap();
+    let settings = client.rocket().state::<Settings>().unwrap();
+    let username = format!("super_{}", uuid::Uuid::new_v4().simple());
+
+    let user = common::block_on(management::create_superuser(db, settings, &username, "super@example.com", "initialPass1")).unwrap();
+    assert!(user.is_admin);
+    assert!(user.is_active);
+    assert_eq!(user.email, "super@example.com");
+
+    // 同じユーザー名では作成できない
+    assert!(common::block_on(management::create_superuser(db, settings, &username, "", "initialPass1")).is_err());
+
+    let response = client.post("/auth/login")
+        .body(common::with_login_csrf(&client, format!("username={}&password=initialPass1", username)))
+        .header(ContentType::Form)
+        .dispatch();
+    assert_eq!(response.status(), Status::SeeOther);
+    assert_eq!(client.get("/admin/users").dispatch().status(), Status::Ok);
+
+    common::block_on(management::change_password(db, settings, &username, "changedPass2")).unwrap();
+
+    // パスワード変更で既存のセッションは無効化される
+    assert_eq!(client.get("/admin/users").dispatch().status(), Status::SeeOther);
+    assert!(common::block_on(UserService::authenticate(db, &username, "changedPass2")).is_ok());
+}
+
+#[test]
+fn test_changepassword_unknown_user() {
+    let client = common::setup();
+    let db = client.rocket().state::<DatabaseConnection>().unwrap();
+    let settings = client.rocket().state::<Settings>().unwrap();
+
+    assert!(common::block_on(management::change_password(db, settings, "no_such_user", "whatever123")).is_err());
+}
@@ -0,0 +1,386 @@
+use jsonwebtoken::{Algorithm, EncodingKey, Header};
+use rocket::http::{ContentType, Status};
+use rocket::local::blocking::Client;
+use rocket::serde::json::serde_json::{self, json, Value};
+use rocket::tokio::io::{AsyncReadExt, AsyncWriteExt};
+use rocket::tokio::net::{TcpListener, TcpStream};
+use rust_django_starter::auth_utils::UNUSABLE_PASSWORD_PREFIX;
+use rust_django_starter::entities::{group, group_user, prelude::*, user, user_identity};
+use rust_django_starter::oidc::code_challenge;
+use sea_orm::{ActiveModelTrait, ColumnTrait, DatabaseConnection, EntityTrait, QueryFilter, Set};
+use std::collections::HashMap;
+use std::sync::{Arc, Mutex, OnceLock};
+
+mod common;
+
+/// テスト専用の署名鍵 (スタブIdPでのみ使用)
+const SIGNING_KEY_PEM: &str = include_str!("fixtures/oidc_test_key.pem");
+/// 上記の鍵の公開鍵 (JWK の `n`)
+const SIGNING_KEY_N: &str = "tpKHhWw_tlUZOwGVqsDmaqri7IZQv1TVskV15iFobvn-lQI0meXPuNytsF3ltDWSKUCywfr39XLb3bsy_RJHobCdU-kB2ORTTKvb9Y9G6O1g7YoQeCgA9WBHZW8bhG1WlMkjM_7kKU4Bev1l6h62Sri2wSMLVOQxw2C_bikpFIT9L9YM0GruNkgjk3cIJfB1YfpWdqCQwfsZMmJWgaR6fdG7qwGNY2tQQmECSr9x_tv3gpekltZAeh6QjucTNKPrmoc0mGqA7g7jZus-FQCafDRV2tOS80Ao4QRUDQR4ni2y5Kjd50aiiyTjPCCe7kZOh6VtQk0-_dru3yMbePOxiQ";
+
+const CLIENT_ID: &str = "starter";
+const CLIENT_SECRET: &str = "stub-secret";
+
+/// 認可エンドポイントで発行したコード
+struct PendingCode {
+    claims: Value,
+    code_challenge: String,
+    redirect_uri: String,
+}
+
+/// テスト用の最小限の OpenID Connect プロバイダー。
+/// ディスカバリー・JWKS・トークンエンドポイントをHTTPで提供し、認可エンドポイントは `authorize()` で代用する。
+struct StubIdp {
+    base_url: String,
+    codes: Arc<Mutex<HashMap<String, PendingCode>>>,
+}
+
+impl StubIdp {
+    fn start() -> StubIdp {
+        let codes: Arc<Mutex<HashMap<String, PendingCode>>> = Arc::default();
+        let listener = common::block_on(TcpListener::bind("127.0.0.1:0")).unwrap();
+        let base_url = format!("http://{}", listener.local_addr().unwrap());
+
+        let (server_codes, issuer) = (codes.clone(), base_url.clone());
+        common::block_on(async move {
+            rocket::tokio::spawn(async move {
+                loop {
+                    let (stream, _) = listener.accept().await.unwrap();
+                    rocket::tokio::spawn(handle(stream, issuer.clone(), server_codes.clone()));
+                }
+            });
+        });
+
+        StubIdp { base_url, codes }
+    }
+
+    /// ユーザーがIdPでログインした結果として、コールバックのURL (パス部分) を返す
+    fn authorize(&self, authorization_url: &str, claims: Value) -> String {
+        let url = reqwest::Url::parse(authorization_url).unwrap();
+        assert!(authorization_url.starts_with(&format!("{}/authorize?", self.base_url)));
+        let query: HashMap<String, String> = url.query_pairs().into_owned().collect();
+        assert_eq!(query["response_type"], "code");
+        assert_eq!(query["client_id"], CLIENT_ID);
+        assert_eq!(query["code_challenge_method"], "S256");
+        assert!(query["scope"].split(' ').any(|s| s == "openid"));
+
+        let mut claims = claims;
+        claims.as_object_mut().unwrap().entry("nonce").or_insert(json!(query["nonce"]));
+        let code = uuid::Uuid::new_v4().simple().to_string();
+        self.codes.lock().unwrap().insert(code.clone(), PendingCode {
+            claims,
+            code_challenge: query["code_challenge"].clone(),
+            redirect_uri: query["redirect_uri"].clone(),
+        });
+
+        let redirect_uri = reqwest::Url::parse(&query["redirect_uri"]).unwrap();
+        format!("{}?code={}&state={}", redirect_uri.path(), code, query["state"])
+    }
+}
+
+fn sign(issuer: &str, claims: &Value) -> String {
+    let now = chrono::Utc::now().timestamp();
+    let mut payload = json!({ "iss": issuer, "aud": CLIENT_ID, "iat": now, "exp": now + 300 });
+    for (key, value) in claims.as_object().unwrap() {
+        payload[key] = value.clone();
+    }
+    if payload.get("alg").and_then(Value::as_str) == Some("HS256") {
+        // 共通鍵 (クライアントシークレット) で署名したトークン
+        let header = Header::new(Algorithm::HS256);
+        return jsonwebtoken::encode(&header, &payload, &EncodingKey::from_secret(CLIENT_SECRET.as_bytes())).unwrap();
+    }
+    let mut header = Header::new(Algorithm::RS256);
+    header.kid = Some("stub-key".to_string());
+    jsonwebtoken::encode(&header, &payload, &EncodingKey::from_rsa_pem(SIGNING_KEY_PEM.as_bytes()).unwrap()).unwrap()
+}
+
+fn token_response(issuer: &str, codes: &Mutex<HashMap<String, PendingCode>>, body: &str) -> (u16, Value) {
+    let params: HashMap<String, String> = reqwest::Url::parse(&format!("http://stub/?{}", body))
+        .unwrap()
+        .query_pairs()
+        .into_owned()
+        .collect();
+    let param = |key: &str| params.get(key).map(String::as_str).unwrap_or_default();
+    let pending = match codes.lock().unwrap().remove(param("code")) {
+        Some(pending) => pending,
+        None => return (400, json!({ "error": "invalid_grant" })),
+    };
+
+    let valid = param("grant_type") == "authorization_code"
+        && param("client_id") == CLIENT_ID
+        && param("client_secret") == CLIENT_SECRET
+        && param("redirect_uri") == pending.redirect_uri
+        && code_challenge(param("code_verifier")) == pending.code_challenge;
+    if !valid {
+        return (400, json!({ "error": "invalid_grant" }));
+    }
+    (200, json!({
+        "access_token": "stub-access-token",
+        "token_type": "Bearer",
+        "expires_in": 300,
+        "id_token": sign(issuer, &pending.claims),
+    }))
+}
+
+async fn handle(mut stream: TcpStream, issuer: String, codes: Arc<Mutex<HashMap<String, PendingCode>>>) {
+    let mut buf = Vec::new();
+    let mut chunk = [0u8; 4096];
+    let header_end = loop {
+        let n = stream.read(&mut chunk).await.unwrap_or(0);
+        if n == 0 {
+            return;
+        }
+        buf.extend_from_slice(&chunk[..n]);
+        if let Some(pos) = buf.windows(4).position(|w| w == b"\r\n\r\n") {
+            break pos + 4;
+        }
+    };
+    let head = String::from_utf8_lossy(&buf[..header_end]).to_string();
+    let content_length = head
+        .lines()
+        .find_map(|line| line.to_lowercase().strip_prefix("content-length:").map(|v| v.trim().parse::<usize>().unwrap_or(0)))
+        .unwrap_or(0);
+    while buf.len() < header_end + content_length {
+        let n = stream.read(&mut chunk).await.unwrap_or(0);
+        if n == 0 {
+            break;
+        }
+        buf.extend_from_slice(&chunk[..n]);
+    }
+    let body = String::from_utf8_lossy(&buf[header_end..]).to_string();
+
+    let request_line = head.lines().next().unwrap_or_default();
+    let (status, payload) = match request_line.split(' ').take(2).collect::<Vec<_>>()[..] {
+        ["GET", "/.well-known/openid-configuration"] => (200, json!({
+            "issuer": issuer,
+            "authorization_endpoint": format!("{}/authorize", issuer),
+            "token_endpoint": format!("{}/token", issuer),
+            "jwks_uri": format!("{}/jwks", issuer),
+            "response_types_supported": ["code"],
+            "subject_types_supported": ["public"],
+            "id_token_signing_alg_values_supported": ["RS256"],
+        })),
+        ["GET", "/jwks"] => (200, json!({
+            "keys": [{ "kty": "RSA", "kid": "stub-key", "use": "sig", "alg": "RS256", "n": SIGNING_KEY_N, "e": "AQAB" }],
+        })),
+        ["POST", "/token"] => token_response(&issuer, &codes, &body),
+        _ => (404, json!({ "error": "not_found" })),
+    };
+
+    let payload = serde_json::to_string(&payload).unwrap();
+    let response = format!(
+        "HTTP/1.1 {} OK\r\nContent-Type: application/json\r\nContent-Length: {}\r\nConnection: close\r\n\r\n{}",
+        status,
+        payload.len(),
+        payload
+    );
+    let _ = stream.write_all(response.as_bytes()).await;
+}
+
+/// スタブIdPを起動し、`company` プロバイダーとして設定する (テストプロセスで1回だけ)
+fn stub_idp() -> &'static StubIdp {
+    static STUB: OnceLock<StubIdp> = OnceLock::new();
+    STUB.get_or_init(|| {
+        let stub = StubIdp::start();
+        std::env::set_var("OIDC_PROVIDERS", "company");
+        std::env::set_var("OIDC_COMPANY_ISSUER", &stub.base_url);
+        std::env::set_var("OIDC_COMPANY_CLIENT_ID", CLIENT_ID);
+        std::env::set_var("OIDC_COMPANY_CLIENT_SECRET", CLIENT_SECRET);
+        std::env::set_var("OIDC_COMPANY_DISPLAY_NAME", "Company SSO");
+        std::env::set_var("OIDC_COMPANY_GROUPS_CLAIM", "groups");
+        std::env::set_var("OIDC_COMPANY_MANAGED_GROUPS", "oidc-staff-*");
+        stub
+    })
+}
+
+fn setup() -> (&'static StubIdp, Client) {
+    let stub = stub_idp();
+    (stub, common::setup())
+}
+
+/// `/auth/oidc/company/login` から IdP でのログインを経てコールバックを呼び出す
+fn sign_in(stub: &StubIdp, client: &Client, next: Option<&str>, claims: Value) -> (Status, Option<String>) {
+    let uri = match next {
+        Some(next) => format!("/auth/oidc/company/login?next={}", next),
+        None => "/auth/oidc/company/login".to_string(),
+    };
+    let response = client.get(uri).dispatch();
+    assert_eq!(response.status(), Status::SeeOther);
+    let authorization_url = response.headers().get_one("Location").unwrap().to_string();
+
+    let callback = stub.authorize(&authorization_url, claims);
+    let response = client.get(callback).dispatch();
+    (response.status(), response.headers().get_one("Location").map(str::to_string))
+}
+
+fn find_user(db: &DatabaseConnection, username: &str) -> Option<user::Model> {
+    common::block_on(User::find().filter(user::Column::Username.eq(username)).one(db)).unwrap()
+}
+
+fn group_names(db: &DatabaseConnection, user: &user::Model) -> Vec<String> {
+    use sea_orm::ModelTrait;
+    common::block_on(user.find_related(Group).all(db))
+        .unwrap()
+        .into_iter()
+        .map(|g| g.name)
+        .collect()
+}
+
+#[test]
+fn test_login_page_offers_provider_next_to_password_login() {
+    let (_, client) = setup();
+
+    let body = client.get("/auth/login").dispatch().into_string().unwrap();
+    assert!(body.contains("Company SSO でログイン"));
+    assert!(body.contains("id_password"));
+
+    common::create_test_user_with_perms(&client, "oidc_local_user", &[]);
+    let response = client.post("/auth/login")
+        .body(common::with_login_csrf(&client, "username=oidc_local_user&password=password"))
+        .header(ContentType::Form)
+        .dispatch();
+    assert_eq!(response.status(), Status::SeeOther);
+    assert_eq!(client.get("/account").dispatch().status(), Status::Ok);
+}
+
+#[test]
+fn test_first_login_provisions_user_and_syncs_groups() {
+    let (stub, client) = setup();
+    let db = client.rocket().state::<DatabaseConnection>().unwrap();
+
+    let suffix = &uuid::Uuid::new_v4().simple().to_string()[..8];
+    let username = format!("oidc_{}", suffix);
+    let group_name = format!("oidc-staff-{}", suffix);
+    common::block_on(group::ActiveModel { name: Set(group_name.clone()), ..Default::default() }.insert(db)).unwrap();
+
+    let claims = json!({
+        "sub": format!("sub-{}", suffix),
+        "preferred_username": username,
+        "email": format!("{}@example.com", username),
+        "email_verified": true,
+        "groups": [group_name, "not-a-local-group"],
+    });
+    let (status, location) = sign_in(stub, &client, Some("/todo"), claims.clone());
+    assert_eq!(status, Status::SeeOther);
+    assert_eq!(location.as_deref(), Some("/todo"));
+    assert_eq!(client.get("/account").dispatch().status(), Status::Ok);
+
+    // パスワードでは利用できないユーザーとして作成され、IdPのグループに所属する
+    let user = find_user(db, &username).expect("user is provisioned");
+    assert!(user.password_hash.starts_with(UNUSABLE_PASSWORD_PREFIX));
+    assert_eq!(user.email, format!("{}@example.com", username));
+    assert_eq!(group_names(db, &user), vec![group_name.clone()]);
+
+    // 2回目以降は同じユーザーでログインし、グループはクレームに合わせて更新される
+    let other = common::setup();
+    let mut claims = claims;
+    claims["groups"] = json!([]);
+    assert_eq!(sign_in(stub, &other, None, claims).0, Status::SeeOther);
+    let identities = common::block_on(
+        UserIdentity::find().filter(user_identity::Column::Subject.eq(format!("sub-{}", suffix))).all(db)
+    ).unwrap();
+    assert_eq!(identities.len(), 1);
+    assert_eq!(identities[0].user_id, user.id);
+    assert!(group_names(db, &user).is_empty());
+    assert!(common::block_on(GroupUser::find().filter(group_user::Column::UserId.eq(user.id)).all(db)).unwrap().is_empty());
+}
+
+#[test]
+fn test_group_sync_keeps_groups_not_managed_by_idp() {
+    let (stub, client) = setup();
+    let db = client.rocket().state::<DatabaseConnection>().unwrap();
+
+    let suffix = &uuid::Uuid::new_v4().simple().to_string()[..8];
+    let username = format!("oidc_{}", suffix);
+    let managed = format!("oidc-staff-{}", suffix);
+    let local = format!("oidc-local-{}", suffix);
+    let mut ids = HashMap::new();
+    for name in [&managed, &local] {
+        let group = common::block_on(group::ActiveModel { name: Set(name.clone()), ..Default::default() }.insert(db)).unwrap();
+        ids.insert(name.clone(), group.id);
+    }
+
+    let claims = json!({ "sub": format!("sub-{}", suffix), "preferred_username": username, "groups": [managed] });
+    assert_eq!(sign_in(stub, &client, None, claims.clone()).0, Status::SeeOther);
+    let user = find_user(db, &username).expect("user is provisioned");
+    assert_eq!(group_names(db, &user), vec![managed.clone()]);
+
+    // 管理画面で追加したグループは、IdPが管理するグループでなければ次のログインでも外されない
+    common::block_on(group_user::ActiveModel { group_id: Set(ids[&local]), user_id: Set(user.id), ..Default::default() }.insert(db)).unwrap();
+    let mut claims = claims;
+    claims["groups"] = json!([local]);
+    assert_eq!(sign_in(stub, &common::setup(), None, claims).0, Status::SeeOther);
+    assert_eq!(group_names(db, &user), vec![local.clone()]);
+
+    // IdPが管理しないグループには、クレームに含まれていても追加しない
+    common::block_on(GroupUser::delete_many().filter(group_user::Column::UserId.eq(user.id)).exec(db)).unwrap();
+    let claims = json!({ "sub": format!("sub-{}", suffix), "groups": [local] });
+    assert_eq!(sign_in(stub, &common::setup(), None, claims).0, Status::SeeOther);
+    assert!(group_names(db, &user).is_empty());
+}
+
+#[test]
+fn test_verified_email_links_existing_user() {
+    let (stub, client) = setup();
+    let db = client.rocket().state::<DatabaseConnection>().unwrap();
+
+    let suffix = &uuid::Uuid::new_v4().simple().to_string()[..8];
+    let existing = common::create_test_user_with_perms(&client, &format!("oidc_existing_{}", suffix), &[]);
+    let email = format!("existing_{}@example.com", suffix);
+    let mut active: user::ActiveModel = existing.clone().into();
+    active.email = Set(email.clone());
+    common::block_on(active.update(db)).unwrap();
+
+    let claims = json!({ "sub": format!("verified-{}", suffix), "email": email, "email_verified": true });
+    assert_eq!(sign_in(stub, &client, None, claims).0, Status::SeeOther);
+    let identity = common::block_on(
+        UserIdentity::find().filter(user_identity::Column::Subject.eq(format!("verified-{}", suffix))).one(db)
+    ).unwrap().unwrap();
+    assert_eq!(identity.user_id, existing.id);
+    // 既存ユーザーのパスワードはそのまま使える
+    assert!(!find_user(db, &existing.username).unwrap().password_hash.starts_with(UNUSABLE_PASSWORD_PREFIX));
+
+    // 未検証のメールアドレスでは既存ユーザーに紐付けない
+    let unverified = common::setup();
+    let claims = json!({ "sub": format!("unverified-{}", suffix), "email": email, "email_verified": false });
+    assert_eq!(sign_in(stub, &unverified, None, claims).0, Status::SeeOther);
+    let identity = common::block_on(
+        UserIdentity::find().filter(user_identity::Column::Subject.eq(format!("unverified-{}", suffix))).one(db)
+    ).unwrap().unwrap();
+    assert_ne!(identity.user_id, existing.id);
+}
+
+#[test]
+fn test_callback_rejects_invalid_state_nonce_and_algorithm() {
+    let (stub, client) = setup();
+    let suffix = &uuid::Uuid::new_v4().simple().to_string()[..8];
+    let claims = json!({ "sub": format!("reject-{}", suffix) });
+
+    // 外部サイトへの next は無視される
+    let response = client.get("/auth/oidc/company/login?next=//evil.example.com/").dispatch();
+    let authorization_url = response.headers().get_one("Location").unwrap().to_string();
+    let callback = stub.authorize(&authorization_url, claims.clone());
+
+    // state が一致しない
+    let forged = callback.replace("state=", "state=x");
+    assert_eq!(client.get(forged).dispatch().status(), Status::BadRequest);
+    // 認可リクエストは1回限り (Cookieは削除済み)
+    assert_eq!(client.get(callback).dispatch().status(), Status::BadRequest);
+
+    let (status, location) = sign_in(stub, &client, Some("//evil.example.com/"), claims.clone());
+    assert_eq!(status, Status::SeeOther);
+    assert_eq!(location.as_deref(), Some("/"));
+
+    // nonce が一致しない
+    let mut replayed = claims.clone();
+    replayed["nonce"] = json!("other-nonce");
+    assert_eq!(sign_in(stub, &common::setup(), None, replayed).0, Status::Unauthorized);
+
+    // 共通鍵方式 (HS256) の署名は受け付けない
+    let mut symmetric = claims;
+    symmetric["alg"] = json!("HS256");
+    assert_eq!(sign_in(stub, &common::setup(), None, symmetric).0, Status::Unauthorized);
+
+    // 存在しないプロバイダー
+    assert_eq!(client.get("/auth/oidc/unknown/login").dispatch().status(), Status::NotFound);
+}
@@ -0,0 +1,40 @@
+use rocket::http::{ContentType, Status};
+use rust_django_starter::entities::user;
+use rust_django_starter::services::user_service::UserService;
+use sea_orm::{ActiveModelTrait, DatabaseConnection, Set};
+
+mod common;
+
+/// Djangoの `make_password("lètmein", "seasalt", "pbkdf2_sha256")` (反復回数のみ変更)
+const DJANGO_PBKDF2_HASH: &str = "pbkdf2_sha256$10000$seasalt$CWWFdHOWwPnki7HvkcqN9iA2T3KLW1cf2uZ5kvArtVY=";
+
+#[test]
+fn test_login_with_django_hash_upgrades_to_argon2() {
+    let client = common::setup();
+    let db = client.rocket().state::<DatabaseConnection>().unwrap();
+
+    // Djangoから移行したユーザーのハッシュをそのまま保存する
+    let user = common::create_test_user_with_perms(&client, "django_user", &[]);
+    let mut active: user::ActiveModel = user.clone().into();
+    active.password_hash = Set(DJANGO_PBKDF2_HASH.to_string());
+    common::block_on(active.update(db)).unwrap();
+
+    let response = client.post("/auth/login")
+        .body(common::with_login_csrf(&client, "username=django_user&password=wrong"))
+        .header(ContentType::Form)
+        .dispatch();
+    assert_eq!(response.status(), Status::Ok);
+    let reloaded = common::block_on(UserService::find_by_id(db, user.id)).unwrap().unwrap();
+    assert_eq!(reloaded.password_hash, DJANGO_PBKDF2_HASH);
+
+    let response = client.post("/auth/login")
+        .body(common::with_login_csrf(&client, "username=django_user&password=l%C3%A8tmein"))
+        .header(ContentType::Form)
+        .dispatch();
+    assert_eq!(response.status(), Status::SeeOther);
+
+    // ログイン成功時に既定の Argon2id で保存し直される
+    let reloaded = common::block_on(UserService::find_by_id(db, user.id)).unwrap().unwrap();
+    assert!(reloaded.password_hash.starts_with("$argon2id$"));
+    assert!(common::block_on(UserService::authenticate(db, "django_user", "lètmein")).is_ok());
+}
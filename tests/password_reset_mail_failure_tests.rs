@@ -0,0 +1,31 @@
+use rocket::http::{ContentType, Status};
+use sea_orm::{ActiveModelTrait, DatabaseConnection, Set};
+use rust_django_starter::entities::user;
+
+mod common;
+
+#[test]
+fn test_mail_failure_does_not_reveal_registered_email() {
+    // 接続できないSMTPサーバーを指定して送信を失敗させる (環境変数はこのテストバイナリ内でのみ有効)
+    std::env::set_var("EMAIL_BACKEND", "smtp");
+    std::env::set_var("EMAIL_HOST", "127.0.0.1");
+    std::env::set_var("EMAIL_PORT", "1");
+    let client = common::setup();
+    let name = format!("reset_fail_{}", uuid::Uuid::new_v4().simple());
+    let user = common::create_test_user_with_perms(&client, &name, &[]);
+    let email = format!("{}@example.com", name);
+    let mut active: user::ActiveModel = user.into();
+    active.email = Set(email.clone());
+    common::block_on(active.update(client.rocket().state::<DatabaseConnection>().unwrap())).unwrap();
+
+    // 登録済みのアドレスでも、未登録のアドレスと同じく完了画面へ遷移する
+    for address in [email.as_str(), "nobody@example.com"] {
+        let response = client.post("/auth/password_reset")
+            .body(format!("email={}&csrf_token={}", address.replace('@', "%40"), common::login_csrf_token(&client)))
+            .header(ContentType::Form)
+            .dispatch();
+        assert_eq!(response.status(), Status::SeeOther);
+        assert_eq!(response.headers().get_one("Location"), Some("/auth/password_reset/done"));
+        assert!(client.cookies().get("_flash").is_none());
+    }
+}
@@ -0,0 +1,180 @@
+use std::path::PathBuf;
+use rocket::get;
+use rocket::http::uri::Host;
+use rocket::http::{ContentType, Header, Status};
+use rocket::local::blocking::Client;
+use rust_django_starter::controllers::password_reset::CurrentSite;
+use rust_django_starter::entities::user;
+use rust_django_starter::settings::Settings;
+use sea_orm::{ActiveModelTrait, DatabaseConnection, Set};
+
+mod common;
+
+/// ファイルバックエンドでメールを書き出すようにしてクライアントを作成する
+fn setup_with_file_mail() -> (Client, PathBuf) {
+    let dir = std::env::temp_dir().join(format!("rust_django_test_mail_{}", std::process::id()));
+    std::env::set_var("EMAIL_BACKEND", "file");
+    std::env::set_var("EMAIL_FILE_PATH", &dir);
+    (common::setup(), dir)
+}
+
+fn create_user_with_email(client: &Client, username: &str, email: &str) -> user::Model {
+    let user = common::create_test_user_with_perms(client, username, &[]);
+    let db = client.rocket().state::<DatabaseConnection>().unwrap();
+    let mut active: user::ActiveModel = user.into();
+    active.email = Set(email.to_owned());
+    common::block_on(active.update(db)).unwrap()
+}
+
+/// 指定したアドレス宛てのメール本文
+fn find_mail(dir: &PathBuf, to: &str) -> Option<String> {
+    std::fs::read_dir(dir).ok()?.flatten().find_map(|entry| {
+        let content = std::fs::read_to_string(entry.path()).ok()?;
+        content.contains(&format!("To: {}", to)).then_some(content)
+    })
+}
+
+/// 指定したアドレス宛てのメール本文からリセット用URLのパスを取り出す
+fn find_reset_path(dir: &PathBuf, to: &str) -> Option<String> {
+    for entry in std::fs::read_dir(dir).ok()? {
+        let content = std::fs::read_to_string(entry.ok()?.path()).ok()?;
+        if !content.contains(&format!("To: {}", to)) {
+            continue;
+        }
+        let start = content.find("/auth/reset/")?;
+        let path = content[start..].split_whitespace().next()?;
+        return Some(path.to_string());
+    }
+    None
+}
+
+fn csrf_token(client: &Client) -> String {
+    client.cookies().get("csrf_token").unwrap().value().to_string()
+}
+
+#[test]
+fn test_password_reset_flow() {
+    let (client, dir) = setup_with_file_mail();
+    create_user_with_email(&client, "reset_user", "reset_user@example.com");
+
+    assert_eq!(client.get("/auth/password_reset").dispatch().status(), Status::Ok);
+    let response = client.post("/auth/password_reset")
+        .body(format!("email=RESET_USER%40example.com&csrf_token={}", csrf_token(&client)))
+        .header(ContentType::Form)
+        .dispatch();
+    assert_eq!(response.status(), Status::SeeOther);
+    assert_eq!(response.headers().get_one("Location"), Some("/auth/password_reset/done"));
+
+    let reset_path = find_reset_path(&dir, "reset_user@example.com").expect("reset email should be written");
+    let response = client.get(reset_path.clone()).dispatch();
+    assert_eq!(response.status(), Status::Ok);
+    assert!(response.into_string().unwrap().contains("new_password1"));
+
+    let response = client.post(reset_path.clone())
+        .body(format!("new_password1=NewPassw0rd&new_password2=NewPassw0rd&csrf_token={}", csrf_token(&client)))
+        .header(ContentType::Form)
+        .dispatch();
+    assert_eq!(response.status(), Status::SeeOther);
+    assert_eq!(response.headers().get_one("Location"), Some("/auth/reset/done"));
+
+    let response = client.post("/auth/login")
+        .body(common::with_login_csrf(&client, "username=reset_user&password=NewPassw0rd"))
+        .header(ContentType::Form)
+        .dispatch();
+    assert_eq!(response.status(), Status::SeeOther);
+
+    // パスワードが変わったため、同じリンクは使えない
+    let body = client.get(reset_path).dispatch().into_string().unwrap();
+    assert!(!body.contains("new_password1"));
+    assert!(body.contains("リンクが無効です"));
+}
+
+#[test]
+fn test_password_reset_unknown_email_does_not_leak() {
+    let (client, dir) = setup_with_file_mail();
+    client.get("/auth/password_reset").dispatch();
+
+    let response = client.post("/auth/password_reset")
+        .body(format!("email=nobody%40example.com&csrf_token={}", csrf_token(&client)))
+        .header(ContentType::Form)
+        .dispatch();
+    assert_eq!(response.status(), Status::SeeOther);
+    assert_eq!(response.headers().get_one("Location"), Some("/auth/password_reset/done"));
+    assert!(find_reset_path(&dir, "nobody@example.com").is_none());
+}
+
+#[test]
+fn test_password_reset_rejects_tampered_token() {
+    let (client, _dir) = setup_with_file_mail();
+    let user = create_user_with_email(&client, "reset_tampered", "reset_tampered@example.com");
+
+    let uid = rust_django_starter::tokens::encode_uid(user.id);
+    let body = client.get(format!("/auth/reset/{}/abc-invalid", uid)).dispatch().into_string().unwrap();
+    assert!(body.contains("リンクが無効です"));
+}
+
+#[test]
+fn test_password_reset_rejects_weak_password() {
+    let (client, _dir) = setup_with_file_mail();
+    let user = create_user_with_email(&client, "reset_weak", "reset_weak@example.com");
+
+    let tokens = client.rocket().state::<rust_django_starter::tokens::PasswordResetTokenGenerator>().unwrap();
+    let path = format!("/auth/reset/{}/{}", rust_django_starter::tokens::encode_uid(user.id), tokens.make_token(&user));
+    client.get(path.clone()).dispatch();
+
+    // パスワードバリデーターのエラーはフィールドごとに表示され、パスワードは変更されない
+    let response = client.post(path)
+        .body(format!("new_password1=reset_weak1&new_password2=reset_weak1&csrf_token={}", csrf_token(&client)))
+        .header(ContentType::Form)
+        .dispatch();
+    assert_eq!(response.status(), Status::Ok);
+    assert!(response.into_string().unwrap().contains("ユーザー名 と似すぎています"));
+
+    let response = client.post("/auth/login")
+        .body(common::with_login_csrf(&client, "username=reset_weak&password=password"))
+        .header(ContentType::Form)
+        .dispatch();
+    assert_eq!(response.status(), Status::SeeOther);
+}
+
+#[test]
+fn test_password_reset_rejects_disallowed_host() {
+    let (client, dir) = setup_with_file_mail();
+    create_user_with_email(&client, "reset_host", "reset_host@example.com");
+
+    // ALLOWED_HOSTS にない Host では攻撃者のドメインへのリンクを送らない
+    let mut request = client.post("/auth/password_reset")
+        .body(format!("email=reset_host%40example.com&csrf_token={}", csrf_token(&client)))
+        .header(ContentType::Form);
+    request.set_host(Host::parse("evil.test").unwrap());
+    assert_eq!(request.dispatch().status(), Status::BadRequest);
+    assert!(find_mail(&dir, "reset_host@example.com").is_none());
+
+    // 許可されたホストでは、偽装できる X-Forwarded-Proto を使わずにリンクを組み立てる
+    let mut request = client.post("/auth/password_reset")
+        .body(format!("email=reset_host%40example.com&csrf_token={}", csrf_token(&client)))
+        .header(ContentType::Form)
+        .header(Header::new("X-Forwarded-Proto", "https"))
+        .header(Header::new("Referer", "https://localhost:8000/auth/password_reset"));
+    request.set_host(Host::parse("localhost:8000").unwrap());
+    assert_eq!(request.dispatch().status(), Status::SeeOther);
+    let mail = find_mail(&dir, "reset_host@example.com").expect("reset email should be written");
+    assert!(mail.contains("http://localhost:8000/auth/reset/"));
+}
+
+#[get("/site")]
+fn site(site: CurrentSite) -> String {
+    format!("{}://{}", site.protocol, site.domain)
+}
+
+#[test]
+fn test_current_site_uses_site_url() {
+    let mut settings = Settings::default();
+    settings.site_url = Some("https://example.com/".to_string());
+    let client = Client::tracked(rocket::build().manage(settings).mount("/", rocket::routes![site])).unwrap();
+
+    // SITE_URL を設定している場合は Host ヘッダーを使わない
+    let mut request = client.get("/site");
+    request.set_host(Host::parse("evil.test").unwrap());
+    assert_eq!(request.dispatch().into_string().as_deref(), Some("https://example.com"));
+}
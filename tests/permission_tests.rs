@@ -0,0 +1,48 @@
+use rocket::http::{ContentType, Status};
+
+mod common;
+
+#[test]
+fn test_staff_with_view_user_permission() {
+    let client = common::setup();
+    common::create_test_user_with_perms(&client, "perm_viewer", &["auth.view_user"]);
+
+    let response = client.post("/auth/login")
+        .body(common::with_login_csrf(&client, "username=perm_viewer&password=password"))
+        .header(ContentType::Form)
+        .dispatch();
+    assert_eq!(response.status(), Status::SeeOther);
+
+    // 閲覧権限があるのでユーザー一覧は見られる
+    let response = client.get("/admin/users").dispatch();
+    assert_eq!(response.status(), Status::Ok);
+
+    // ダッシュボードにはユーザーのみ表示される
+    let response = client.get("/admin").dispatch();
+    assert_eq!(response.status(), Status::Ok);
+    let body = response.into_string().unwrap();
+    assert!(body.contains("/admin/users"));
+    assert!(!body.contains("/admin/users/create"));
+
+    // 追加権限・グループ閲覧権限はない
+    let response = client.get("/admin/users/create").dispatch();
+    assert_eq!(response.status(), Status::Forbidden);
+    let response = client.get("/admin/groups").dispatch();
+    assert_eq!(response.status(), Status::Forbidden);
+}
+
+#[test]
+fn test_user_without_permissions_cannot_access_admin() {
+    let client = common::setup();
+    common::create_test_user_with_perms(&client, "perm_none", &[]);
+
+    client.post("/auth/login")
+        .body(common::with_login_csrf(&client, "username=perm_none&password=password"))
+        .header(ContentType::Form)
+        .dispatch();
+
+    let response = client.get("/admin").dispatch();
+    assert_eq!(response.status(), Status::Forbidden);
+    let response = client.get("/admin/users").dispatch();
+    assert_eq!(response.status(), Status::Forbidden);
+}
@@ -0,0 +1,83 @@
+use rocket::http::{ContentType, Header, Status};
+use rocket::local::blocking::Client;
+use rust_django_starter::fairings::security::{SecurityConfig, SecurityMiddleware};
+use rust_django_starter::settings::Settings;
+
+mod common;
+
+#[test]
+fn test_default_headers_and_csp_nonce_in_admin_scripts() {
+    let client = common::setup();
+    common::create_test_user_with_perms(&client, "security_headers_user", &["auth.view_user"]);
+    let response = client.post("/auth/login")
+        .body(common::with_login_csrf(&client, "username=security_headers_user&password=password"))
+        .header(ContentType::Form)
+        .dispatch();
+    assert_eq!(response.status(), Status::SeeOther);
+
+    let response = client.get("/admin/users").dispatch();
+    assert_eq!(response.status(), Status::Ok);
+    let headers = response.headers();
+    assert_eq!(headers.get_one("X-Content-Type-Options"), Some("nosniff"));
+    assert_eq!(headers.get_one("X-Frame-Options"), Some("DENY"));
+    assert_eq!(headers.get_one("Referrer-Policy"), Some("same-origin"));
+    assert_eq!(headers.get_one("Cross-Origin-Opener-Policy"), Some("same-origin"));
+    // HSTS はデフォルトでは付けない
+    assert_eq!(headers.get_one("Strict-Transport-Security"), None);
+
+    let csp = headers.get_one("Content-Security-Policy").unwrap().to_string();
+    let start = csp.find("'nonce-").unwrap() + 7;
+    let nonce = &csp[start..start + csp[start..].find('\'').unwrap()];
+    let body = response.into_string().unwrap();
+    assert!(body.contains(&format!("<script nonce=\"{}\">", nonce)));
+    assert!(!body.contains("onclick="));
+    // 外部のスクリプトはホストではなく nonce と SRI のハッシュで許可する
+    assert!(!csp.contains("https://"));
+    let htmx = body.lines().find(|line| line.contains("htmx.org")).unwrap();
+    assert!(htmx.contains("integrity=\"sha384-"));
+    assert!(htmx.contains(&format!("nonce=\"{}\"", nonce)));
+
+    // nonce はリクエストごとに変わる
+    let next = client.get("/admin/users").dispatch();
+    assert!(!next.headers().get_one("Content-Security-Policy").unwrap().contains(nonce));
+}
+
+#[rocket::get("/page")]
+fn page() -> &'static str {
+    "page"
+}
+
+#[derive(rocket::Responder)]
+struct Embeddable {
+    inner: &'static str,
+    frame_options: Header<'static>,
+}
+
+#[rocket::get("/embeddable")]
+fn embeddable() -> Embeddable {
+    Embeddable { inner: "embeddable", frame_options: Header::new("X-Frame-Options", "SAMEORIGIN") }
+}
+
+#[test]
+fn test_hsts_on_https_only_and_handler_headers_are_kept() {
+    let mut config = SecurityConfig::from_settings(&Settings::default());
+    config.hsts_seconds = 3600;
+    config.hsts_include_subdomains = true;
+    config.referrer_policy = String::new();
+    config.csp_report_only = "default-src 'self'; script-src 'nonce-{nonce}'".into();
+    let rocket = rocket::build()
+        .attach(SecurityMiddleware::new(config))
+        .mount("/", rocket::routes![page, embeddable]);
+    let client = Client::tracked(rocket).unwrap();
+
+    let response = client.get("/page").dispatch();
+    assert_eq!(response.headers().get_one("Strict-Transport-Security"), None);
+    assert_eq!(response.headers().get_one("Referrer-Policy"), None);
+    assert!(response.headers().get_one("Content-Security-Policy-Report-Only").unwrap().contains("'nonce-"));
+
+    let response = client.get("/page").header(Header::new("X-Forwarded-Proto", "https")).dispatch();
+    assert_eq!(response.headers().get_one("Strict-Transport-Security"), Some("max-age=3600; includeSubDomains"));
+
+    let response = client.get("/embeddable").dispatch();
+    assert_eq!(response.headers().get_one("X-Frame-Options"), Some("SAMEORIGIN"));
+}
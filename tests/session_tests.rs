@@ -0,0 +1,90 @@
+use rocket::http::{ContentType, Status};
+
+mod common;
+
+#[test]
+fn test_logout_invalidates_session_on_server() {
+    let client = common::setup();
+    common::create_test_admin(&client);
+
+    let response = client.post("/auth/login")
+        .body(common::with_login_csrf(&client, "username=admin&password=password"))
+        .header(ContentType::Form)
+        .dispatch();
+    let cookie = response.cookies().get("sessionid").unwrap().clone();
+
+    let response = client.get("/admin/users").cookie(cookie.clone()).dispatch();
+    assert_eq!(response.status(), Status::Ok);
+
+    client.post("/auth/logout").header(common::csrf_header(&client)).cookie(cookie.clone()).dispatch();
+
+    // ログアウト前のCookieを再送しても、サーバー側でセッションが削除されているため拒否される
+    let response = client.get("/admin/users").cookie(cookie).dispatch();
+    assert_eq!(response.status(), Status::SeeOther);
+}
+
+#[test]
+fn test_each_login_creates_separate_session() {
+    let client = common::setup();
+    common::create_test_admin(&client);
+
+    let first = client.post("/auth/login")
+        .body(common::with_login_csrf(&client, "username=admin&password=password"))
+        .header(ContentType::Form)
+        .dispatch()
+        .cookies()
+        .get("sessionid")
+        .unwrap()
+        .clone();
+
+    // 別のブラウザからのログインを想定して、Cookieを持たない状態でログインする
+    let other = common::setup();
+    let second = other.post("/auth/login")
+        .body(common::with_login_csrf(&other, "username=admin&password=password"))
+        .header(ContentType::Form)
+        .dispatch()
+        .cookies()
+        .get("sessionid")
+        .unwrap()
+        .clone();
+
+    assert_ne!(first.value(), second.value());
+
+    // 片方のログアウトはもう片方のセッションに影響しない
+    other.post("/auth/logout").header(common::csrf_header(&other)).cookie(second).dispatch();
+    let response = client.get("/admin/users").cookie(first).dispatch();
+    assert_eq!(response.status(), Status::Ok);
+}
+
+#[test]
+fn test_revoke_other_sessions_from_security_page() {
+    // 他のテストと干渉しないよう専用ユーザーを使う
+    let client = common::setup();
+    common::create_test_user_with_perms(&client, "session_owner", &[]);
+    let other = common::setup();
+
+    for c in [&client, &other] {
+        let response = c.post("/auth/login")
+            .body(common::with_login_csrf(c, "username=session_owner&password=password"))
+            .header(ContentType::Form)
+            .dispatch();
+        assert_eq!(response.status(), Status::SeeOther);
+    }
+
+    let response = client.get("/account/security").dispatch();
+    assert_eq!(response.status(), Status::Ok);
+    let body = response.into_string().unwrap();
+    assert!(body.contains("この端末"));
+    assert!(body.contains("/account/security/revoke_others"));
+
+    let csrf_token = client.cookies().get("csrf_token").unwrap().value().to_string();
+    let response = client.post("/account/security/revoke_others")
+        .body(format!("csrf_token={}", csrf_token))
+        .header(ContentType::Form)
+        .dispatch();
+    assert_eq!(response.status(), Status::SeeOther);
+
+    // 自分のセッションは残り、他の端末のセッションは無効になる
+    assert_eq!(client.get("/account/security").dispatch().status(), Status::Ok);
+    assert_eq!(other.get("/account/security").dispatch().status(), Status::SeeOther);
+}
@@ -0,0 +1,52 @@
+use rocket::http::{ContentType, Status};
+use rocket::local::blocking::Client;
+use rust_django_starter::settings::Settings;
+
+mod common;
+
+/// 環境変数で設定を上書きしてクライアントを作成する
+/// (環境変数はこのテストバイナリ内でのみ有効)
+fn setup_with_settings() -> Client {
+    std::env::set_var("LOGIN_REDIRECT_URL", "/todo");
+    std::env::set_var("LIST_PER_PAGE", "1");
+    std::env::set_var("SESSION_COOKIE_SECURE", "yes");
+    common::setup()
+}
+
+#[test]
+fn test_settings_are_managed_state() {
+    let client = setup_with_settings();
+    let settings = client.rocket().state::<Settings>().expect("settings are managed");
+    assert_eq!(settings.login_redirect_url, "/todo");
+    assert_eq!(settings.list_per_page, 1);
+    assert!(settings.session_cookie_secure);
+}
+
+#[test]
+fn test_login_redirect_url_and_secure_session_cookie() {
+    let client = setup_with_settings();
+    common::create_test_user_with_perms(&client, "settings_redirect_user", &[]);
+
+    let response = client.post("/auth/login")
+        .body(common::with_login_csrf(&client, "username=settings_redirect_user&password=password"))
+        .header(ContentType::Form)
+        .dispatch();
+    assert_eq!(response.status(), Status::SeeOther);
+    assert_eq!(response.headers().get_one("Location"), Some("/todo"));
+    let cookie = response.headers().get("Set-Cookie").find(|c| c.starts_with("sessionid=")).unwrap();
+    assert!(cookie.contains("Secure"));
+}
+
+#[test]
+fn test_templates_receive_settings_without_secrets() {
+    let client = setup_with_settings();
+    let body = client.get("/").dispatch().into_string().unwrap();
+    // テンプレートでは `{{ settings.login_url }}` として参照できる (Teraが `/` をエスケープする)
+    assert!(body.contains("href=\"&#x2F;auth&#x2F;login\""));
+
+    let settings = client.rocket().state::<Settings>().unwrap();
+    let value = rocket::serde::json::serde_json::to_value(settings).unwrap();
+    assert_eq!(value["login_url"], "/auth/login");
+    assert!(value.get("database_url").is_none());
+    assert!(value.get("email_host_password").is_none());
+}
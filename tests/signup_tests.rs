@@ -0,0 +1,152 @@
+use std::path::PathBuf;
+use rocket::http::{ContentType, Status};
+use rocket::local::blocking::Client;
+use rust_django_starter::entities::{group, user};
+use rust_django_starter::services::user_service::UserService;
+use sea_orm::{ActiveModelTrait, ColumnTrait, DatabaseConnection, EntityTrait, ModelTrait, QueryFilter, Set};
+
+mod common;
+
+const DEFAULT_GROUP: &str = "signup_default_group";
+
+/// ユーザー登録を有効にし、メールをファイルに書き出すようにしてクライアントを作成する
+/// (環境変数はこのテストバイナリ内でのみ有効)
+fn setup_with_signup() -> (Client, PathBuf) {
+    let dir = std::env::temp_dir().join(format!("rust_django_test_signup_mail_{}", std::process::id()));
+    std::env::set_var("EMAIL_BACKEND", "file");
+    std::env::set_var("EMAIL_FILE_PATH", &dir);
+    std::env::set_var("SIGNUP_ENABLED", "true");
+    std::env::set_var("SIGNUP_DEFAULT_GROUPS", DEFAULT_GROUP);
+    (common::setup(), dir)
+}
+
+fn ensure_group(client: &Client, name: &str) {
+    let db = client.rocket().state::<DatabaseConnection>().unwrap();
+    common::block_on(async {
+        let exists = group::Entity::find().filter(group::Column::Name.eq(name)).one(db).await.unwrap();
+        if exists.is_none() {
+            let _ = group::ActiveModel { name: Set(name.to_owned()), ..Default::default() }.insert(db).await;
+        }
+    });
+}
+
+/// 指定したアドレス宛てのメール本文から有効化URLのパスを取り出す
+fn find_activation_path(dir: &PathBuf, to: &str) -> Option<String> {
+    for entry in std::fs::read_dir(dir).ok()? {
+        let content = std::fs::read_to_string(entry.ok()?.path()).ok()?;
+        if !content.contains(&format!("To: {}", to)) {
+            continue;
+        }
+        let start = content.find("/auth/activate/")?;
+        let path = content[start..].split_whitespace().next()?;
+        return Some(path.to_string());
+    }
+    None
+}
+
+fn csrf_token(client: &Client) -> String {
+    client.cookies().get("csrf_token").unwrap().value().to_string()
+}
+
+fn login(client: &Client, username: &str, password: &str) -> Status {
+    client.post("/auth/login")
+        .body(common::with_login_csrf(client, format!("username={}&password={}", username, password)))
+        .header(ContentType::Form)
+        .dispatch()
+        .status()
+}
+
+#[test]
+fn test_signup_activation_flow() {
+    let (client, dir) = setup_with_signup();
+    ensure_group(&client, DEFAULT_GROUP);
+    let db = client.rocket().state::<DatabaseConnection>().unwrap();
+    let username = format!("signup_{}", &uuid::Uuid::new_v4().simple().to_string()[..12]);
+    let email = format!("{}@example.com", username);
+
+    assert!(client.get("/auth/login").dispatch().into_string().unwrap().contains("アカウントを作成"));
+    assert_eq!(client.get("/auth/signup").dispatch().status(), Status::Ok);
+
+    // 入力エラーはフィールドごとに表示され、ユーザーは作成されない
+    let response = client.post("/auth/signup")
+        .body(format!("username={}&email=invalid&password1=Tr0ubador-Horse&password2=other&csrf_token={}", username, csrf_token(&client)))
+        .header(ContentType::Form)
+        .dispatch();
+    assert_eq!(response.status(), Status::Ok);
+    let body = response.into_string().unwrap();
+    assert!(body.contains("有効なメールアドレスを入力してください"));
+    assert!(body.contains("確認用パスワードが一致しません"));
+    assert!(common::block_on(UserService::find_by_username(db, &username)).unwrap().is_none());
+
+    let response = client.post("/auth/signup")
+        .body(format!("username={}&email={}&password1=Tr0ubador-Horse&password2=Tr0ubador-Horse&csrf_token={}", username, email, csrf_token(&client)))
+        .header(ContentType::Form)
+        .dispatch();
+    assert_eq!(response.status(), Status::SeeOther);
+    assert_eq!(response.headers().get_one("Location"), Some("/auth/signup/done"));
+
+    // 無効状態で作成され、デフォルトのグループに所属している
+    let user: user::Model = common::block_on(UserService::find_by_username(db, &username)).unwrap().unwrap();
+    assert!(!user.is_active);
+    let groups = common::block_on(user.find_related(group::Entity).all(db)).unwrap();
+    assert_eq!(groups.iter().map(|g| g.name.as_str()).collect::<Vec<_>>(), vec![DEFAULT_GROUP]);
+    assert_eq!(login(&client, &username, "Tr0ubador-Horse"), Status::Ok);
+
+    // メールのリンクで有効化される (2回目は無効)
+    let path = find_activation_path(&dir, &email).expect("activation email should be written");
+    let body = client.get(path.clone()).dispatch().into_string().unwrap();
+    assert!(body.contains("アカウントが有効になりました"));
+    let body = client.get(path).dispatch().into_string().unwrap();
+    assert!(body.contains("有効化のリンクが無効です"));
+
+    assert_eq!(login(&client, &username, "Tr0ubador-Horse"), Status::SeeOther);
+}
+
+#[test]
+fn test_signup_rejects_existing_username_and_invalid_link() {
+    let (client, _dir) = setup_with_signup();
+    common::create_test_user_with_perms(&client, "signup_taken", &[]);
+    client.get("/auth/signup").dispatch();
+
+    let response = client.post("/auth/signup")
+        .body(format!("username=signup_taken&email=taken%40example.com&password1=Tr0ubador-Horse&password2=Tr0ubador-Horse&csrf_token={}", csrf_token(&client)))
+        .header(ContentType::Form)
+        .dispatch();
+    assert_eq!(response.status(), Status::Ok);
+    assert!(response.into_string().unwrap().contains("このユーザー名は既に使用されています"));
+
+    let body = client.get("/auth/activate/MQ/invalid-token").dispatch().into_string().unwrap();
+    assert!(body.contains("有効化のリンクが無効です"));
+}
+
+#[test]
+fn test_activation_link_cannot_reactivate_deactivated_user() {
+    let (client, dir) = setup_with_signup();
+    ensure_group(&client, DEFAULT_GROUP);
+    let db = client.rocket().state::<DatabaseConnection>().unwrap();
+    let username = format!("signup_{}", &uuid::Uuid::new_v4().simple().to_string()[..12]);
+    let email = format!("{}@example.com", username);
+
+    let response = client.post("/auth/signup")
+        .body(format!("username={}&email={}&password1=Tr0ubador-Horse&password2=Tr0ubador-Horse&csrf_token={}", username, email, csrf_token(&client)))
+        .header(ContentType::Form)
+        .dispatch();
+    assert_eq!(response.status(), Status::SeeOther);
+
+    let path = find_activation_path(&dir, &email).expect("activation email should be written");
+    let body = client.get(path.clone()).dispatch().into_string().unwrap();
+    assert!(body.contains("アカウントが有効になりました"));
+
+    // 管理者が無効化した後に同じリンクを使っても、再び有効化されない
+    let user = common::block_on(UserService::find_by_username(db, &username)).unwrap().unwrap();
+    assert!(user.activated_at.is_some());
+    let mut active: user::ActiveModel = user.into();
+    active.is_active = Set(false);
+    common::block_on(active.update(db)).unwrap();
+
+    let body = client.get(path).dispatch().into_string().unwrap();
+    assert!(body.contains("有効化のリンクが無効です"));
+    let user = common::block_on(UserService::find_by_username(db, &username)).unwrap().unwrap();
+    assert!(!user.is_active);
+    assert_eq!(login(&client, &username, "Tr0ubador-Horse"), Status::Ok);
+}
@@ -0,0 +1,193 @@
+use rocket::http::{ContentType, Status};
+use rocket::local::blocking::Client;
+use rust_django_starter::entities::{log_entry, prelude::LogEntry, user};
+use rust_django_starter::otp::{unix_now, Totp};
+use rust_django_starter::services::login_throttle::LoginThrottle;
+use rust_django_starter::services::two_factor::TwoFactorService;
+use rust_django_starter::services::user_service::UserService;
+use sea_orm::{ColumnTrait, DatabaseConnection, EntityTrait, QueryFilter, QueryOrder};
+
+mod common;
+
+fn login(client: &Client, username: &str, password: &str) -> (Status, Option<String>) {
+    let response = client.post("/auth/login")
+        .body(common::with_login_csrf(client, format!("username={}&password={}", username, password)))
+        .header(ContentType::Form)
+        .dispatch();
+    let location = response.headers().get_one("Location").map(str::to_string);
+    (response.status(), location)
+}
+
+fn csrf_token(client: &Client) -> String {
+    client.cookies().get("csrf_token").unwrap().value().to_string()
+}
+
+fn post_form(client: &Client, uri: &str, body: String) -> (Status, Option<String>, String) {
+    let response = client.post(uri.to_string())
+        .body(body)
+        .header(ContentType::Form)
+        .dispatch();
+    let status = response.status();
+    let location = response.headers().get_one("Location").map(str::to_string);
+    (status, location, response.into_string().unwrap_or_default())
+}
+
+/// 2要素認証を無効にした状態のユーザーを用意する (前回のテスト実行の状態を消去する)
+fn fresh_user(client: &Client, username: &str) -> user::Model {
+    let db = client.rocket().state::<DatabaseConnection>().unwrap();
+    let throttle = client.rocket().state::<LoginThrottle>().unwrap();
+    let user = common::create_test_user_with_perms(client, username, &[]);
+    common::block_on(throttle.reset(db, username)).unwrap();
+    common::block_on(TwoFactorService::disable(db, user)).unwrap()
+}
+
+fn reload(client: &Client, id: i32) -> user::Model {
+    let db = client.rocket().state::<DatabaseConnection>().unwrap();
+    common::block_on(UserService::find_by_id(db, id)).unwrap().unwrap()
+}
+
+#[test]
+fn test_enroll_and_login_with_totp_and_recovery_code() {
+    let client = common::setup();
+    let user = fresh_user(&client, "otp_user");
+
+    assert_eq!(login(&client, "otp_user", "password"), (Status::SeeOther, Some("/".to_string())));
+
+    // 登録画面に表示されたシークレットで確認コードを生成して有効にする
+    let body = client.get("/account/two_factor/setup").dispatch().into_string().unwrap();
+    assert!(body.contains("otpauth:"));
+    let secret = body
+        .split("id=\"id_secret\"")
+        .nth(1)
+        .and_then(|rest| rest.split('>').nth(1))
+        .and_then(|rest| rest.split('<').next())
+        .unwrap()
+        .to_string();
+    let totp = Totp::from_base32(&secret).unwrap();
+
+    let (status, location, _) = post_form(&client, "/account/two_factor/setup",
+        format!("otp_token={}&csrf_token={}", totp.current_code(), csrf_token(&client)));
+    assert_eq!(status, Status::SeeOther);
+    assert_eq!(location.as_deref(), Some("/account/two_factor/recovery_codes"));
+    assert!(reload(&client, user.id).totp_secret.is_some());
+
+    // リカバリーコードは発行直後に1回だけ表示される
+    let body = client.get("/account/two_factor/recovery_codes").dispatch().into_string().unwrap();
+    let codes: Vec<String> = body
+        .split("<ul id=\"recovery-codes\"")
+        .nth(1)
+        .unwrap()
+        .split("</ul>")
+        .next()
+        .unwrap()
+        .split("<li>")
+        .skip(1)
+        .map(|li| li.split("</li>").next().unwrap().trim().to_string())
+        .collect();
+    assert_eq!(codes.len(), 10);
+    let body = client.get("/account/two_factor/recovery_codes").dispatch().into_string().unwrap();
+    assert!(!body.contains(&codes[0]));
+
+    client.post("/auth/logout").header(common::csrf_header(&client)).dispatch();
+
+    // パスワードだけではログインが完了しない
+    assert_eq!(login(&client, "otp_user", "password"), (Status::SeeOther, Some("/auth/login/otp".to_string())));
+    assert_eq!(client.get("/account/security").dispatch().status(), Status::SeeOther);
+
+    client.get("/auth/login/otp").dispatch();
+    let (status, _, body) = post_form(&client, "/auth/login/otp",
+        format!("otp_token=000000&csrf_token={}", csrf_token(&client)));
+    assert_eq!(status, Status::Ok);
+    assert!(body.contains("認証コードが正しくありません"));
+
+    // リカバリーコードでログインできる
+    let (status, location, _) = post_form(&client, "/auth/login/otp",
+        format!("otp_token={}&csrf_token={}", codes[0], csrf_token(&client)));
+    assert_eq!((status, location.as_deref()), (Status::SeeOther, Some("/")));
+    assert_eq!(client.get("/account/security").dispatch().status(), Status::Ok);
+    client.post("/auth/logout").header(common::csrf_header(&client)).dispatch();
+
+    // 使用済みのリカバリーコードは使えないが、認証アプリのコードは使える
+    login(&client, "otp_user", "password");
+    client.get("/auth/login/otp").dispatch();
+    let (status, _, _) = post_form(&client, "/auth/login/otp",
+        format!("otp_token={}&csrf_token={}", codes[0], csrf_token(&client)));
+    assert_eq!(status, Status::Ok);
+
+    // 有効化で現在のコードは使用済みのため、次のステップのコードを使う
+    let next_code = totp.code_at(Totp::counter_at(unix_now()) + 1);
+    let (status, location, _) = post_form(&client, "/auth/login/otp",
+        format!("otp_token={}&csrf_token={}", next_code, csrf_token(&client)));
+    assert_eq!((status, location.as_deref()), (Status::SeeOther, Some("/")));
+    assert_eq!(client.get("/account/security").dispatch().status(), Status::Ok);
+}
+
+#[test]
+fn test_admin_can_reset_two_factor() {
+    let client = common::setup();
+    let user = fresh_user(&client, "otp_reset_user");
+    let db = client.rocket().state::<DatabaseConnection>().unwrap();
+
+    let secret = Totp::generate_secret();
+    let code = Totp::from_base32(&secret).unwrap().current_code();
+    common::block_on(TwoFactorService::enable(db, user.clone(), &secret, &code)).unwrap();
+    assert_eq!(login(&client, "otp_reset_user", "password").1.as_deref(), Some("/auth/login/otp"));
+
+    let admin = common::setup();
+    common::create_test_admin(&admin);
+    login(&admin, "admin", "password");
+    let body = admin.get(format!("/admin/users/edit/{}", user.id)).dispatch().into_string().unwrap();
+    assert!(body.contains("2要素認証をリセット"));
+
+    let (status, _, _) = post_form(&admin, &format!("/admin/users/otp_reset/{}", user.id),
+        format!("csrf_token={}", csrf_token(&admin)));
+    assert_eq!(status, Status::SeeOther);
+
+    let reloaded = reload(&client, user.id);
+    assert!(reloaded.totp_secret.is_none());
+    assert_eq!(common::block_on(TwoFactorService::remaining_recovery_codes(db, user.id)).unwrap(), 0);
+
+    // リセットは操作履歴に記録される
+    let entry = common::block_on(
+        LogEntry::find()
+            .filter(log_entry::Column::ContentType.eq("user"))
+            .filter(log_entry::Column::ObjectId.eq(user.id.to_string()))
+            .order_by_desc(log_entry::Column::Id)
+            .one(db),
+    )
+    .unwrap()
+    .unwrap();
+    assert_eq!(entry.get_change_message(), "totp_secret を変更しました。");
+
+    // リセット後はパスワードだけでログインできる
+    assert_eq!(login(&client, "otp_reset_user", "password"), (Status::SeeOther, Some("/".to_string())));
+}
+
+#[test]
+fn test_password_login_does_not_reset_otp_failures() {
+    let client = common::setup();
+    let name = format!("otp_throttle_{}", uuid::Uuid::new_v4().simple());
+    let user = common::create_test_user_with_perms(&client, &name, &[]);
+    let db = client.rocket().state::<DatabaseConnection>().unwrap();
+    let limit = client.rocket().state::<LoginThrottle>().unwrap().config().failure_limit;
+    let secret = Totp::generate_secret();
+    let totp = Totp::from_base32(&secret).unwrap();
+    common::block_on(TwoFactorService::enable(db, user, &secret, &totp.current_code())).unwrap();
+    let wrong_code = |client: &Client| {
+        let code = if totp.current_code() == "000000" { "111111" } else { "000000" };
+        post_form(client, "/auth/login/otp", format!("otp_token={}&csrf_token={}", code, csrf_token(client))).0
+    };
+
+    assert_eq!(login(&client, &name, "password").1.as_deref(), Some("/auth/login/otp"));
+    for _ in 0..limit - 1 {
+        assert_eq!(wrong_code(&client), Status::Ok);
+    }
+
+    // パスワードで再ログインしても、認証コードの失敗回数は消えない
+    assert_eq!(login(&client, &name, "password").1.as_deref(), Some("/auth/login/otp"));
+    assert_eq!(wrong_code(&client), Status::Ok);
+    assert_eq!(wrong_code(&client), Status::TooManyRequests);
+    let (status, _, _) = post_form(&client, "/auth/login/otp",
+        format!("otp_token={}&csrf_token={}", totp.current_code(), csrf_token(&client)));
+    assert_eq!(status, Status::TooManyRequests);
+}